@@ -32,6 +32,9 @@ public record Authentication
 public enum HeartbeatType {
     MachineAlive,
     TaskAlive,
+    TaskStarted,
+    TaskPaused,
+    TaskFailed,
 }
 
 public record HeartbeatData(HeartbeatType Type);
@@ -9,6 +9,7 @@ use anyhow::{Context, Result};
 use downcast_rs::Downcast;
 use onefuzz::az_copy;
 use onefuzz::process::Output;
+use reqwest::Url;
 use tokio::fs;
 use tokio::process::Command;
 use uuid::Uuid;
@@ -20,6 +21,9 @@ const DEFAULT_SETUP_SCRIPT_TIMEOUT: Duration = Duration::from_secs(59 * 60);
 
 const SETUP_PATH_ENV: &str = "ONEFUZZ_TARGET_SETUP_PATH";
 
+// Written next to the setup dir once it has been fully synced.
+const SETUP_MARKER_SUFFIX: &str = ".setup-complete";
+
 pub type SetupOutput = Option<Output>;
 
 #[async_trait]
@@ -71,19 +75,38 @@ impl SetupRunner {
         // Download the setup container.
         let setup_url = work_set.setup_url.url()?;
         let setup_dir = work_set.setup_dir()?;
-        // `azcopy sync` requires the local dir to exist.
-        fs::create_dir_all(&setup_dir).await.with_context(|| {
-            format!("unable to create setup container: {}", setup_dir.display())
-        })?;
-        az_copy::sync(setup_url.to_string(), &setup_dir, false).await?;
-        debug!(
-            "synced setup container from {} to {}",
-            setup_url,
-            setup_dir.display(),
-        );
+        let marker_path = setup_marker_path(&setup_dir)?;
+        let marker = setup_marker(&setup_url);
+
+        if work_set.reuse_setup_if_present() && is_setup_complete(&marker_path, &marker).await {
+            info!("reusing existing setup dir: {}", setup_dir.display());
+        } else {
+            // Invalidate any previous marker before syncing, so that an interrupted
+            // sync is never mistaken for a complete one.
+            if fs::metadata(&marker_path).await.is_ok() {
+                fs::remove_file(&marker_path).await.with_context(|| {
+                    format!("unable to remove setup marker: {}", marker_path.display())
+                })?;
+            }
+
+            // `azcopy sync` requires the local dir to exist.
+            fs::create_dir_all(&setup_dir).await.with_context(|| {
+                format!("unable to create setup container: {}", setup_dir.display())
+            })?;
+            az_copy::sync(setup_url.to_string(), &setup_dir, false).await?;
+            debug!(
+                "synced setup container from {} to {}",
+                setup_url,
+                setup_dir.display(),
+            );
+
+            // Ensure `target_exe` is executable, so tasks don't have to.
+            onefuzz::fs::set_executable(&setup_dir).await?;
 
-        // Ensure `target_exe` is executable, so tasks don't have to.
-        onefuzz::fs::set_executable(&setup_dir).await?;
+            fs::write(&marker_path, &marker).await.with_context(|| {
+                format!("unable to write setup marker: {}", marker_path.display())
+            })?;
+        }
 
         // Create setup container directory symlinks for tasks.
         let working_dirs = work_set
@@ -138,6 +161,29 @@ impl SetupRunner {
     }
 }
 
+fn setup_marker_path(setup_dir: &Path) -> Result<PathBuf> {
+    let name = setup_dir
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid setup dir: {}", setup_dir.display()))?;
+    Ok(setup_dir.with_file_name(format!("{}{SETUP_MARKER_SUFFIX}", name.to_string_lossy())))
+}
+
+/// Identifies the setup container a setup dir was synced from.
+///
+/// The SAS token is excluded, since it is expected to change between work sets.
+fn setup_marker(setup_url: &Url) -> String {
+    let mut url = setup_url.clone();
+    url.set_query(None);
+    onefuzz::sha256::digest(url.as_str())
+}
+
+async fn is_setup_complete(marker_path: &Path, marker: &str) -> bool {
+    match fs::read_to_string(marker_path).await {
+        Ok(contents) => contents == marker,
+        Err(_) => false,
+    }
+}
+
 #[cfg(target_family = "windows")]
 async fn create_setup_symlink(setup_dir: &Path, working_dir: impl AsRef<Path>) -> Result<()> {
     use std::os::windows::fs::symlink_dir;
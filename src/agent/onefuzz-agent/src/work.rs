@@ -93,6 +93,12 @@ impl WorkSet {
         self.setup_url.as_path(setup_root)
     }
 
+    /// Whether every work unit in the set allows reusing a previously completed
+    /// setup dir, rather than syncing it again.
+    pub fn reuse_setup_if_present(&self) -> bool {
+        !self.work_units.is_empty() && self.work_units.iter().all(WorkUnit::reuse_setup_if_present)
+    }
+
     pub fn extra_setup_dir(&self) -> Result<Option<PathBuf>> {
         let root = self.get_root_folder()?;
         self.extra_setup_url
@@ -127,6 +133,19 @@ impl WorkUnit {
     pub fn config_path(&self, machine_id: Uuid) -> Result<PathBuf> {
         Ok(self.working_dir(machine_id)?.join("config.json"))
     }
+
+    /// Whether the task config sets `reuse_setup_if_present`.
+    pub fn reuse_setup_if_present(&self) -> bool {
+        #[derive(Deserialize)]
+        struct SetupOptions {
+            #[serde(default)]
+            reuse_setup_if_present: bool,
+        }
+
+        serde_json::from_str::<SetupOptions>(self.config.expose_ref())
+            .map(|options| options.reuse_setup_if_present)
+            .unwrap_or_default()
+    }
 }
 
 #[async_trait]
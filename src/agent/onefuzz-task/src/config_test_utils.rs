@@ -28,7 +28,7 @@ macro_rules! config_test {
 }
 
 pub mod arbitraries {
-    use std::path::PathBuf;
+    use std::{collections::HashMap, path::PathBuf};
 
     use onefuzz::{blob::BlobContainerUrl, machine_id::MachineIdentity, syncdir::SyncedDir};
     use onefuzz_telemetry::{InstanceTelemetryKey, MicrosoftTelemetryKey};
@@ -36,7 +36,10 @@ pub mod arbitraries {
     use reqwest::Url;
     use uuid::Uuid;
 
-    use crate::tasks::{analysis, config::CommonConfig, coverage, fuzz, merge, report};
+    use crate::tasks::{
+        analysis, config::CommonConfig, coverage, fuzz, merge, report,
+        task_lease::DuplicateTaskPolicy,
+    };
 
     prop_compose! {
         fn arb_uuid()(
@@ -98,10 +101,22 @@ pub mod arbitraries {
         }
     }
 
+    prop_compose! {
+        fn arb_s3_url()(
+            url in r"s3://[a-z0-9]{3,63}(/[a-z0-9]{1,16})?"
+        ) -> Url {
+            match Url::parse(&url) {
+                Ok(url) => url,
+                Err(err) => panic!("invalid url generated ({}): {}", err, url),
+            }
+        }
+    }
+
     fn arb_blob_container_url() -> impl Strategy<Value = BlobContainerUrl> {
         prop_oneof![
             arb_url().prop_map(BlobContainerUrl::BlobContainer),
             arb_pathbuf().prop_map(BlobContainerUrl::Path),
+            arb_s3_url().prop_map(BlobContainerUrl::S3),
         ]
     }
 
@@ -127,30 +142,198 @@ pub mod arbitraries {
         }
     }
 
+    // The common config is generated in groups of fields, each boxed, since a
+    // single strategy over every field has a value tree too large for the
+    // default test thread stack.
     prop_compose! {
-        fn arb_common_config()(
+        fn arb_common_identity()(
             job_id in arb_uuid(),
             task_id in arb_uuid(),
             instance_id in arb_uuid(),
             heartbeat_queue in option::of(arb_url()),
+            heartbeat_extra_sinks in prop::collection::vec(arb_url(), 0..3),
+            heartbeat_on_start in any::<bool>(),
             job_result_queue in option::of(arb_url()),
             instance_telemetry_key in option::of(arb_instance_telemetry_key()), // consider implementing Arbitrary for these types for a canonical way to generate them
             microsoft_telemetry_key in option::of(arb_microsoft_telemetry_key()), // We can probably derive Arbitrary if it's implemented for the composing types like Url
             logs in option::of(arb_url()),
+        ) -> (
+            Uuid,
+            Uuid,
+            Uuid,
+            Option<Url>,
+            Vec<Url>,
+            bool,
+            Option<Url>,
+            Option<InstanceTelemetryKey>,
+            Option<MicrosoftTelemetryKey>,
+            Option<Url>,
+        ) {
+            (
+                job_id,
+                task_id,
+                instance_id,
+                heartbeat_queue,
+                heartbeat_extra_sinks,
+                heartbeat_on_start,
+                job_result_queue,
+                instance_telemetry_key,
+                microsoft_telemetry_key,
+                logs,
+            )
+        }
+    }
+
+    prop_compose! {
+        fn arb_common_machine()(
             setup_dir in arb_pathbuf(),
             extra_setup_dir in option::of(arb_pathbuf()),
             extra_output in option::of(arb_synced_dir()),
             min_available_memory_mb in any::<u64>(),
             machine_identity in arb_machine_identity(),
+            machine_name_override in option::of(".*"),
             tags in prop::collection::hash_map(".*", ".*", 3),
             from_agent_to_task_endpoint in ".*",
             from_task_to_agent_endpoint in ".*",
+            random_seed in option::of(any::<u64>()),
+        ) -> (
+            PathBuf,
+            Option<PathBuf>,
+            Option<SyncedDir>,
+            u64,
+            MachineIdentity,
+            Option<String>,
+            HashMap<String, String>,
+            String,
+            String,
+            Option<u64>,
+        ) {
+            (
+                setup_dir,
+                extra_setup_dir,
+                extra_output,
+                min_available_memory_mb,
+                machine_identity,
+                machine_name_override,
+                tags,
+                from_agent_to_task_endpoint,
+                from_task_to_agent_endpoint,
+                random_seed,
+            )
+        }
+    }
+
+    prop_compose! {
+        fn arb_common_options()(
+            preflight_checks in any::<bool>(),
+            reuse_setup_if_present in any::<bool>(),
+            metrics_textfile_dir in option::of(arb_pathbuf()),
+            event_socket in option::of(arb_pathbuf()),
+            source_revision in option::of("[0-9a-f]{40}"),
+            max_open_fds in option::of(any::<u64>()),
+            telemetry_sample_rate in option::of(0.0..=1.0f64),
+            manifest_output in option::of(arb_url()),
+        ) -> (
+            bool,
+            bool,
+            Option<PathBuf>,
+            Option<PathBuf>,
+            Option<String>,
+            Option<u64>,
+            Option<f64>,
+            Option<Url>,
+        ) {
+            (
+                preflight_checks,
+                reuse_setup_if_present,
+                metrics_textfile_dir,
+                event_socket,
+                source_revision,
+                max_open_fds,
+                telemetry_sample_rate,
+                manifest_output,
+            )
+        }
+    }
+
+    prop_compose! {
+        fn arb_common_startup()(
+            node_lock in option::of("[a-z0-9_-]{1,16}"),
+            node_lock_timeout_seconds in option::of(any::<u64>()),
+            wait_for_file in option::of(arb_pathbuf()),
+            wait_timeout_seconds in option::of(any::<u64>()),
+            on_duplicate_task in option::of(prop_oneof![
+                Just(DuplicateTaskPolicy::Warn),
+                Just(DuplicateTaskPolicy::Fail),
+            ]),
+        ) -> (
+            Option<String>,
+            Option<u64>,
+            Option<PathBuf>,
+            Option<u64>,
+            Option<DuplicateTaskPolicy>,
+        ) {
+            (
+                node_lock,
+                node_lock_timeout_seconds,
+                wait_for_file,
+                wait_timeout_seconds,
+                on_duplicate_task,
+            )
+        }
+    }
+
+    prop_compose! {
+        fn arb_common_config()(
+            (
+                job_id,
+                task_id,
+                instance_id,
+                heartbeat_queue,
+                heartbeat_extra_sinks,
+                heartbeat_on_start,
+                job_result_queue,
+                instance_telemetry_key,
+                microsoft_telemetry_key,
+                logs,
+            ) in arb_common_identity().boxed(),
+            (
+                setup_dir,
+                extra_setup_dir,
+                extra_output,
+                min_available_memory_mb,
+                machine_identity,
+                machine_name_override,
+                tags,
+                from_agent_to_task_endpoint,
+                from_task_to_agent_endpoint,
+                random_seed,
+            ) in arb_common_machine().boxed(),
+            (
+                preflight_checks,
+                reuse_setup_if_present,
+                metrics_textfile_dir,
+                event_socket,
+                source_revision,
+                max_open_fds,
+                telemetry_sample_rate,
+                manifest_output,
+            ) in arb_common_options().boxed(),
+            (
+                node_lock,
+                node_lock_timeout_seconds,
+                wait_for_file,
+                wait_timeout_seconds,
+                on_duplicate_task,
+            ) in arb_common_startup().boxed(),
         ) -> CommonConfig {
             CommonConfig {
                 job_id,
                 task_id,
                 instance_id,
                 heartbeat_queue,
+                heartbeat_extra_sinks,
+                heartbeat_on_start,
                 job_result_queue,
                 instance_telemetry_key,
                 microsoft_telemetry_key,
@@ -160,9 +343,27 @@ pub mod arbitraries {
                 extra_output,
                 min_available_memory_mb,
                 machine_identity,
+                machine_name_override,
                 tags,
                 from_agent_to_task_endpoint,
                 from_task_to_agent_endpoint,
+                random_seed,
+                preflight_checks,
+                reuse_setup_if_present,
+                metrics_textfile_dir,
+                event_socket,
+                source_revision,
+                max_open_fds,
+                telemetry_sample_rate,
+                manifest_output,
+                node_lock,
+                node_lock_timeout_seconds,
+                wait_for_file,
+                wait_timeout_seconds,
+                on_duplicate_task,
+                cancellation_token: Default::default(),
+                pause: Default::default(),
+                config_hash: Default::default(),
             }
         }
     }
@@ -190,6 +391,12 @@ pub mod arbitraries {
             reports in option::of(arb_synced_dir()),
             unique_reports in option::of(arb_synced_dir()),
             no_repro in option::of(arb_synced_dir()),
+            shared_work_queue in Just(None),
+            dead_letter_queue in Just(None),
+            run_target_as_user in Just(None),
+            continue_on_error in any::<bool>(),
+            crash_reports in option::of(arb_synced_dir()),
+            include_report in any::<bool>(),
             common in arb_common_config(),
         ) -> analysis::generic::Config {
             analysis::generic::Config {
@@ -205,6 +412,12 @@ pub mod arbitraries {
                 reports,
                 unique_reports,
                 no_repro,
+                shared_work_queue,
+                dead_letter_queue,
+                run_target_as_user,
+                continue_on_error,
+                crash_reports,
+                include_report,
                 common,
             }
         }
@@ -232,6 +445,8 @@ pub mod arbitraries {
             input_queue in arb_url(),
             inputs in arb_synced_dir(),
             unique_inputs in arb_synced_dir(),
+            poll_interval_seconds in option::of(1..3600u64),
+            max_poll_interval_seconds in option::of(1..3600u64),
             common in arb_common_config(),
         ) -> merge::generic::Config {
             merge::generic::Config {
@@ -246,6 +461,8 @@ pub mod arbitraries {
                 input_queue,
                 inputs,
                 unique_inputs,
+                poll_interval_seconds,
+                max_poll_interval_seconds,
                 common,
             }
         }
@@ -272,6 +489,16 @@ pub mod arbitraries {
             input_queue in Just(None),
             readonly_inputs in prop::collection::vec(arb_synced_dir(), 10),
             coverage in arb_synced_dir(),
+            inputs_filter in option::of(prop::collection::vec(".*", 3)),
+            min_coverage_percent in option::of(0.0..=100.0f64),
+            incremental_coverage in any::<bool>(),
+            coverage_shards in option::of(arb_synced_dir()),
+            per_input_coverage in any::<bool>(),
+            baseline_for_diff in option::of(arb_url()),
+            coverage_output_format in prop_oneof![
+                Just(coverage::generic::CoverageOutputFormat::Json),
+                Just(coverage::generic::CoverageOutputFormat::Binary),
+            ],
             common in arb_common_config(),
         ) -> coverage::generic::Config {
             coverage::generic::Config {
@@ -285,6 +512,13 @@ pub mod arbitraries {
                 input_queue,
                 readonly_inputs,
                 coverage,
+                inputs_filter,
+                min_coverage_percent,
+                incremental_coverage,
+                coverage_shards,
+                per_input_coverage,
+                baseline_for_diff,
+                coverage_output_format,
                 common,
             }
         }
@@ -350,6 +584,7 @@ pub mod arbitraries {
             check_retry_count in any::<u64>(),
             minimized_stack_depth in option::of(any::<usize>()),
             check_queue in any::<bool>(),
+            classification_rules in Just(None),
             common in arb_common_config(),
         ) -> report::dotnet::generic::Config {
             report::dotnet::generic::Config {
@@ -367,6 +602,7 @@ pub mod arbitraries {
                 check_retry_count,
                 minimized_stack_depth,
                 check_queue,
+                classification_rules,
                 common,
             }
         }
@@ -386,6 +622,8 @@ pub mod arbitraries {
             generator_exe in Just("src/lib.rs".to_string()),
             generator_env in prop::collection::hash_map(".*", ".*", 10),
             generator_options in arb_string_vec_no_vars(),
+            generators in Just(vec![]),
+            rotation in Just(Default::default()),
             readonly_inputs in prop::collection::vec(arb_synced_dir(), 10),
             crashes in arb_synced_dir(),
             tools in option::of(arb_synced_dir()),
@@ -398,12 +636,21 @@ pub mod arbitraries {
             check_retry_count in any::<u64>(),
             rename_output in any::<bool>(),
             ensemble_sync_delay in option::of(any::<u64>()),
+            input_extension in option::of("[a-z]{1,4}"),
+            input_via in Just(Default::default()),
+            reject_inputs_over_bytes in option::of(any::<u64>()),
+            feedback in any::<bool>(),
+            target_nice in option::of(-20i32..=19),
+            expected_exit_codes in prop::collection::vec(1i32..=255, 0..4),
+            pre_exec_transform in option::of(prop::collection::vec(".*", 1..3)),
             common in arb_common_config(),
         ) -> fuzz::generator::Config {
             fuzz::generator::Config {
                 generator_exe,
                 generator_env,
                 generator_options,
+                generators,
+                rotation,
                 readonly_inputs,
                 crashes,
                 tools,
@@ -416,6 +663,13 @@ pub mod arbitraries {
                 check_retry_count,
                 rename_output,
                 ensemble_sync_delay,
+                input_extension,
+                input_via,
+                reject_inputs_over_bytes,
+                feedback,
+                target_nice,
+                expected_exit_codes,
+                pre_exec_transform,
                 common,
             }
         }
@@ -69,6 +69,13 @@ pub fn build_coverage_config(
         input_queue,
         readonly_inputs,
         coverage,
+        inputs_filter: None,
+        min_coverage_percent: None,
+        incremental_coverage: false,
+        coverage_shards: None,
+        per_input_coverage: false,
+        baseline_for_diff: None,
+        coverage_output_format: Default::default(),
         common,
     };
 
@@ -189,6 +196,13 @@ impl Template<Coverage> for Coverage {
             },
             coverage_filter: None,
             coverage: context.to_monitored_sync_dir("coverage", self.coverage.clone())?,
+            inputs_filter: None,
+            min_coverage_percent: None,
+            incremental_coverage: false,
+            coverage_shards: None,
+            per_input_coverage: false,
+            baseline_for_diff: None,
+            coverage_output_format: Default::default(),
             module_allowlist: self.module_allowlist.clone(),
             source_allowlist: self.source_allowlist.clone(),
         };
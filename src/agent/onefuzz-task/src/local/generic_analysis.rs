@@ -82,6 +82,12 @@ impl Template<Analysis> for Analysis {
                 .no_repro
                 .as_ref()
                 .and_then(|path| context.to_monitored_sync_dir("no_repro", path).ok()),
+            shared_work_queue: None,
+            dead_letter_queue: None,
+            run_target_as_user: None,
+            continue_on_error: true,
+            crash_reports: None,
+            include_report: false,
 
             common: CommonConfig {
                 task_id: uuid::Uuid::new_v4(),
@@ -60,6 +60,8 @@ impl Template<Generator> for Generator {
             generator_exe: self.generator_exe.clone(),
             generator_env: self.generator_env.clone(),
             generator_options: self.generator_options.clone(),
+            generators: vec![],
+            rotation: Default::default(),
 
             readonly_inputs: self
                 .readonly_inputs
@@ -86,6 +88,13 @@ impl Template<Generator> for Generator {
 
             rename_output: self.rename_output,
             ensemble_sync_delay: self.ensemble_sync_delay,
+            input_extension: None,
+            input_via: Default::default(),
+            reject_inputs_over_bytes: None,
+            feedback: false,
+            target_nice: None,
+            expected_exit_codes: vec![],
+            pre_exec_transform: None,
             common: CommonConfig {
                 task_id: uuid::Uuid::new_v4(),
                 ..context.common.clone()
@@ -93,7 +93,43 @@ impl Template<LibfuzzerCrashReport> for LibfuzzerCrashReport {
             check_fuzzer_help: self.check_fuzzer_help,
             check_retry_count: self.check_retry_count,
             minimized_stack_depth: self.minimized_stack_depth,
+            stack_hash_frame_count: None,
             check_queue: self.check_queue,
+            symbol_server_url: None,
+            classification_rules: None,
+            report_format: Default::default(),
+            report_date_partitioning: false,
+            upload_queue_depth: None,
+            normalize_input_permissions: None,
+            seccomp_profile: None,
+            clusterfuzz_signature: false,
+            fresh_process_per_crash: false,
+            poll_interval_seconds: None,
+            max_poll_interval_seconds: None,
+            reject_inputs_over_bytes: None,
+            verify_input_checksum: false,
+            report_webhook: None,
+            report_webhook_secret: None,
+            report_notification_queue: None,
+            severity_overrides: Default::default(),
+            classify_cwe: false,
+            cwe_overrides: Default::default(),
+            target_rss_limit_mb: None,
+            dedup_state_container: None,
+            crash_ttl_days: None,
+            generate_repro_script: false,
+            report_metrics_window_seconds: None,
+            repro_count: None,
+            reproducible_min_ratio: None,
+            flaky_min_ratio: None,
+            tee_target_output: false,
+            capture_module_list: false,
+            known_crashes_container: None,
+            pre_exec_transform: None,
+            rss_limit_mb: None,
+            target_nice: None,
+            auxiliary_files: vec![],
+            run_target_as_user: None,
             common: CommonConfig {
                 task_id: uuid::Uuid::new_v4(),
                 ..context.common.clone()
@@ -59,6 +59,35 @@ pub fn build_fuzz_config(
         ensemble_sync_delay,
         check_fuzzer_help,
         expect_crash_on_failure,
+        only_unique_crashes: false,
+        max_runs: None,
+        smoke_test_inputs: None,
+        stop_after_crashes: None,
+        require_instrumentation: false,
+        focus_function: None,
+        rss_limit_mb: None,
+        target_nice: None,
+        auxiliary_files: vec![],
+        normalize_input_permissions: None,
+        seccomp_profile: None,
+        max_corpus_size: None,
+        use_value_profile: None,
+        entropic: None,
+        cross_over: None,
+        verify_input_checksum: false,
+        corpus_archive_output: None,
+        corpus_archive_only: false,
+        target_rss_limit_mb: None,
+        crash_ttl_days: None,
+        chunk_large_inputs_over_bytes: None,
+        large_crashes: None,
+        replay_only: false,
+        corpus_minimize_only: false,
+        record_with_rr: false,
+        corpus_layout: Default::default(),
+        tee_target_output: false,
+        run_target_as_user: None,
+        corpus_sync_interval_seconds: None,
         common,
         extra: (),
     };
@@ -67,6 +67,10 @@ impl Template<LibfuzzerMerge> for LibfuzzerMerge {
             preserve_existing_outputs: self.preserve_existing_outputs,
 
             check_fuzzer_help: self.check_fuzzer_help,
+            poll_interval_seconds: None,
+            max_poll_interval_seconds: None,
+            max_output_inputs: None,
+            merge_control_container: None,
 
             common: CommonConfig {
                 task_id: uuid::Uuid::new_v4(),
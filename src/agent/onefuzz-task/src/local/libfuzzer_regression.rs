@@ -94,6 +94,7 @@ impl Template<LibfuzzerRegression> for LibfuzzerRegression {
             check_fuzzer_help: self.check_fuzzer_help,
             check_retry_count: self.check_retry_count,
             minimized_stack_depth: self.minimized_stack_depth,
+            summary_output: None,
 
             common: CommonConfig {
                 task_id: uuid::Uuid::new_v4(),
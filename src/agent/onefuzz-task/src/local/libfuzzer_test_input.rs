@@ -45,6 +45,7 @@ impl Template<LibfuzzerTestInput> for LibfuzzerTestInput {
             let libfuzzer_test_input = crate::tasks::report::libfuzzer_report::TestInputArgs {
                 input_url: None,
                 input: c.input.as_path(),
+                transformed_input: None,
                 target_exe: c.target_exe.as_path(),
                 target_options: &c.target_options,
                 target_env: &c.target_env,
@@ -56,11 +57,20 @@ impl Template<LibfuzzerTestInput> for LibfuzzerTestInput {
                 target_timeout: c.target_timeout,
                 check_retry_count: c.check_retry_count,
                 minimized_stack_depth: c.minimized_stack_depth,
+                stack_hash_frame_count: None,
                 machine_identity: MachineIdentity {
                     machine_id: uuid::Uuid::new_v4(),
                     machine_name: "local".to_string(),
                     scaleset_name: None,
                 },
+                seccomp_filter: None,
+                target_user: None,
+                fresh_state: false,
+                auxiliary_files: &[],
+                memory_limit_mb: None,
+                nice: None,
+                tee_output: false,
+                capture_module_list: false,
             };
 
             crate::tasks::report::libfuzzer_report::test_input(libfuzzer_test_input)
@@ -52,6 +52,7 @@ impl Template<TestInput> for TestInput {
             let libfuzzer_test_input = crate::tasks::report::generic::TestInputArgs {
                 input_url: None,
                 input: c.input.as_path(),
+                transformed_input: None,
                 target_exe: c.target_exe.as_path(),
                 target_options: &c.target_options,
                 target_env: &c.target_env,
@@ -64,11 +65,24 @@ impl Template<TestInput> for TestInput {
                 check_asan_log: c.check_asan_log,
                 check_debugger: c.check_debugger,
                 minimized_stack_depth: c.minimized_stack_depth,
+                stack_hash_frame_count: None,
                 machine_identity: MachineIdentity {
                     machine_id: uuid::Uuid::new_v4(),
                     machine_name: "local".to_string(),
                     scaleset_name: None,
                 },
+                input_via: Default::default(),
+                seccomp_filter: None,
+                target_user: None,
+                fresh_state: false,
+                working_dir: None,
+                auxiliary_files: &[],
+                expected_exit_codes: &[],
+                memory_limit_mb: None,
+                nice: None,
+                input_placeholder: None,
+                tee_output: false,
+                capture_module_list: false,
             };
 
             crate::tasks::report::generic::test_input(libfuzzer_test_input)
@@ -9,12 +9,17 @@ use ipc_channel::ipc::{self, IpcReceiver, IpcSender};
 use flexi_logger::{Duplicate, FileSpec, Logger, WriteMode};
 use onefuzz::ipc::IpcMessageKind;
 use onefuzz_telemetry::{error, info, warn};
+use reqwest::Url;
 use std::time::Duration;
 use tokio::task;
 
-use onefuzz_task_lib::tasks::config::{CommonConfig, Config};
+use onefuzz_task_lib::tasks::{
+    config::{CommonConfig, Config},
+    error::{self, TaskError},
+};
 
 const OOM_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
 
 pub async fn run(args: &clap::ArgMatches) -> Result<()> {
     let _logger = Logger::try_with_env_or_str("info")?
@@ -50,7 +55,15 @@ pub async fn run(args: &clap::ArgMatches) -> Result<()> {
         .get_one::<PathBuf>(EXTRA_SETUP_DIR_ARG)
         .map(ToOwned::to_owned);
 
-    let config = Config::from_file(config_path, setup_dir.clone(), extra_setup_dir)?;
+    // Accept a URL in place of a path, so the config needn't be mounted.
+    let config_url = config_path
+        .to_str()
+        .and_then(|path| Url::parse(path).ok())
+        .filter(|url| matches!(url.scheme(), "https" | "file"));
+    let config = match config_url {
+        Some(url) => Config::from_url(url, setup_dir.clone(), extra_setup_dir).await?,
+        None => Config::from_file(config_path, setup_dir.clone(), extra_setup_dir)?,
+    };
 
     info!("Creating channel from agent to task");
     let (agent_sender, receive_from_agent): (
@@ -74,8 +87,17 @@ pub async fn run(args: &clap::ArgMatches) -> Result<()> {
     info!("Sending receiver to agent");
     oneshot_receiver.send(receive_from_task)?;
 
+    let pause = config.common().pause.clone();
     let shutdown_listener = task::spawn_blocking(move || loop {
         match receive_from_agent.recv() {
+            Ok(IpcMessageKind::Pause) => {
+                info!("Pausing task at the request of the agent");
+                pause.pause();
+            }
+            Ok(IpcMessageKind::Resume) => {
+                info!("Resuming task at the request of the agent");
+                pause.resume();
+            }
             Ok(msg) => info!("Received unexpected message from agent: {:?}", msg),
             Err(ipc::IpcError::Disconnected) => {
                 info!("Agent disconnected from the IPC channel. Shutting down");
@@ -93,29 +115,42 @@ pub async fn run(args: &clap::ArgMatches) -> Result<()> {
     });
 
     init_telemetry(config.common()).await;
+    super::panic_hook::install(config.common());
 
     let min_available_memory_bytes = 1_000_000 * config.common().min_available_memory_mb;
+    let cancellation_token = config.common().cancellation_token.clone();
 
-    let result = match min_available_memory_bytes {
-        0 => {
+    // With the watchdog disabled, never report running out of memory.
+    let check_oom = async {
+        if min_available_memory_bytes == 0 {
             log::info!("memory watchdog is disabled: this task may fail suddenly if it runs out of memory.");
-            config.run().await
+            std::future::pending().await
+        } else {
+            out_of_memory(min_available_memory_bytes).await
         }
-        _ => {
-            // If the memory limit is 0, this will never return.
-            let check_oom = out_of_memory(min_available_memory_bytes);
-
-            tokio::select! {
-                result = config.run() => result,
-
-                // Ignore this task if it returns due to a querying error.
-                Ok(oom) = check_oom => {
-                    // Convert the OOM notification to an error, so we can log it below.
-                    let err = anyhow::format_err!("out of memory: {} bytes available, {} required", oom.available_bytes, oom.min_bytes);
-                    Err(err)
-                },
-
-                _shutdown = shutdown_listener => {
+    };
+    let run = config.run();
+    tokio::pin!(run);
+
+    let result = tokio::select! {
+        result = &mut run => result,
+
+        // Ignore this task if it returns due to a querying error.
+        Ok(oom) = check_oom => {
+            // Convert the OOM notification to an error, so we can log it below.
+            let err = anyhow::format_err!("out of memory: {} bytes available, {} required", oom.available_bytes, oom.min_bytes);
+            let result = Err(TaskError::ResourceExhausted(err));
+            error::report_task_stop(&result);
+            result
+        },
+
+        _shutdown = shutdown_listener => {
+            // Give in-flight syncs a chance to finish the current file.
+            cancellation_token.cancel();
+            match tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, &mut run).await {
+                Ok(result) => result,
+                Err(_) => {
+                    warn!("task did not stop within {:?} of shutdown", SHUTDOWN_GRACE_PERIOD);
                     Ok(())
                 }
             }
@@ -123,12 +158,12 @@ pub async fn run(args: &clap::ArgMatches) -> Result<()> {
     };
 
     if let Err(err) = &result {
-        error!("error running task: {:?}", err);
+        error!("error running task ({}): {:?}", err.category(), err.inner());
     }
 
     onefuzz_telemetry::try_flush_and_close().await;
 
-    result
+    result.map_err(Into::into)
 }
 
 const MAX_OOM_QUERY_ERRORS: usize = 5;
@@ -1 +1,2 @@
 pub mod cmd;
+mod panic_hook;
@@ -0,0 +1,135 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Report panics, which would otherwise only be noticed as missing heartbeats.
+//!
+//! The hook runs on the panicking thread, which may hold any lock, including
+//! the telemetry and heartbeat locks, and may be a runtime worker that can't
+//! block on a future. So it only writes to stderr, skips telemetry clients
+//! that are in use, and sends the final heartbeat from a thread and runtime of
+//! its own, rather than through the task's heartbeat client. The hook waits a
+//! bounded time for that thread, and leaves it behind if the send is stuck.
+
+use std::backtrace::Backtrace;
+use std::panic::PanicInfo;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::Result;
+use onefuzz_task_lib::tasks::{config::CommonConfig, heartbeat::send_failed_heartbeat};
+use onefuzz_telemetry::{Event, EventData};
+use reqwest::Url;
+use uuid::Uuid;
+
+// How long the hook waits for the final heartbeat before letting the panic
+// continue.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Set by the first panic, so that only it is reported.
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone)]
+struct FailedHeartbeat {
+    queue_url: Url,
+    task_id: Uuid,
+    job_id: Uuid,
+    machine_id: Uuid,
+    machine_name: String,
+}
+
+/// Report the first panic in telemetry, with a backtrace, and send a
+/// `TaskFailed` heartbeat, before the default panic hook runs.
+pub fn install(config: &CommonConfig) {
+    let heartbeat = config
+        .heartbeat_queue
+        .clone()
+        .map(|queue_url| FailedHeartbeat {
+            queue_url,
+            task_id: config.task_id,
+            job_id: config.job_id,
+            machine_id: config.machine_identity.machine_id,
+            machine_name: config.machine_name().to_owned(),
+        });
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if !PANICKED.swap(true, Ordering::SeqCst) {
+            report_panic(info, heartbeat.as_ref());
+        }
+        default_hook(info);
+    }));
+}
+
+fn report_panic(info: &PanicInfo, heartbeat: Option<&FailedHeartbeat>) {
+    let message = panic_message(info);
+    let backtrace = Backtrace::force_capture().to_string();
+    onefuzz_telemetry::try_track_event_and_flush(
+        &Event::task_panic,
+        &[
+            EventData::PanicMessage(message),
+            EventData::Backtrace(backtrace),
+        ],
+    );
+
+    if let Some(heartbeat) = heartbeat {
+        if let Err(err) = send_heartbeat(heartbeat.clone()) {
+            eprintln!("failed to send failed heartbeat after panic: {err:?}");
+        }
+    }
+}
+
+fn send_heartbeat(heartbeat: FailedHeartbeat) -> Result<()> {
+    let (done, result) = mpsc::sync_channel(1);
+
+    // Detached, so that a send stuck outside the runtime, such as in runtime
+    // setup, can't hold up the panic.
+    std::thread::Builder::new()
+        .name("panic-heartbeat".into())
+        .spawn(move || {
+            let sent = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(anyhow::Error::from)
+                .and_then(|runtime| {
+                    runtime.block_on(async {
+                        let send = send_failed_heartbeat(
+                            heartbeat.queue_url,
+                            heartbeat.task_id,
+                            heartbeat.job_id,
+                            heartbeat.machine_id,
+                            heartbeat.machine_name,
+                        );
+                        tokio::time::timeout(HEARTBEAT_TIMEOUT, send).await?
+                    })
+                });
+
+            // The hook may have stopped waiting, in which case there is no one
+            // left to tell.
+            let _ = done.send(sent);
+        })?;
+
+    match result.recv_timeout(HEARTBEAT_TIMEOUT) {
+        Ok(sent) => sent,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            anyhow::bail!("timed out after {HEARTBEAT_TIMEOUT:?}")
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            anyhow::bail!("heartbeat thread panicked")
+        }
+    }
+}
+
+fn panic_message(info: &PanicInfo) -> String {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+
+    match info.location() {
+        Some(location) => format!("{message} at {location}"),
+        None => message.to_owned(),
+    }
+}
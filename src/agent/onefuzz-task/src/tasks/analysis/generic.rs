@@ -2,29 +2,52 @@
 // Licensed under the MIT License.
 
 use crate::tasks::{
-    config::CommonConfig, heartbeat::HeartbeatSender, report::crash_report::monitor_reports,
-    utils::try_resolve_setup_relative_path,
+    config::CommonConfig,
+    error::TaskError,
+    heartbeat::HeartbeatSender,
+    lifecycle,
+    report::{crash_report::monitor_reports, generic::DeadLetter},
+    utils::{default_bool_true, resolve_target_user, try_resolve_setup_relative_path},
 };
 use anyhow::{Context, Result};
-use onefuzz::{az_copy, blob::url::BlobUrl};
+use onefuzz::{
+    az_copy,
+    blob::{store, url::BlobUrl},
+};
 use onefuzz::{
     expand::Expand,
     fs::{set_executable, OwnedDir},
     jitter::delay_with_jitter,
     process::monitor_process,
+    sha256,
     syncdir::SyncedDir,
 };
+use onefuzz_telemetry::LifecycleState;
+use reqwest::Url;
 use serde::Deserialize;
 use std::process::Stdio;
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
     str,
+    sync::Mutex,
+    time::Duration,
 };
-use storage_queue::{QueueClient, EMPTY_QUEUE_DELAY};
+use storage_queue::{Message, QueueClient, EMPTY_QUEUE_DELAY};
 use tempfile::tempdir_in;
 use tokio::{fs, process::Command};
 
+// How long an input claimed from the shared work queue is hidden from other
+// nodes. If the claiming node dies, the input is reprocessed after this.
+const SHARED_WORK_VISIBILITY_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+// How many times an input is claimed from the shared work queue before it is
+// given up on, should its analysis keep failing.
+const SHARED_WORK_MAX_DEQUEUE_COUNT: u64 = 5;
+
+// Set to the path of the input's crash report, with `include_report`.
+const CRASH_REPORT_ENV: &str = "ONEFUZZ_CRASH_REPORT";
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub analyzer_exe: String,
@@ -43,6 +66,40 @@ pub struct Config {
     pub unique_reports: Option<SyncedDir>,
     pub no_repro: Option<SyncedDir>,
 
+    /// Queue of input URLs shared by every node running this task. If set,
+    /// each node claims inputs from it instead of scanning all of `crashes`,
+    /// so that the nodes analyze each input once between them.
+    #[serde(default)]
+    pub shared_work_queue: Option<Url>,
+
+    /// Where inputs from `shared_work_queue` are sent once they have failed
+    /// to be analyzed too many times. They are dropped if unset.
+    #[serde(default)]
+    pub dead_letter_queue: Option<QueueClient>,
+
+    /// Run the analyzer as this user, rather than as the agent's user, so
+    /// that it doesn't run as root. Directories the analyzer writes to are
+    /// given to the user. Linux only.
+    #[serde(default)]
+    pub run_target_as_user: Option<String>,
+
+    /// Log inputs that the analyzer fails on and move on to the next input,
+    /// rather than stopping the task. Failures to start the analyzer at all
+    /// still stop the task. Defaults to `true`.
+    #[serde(default = "default_bool_true")]
+    pub continue_on_error: bool,
+
+    /// Crash reports written by the report task, named by the SHA-256 of their
+    /// input, as in its `reports` container. Required by `include_report`.
+    #[serde(default)]
+    pub crash_reports: Option<SyncedDir>,
+
+    /// Pass the analyzer the path of the input's crash report from
+    /// `crash_reports`, in the `ONEFUZZ_CRASH_REPORT` environment variable.
+    /// The variable is unset for inputs without a report.
+    #[serde(default)]
+    pub include_report: bool,
+
     #[serde(flatten)]
     pub common: CommonConfig,
 }
@@ -74,6 +131,36 @@ impl Config {
     }
 }
 
+/// Inputs that the analyzer failed on, when `continue_on_error` is set.
+#[derive(Default)]
+pub struct FailedInputs(Mutex<Vec<PathBuf>>);
+
+impl FailedInputs {
+    fn record(&self, input: &Path, err: &anyhow::Error) {
+        warn!(
+            "analyzer failed on input, continuing: {}: {:?}",
+            input.display(),
+            err
+        );
+        self.0.lock().unwrap().push(input.to_owned());
+    }
+
+    /// Log the inputs that failed since the last summary.
+    fn log_summary(&self) {
+        let failed = std::mem::take(&mut *self.0.lock().unwrap());
+        if failed.is_empty() {
+            return;
+        }
+
+        let names = failed
+            .iter()
+            .map(|input| input.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        warn!("analyzer failed on {} inputs: {}", failed.len(), names);
+    }
+}
+
 pub async fn run(config: Config) -> Result<()> {
     let task_dir = config
         .analysis
@@ -88,8 +175,27 @@ pub async fn run(config: Config) -> Result<()> {
     tmp.reset().await?;
 
     config.analysis.init().await?;
+
+    let target_user = resolve_target_user(config.run_target_as_user.as_deref())?;
+    if let Some(user) = &target_user {
+        user.grant_dir(tmp.path())?;
+        user.grant_dir(&config.analysis.local_path)?;
+    }
+    if config.include_report {
+        let Some(crash_reports) = &config.crash_reports else {
+            return Err(
+                TaskError::Configuration(anyhow!("include_report requires crash_reports")).into(),
+            );
+        };
+        crash_reports
+            .init_pull(&config.common.cancellation_token)
+            .await?;
+        if let Some(user) = &target_user {
+            user.grant_dir(&crash_reports.local_path)?;
+        }
+    }
     if let Some(tools) = &config.tools {
-        tools.init_pull().await?;
+        tools.init_pull(&config.common.cancellation_token).await?;
     }
 
     let job_result_client = config.common.init_job_result().await?;
@@ -99,7 +205,10 @@ pub async fn run(config: Config) -> Result<()> {
     // report SyncedDir. The idea is that the option for where to write reports
     // is only available for target option / env expansion if one of the reports
     // SyncedDir is provided.
-    let reports_dir = tempdir_in(temp_path)?;
+    let reports_dir = tempdir_in(&temp_path)?;
+    if let Some(user) = &target_user {
+        user.grant_dir(reports_dir.path())?;
+    }
     let (reports_path, reports_monitor_future) =
         if config.unique_reports.is_some() || config.reports.is_some() || config.no_repro.is_some()
         {
@@ -130,8 +239,31 @@ pub async fn run(config: Config) -> Result<()> {
     if let Some(tools) = &config.tools {
         set_executable(&tools.local_path).await?;
     }
-    run_existing(&config, &reports_path).await?;
-    let poller = poll_inputs(&config, tmp, &reports_path);
+    lifecycle::transition(LifecycleState::Ready);
+    let failed = FailedInputs::default();
+    let poller = async {
+        match &config.shared_work_queue {
+            Some(url) => {
+                let queue = QueueClient::new(url.clone())?;
+                let work_tmp = tempdir_in(&temp_path)?;
+                let work_dir = OwnedDir::new(work_tmp.path());
+                work_dir.reset().await?;
+                if let Some(user) = &target_user {
+                    user.grant_dir(work_dir.path())?;
+                }
+
+                futures::try_join!(
+                    poll_shared_work(&config, &queue, work_dir, &reports_path, &failed),
+                    poll_inputs(&config, tmp, &reports_path, &failed)
+                )?;
+                Ok(())
+            }
+            None => {
+                run_existing(&config, &reports_path, &failed).await?;
+                poll_inputs(&config, tmp, &reports_path, &failed).await
+            }
+        }
+    };
 
     match reports_monitor_future {
         Some(monitor) => {
@@ -141,28 +273,40 @@ pub async fn run(config: Config) -> Result<()> {
             poller.await?;
         }
     };
+    failed.log_summary();
 
     Ok(())
 }
 
-async fn run_existing(config: &Config, reports_dir: &Option<PathBuf>) -> Result<()> {
+async fn run_existing(
+    config: &Config,
+    reports_dir: &Option<PathBuf>,
+    failed: &FailedInputs,
+) -> Result<()> {
     if let Some(crashes) = &config.crashes {
         info!("processing initial inputs");
-        crashes.init_pull().await?;
+        crashes.init_pull(&config.common.cancellation_token).await?;
         let mut count: u64 = 0;
         let mut read_dir = fs::read_dir(&crashes.local_path).await?;
         while let Some(file) = read_dir.next_entry().await? {
             debug!("Processing file {:?}", file);
-            run_tool(file.path(), config, reports_dir).await?;
+            run_tool(file.path(), config, reports_dir, failed).await?;
             count += 1;
 
             // sync the analysis container after every 10 inputs
             if count % 10 == 0 {
-                config.analysis.sync_push().await?;
+                config
+                    .analysis
+                    .sync_push(&config.common.cancellation_token)
+                    .await?;
             }
         }
         info!("processed {} initial inputs", count);
-        config.analysis.sync_push().await?;
+        failed.log_summary();
+        config
+            .analysis
+            .sync_push(&config.common.cancellation_token)
+            .await?;
     }
     Ok(())
 }
@@ -183,6 +327,7 @@ async fn poll_inputs(
     config: &Config,
     tmp_dir: OwnedDir,
     reports_dir: &Option<PathBuf>,
+    failed: &FailedInputs,
 ) -> Result<()> {
     info!("polling for new inputs");
     let heartbeat = config.common.init_heartbeat(None).await?;
@@ -196,8 +341,11 @@ async fn poll_inputs(
                 if !already_checked(config, &input_url).await? {
                     let destination_path = _copy(input_url, &tmp_dir).await?;
 
-                    run_tool(destination_path, config, reports_dir).await?;
-                    config.analysis.sync_push().await?
+                    run_tool(destination_path, config, reports_dir, failed).await?;
+                    config
+                        .analysis
+                        .sync_push(&config.common.cancellation_token)
+                        .await?
                 }
                 message.delete().await?;
             } else {
@@ -210,25 +358,134 @@ async fn poll_inputs(
     Ok(())
 }
 
+/// Analyze inputs claimed from `queue` until the task is stopped.
+///
+/// An input's message is deleted once its analysis is pushed, so the input is
+/// reclaimed by another node if this one fails first. An input which fails
+/// `SHARED_WORK_MAX_DEQUEUE_COUNT` times is dead-lettered instead.
+async fn poll_shared_work(
+    config: &Config,
+    queue: &QueueClient,
+    work_dir: OwnedDir,
+    reports_dir: &Option<PathBuf>,
+    failed: &FailedInputs,
+) -> Result<()> {
+    info!("claiming inputs from shared work queue");
+    let heartbeat = config.common.init_heartbeat(None).await?;
+    loop {
+        heartbeat.alive();
+        let Some(message) = queue
+            .pop_with_visibility_timeout(SHARED_WORK_VISIBILITY_TIMEOUT)
+            .await?
+        else {
+            debug!("shared work queue is empty, sleeping");
+            delay_with_jitter(EMPTY_QUEUE_DELAY).await;
+            continue;
+        };
+
+        match analyze_shared_input(config, &message, &work_dir, reports_dir, failed).await {
+            Ok(()) => message.delete().await?,
+            Err(err) if message.dequeue_count() >= SHARED_WORK_MAX_DEQUEUE_COUNT => {
+                dead_letter(config, &message, &err).await?;
+                message.delete().await?;
+            }
+            Err(err) => {
+                warn!(
+                    "failed to analyze shared input {}, leaving it to be retried: {:?}",
+                    message.id(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+async fn analyze_shared_input(
+    config: &Config,
+    message: &Message,
+    work_dir: &OwnedDir,
+    reports_dir: &Option<PathBuf>,
+    failed: &FailedInputs,
+) -> Result<()> {
+    let input_url = message
+        .parse(|data| BlobUrl::parse(str::from_utf8(data)?))
+        .with_context(|| format!("unable to parse URL from queue: {message:?}"))?;
+    let destination_path = _copy(input_url, work_dir).await?;
+
+    let result = async {
+        run_tool(&destination_path, config, reports_dir, failed).await?;
+        config
+            .analysis
+            .sync_push(&config.common.cancellation_token)
+            .await
+    }
+    .await;
+
+    if let Err(err) = fs::remove_file(&destination_path).await {
+        warn!(
+            "unable to remove analyzed input {}: {}",
+            destination_path.display(),
+            err
+        );
+    }
+    result
+}
+
+async fn dead_letter(config: &Config, message: &Message, err: &anyhow::Error) -> Result<()> {
+    let input = message
+        .parse(|data| Ok(String::from_utf8_lossy(data).into_owned()))
+        .unwrap_or_default();
+    warn!(
+        "giving up on shared input {} after {} attempts: {:?}",
+        input,
+        message.dequeue_count(),
+        err
+    );
+
+    if let Some(dead_letter_queue) = &config.dead_letter_queue {
+        let dead_letter = DeadLetter {
+            input_url: Url::parse(&input).ok(),
+            input,
+            error: format!("{err:?}"),
+        };
+        dead_letter_queue
+            .enqueue(dead_letter)
+            .await
+            .context("dead-lettering input failed")?;
+    }
+    Ok(())
+}
+
 async fn _copy(input_url: BlobUrl, destination_folder: &OwnedDir) -> Result<PathBuf> {
     let file_name = input_url.name();
     let mut destination_path = PathBuf::from(destination_folder.path());
     destination_path.push(file_name);
-    match input_url {
+    match &input_url {
         BlobUrl::AzureBlob(input_url) => {
             az_copy::copy(input_url.as_ref(), &destination_path, false).await?
         }
         BlobUrl::LocalFile(path) => {
             tokio::fs::copy(path, &destination_path).await?;
         }
+        BlobUrl::S3Object(_) => {
+            let container = input_url
+                .container_url()
+                .ok_or_else(|| format_err!("no container for input: {}", input_url))?;
+            store::open(&container)?
+                .download(&input_url.name(), &destination_path)
+                .await?;
+        }
     }
     Ok(destination_path)
 }
 
+/// Run the analyzer on `input`. If it fails and `continue_on_error` is set,
+/// the failure is recorded in `failed` instead of being returned.
 pub async fn run_tool(
     input: impl AsRef<Path>,
     config: &Config,
     reports_dir: &Option<PathBuf>,
+    failed: &FailedInputs,
 ) -> Result<()> {
     let target_exe =
         try_resolve_setup_relative_path(&config.common.setup_dir, &config.target_exe).await?;
@@ -242,6 +499,9 @@ pub async fn run_tool(
     let analyzer_path = expand.evaluate_value(&config.analyzer_exe)?;
 
     let mut cmd = Command::new(&analyzer_path);
+    if let Some(user) = resolve_target_user(config.run_target_as_user.as_deref())? {
+        user.apply_async(&mut cmd);
+    }
     cmd.kill_on_drop(true)
         .env_remove("RUST_LOG")
         .stdin(Stdio::null())
@@ -256,15 +516,50 @@ pub async fn run_tool(
         cmd.env(k, expand.evaluate_value(v)?);
     }
 
+    if config.include_report {
+        if let Some(report) = find_crash_report(config, input.as_ref()).await? {
+            cmd.env(CRASH_REPORT_ENV, report);
+        }
+    }
+
     info!("analyzing input with {:?}", cmd);
     let output = cmd
         .spawn()
         .with_context(|| format!("analyzer failed to start: {analyzer_path}"))?;
 
-    monitor_process(output, "analyzer".to_string(), true, None)
+    let result = monitor_process(output, "analyzer".to_string(), true, None)
         .await
-        .with_context(|| format!("analyzer failed to run: {analyzer_path}"))?;
-    Ok(())
+        .with_context(|| format!("analyzer failed to run: {analyzer_path}"));
+    match result {
+        Err(err) if config.continue_on_error => {
+            failed.record(input.as_ref(), &err);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// The crash report for `input` in `crash_reports`, if any. Reports written
+/// since the container was last pulled are pulled if it isn't found.
+async fn find_crash_report(config: &Config, input: &Path) -> Result<Option<PathBuf>> {
+    let Some(crash_reports) = &config.crash_reports else {
+        return Ok(None);
+    };
+
+    let digest = sha256::digest_file(input).await?;
+    let report = crash_reports.local_path.join(format!("{digest}.json"));
+    if !report.is_file() {
+        crash_reports
+            .sync_pull(&config.common.cancellation_token)
+            .await?;
+    }
+
+    if report.is_file() {
+        Ok(Some(report))
+    } else {
+        debug!("no crash report for input: {}", input.display());
+        Ok(None)
+    }
 }
 
 #[cfg(test)]
@@ -8,6 +8,7 @@ use onefuzz::{
     monitor::DirectoryMonitor,
     syncdir::SyncedDir,
 };
+use onefuzz_telemetry::LifecycleState;
 use reqwest::Url;
 use std::{
     collections::HashMap,
@@ -19,13 +20,16 @@ use std::{env, process::ExitStatus};
 use storage_queue::{Message, QueueClient};
 use tokio::{fs, process::Command, time::timeout};
 use tokio_stream::wrappers::ReadDirStream;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 use crate::tasks::{
     config::CommonConfig,
     coverage::COBERTURA_COVERAGE_FILE,
+    error::TaskError,
     generic::input_poller::{CallbackImpl, InputPoller, Processor},
     heartbeat::{HeartbeatSender, TaskHeartbeatClient},
+    lifecycle,
     utils::try_resolve_setup_relative_path,
 };
 
@@ -80,8 +84,9 @@ impl DotnetCoverageTask {
     pub async fn run(&mut self) -> Result<()> {
         info!("starting dotnet_coverage task");
 
-        self.config.tools.init_pull().await?;
-        self.config.coverage.init_pull().await?;
+        let cancellation_token = &self.config.common.cancellation_token;
+        self.config.tools.init_pull(cancellation_token).await?;
+        self.config.coverage.init_pull(cancellation_token).await?;
 
         let dotnet_path = dotnet_path()?;
         let dotnet_coverage_path = dotnet_coverage_path()?;
@@ -95,9 +100,13 @@ impl DotnetCoverageTask {
         );
 
         if !context.uses_input() {
-            bail!("input is not specified on the command line or arguments for the target");
+            return Err(TaskError::Configuration(anyhow!(
+                "input is not specified on the command line or arguments for the target"
+            ))
+            .into());
         }
 
+        lifecycle::transition(LifecycleState::Ready);
         context.heartbeat.alive();
 
         let coverage_local_path = self.config.coverage.local_path.canonicalize()?;
@@ -106,6 +115,7 @@ impl DotnetCoverageTask {
         let timeout = self.config.timeout();
         let coverage_dir = self.config.coverage.clone();
         let dotnet_coverage_path = dotnet_coverage_path;
+        let monitor_cancellation_token = cancellation_token.clone();
 
         tokio::spawn(async move {
             if let Err(e) = start_directory_monitor(
@@ -114,6 +124,7 @@ impl DotnetCoverageTask {
                 timeout,
                 coverage_dir,
                 &dotnet_coverage_path,
+                &monitor_cancellation_token,
             )
             .await
             {
@@ -124,7 +135,7 @@ impl DotnetCoverageTask {
         for dir in &self.config.readonly_inputs {
             debug!("recording coverage for {}", dir.local_path.display());
 
-            dir.init_pull().await?;
+            dir.init_pull(cancellation_token).await?;
             let dir_count = context.record_corpus(&dir.local_path).await?;
 
             info!(
@@ -155,6 +166,7 @@ async fn start_directory_monitor(
     timeout: Duration,
     coverage_dir: SyncedDir,
     dotnet_coverage_path: &Path,
+    cancellation_token: &CancellationToken,
 ) -> Result<()> {
     info!(
         "Starting dotnet coverage intermediate file directory monitor on {}",
@@ -169,6 +181,7 @@ async fn start_directory_monitor(
             timeout,
             coverage_dir.clone(),
             dotnet_coverage_path,
+            cancellation_token,
         )
         .await?;
         info!("Updated and synced coverage");
@@ -366,12 +379,13 @@ pub async fn save_and_sync_coverage(
     timeout: Duration,
     coverage_dir: SyncedDir,
     dotnet_coverage_path: &Path,
+    cancellation_token: &CancellationToken,
 ) -> Result<()> {
     info!("Saving and syncing coverage");
     let mut cmd = command_for_merge(coverage_local_path, dotnet_coverage_path).await?;
     spawn_with_timeout(&mut cmd, timeout).await?;
     info!("Pushing coverage");
-    coverage_dir.sync_push().await?;
+    coverage_dir.sync_push(cancellation_token).await?;
 
     Ok(())
 }
@@ -455,6 +469,7 @@ impl<'a> Processor for TaskContext<'a> {
             self.config.timeout(),
             self.config.coverage.clone(),
             &self.dotnet_coverage_path,
+            &self.config.common.cancellation_token,
         )
         .await?;
 
@@ -1,7 +1,7 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
@@ -29,7 +29,8 @@ use onefuzz_file_format::coverage::{
 use onefuzz_result::job_result::JobResultData;
 use onefuzz_result::job_result::{JobResultSender, TaskJobResultClient};
 use onefuzz_telemetry::{
-    event, warn, Event::coverage_data, Event::coverage_empty, Event::coverage_failed, EventData,
+    event, warn, Event::coverage_below_minimum, Event::coverage_data, Event::coverage_diff,
+    Event::coverage_empty, Event::coverage_failed, EventData, LifecycleState,
 };
 use storage_queue::{Message, QueueClient};
 use tokio::fs;
@@ -39,15 +40,28 @@ use tokio_stream::wrappers::ReadDirStream;
 use url::Url;
 
 use crate::tasks::config::CommonConfig;
+use crate::tasks::error::TaskError;
 use crate::tasks::generic::input_poller::{CallbackImpl, InputPoller, Processor};
 use crate::tasks::heartbeat::{HeartbeatSender, TaskHeartbeatClient};
+use crate::tasks::lifecycle;
 use crate::tasks::utils::try_resolve_setup_relative_path;
 
 use super::COBERTURA_COVERAGE_FILE;
 
+mod baseline;
+mod binary_map;
+mod per_input;
+
+use baseline::Baseline;
+use per_input::PerInputCoverage;
+
 const MAX_COVERAGE_RECORDING_ATTEMPTS: usize = 2;
 const COVERAGE_FILE: &str = "coverage.json";
 const SOURCE_COVERAGE_FILE: &str = "source-coverage.json";
+const COVERED_INPUTS_FILE: &str = "covered-inputs.json";
+const PER_INPUT_COVERAGE_FILE: &str = "per-input-coverage.json";
+const COVERAGE_DIFF_FILE: &str = "coverage-diff.json";
+const COVERAGE_MAP_FILE: &str = "coverage.bin";
 
 const DEFAULT_TARGET_TIMEOUT: Duration = Duration::from_secs(120);
 
@@ -72,10 +86,69 @@ pub struct Config {
     pub readonly_inputs: Vec<SyncedDir>,
     pub coverage: SyncedDir,
 
+    /// Only record coverage for inputs whose file name or SHA-256 digest is
+    /// listed. If unset, all inputs are recorded.
+    #[serde(default)]
+    pub inputs_filter: Option<Vec<String>>,
+
+    /// Fail the task if the percentage of coverable blocks reached by the
+    /// `readonly_inputs` is below this, such as after a change that silently
+    /// loses instrumentation.
+    #[serde(default)]
+    pub min_coverage_percent: Option<f64>,
+
+    /// Only record coverage for inputs which a previous run hasn't, adding to
+    /// the existing coverage rather than replaying the whole corpus. Recorded
+    /// inputs are listed by SHA-256 in `covered-inputs.json` in the coverage
+    /// directory. Without prior coverage, every input is recorded.
+    #[serde(default)]
+    pub incremental_coverage: bool,
+
+    /// Merge the binary coverage files in this container, such as the
+    /// `coverage.json` of each node's shard, into `coverage`, summing the hit
+    /// counts. The merged coverage replaces any existing coverage, so that
+    /// rerunning the merge doesn't count the shards twice. Files in an
+    /// unknown coverage format or version fail the task.
+    #[serde(default)]
+    pub coverage_shards: Option<SyncedDir>,
+
+    /// Also record the blocks that each input reaches first, and save them by
+    /// the input's SHA-256 in `per-input-coverage.json` in the coverage
+    /// directory, for test-impact analysis. Each input is hashed, and the index
+    /// is rewritten with the coverage, so this is off by default. The format
+    /// is described in `coverage/generic/per_input.rs`.
+    #[serde(default)]
+    pub per_input_coverage: bool,
+
+    /// Binary coverage to compare the recorded coverage against, such as the
+    /// `coverage.json` of a previous run. The blocks gained over it are
+    /// reported in the `coverage_diff` event, and saved in
+    /// `coverage-diff.json` in the coverage directory, as described in
+    /// `coverage/generic/baseline.rs`. A baseline that can't be downloaded
+    /// counts as empty, so all coverage is gained.
+    #[serde(default)]
+    pub baseline_for_diff: Option<Url>,
+
+    /// Format to also save the binary coverage in. With `binary`, the blocks
+    /// reached are saved in `coverage.bin` in the coverage directory, a
+    /// compact map that can be memory-mapped, as described in
+    /// `coverage/generic/binary_map.rs`. `coverage.json` is always saved,
+    /// since it keeps hit counts and later runs resume from it.
+    #[serde(default)]
+    pub coverage_output_format: CoverageOutputFormat,
+
     #[serde(flatten)]
     pub common: CommonConfig,
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverageOutputFormat {
+    #[default]
+    Json,
+    Binary,
+}
+
 impl Config {
     pub fn timeout(&self) -> Duration {
         self.target_timeout
@@ -89,6 +162,28 @@ impl Config {
             .target_options(&self.target_options)
             .coverage_dir(&self.coverage.local_path)
     }
+
+    /// Check `input` against `inputs_filter`.
+    ///
+    /// The input is only hashed if its file name is not listed.
+    pub async fn is_input_selected(&self, input: &Path) -> Result<bool> {
+        let filter = match &self.inputs_filter {
+            Some(filter) => filter,
+            None => return Ok(true),
+        };
+
+        if let Some(name) = input.file_name() {
+            let name = name.to_string_lossy();
+            if filter.iter().any(|entry| *entry == name) {
+                return Ok(true);
+            }
+        }
+
+        let digest = onefuzz::sha256::digest_file(input).await?;
+        Ok(filter
+            .iter()
+            .any(|entry| entry.eq_ignore_ascii_case(&digest)))
+    }
 }
 
 pub struct CoverageTask {
@@ -106,22 +201,73 @@ impl CoverageTask {
         info!("starting coverage task");
 
         if self.config.coverage_filter.is_some() {
-            bail!("the `coverage_filter` option for the `coverage` task is deprecated");
+            return Err(TaskError::Configuration(anyhow!(
+                "the `coverage_filter` option for the `coverage` task is deprecated"
+            ))
+            .into());
         }
 
-        self.config.coverage.init_pull().await?;
+        if let Some(min_coverage_percent) = self.config.min_coverage_percent {
+            if !(0.0..=100.0).contains(&min_coverage_percent) {
+                return Err(TaskError::Configuration(anyhow!(
+                    "min_coverage_percent must be between 0 and 100, got {}",
+                    min_coverage_percent
+                ))
+                .into());
+            }
+        }
+
+        if self.config.coverage_shards.is_some() && self.config.incremental_coverage {
+            return Err(TaskError::Configuration(anyhow!(
+                "coverage_shards cannot be used with incremental_coverage"
+            ))
+            .into());
+        }
+
+        self.config
+            .coverage
+            .init_pull(&self.config.common.cancellation_token)
+            .await?;
 
         let coverage_file = self.config.coverage.local_path.join(COVERAGE_FILE);
 
-        let coverage = {
-            if let Ok(text) = fs::read_to_string(&coverage_file).await {
+        let (coverage, prior_coverage) = {
+            if let Some(shards) = &self.config.coverage_shards {
+                shards
+                    .init_pull(&self.config.common.cancellation_token)
+                    .await?;
+                (load_coverage_shards(&shards.local_path).await?, false)
+            } else if let Ok(text) = fs::read_to_string(&coverage_file).await {
                 let json = BinaryCoverageJson::deserialize(&text)?;
-                BinaryCoverage::try_from(json)?
+                (BinaryCoverage::try_from(json)?, true)
             } else {
-                BinaryCoverage::default()
+                (BinaryCoverage::default(), false)
             }
         };
 
+        let covered_inputs = if self.config.incremental_coverage {
+            let path = self.config.coverage.local_path.join(COVERED_INPUTS_FILE);
+            Some(load_covered_inputs(&path, prior_coverage).await?)
+        } else {
+            None
+        };
+
+        let per_input = if self.config.per_input_coverage {
+            let path = self
+                .config
+                .coverage
+                .local_path
+                .join(PER_INPUT_COVERAGE_FILE);
+            Some(PerInputCoverage::load(&path, prior_coverage).await?)
+        } else {
+            None
+        };
+
+        let baseline = match &self.config.baseline_for_diff {
+            Some(url) => Some(Baseline::download(url.clone()).await?),
+            None => None,
+        };
+
         let allowlist = self.load_target_allowlist().await?;
 
         let heartbeat = self.config.common.init_heartbeat(None).await?;
@@ -141,12 +287,19 @@ impl CoverageTask {
             heartbeat,
             job_result,
             target_exe.to_string(),
-        )?;
-
-        if !context.uses_input() {
-            bail!("input is not specified on the command line or arguments for the target");
+        )?
+        .covered_inputs(covered_inputs)
+        .per_input(per_input)
+        .baseline(baseline);
+
+        if self.config.coverage_shards.is_none() && !context.uses_input() {
+            return Err(TaskError::Configuration(anyhow!(
+                "input is not specified on the command line or arguments for the target"
+            ))
+            .into());
         }
 
+        lifecycle::transition(LifecycleState::Ready);
         info!("report initial coverage");
         context.report_coverage_stats().await;
         context.heartbeat.alive();
@@ -154,7 +307,8 @@ impl CoverageTask {
         for dir in &self.config.readonly_inputs {
             debug!("recording coverage for {}", dir.local_path.display());
 
-            dir.init_pull().await?;
+            dir.init_pull(&self.config.common.cancellation_token)
+                .await?;
             let dir_count = context.record_corpus(&dir.local_path).await?;
 
             info!(
@@ -170,6 +324,10 @@ impl CoverageTask {
         context.report_coverage_stats().await;
         context.heartbeat.alive();
 
+        if let Some(min_coverage_percent) = self.config.min_coverage_percent {
+            context.check_min_coverage(min_coverage_percent).await?;
+        }
+
         if let Some(queue) = &self.config.input_queue {
             info!("polling queue for new coverage inputs");
 
@@ -229,6 +387,15 @@ struct TaskContext<'a> {
     heartbeat: Option<TaskHeartbeatClient>,
     job_result: Option<TaskJobResultClient>,
     cache: Arc<DebugInfoCache>,
+
+    /// Digests of the inputs recorded so far, if `incremental_coverage` is set.
+    covered_inputs: Option<BTreeSet<String>>,
+
+    /// Blocks each input covered first, if `per_input_coverage` is set.
+    per_input: Option<PerInputCoverage>,
+
+    /// Coverage to report gains over, if `baseline_for_diff` is set.
+    baseline: Option<Baseline>,
 }
 
 impl<'a> TaskContext<'a> {
@@ -259,9 +426,53 @@ impl<'a> TaskContext<'a> {
             heartbeat,
             job_result,
             cache: Arc::new(cache),
+            covered_inputs: None,
+            per_input: None,
+            baseline: None,
         })
     }
 
+    /// Skip inputs listed in `covered_inputs`, and add each input recorded.
+    pub fn covered_inputs(mut self, covered_inputs: Option<BTreeSet<String>>) -> Self {
+        self.covered_inputs = covered_inputs;
+        self
+    }
+
+    /// Record the blocks each input covers first in `per_input`.
+    pub fn per_input(mut self, per_input: Option<PerInputCoverage>) -> Self {
+        self.per_input = per_input;
+        self
+    }
+
+    /// Report the blocks gained over `baseline`.
+    pub fn baseline(mut self, baseline: Option<Baseline>) -> Self {
+        self.baseline = baseline;
+        self
+    }
+
+    /// Record coverage for `input`, unless it was already recorded with
+    /// `incremental_coverage` set. Returns whether it was recorded.
+    async fn record_new_input(&mut self, input: &Path) -> Result<bool> {
+        let digest = match &self.covered_inputs {
+            Some(covered_inputs) => {
+                let digest = onefuzz::sha256::digest_file(input).await?;
+                if covered_inputs.contains(&digest) {
+                    debug!("skipping already covered input: {}", input.display());
+                    return Ok(false);
+                }
+                Some(digest)
+            }
+            None => None,
+        };
+
+        self.record_input(input).await?;
+
+        if let (Some(covered_inputs), Some(digest)) = (&mut self.covered_inputs, digest) {
+            covered_inputs.insert(digest);
+        }
+        Ok(true)
+    }
+
     pub async fn record_input(&mut self, input: &Path) -> Result<()> {
         debug!("recording coverage for {}", input.display());
         let attempts = MAX_COVERAGE_RECORDING_ATTEMPTS;
@@ -304,7 +515,14 @@ impl<'a> TaskContext<'a> {
             event!(coverage_empty; EventData::Path = input.display().to_string());
             metric!(coverage_empty; 1.0; EventData::Path = input.display().to_string());
         }
+        let digest = match &self.per_input {
+            Some(_) => Some(onefuzz::sha256::digest_file(input).await?),
+            None => None,
+        };
         let mut self_coverage = RwLock::write(&self.coverage).await;
+        if let (Some(per_input), Some(digest)) = (&mut self.per_input, digest) {
+            per_input.record(digest, &self_coverage, &coverage);
+        }
         self_coverage.merge(&coverage);
         Ok(())
     }
@@ -428,22 +646,31 @@ impl<'a> TaskContext<'a> {
             match entry {
                 Ok(entry) => {
                     if entry.file_type().await?.is_file() {
-                        if let Err(e) = self.record_input(&entry.path()).await {
-                            event!(coverage_failed; EventData::Path = entry.path().display().to_string());
-                            metric!(coverage_failed; 1.0; EventData::Path = entry.path().display().to_string());
-                            warn!(
-                                "ignoring error recording coverage for input: {}, error: {}",
-                                entry.path().display(),
-                                e
-                            );
-                        } else {
-                            count += 1;
-
-                            // make sure we save & sync coverage every 10 inputs
-                            if count % 10 == 0 {
-                                self.save_and_sync_coverage().await?;
-                                info!("report coverage");
-                                self.report_coverage_stats().await;
+                        if !self.config.is_input_selected(&entry.path()).await? {
+                            debug!("skipping filtered input: {}", entry.path().display());
+                            continue;
+                        }
+
+                        match self.record_new_input(&entry.path()).await {
+                            Err(e) => {
+                                event!(coverage_failed; EventData::Path = entry.path().display().to_string());
+                                metric!(coverage_failed; 1.0; EventData::Path = entry.path().display().to_string());
+                                warn!(
+                                    "ignoring error recording coverage for input: {}, error: {}",
+                                    entry.path().display(),
+                                    e
+                                );
+                            }
+                            Ok(false) => {}
+                            Ok(true) => {
+                                count += 1;
+
+                                // make sure we save & sync coverage every 10 inputs
+                                if count % 10 == 0 {
+                                    self.save_and_sync_coverage().await?;
+                                    info!("report coverage");
+                                    self.report_coverage_stats().await;
+                                }
                             }
                         }
                     } else {
@@ -476,6 +703,43 @@ impl<'a> TaskContext<'a> {
                 ]),
             )
             .await;
+
+        if let Some(baseline) = &self.baseline {
+            let diff = baseline.diff(&coverage);
+            event!(coverage_diff; Covered = diff.covered, CoverageGained = diff.gained);
+            metric!(coverage_diff; 1.0; Covered = diff.covered, CoverageGained = diff.gained);
+        }
+    }
+
+    /// Fail if the recorded coverage is below `min_coverage_percent`.
+    pub async fn check_min_coverage(&self, min_coverage_percent: f64) -> Result<()> {
+        let coverage = RwLock::read(&self.coverage).await;
+        let coverage_percent = CoverageStats::new(&coverage).rate * 100.0;
+
+        if coverage_percent < min_coverage_percent {
+            event!(
+                coverage_below_minimum;
+                EventData::Coverage = coverage_percent,
+                EventData::MinCoverage = min_coverage_percent
+            );
+            metric!(
+                coverage_below_minimum;
+                1.0;
+                EventData::Coverage = coverage_percent,
+                EventData::MinCoverage = min_coverage_percent
+            );
+            bail!(
+                "coverage of {:.2}% is below min_coverage_percent of {:.2}%",
+                coverage_percent,
+                min_coverage_percent
+            );
+        }
+
+        info!(
+            "coverage of {:.2}% meets min_coverage_percent of {:.2}%",
+            coverage_percent, min_coverage_percent
+        );
+        Ok(())
     }
 
     pub async fn save_coverage(
@@ -523,7 +787,41 @@ impl<'a> TaskContext<'a> {
             &copbertura_file_path,
         )
         .await?;
-        self.config.coverage.sync_push().await?;
+
+        if self.config.coverage_output_format == CoverageOutputFormat::Binary {
+            let path = self.config.coverage.local_path.join(COVERAGE_MAP_FILE);
+            binary_map::save(&*self.coverage.read().await, &path).await?;
+        }
+
+        if let Some(covered_inputs) = &self.covered_inputs {
+            let path = self.config.coverage.local_path.join(COVERED_INPUTS_FILE);
+            let json = serde_json::to_vec(covered_inputs)?;
+            fs::write(&path, json)
+                .await
+                .with_context(|| format!("writing covered inputs {}", path.display()))?;
+        }
+
+        if let Some(per_input) = &self.per_input {
+            let path = self
+                .config
+                .coverage
+                .local_path
+                .join(PER_INPUT_COVERAGE_FILE);
+            per_input.save(&path).await?;
+        }
+
+        if let Some(baseline) = &self.baseline {
+            let path = self.config.coverage.local_path.join(COVERAGE_DIFF_FILE);
+            let diff = baseline.diff(&*self.coverage.read().await);
+            fs::write(&path, serde_json::to_vec(&diff)?)
+                .await
+                .with_context(|| format!("writing coverage diff {}", path.display()))?;
+        }
+
+        self.config
+            .coverage
+            .sync_push(&self.config.common.cancellation_token)
+            .await?;
         Ok(())
     }
 
@@ -568,7 +866,14 @@ impl<'a> Processor for TaskContext<'a> {
     async fn process(&mut self, _url: Option<Url>, input: &Path) -> Result<()> {
         self.heartbeat.alive();
 
-        self.record_input(input).await?;
+        if !self.config.is_input_selected(input).await? {
+            debug!("skipping filtered input: {}", input.display());
+            return Ok(());
+        }
+
+        if !self.record_new_input(input).await? {
+            return Ok(());
+        }
         self.save_and_sync_coverage().await?;
         self.report_coverage_stats().await;
 
@@ -576,6 +881,84 @@ impl<'a> Processor for TaskContext<'a> {
     }
 }
 
+/// Load the digests of inputs recorded by previous runs. Only loaded if there
+/// is `prior_coverage`, since the list is meaningless without it.
+async fn load_covered_inputs(path: &Path, prior_coverage: bool) -> Result<BTreeSet<String>> {
+    if !prior_coverage {
+        info!("no prior coverage, recording coverage for all inputs");
+        return Ok(BTreeSet::new());
+    }
+
+    match fs::read(path).await {
+        Ok(data) => {
+            let covered_inputs: BTreeSet<String> = serde_json::from_slice(&data)
+                .with_context(|| format!("parsing covered inputs {}", path.display()))?;
+            info!(
+                "skipping {} inputs with prior coverage",
+                covered_inputs.len()
+            );
+            Ok(covered_inputs)
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            info!("no list of covered inputs, recording coverage for all inputs");
+            Ok(BTreeSet::new())
+        }
+        Err(err) => Err(err).with_context(|| format!("reading covered inputs {}", path.display())),
+    }
+}
+
+/// Sum the binary coverage files within `dir`, at any depth.
+async fn load_coverage_shards(dir: &Path) -> Result<BinaryCoverage> {
+    let mut merged = BinaryCoverage::default();
+    let mut count = 0;
+
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("reading coverage shards {}", dir.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !name.ends_with(".json") {
+                continue;
+            }
+            // Written alongside `coverage.json` by the collection path.
+            if name == SOURCE_COVERAGE_FILE
+                || name == COVERED_INPUTS_FILE
+                || name == PER_INPUT_COVERAGE_FILE
+                || name == COVERAGE_DIFF_FILE
+            {
+                debug!("skipping non-binary coverage file: {}", path.display());
+                continue;
+            }
+
+            let text = fs::read_to_string(&path)
+                .await
+                .with_context(|| format!("reading coverage shard {}", path.display()))?;
+            let shard = BinaryCoverageJson::deserialize(&text)
+                .and_then(BinaryCoverage::try_from)
+                .with_context(|| {
+                    format!(
+                        "coverage shard is not in a supported binary coverage format: {}",
+                        path.display()
+                    )
+                })?;
+            merged.add(&shard);
+            count += 1;
+        }
+    }
+
+    info!("merged {} coverage shards", count);
+    Ok(merged)
+}
+
 #[derive(Default)]
 struct CoverageStats {
     covered: u64,
@@ -631,4 +1014,48 @@ mod tests {
     }
 
     config_test!(Config);
+
+    #[tokio::test]
+    async fn test_load_covered_inputs() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(super::COVERED_INPUTS_FILE);
+
+        assert!(super::load_covered_inputs(&path, true).await?.is_empty());
+
+        tokio::fs::write(&path, r#"["aa", "bb"]"#).await?;
+        assert_eq!(super::load_covered_inputs(&path, true).await?.len(), 2);
+
+        // The list is ignored without the coverage it describes.
+        assert!(super::load_covered_inputs(&path, false).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_coverage_shards() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let node = dir.path().join("node-1");
+        tokio::fs::create_dir(&node).await?;
+
+        let shard = |count: u32| {
+            format!(
+                r#"{{"version": "1.0", "coverage": {{"/setup/fuzz": {{"blocks": {{"10": {count}, "20": 0}}}}}}}}"#
+            )
+        };
+        tokio::fs::write(dir.path().join("coverage.json"), shard(1)).await?;
+        tokio::fs::write(node.join("coverage.json"), shard(2)).await?;
+        tokio::fs::write(node.join(super::SOURCE_COVERAGE_FILE), "{}").await?;
+
+        let merged = super::load_coverage_shards(dir.path()).await?;
+        let stats = super::CoverageStats::new(&merged);
+        assert_eq!(stats.features, 2);
+        assert_eq!(stats.covered, 1);
+        let module = merged.modules.values().next().unwrap();
+        assert_eq!(module.offsets.values().map(|c| c.0).max(), Some(3));
+
+        tokio::fs::write(dir.path().join("future.json"), r#"{"version": "9.0"}"#).await?;
+        assert!(super::load_coverage_shards(dir.path()).await.is_err());
+
+        Ok(())
+    }
 }
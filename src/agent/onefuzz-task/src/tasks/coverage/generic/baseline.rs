@@ -0,0 +1,175 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Coverage gained over a baseline, such as the coverage of the corpus before
+//! new seeds were added.
+//!
+//! The diff is written as JSON:
+//!
+//! ```json
+//! {
+//!   "baseline_missing": false,
+//!   "baseline_covered": 1200,
+//!   "covered": 1250,
+//!   "gained": 64
+//! }
+//! ```
+//!
+//! `gained` counts the blocks reached which the baseline didn't reach, so it
+//! can be more than `covered - baseline_covered` when blocks the baseline
+//! reached are no longer reached. Modules are matched by file name, so that a
+//! baseline recorded with the target in another directory still applies. A
+//! baseline that can't be downloaded counts as empty, so that all coverage is
+//! gained.
+
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+
+use anyhow::{Context, Result};
+use coverage::binary::{BinaryCoverage, Offset};
+use onefuzz_file_format::coverage::binary::BinaryCoverageJson;
+use serde::Serialize;
+use tokio::fs;
+use url::Url;
+
+use crate::tasks::utils::download_input;
+
+/// Blocks reached by the baseline, by module file name.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    reached: HashMap<String, BTreeSet<Offset>>,
+    missing: bool,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Serialize)]
+pub struct CoverageDiff {
+    pub baseline_missing: bool,
+    pub baseline_covered: u64,
+    pub covered: u64,
+    pub gained: u64,
+}
+
+impl Baseline {
+    /// Download the binary coverage at `url`, or a missing baseline if it
+    /// can't be downloaded.
+    pub async fn download(url: Url) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let path = match download_input(url.clone(), dir.path()).await {
+            Ok(path) => path,
+            Err(err) => {
+                warn!(
+                    "unable to download baseline coverage, counting all coverage as gained: {} - {:?}",
+                    url, err
+                );
+                return Ok(Self::missing());
+            }
+        };
+
+        let text = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("reading baseline coverage {}", path.display()))?;
+        let json = BinaryCoverageJson::deserialize(&text)
+            .with_context(|| format!("parsing baseline coverage {url}"))?;
+        Ok(Self::from(&BinaryCoverage::try_from(json)?))
+    }
+
+    fn missing() -> Self {
+        Self {
+            missing: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn diff(&self, coverage: &BinaryCoverage) -> CoverageDiff {
+        let mut diff = CoverageDiff {
+            baseline_missing: self.missing,
+            baseline_covered: self.reached.values().map(|r| r.len() as u64).sum(),
+            ..CoverageDiff::default()
+        };
+
+        for (path, module) in &coverage.modules {
+            let baseline = self.reached.get(path.file_name());
+            for (offset, count) in &module.offsets {
+                if !count.reached() {
+                    continue;
+                }
+                diff.covered += 1;
+                if !baseline.is_some_and(|baseline| baseline.contains(offset)) {
+                    diff.gained += 1;
+                }
+            }
+        }
+
+        diff
+    }
+}
+
+impl From<&BinaryCoverage> for Baseline {
+    fn from(coverage: &BinaryCoverage) -> Self {
+        let mut reached: HashMap<String, BTreeSet<Offset>> = HashMap::new();
+        for (path, module) in &coverage.modules {
+            reached
+                .entry(path.file_name().to_owned())
+                .or_default()
+                .extend(
+                    module
+                        .offsets
+                        .iter()
+                        .filter(|(_, count)| count.reached())
+                        .map(|(offset, _)| *offset),
+                );
+        }
+
+        Self {
+            reached,
+            missing: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use coverage::binary::{Count, FilePath, ModuleBinaryCoverage};
+
+    use super::*;
+
+    fn coverage(path: &str, blocks: &[(u64, u32)]) -> Result<BinaryCoverage> {
+        let mut module = ModuleBinaryCoverage::default();
+        for &(offset, count) in blocks {
+            module.offsets.insert(Offset(offset), Count(count));
+        }
+
+        let mut coverage = BinaryCoverage::default();
+        coverage.modules.insert(FilePath::new(path)?, module);
+        Ok(coverage)
+    }
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let baseline = Baseline::from(&coverage("/old/setup/fuzz", &[(1, 1), (2, 1), (3, 0)])?);
+        let current = coverage("/setup/fuzz", &[(1, 1), (3, 2), (4, 1), (5, 0)])?;
+
+        assert_eq!(
+            baseline.diff(&current),
+            CoverageDiff {
+                baseline_missing: false,
+                baseline_covered: 2,
+                covered: 3,
+                gained: 2,
+            }
+        );
+
+        let diff = Baseline::missing().diff(&current);
+        assert!(diff.baseline_missing);
+        assert_eq!(diff.gained, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_missing() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let url = Url::from_file_path(dir.path().join("coverage.json")).unwrap();
+        assert!(Baseline::download(url).await?.missing);
+        Ok(())
+    }
+}
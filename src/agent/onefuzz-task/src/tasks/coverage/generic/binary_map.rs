@@ -0,0 +1,181 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Compact binary map of the blocks reached, for tools which memory-map the
+//! coverage rather than parse `coverage.json`.
+//!
+//! All integers are little-endian, and every field starts 8-byte aligned:
+//!
+//! ```text
+//! magic         [u8; 8]   b"OFZCOVMP"
+//! version       u32       1
+//! module_count  u32
+//!
+//! For each module, sorted by path:
+//!   path_len    u32
+//!   block_count u32
+//!   path        [u8; path_len], UTF-8, zero-padded to a multiple of 8
+//!   offsets     [u64; block_count], the module's blocks, ascending
+//!   reached     [u8; (block_count + 7) / 8], zero-padded to a multiple of 8
+//! ```
+//!
+//! Bit `i % 8` (least significant first) of byte `i / 8` of `reached` is set
+//! if block `i` was reached. Hit counts are not kept, so a map reads back as
+//! coverage with a count of 1 for each block reached.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use coverage::binary::BinaryCoverage;
+#[cfg(test)]
+use coverage::binary::{Count, FilePath, ModuleBinaryCoverage, Offset};
+use tokio::fs;
+
+const MAGIC: &[u8; 8] = b"OFZCOVMP";
+const VERSION: u32 = 1;
+
+pub async fn save(coverage: &BinaryCoverage, path: &Path) -> Result<()> {
+    fs::write(path, encode(coverage))
+        .await
+        .with_context(|| format!("writing coverage map {}", path.display()))
+}
+
+pub fn encode(coverage: &BinaryCoverage) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&VERSION.to_le_bytes());
+    data.extend_from_slice(&(coverage.modules.len() as u32).to_le_bytes());
+
+    for (path, module) in &coverage.modules {
+        let path = path.as_str().as_bytes();
+        data.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(module.offsets.len() as u32).to_le_bytes());
+        data.extend_from_slice(path);
+        pad(&mut data);
+
+        let mut reached = vec![0u8; bitset_len(module.offsets.len())];
+        for (i, (offset, count)) in module.offsets.iter().enumerate() {
+            data.extend_from_slice(&offset.0.to_le_bytes());
+            if count.reached() {
+                reached[i / 8] |= 1 << (i % 8);
+            }
+        }
+        data.extend_from_slice(&reached);
+        pad(&mut data);
+    }
+
+    data
+}
+
+#[cfg(test)]
+pub fn decode(data: &[u8]) -> Result<BinaryCoverage> {
+    let mut reader = Reader { data, pos: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        bail!("not a coverage map");
+    }
+    let version = reader.u32()?;
+    if version != VERSION {
+        bail!("unsupported coverage map version {}", version);
+    }
+
+    let mut coverage = BinaryCoverage::default();
+    for _ in 0..reader.u32()? {
+        let path_len = reader.u32()? as usize;
+        let block_count = reader.u32()? as usize;
+        let path = std::str::from_utf8(reader.take(path_len)?)
+            .context("coverage map module path is not UTF-8")?;
+        let path = FilePath::new(path)?;
+        reader.align()?;
+
+        let mut offsets = Vec::with_capacity(block_count);
+        for _ in 0..block_count {
+            offsets.push(Offset(reader.u64()?));
+        }
+        let reached = reader.take(bitset_len(block_count))?;
+        reader.align()?;
+
+        let mut module = ModuleBinaryCoverage::default();
+        for (i, offset) in offsets.into_iter().enumerate() {
+            let count = (reached[i / 8] >> (i % 8)) & 1;
+            module.offsets.insert(offset, Count(count.into()));
+        }
+        coverage.modules.insert(path, module);
+    }
+
+    Ok(coverage)
+}
+
+fn bitset_len(block_count: usize) -> usize {
+    (block_count + 7) / 8
+}
+
+// Bytes needed after `len` bytes to reach 8-byte alignment.
+fn padding(len: usize) -> usize {
+    (8 - len % 8) % 8
+}
+
+fn pad(data: &mut Vec<u8>) {
+    data.resize(data.len() + padding(data.len()), 0);
+}
+
+#[cfg(test)]
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+#[cfg(test)]
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| format_err!("coverage map truncated at byte {}", self.pos))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn align(&mut self) -> Result<()> {
+        self.take(padding(self.pos))?;
+        Ok(())
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let mut coverage = BinaryCoverage::default();
+        let blocks = (0..20u64).map(|i| (Offset(i * 16), Count((i % 3 == 0).into())));
+        coverage.modules.insert(
+            FilePath::new("/setup/fuzz")?,
+            ModuleBinaryCoverage {
+                offsets: blocks.collect(),
+            },
+        );
+        coverage.modules.insert(
+            FilePath::new("/setup/lib.so")?,
+            ModuleBinaryCoverage::default(),
+        );
+
+        let data = encode(&coverage);
+        assert_eq!(data.len() % 8, 0);
+        assert_eq!(decode(&data)?, coverage);
+
+        assert!(decode(&data[..data.len() - 8]).is_err());
+        let mut future = data.clone();
+        future[8] = 2;
+        assert!(decode(&future).is_err());
+        Ok(())
+    }
+}
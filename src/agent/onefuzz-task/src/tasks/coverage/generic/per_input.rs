@@ -0,0 +1,257 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Index of the blocks that each input covered first, for test-impact
+//! analysis.
+//!
+//! The index is written as JSON:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "modules": ["/setup/fuzz", "/setup/libpng.so"],
+//!   "inputs": {
+//!     "<SHA-256 of the input>": [[0, [4112, 16, 8]], [1, [592]]]
+//!   }
+//! }
+//! ```
+//!
+//! Each input maps to the blocks it reached which no input recorded before it
+//! had, grouped by module. A module is given as an index into `modules`, and
+//! its blocks as offsets sorted in ascending order and delta-encoded: the
+//! first value is an offset, and each later value is the distance from the
+//! previous offset. Inputs which reached no new blocks map to an empty list,
+//! so every recorded input is listed.
+//!
+//! Which input covers a block first depends on the order in which inputs are
+//! recorded, so only the union of the blocks of a set of inputs is stable
+//! across runs.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use coverage::binary::BinaryCoverage;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+const VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PerInputCoverage {
+    version: u32,
+    modules: Vec<String>,
+    inputs: BTreeMap<String, Vec<(usize, Vec<u64>)>>,
+}
+
+impl Default for PerInputCoverage {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            modules: Vec::new(),
+            inputs: BTreeMap::new(),
+        }
+    }
+}
+
+impl PerInputCoverage {
+    /// Load the index written by previous runs. Only loaded if there is
+    /// `prior_coverage`, since the blocks each input covered first are
+    /// relative to it.
+    pub async fn load(path: &Path, prior_coverage: bool) -> Result<Self> {
+        if !prior_coverage {
+            return Ok(Self::default());
+        }
+
+        let data = match fs::read(path).await {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                info!("no per-input coverage index, starting a new one");
+                return Ok(Self::default());
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("reading per-input coverage {}", path.display()))
+            }
+        };
+
+        let index: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("parsing per-input coverage {}", path.display()))?;
+        if index.version != VERSION {
+            bail!(
+                "unsupported per-input coverage version {}: {}",
+                index.version,
+                path.display()
+            );
+        }
+        Ok(index)
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec(self)?;
+        fs::write(path, json)
+            .await
+            .with_context(|| format!("writing per-input coverage {}", path.display()))
+    }
+
+    /// Record the blocks reached in `input` but not in `total`, the coverage of
+    /// the inputs recorded before it. An input already recorded keeps the
+    /// blocks it covered first, since a later recording covers nothing new.
+    pub fn record(&mut self, digest: String, total: &BinaryCoverage, input: &BinaryCoverage) {
+        let mut modules = Vec::new();
+
+        for (path, module) in &input.modules {
+            let prior = total.modules.get(path);
+            let new_offsets = module
+                .offsets
+                .iter()
+                .filter(|(_, count)| count.reached())
+                .map(|(offset, _)| offset)
+                .filter(|offset| {
+                    !prior
+                        .and_then(|prior| prior.offsets.get(offset))
+                        .map(|count| count.reached())
+                        .unwrap_or(false)
+                })
+                .map(|offset| offset.0);
+
+            let deltas = encode_deltas(new_offsets);
+            if !deltas.is_empty() {
+                modules.push((self.module_index(path.as_str()), deltas));
+            }
+        }
+
+        self.inputs.entry(digest).or_insert(modules);
+    }
+
+    /// The offsets of the blocks that the input with `digest` covered first,
+    /// by module path.
+    #[cfg(test)]
+    pub fn new_blocks(&self, digest: &str) -> Option<BTreeMap<&str, Vec<u64>>> {
+        let modules = self.inputs.get(digest)?;
+        Some(
+            modules
+                .iter()
+                .filter_map(|(index, deltas)| {
+                    let path = self.modules.get(*index)?;
+                    Some((path.as_str(), decode_deltas(deltas)))
+                })
+                .collect(),
+        )
+    }
+
+    fn module_index(&mut self, path: &str) -> usize {
+        match self.modules.iter().position(|module| module == path) {
+            Some(index) => index,
+            None => {
+                self.modules.push(path.to_owned());
+                self.modules.len() - 1
+            }
+        }
+    }
+}
+
+/// Delta-encode `offsets`, which must be sorted in ascending order.
+fn encode_deltas(offsets: impl IntoIterator<Item = u64>) -> Vec<u64> {
+    let mut previous = 0;
+    offsets
+        .into_iter()
+        .map(|offset| {
+            let delta = offset - previous;
+            previous = offset;
+            delta
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn decode_deltas(deltas: &[u64]) -> Vec<u64> {
+    let mut offset = 0;
+    deltas
+        .iter()
+        .map(|delta| {
+            offset += delta;
+            offset
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use coverage::binary::{Count, FilePath, ModuleBinaryCoverage, Offset};
+
+    use super::*;
+
+    fn coverage(module: &str, blocks: &[(u64, u32)]) -> BinaryCoverage {
+        let mut module_coverage = ModuleBinaryCoverage::default();
+        for &(offset, count) in blocks {
+            module_coverage.offsets.insert(Offset(offset), Count(count));
+        }
+
+        let mut coverage = BinaryCoverage::default();
+        coverage
+            .modules
+            .insert(FilePath::new(module).unwrap(), module_coverage);
+        coverage
+    }
+
+    #[test]
+    fn test_deltas() {
+        let offsets = vec![8, 16, 4112];
+        let deltas = encode_deltas(offsets.clone());
+        assert_eq!(deltas, vec![8, 8, 4096]);
+        assert_eq!(decode_deltas(&deltas), offsets);
+        assert!(encode_deltas(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_record() {
+        let mut index = PerInputCoverage::default();
+        let mut total = BinaryCoverage::default();
+
+        let first = coverage("/setup/fuzz", &[(16, 1), (32, 1), (48, 0)]);
+        index.record("aa".into(), &total, &first);
+        total.merge(&first);
+
+        let second = coverage("/setup/fuzz", &[(16, 3), (48, 1)]);
+        index.record("bb".into(), &total, &second);
+        total.merge(&second);
+
+        index.record("cc".into(), &total, &first);
+        index.record("aa".into(), &total, &first);
+
+        let blocks = |digest| index.new_blocks(digest).unwrap();
+        assert_eq!(blocks("aa")["/setup/fuzz"], vec![16, 32]);
+        assert_eq!(blocks("bb")["/setup/fuzz"], vec![48]);
+        assert!(blocks("cc").is_empty());
+        assert!(index.new_blocks("dd").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_load_and_save() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("per-input-coverage.json");
+
+        assert_eq!(
+            PerInputCoverage::load(&path, true).await?,
+            PerInputCoverage::default()
+        );
+
+        let mut index = PerInputCoverage::default();
+        let input = coverage("/setup/fuzz", &[(16, 1)]);
+        index.record("aa".into(), &BinaryCoverage::default(), &input);
+        index.save(&path).await?;
+
+        assert_eq!(PerInputCoverage::load(&path, true).await?, index);
+        // The index is ignored without the coverage it is relative to.
+        assert_eq!(
+            PerInputCoverage::load(&path, false).await?,
+            PerInputCoverage::default()
+        );
+
+        fs::write(&path, r#"{"version": 9, "modules": [], "inputs": {}}"#).await?;
+        assert!(PerInputCoverage::load(&path, true).await.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::fmt;
+
+use onefuzz_telemetry::{Event::task_stop, EventData};
+
+/// Why a task failed, so that the orchestrator can decide whether to reschedule it.
+///
+/// Failure sites mark their errors by wrapping them in the appropriate variant
+/// before converting back into an `anyhow::Error`. When the task ends, the
+/// outermost marker found in the error chain determines the category.
+#[derive(Debug)]
+pub enum TaskError {
+    /// An infrastructure failure which may succeed if retried, possibly elsewhere.
+    Transient(anyhow::Error),
+
+    /// The task config is invalid, and retrying will not help.
+    Configuration(anyhow::Error),
+
+    /// The target under test is broken or behaved unexpectedly.
+    TargetFailure(anyhow::Error),
+
+    /// The node ran out of a resource, such as memory or disk.
+    ResourceExhausted(anyhow::Error),
+}
+
+impl TaskError {
+    pub fn category(&self) -> &'static str {
+        match self {
+            Self::Transient(_) => "transient",
+            Self::Configuration(_) => "configuration",
+            Self::TargetFailure(_) => "target_failure",
+            Self::ResourceExhausted(_) => "resource_exhausted",
+        }
+    }
+
+    pub fn inner(&self) -> &anyhow::Error {
+        match self {
+            Self::Transient(err)
+            | Self::Configuration(err)
+            | Self::TargetFailure(err)
+            | Self::ResourceExhausted(err) => err,
+        }
+    }
+
+    /// The constructor for errors in the same category as `self`.
+    fn wrapper(&self) -> fn(anyhow::Error) -> Self {
+        match self {
+            Self::Transient(_) => Self::Transient,
+            Self::Configuration(_) => Self::Configuration,
+            Self::TargetFailure(_) => Self::TargetFailure,
+            Self::ResourceExhausted(_) => Self::ResourceExhausted,
+        }
+    }
+
+    /// Categorize an error returned by a task.
+    ///
+    /// Errors without an explicit marker are categorized by their underlying
+    /// error types, and are otherwise assumed to be transient.
+    pub fn classify(err: anyhow::Error) -> Self {
+        // Only unwrap an unadorned marker, since `downcast` also sees through
+        // context, which would then be lost.
+        if err
+            .chain()
+            .next()
+            .map_or(false, |cause| cause.is::<TaskError>())
+        {
+            return err
+                .downcast::<TaskError>()
+                .expect("outermost error is a TaskError");
+        }
+
+        if let Some(wrap) = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<TaskError>())
+            .map(TaskError::wrapper)
+        {
+            return wrap(err);
+        }
+
+        let is_config_error = err
+            .chain()
+            .any(|cause| cause.is::<serde_json::Error>() || cause.is::<url::ParseError>());
+        if is_config_error {
+            return Self::Configuration(err);
+        }
+
+        let is_out_of_space = err.chain().any(|cause| {
+            cause
+                .downcast_ref::<std::io::Error>()
+                .map(|err| err.raw_os_error() == Some(ENOSPC))
+                .unwrap_or(false)
+        });
+        if is_out_of_space {
+            return Self::ResourceExhausted(err);
+        }
+
+        Self::Transient(err)
+    }
+}
+
+#[cfg(windows)]
+const ENOSPC: i32 = 112; // ERROR_DISK_FULL
+
+#[cfg(not(windows))]
+const ENOSPC: i32 = 28;
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} error: {}", self.category(), self.inner())
+    }
+}
+
+impl std::error::Error for TaskError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.inner().as_ref())
+    }
+}
+
+/// Record the outcome of a task in telemetry.
+pub fn report_task_stop(result: &Result<(), TaskError>) {
+    match result {
+        Ok(()) => {
+            event!(task_stop; EventData::ErrorCategory = "none");
+        }
+        Err(err) => {
+            event!(task_stop; EventData::ErrorCategory = err.category());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+
+    #[test]
+    fn test_marked_error_keeps_context() {
+        let err: anyhow::Error = TaskError::Configuration(anyhow!("missing target_exe")).into();
+        let err = err.context("starting task");
+
+        let classified = TaskError::classify(err);
+        assert_eq!(classified.category(), "configuration");
+        assert_eq!(classified.inner().to_string(), "starting task");
+        assert!(format!("{:?}", classified.inner()).contains("missing target_exe"));
+    }
+
+    #[test]
+    fn test_classify_by_source_type() {
+        let err = serde_json::from_str::<u32>("not json")
+            .context("parsing config")
+            .unwrap_err();
+        assert_eq!(TaskError::classify(err).category(), "configuration");
+
+        let err = anyhow!("something went wrong");
+        assert_eq!(TaskError::classify(err).category(), "transient");
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Task events written as JSON lines to a local Unix socket, or a named pipe on
+//! Windows, for a sidecar on the node.
+//!
+//! Delivery is best-effort. Events are read from the telemetry broadcast
+//! channel, which drops the oldest events rather than block the task if they
+//! aren't read in time. Events tracked while the socket is absent or too slow
+//! to accept them are dropped, and the connection is retried periodically.
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use onefuzz_telemetry::{LogEvent, LoggingEvent};
+use serde_json::{json, Map, Value};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::broadcast::error::RecvError,
+    time::timeout,
+};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+type Connection = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Forward task events to the socket at `path` until cancelled.
+pub async fn run(
+    path: &Path,
+    job_id: Uuid,
+    task_id: Uuid,
+    cancellation_token: &CancellationToken,
+) -> Result<()> {
+    let mut events = onefuzz_telemetry::subscribe_to_events()?;
+    let mut connection: Option<Connection> = None;
+    let mut next_connect = Instant::now();
+
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => match event {
+                Ok(LoggingEvent::Event(event)) => event,
+                Ok(LoggingEvent::Trace(_)) => continue,
+                Err(RecvError::Lagged(count)) => {
+                    debug!("event socket dropped {} events", count);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = cancellation_token.cancelled() => break,
+        };
+
+        if connection.is_none() && Instant::now() >= next_connect {
+            match timeout(WRITE_TIMEOUT, connect(path)).await {
+                Ok(Ok(conn)) => connection = Some(conn),
+                Ok(Err(err)) => debug!("unable to connect to event socket: {}", err),
+                Err(_) => debug!("timed out connecting to event socket"),
+            }
+            next_connect = Instant::now() + RECONNECT_DELAY;
+        }
+
+        let Some(conn) = connection.as_mut() else {
+            continue;
+        };
+
+        let line = event_line(&event, job_id, task_id);
+        if !matches!(
+            timeout(WRITE_TIMEOUT, conn.write_all(line.as_bytes())).await,
+            Ok(Ok(()))
+        ) {
+            debug!("failed to write to event socket, reconnecting");
+            connection = None;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn connect(path: &Path) -> std::io::Result<Connection> {
+    let stream = tokio::net::UnixStream::connect(path).await?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(windows)]
+async fn connect(path: &Path) -> std::io::Result<Connection> {
+    let pipe = tokio::net::windows::named_pipe::ClientOptions::new().open(path)?;
+    Ok(Box::new(pipe))
+}
+
+fn event_line(event: &LogEvent, job_id: Uuid, task_id: Uuid) -> String {
+    let data: Map<String, Value> = event
+        .data
+        .iter()
+        .map(|data| {
+            let (name, value) = data.as_values();
+            (name.to_owned(), Value::String(value))
+        })
+        .collect();
+
+    let mut line = json!({
+        "timestamp": event.timestamp.to_rfc3339(),
+        "job_id": job_id,
+        "task_id": task_id,
+        "event": event.event.as_str(),
+        "data": data,
+    })
+    .to_string();
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use onefuzz_telemetry::{Event, EventData};
+
+    use super::*;
+
+    #[test]
+    fn test_event_line() {
+        let event = LogEvent {
+            timestamp: Utc::now(),
+            event: Event::runtime_stats,
+            data: vec![EventData::ExecsSecond(100.5)],
+        };
+
+        let line = event_line(&event, Uuid::nil(), Uuid::nil());
+        assert!(line.ends_with('\n'));
+
+        let value: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["event"], "runtime_stats");
+        assert_eq!(value["task_id"], Uuid::nil().to_string());
+        assert_eq!(value["data"]["execs_sec"], "100.5");
+    }
+}
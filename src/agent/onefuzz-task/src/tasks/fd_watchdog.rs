@@ -0,0 +1,58 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Fails the task when it holds too many open file descriptors (or handles, on
+//! Windows), so that a leak surfaces as a clear error rather than as I/O
+//! failures once the process limit is reached.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use onefuzz_telemetry::{Event::fd_exhausted, EventData};
+use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
+
+use crate::tasks::error::TaskError;
+
+const CHECK_PERIOD: Duration = Duration::from_secs(10);
+const MAX_QUERY_ERRORS: usize = 5;
+
+/// Periodically check the open descriptor count until cancelled, failing if
+/// it exceeds `max_open_fds`.
+pub async fn run(max_open_fds: u64, cancellation_token: &CancellationToken) -> Result<()> {
+    info!("fd watchdog is enabled: the task will fail if more than {max_open_fds} descriptors are open");
+
+    let mut consecutive_query_errors = 0;
+    let mut timer = interval(CHECK_PERIOD);
+
+    loop {
+        tokio::select! {
+            _ = timer.tick() => {}
+            _ = cancellation_token.cancelled() => return Ok(()),
+        }
+
+        match onefuzz::handles::open_count() {
+            Ok(count) => {
+                consecutive_query_errors = 0;
+
+                if count > max_open_fds {
+                    event!(fd_exhausted; EventData::Count = count);
+                    metric!(fd_exhausted; 1.0; EventData::Count = count);
+                    let err = format_err!(
+                        "fd exhaustion: {count} descriptors open, limit is {max_open_fds}"
+                    );
+                    return Err(TaskError::ResourceExhausted(err).into());
+                }
+            }
+            Err(err) => {
+                warn!("error querying open descriptors: {:?}", err);
+
+                consecutive_query_errors += 1;
+                if consecutive_query_errors > MAX_QUERY_ERRORS {
+                    warn!("disabling fd watchdog after repeated query errors");
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -3,34 +3,89 @@
 
 use crate::tasks::{
     config::CommonConfig,
+    error::TaskError,
     heartbeat::{HeartbeatSender, TaskHeartbeatClient},
-    utils::{self, default_bool_true, try_resolve_setup_relative_path},
+    lifecycle,
+    utils::{self, default_bool_true, reject_oversized_input, try_resolve_setup_relative_path},
 };
 use anyhow::{Context, Result};
 use onefuzz::{
     expand::Expand,
     fs::set_executable,
-    input_tester::Tester,
+    input_tester::{InputDelivery, Tester},
     process::monitor_process,
     sha256,
     syncdir::{continuous_sync, SyncOperation::Pull, SyncedDir},
 };
-use onefuzz_telemetry::Event::new_result;
+use onefuzz_telemetry::{
+    Event::{new_generated_crash, new_result},
+    EventData, LifecycleState,
+};
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::{
-    ffi::OsString,
+    ffi::{OsStr, OsString},
     path::{Path, PathBuf},
     process::Stdio,
+    time::Duration,
 };
 use tempfile::tempdir;
 use tokio::{fs, process::Command};
 
+use feedback::CoverageFeedback;
+
+mod feedback;
+
+const DEFAULT_TARGET_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A single generator used by the task, along with its own arguments.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct GeneratorSpec {
+    /// Optional display name, used in logs and telemetry. Defaults to `generator_exe`.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub generator_exe: String,
+    #[serde(default)]
+    pub generator_env: HashMap<String, String>,
+    #[serde(default)]
+    pub generator_options: Vec<String>,
+    /// Relative share of rotations, only used with `RotationPolicy::Weighted`.
+    #[serde(default = "default_generator_weight")]
+    pub weight: u32,
+}
+
+impl GeneratorSpec {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.generator_exe)
+    }
+}
+
+fn default_generator_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationPolicy {
+    /// Use each generator in turn.
+    #[default]
+    RoundRobin,
+    /// Use each generator `weight` times per rotation.
+    Weighted,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
+    #[serde(default)]
     pub generator_exe: String,
+    #[serde(default)]
     pub generator_env: HashMap<String, String>,
+    #[serde(default)]
     pub generator_options: Vec<String>,
+    #[serde(default)]
+    pub generators: Vec<GeneratorSpec>,
+    #[serde(default)]
+    pub rotation: RotationPolicy,
     pub readonly_inputs: Vec<SyncedDir>,
     pub crashes: SyncedDir,
     pub tools: Option<SyncedDir>,
@@ -47,6 +102,41 @@ pub struct Config {
     pub check_retry_count: u64,
     pub rename_output: bool,
     pub ensemble_sync_delay: Option<u64>,
+    /// Extension given to generated inputs before they are tested and saved, for
+    /// targets which choose a code path by file extension. May omit the leading `.`.
+    #[serde(default)]
+    pub input_extension: Option<String>,
+    /// How each generated input is passed to the target.
+    #[serde(default)]
+    pub input_via: InputDelivery,
+    /// Skip generated inputs larger than this many bytes, rather than passing
+    /// them to the target, for targets which can't handle larger inputs and
+    /// would otherwise produce spurious crashes. Unset by default.
+    #[serde(default)]
+    pub reject_inputs_over_bytes: Option<u64>,
+    /// Keep generated inputs which reach new coverage in a corpus of their
+    /// own, and pass it to the generators alongside `readonly_inputs`. Each
+    /// input which doesn't crash is run a second time to record its coverage.
+    #[serde(default)]
+    pub feedback: bool,
+    /// Scheduling priority to run the target with, as a Unix nice value from
+    /// -20 to 19, so that fuzzing can yield to other work on shared machines.
+    /// Mapped to the nearest priority class on Windows. Unset by default, which
+    /// keeps the agent's own priority.
+    #[serde(default)]
+    pub target_nice: Option<i32>,
+    /// Exit codes the target uses to reject inputs, which are treated as clean
+    /// exits rather than crashes. Sanitizers exit with 1 by default, so listing
+    /// 1 also hides their reports unless `exitcode` is set in their options.
+    #[serde(default)]
+    pub expected_exit_codes: Vec<i32>,
+    /// Command, and its arguments, to pipe each generated input through
+    /// before passing the output to the target, for targets which read an
+    /// encoding the generators can't produce. Crashes are saved as generated.
+    /// Inputs for which the command fails or runs for over a minute are
+    /// skipped.
+    #[serde(default)]
+    pub pre_exec_transform: Option<Vec<String>>,
     #[serde(flatten)]
     pub common: CommonConfig,
 }
@@ -64,6 +154,75 @@ impl Config {
                 expand.tools_dir(&tools.local_path)
             })
     }
+
+    /// The generators to rotate through.
+    ///
+    /// If `generators` is empty, the legacy `generator_exe`, `generator_options`
+    /// and `generator_env` fields are used as a single generator.
+    pub fn generators(&self) -> Result<Vec<GeneratorSpec>> {
+        if !self.generators.is_empty() {
+            return Ok(self.generators.clone());
+        }
+
+        if self.generator_exe.is_empty() {
+            return Err(TaskError::Configuration(anyhow!(
+                "either `generator_exe` or `generators` must be specified"
+            ))
+            .into());
+        }
+
+        Ok(vec![GeneratorSpec {
+            name: None,
+            generator_exe: self.generator_exe.clone(),
+            generator_env: self.generator_env.clone(),
+            generator_options: self.generator_options.clone(),
+            weight: default_generator_weight(),
+        }])
+    }
+
+    /// The validated `input_extension`, without a leading `.`.
+    pub fn input_extension(&self) -> Result<Option<&str>> {
+        let extension = match &self.input_extension {
+            Some(extension) => extension.strip_prefix('.').unwrap_or(extension),
+            None => return Ok(None),
+        };
+
+        if extension.is_empty() || extension.contains(['/', '\\']) {
+            return Err(TaskError::Configuration(anyhow!(
+                "invalid input_extension, must be non-empty and not contain path separators: {:?}",
+                self.input_extension
+            ))
+            .into());
+        }
+
+        Ok(Some(extension))
+    }
+}
+
+/// Append `extension` to `file_name`, unless it already ends with it.
+fn with_extension(file_name: OsString, extension: Option<&str>) -> OsString {
+    match extension {
+        Some(extension) if Path::new(&file_name).extension() != Some(OsStr::new(extension)) => {
+            let mut file_name = file_name;
+            file_name.push(".");
+            file_name.push(extension);
+            file_name
+        }
+        _ => file_name,
+    }
+}
+
+/// Order in which generators are used for a single rotation, as indices into
+/// the list of generators.
+fn rotation_schedule(generators: &[GeneratorSpec], policy: RotationPolicy) -> Vec<usize> {
+    match policy {
+        RotationPolicy::RoundRobin => (0..generators.len()).collect(),
+        RotationPolicy::Weighted => generators
+            .iter()
+            .enumerate()
+            .flat_map(|(index, spec)| std::iter::repeat(index).take(spec.weight as usize))
+            .collect(),
+    }
 }
 
 pub struct GeneratorTask {
@@ -83,7 +242,9 @@ impl GeneratorTask {
             )
         })?;
         if let Some(tools) = &self.config.tools {
-            tools.init_pull().await?;
+            tools
+                .init_pull(&self.config.common.cancellation_token)
+                .await?;
             set_executable(&tools.local_path).await?;
         }
 
@@ -91,20 +252,25 @@ impl GeneratorTask {
         let jr_client = self.config.common.init_job_result().await?;
 
         for dir in &self.config.readonly_inputs {
-            dir.init_pull().await?;
+            dir.init_pull(&self.config.common.cancellation_token)
+                .await?;
         }
 
         let sync_task = continuous_sync(
             &self.config.readonly_inputs,
             Pull,
             self.config.ensemble_sync_delay,
+            &self.config.common.cancellation_token,
         );
 
-        let crash_dir_monitor = self
-            .config
-            .crashes
-            .monitor_results(new_result, false, &jr_client);
+        let crash_dir_monitor = self.config.crashes.monitor_results(
+            new_result,
+            false,
+            &jr_client,
+            &self.config.common.cancellation_token,
+        );
 
+        lifecycle::transition(LifecycleState::Ready);
         let fuzzer = self.fuzzing_loop(hb_client);
 
         futures::try_join!(fuzzer, sync_task, crash_dir_monitor)?;
@@ -127,51 +293,151 @@ impl GeneratorTask {
         .check_asan_log(self.config.check_asan_log)
         .check_debugger(self.config.check_debugger)
         .check_retry_count(self.config.check_retry_count)
+        .input_delivery(self.config.input_via)
+        .set_optional(self.config.target_nice, Tester::nice)
+        .expected_exit_codes(&self.config.expected_exit_codes)
         .set_optional(self.config.target_timeout, |tester, timeout| {
             tester.timeout(timeout)
         });
 
+        // Validate before running any generators.
+        self.config.input_extension()?;
+        utils::check_target_nice(self.config.target_nice, self.config.check_debugger)?;
+        utils::check_input_via(self.config.input_via, self.config.check_debugger)?;
+        utils::check_expected_exit_codes(&self.config.expected_exit_codes)?;
+        utils::check_pre_exec_transform(self.config.pre_exec_transform.as_deref())?;
+
+        let generators = self.config.generators()?;
+        let schedule = rotation_schedule(&generators, self.config.rotation);
+        if schedule.is_empty() {
+            return Err(TaskError::Configuration(anyhow!(
+                "no generators are scheduled, check the generator weights"
+            ))
+            .into());
+        }
+        let mut rotation = schedule.iter().cycle();
+
+        let feedback_dir = tempdir()?;
+        let mut feedback = if self.config.feedback {
+            let timeout = self
+                .config
+                .target_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_TARGET_TIMEOUT);
+            Some(CoverageFeedback::new(
+                feedback_dir.path().to_owned(),
+                timeout,
+            ))
+        } else {
+            None
+        };
+
         loop {
-            for corpus_dir in &self.config.readonly_inputs {
+            let mut corpus_dirs: Vec<PathBuf> = self
+                .config
+                .readonly_inputs
+                .iter()
+                .map(|dir| dir.local_path.clone())
+                .collect();
+            if let Some(feedback) = &feedback {
+                if let Some(dir) = feedback.corpus_dir().await? {
+                    corpus_dirs.push(dir.to_owned());
+                }
+            }
+
+            for corpus_dir in &corpus_dirs {
                 heartbeat_client.alive();
-                let corpus_dir = &corpus_dir.local_path;
                 let generated_inputs = tempdir()?;
                 let generated_inputs_path = generated_inputs.path();
 
-                self.generate_inputs(corpus_dir, &generated_inputs_path)
-                    .await
-                    .context("generate inputs failed")?;
-                self.test_inputs(&generated_inputs_path, &tester)
+                // `schedule` is non-empty, so the cycle never ends.
+                let generator = &generators[*rotation.next().unwrap()];
+
+                self.generate_inputs(generator, corpus_dir, &generated_inputs_path)
                     .await
-                    .context("test inputs failed")?;
+                    .with_context(|| format!("generate inputs failed: {}", generator.name()))?;
+                self.test_inputs(
+                    generator,
+                    &generated_inputs_path,
+                    &tester,
+                    feedback.as_mut(),
+                )
+                .await
+                .context("test inputs failed")?;
             }
         }
     }
 
     async fn test_inputs(
         &self,
+        generator: &GeneratorSpec,
         generated_inputs: impl AsRef<Path>,
         tester: &Tester<'_>,
+        mut feedback: Option<&mut CoverageFeedback>,
     ) -> Result<()> {
+        let extension = self.config.input_extension()?;
         let mut read_dir = fs::read_dir(generated_inputs).await?;
         while let Some(file) = read_dir.next_entry().await? {
-            debug!("testing input: {}", file.path().display());
+            let mut input = file.path();
+            if extension.is_some() {
+                let renamed = input.with_file_name(with_extension(file.file_name(), extension));
+                if renamed != input {
+                    fs::rename(&input, &renamed).await?;
+                    input = renamed;
+                }
+            }
+            if reject_oversized_input(&input, self.config.reject_inputs_over_bytes).await? {
+                continue;
+            }
+            let transformed = match &self.config.pre_exec_transform {
+                Some(transform) => {
+                    match utils::transform_input(&self.config.common.setup_dir, transform, &input)
+                        .await?
+                    {
+                        Some(transformed) => Some(transformed),
+                        None => continue,
+                    }
+                }
+                None => None,
+            };
+            let target_input = transformed.as_ref().map_or(input.as_path(), |t| t.path());
+            debug!("testing input: {}", input.display());
 
             let destination_file = if self.config.rename_output {
-                let hash = sha256::digest_file(file.path()).await?;
-                OsString::from(hash)
+                let hash = sha256::digest_file(&input).await?;
+                with_extension(OsString::from(hash), extension)
             } else {
-                file.file_name()
+                input.file_name().map(OsStr::to_owned).unwrap_or_default()
             };
 
             let destination_file = self.config.crashes.local_path.join(destination_file);
             if tester
-                .is_crash(file.path())
+                .is_crash(target_input)
                 .await
-                .with_context(|| format!("testing input failed: {}", file.path().display()))?
+                .with_context(|| format!("testing input failed: {}", input.display()))?
             {
-                fs::rename(file.path(), &destination_file).await?;
-                debug!("crash found {}", destination_file.display());
+                fs::rename(&input, &destination_file).await?;
+                debug!(
+                    "crash found {} (generator: {})",
+                    destination_file.display(),
+                    generator.name()
+                );
+                event!(new_generated_crash;
+                    EventData::ToolName = generator.name(),
+                    EventData::Path = destination_file.display().to_string()
+                );
+                metric!(new_generated_crash; 1.0;
+                    EventData::ToolName = generator.name()
+                );
+            } else if let Some(feedback) = feedback.as_deref_mut() {
+                let cmd = tester.command(target_input)?;
+                if let Err(err) = feedback.check(cmd, &input).await {
+                    warn!(
+                        "unable to record coverage of {}: {:?}",
+                        input.display(),
+                        err
+                    );
+                }
             }
         }
         Ok(())
@@ -179,6 +445,7 @@ impl GeneratorTask {
 
     async fn generate_inputs(
         &self,
+        generator_spec: &GeneratorSpec,
         corpus_dir: impl AsRef<Path>,
         output_dir: impl AsRef<Path>,
     ) -> Result<()> {
@@ -187,10 +454,12 @@ impl GeneratorTask {
             let expand = self
                 .config
                 .get_expand()
+                .generator_exe(&generator_spec.generator_exe)
+                .generator_options(&generator_spec.generator_options)
                 .generated_inputs(&output_dir)
                 .input_corpus(&corpus_dir);
 
-            let generator_path = expand.evaluate_value(&self.config.generator_exe)?;
+            let generator_path = expand.evaluate_value(&generator_spec.generator_exe)?;
 
             let mut generator = Command::new(&generator_path);
             generator
@@ -200,11 +469,11 @@ impl GeneratorTask {
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped());
 
-            for arg in expand.evaluate(&self.config.generator_options)? {
+            for arg in expand.evaluate(&generator_spec.generator_options)? {
                 generator.arg(arg);
             }
 
-            for (k, v) in &self.config.generator_env {
+            for (k, v) in &generator_spec.generator_env {
                 generator.env(k, expand.evaluate_value(v)?);
             }
             (generator, generator_path)
@@ -276,6 +545,48 @@ mod tests {
 
     config_test!(Config);
 
+    #[test]
+    fn test_with_extension() {
+        use super::with_extension;
+        use std::ffi::OsString;
+
+        assert_eq!(
+            with_extension(OsString::from("input-1"), Some("pdf")),
+            OsString::from("input-1.pdf")
+        );
+        assert_eq!(
+            with_extension(OsString::from("input-1.pdf"), Some("pdf")),
+            OsString::from("input-1.pdf")
+        );
+        assert_eq!(
+            with_extension(OsString::from("input-1"), None),
+            OsString::from("input-1")
+        );
+    }
+
+    #[test]
+    fn test_rotation_schedule() {
+        use super::{rotation_schedule, GeneratorSpec, RotationPolicy};
+
+        let spec = |generator_exe: &str, weight| GeneratorSpec {
+            name: None,
+            generator_exe: generator_exe.to_string(),
+            generator_env: Default::default(),
+            generator_options: vec![],
+            weight,
+        };
+        let generators = vec![spec("a", 2), spec("b", 0), spec("c", 1)];
+
+        assert_eq!(
+            rotation_schedule(&generators, RotationPolicy::RoundRobin),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            rotation_schedule(&generators, RotationPolicy::Weighted),
+            vec![0, 0, 2]
+        );
+    }
+
     #[cfg(target_os = "linux")]
     mod linux {
         use super::super::{Config, GeneratorTask};
@@ -345,15 +656,29 @@ mod tests {
                 check_debugger: false,
                 rename_output: false,
                 ensemble_sync_delay: None,
+                input_extension: None,
+                input_via: Default::default(),
+                reject_inputs_over_bytes: None,
+                feedback: false,
+                target_nice: None,
+                expected_exit_codes: vec![],
+                pre_exec_transform: None,
                 generator_env: HashMap::default(),
+                generators: vec![],
+                rotation: Default::default(),
                 check_retry_count: 0,
                 common: Default::default(),
             };
+            let generators = config.generators()?;
             let task = GeneratorTask::new(config);
 
             let generated_inputs = tempdir()?;
-            task.generate_inputs(inputs.to_path_buf(), generated_inputs.path())
-                .await?;
+            task.generate_inputs(
+                &generators[0],
+                inputs.to_path_buf(),
+                generated_inputs.path(),
+            )
+            .await?;
 
             let count = std::fs::read_dir(generated_inputs.path())?.count();
             assert_eq!(count, 100, "No inputs generated");
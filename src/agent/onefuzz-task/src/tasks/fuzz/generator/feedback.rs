@@ -0,0 +1,124 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! A corpus of generated inputs which reached new coverage, for the generator
+//! to mutate on later rotations.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use coverage::allowlist::AllowList;
+use coverage::binary::{BinaryCoverage, DebugInfoCache};
+use coverage::record::CoverageRecorder;
+use onefuzz::{fs::has_files, sha256};
+use tokio::{fs, task::spawn_blocking};
+
+pub struct CoverageFeedback {
+    corpus_dir: PathBuf,
+    coverage: BinaryCoverage,
+    cache: Arc<DebugInfoCache>,
+    timeout: Duration,
+}
+
+impl CoverageFeedback {
+    pub fn new(corpus_dir: PathBuf, timeout: Duration) -> Self {
+        Self {
+            corpus_dir,
+            coverage: BinaryCoverage::default(),
+            cache: Arc::new(DebugInfoCache::new(AllowList::default())),
+            timeout,
+        }
+    }
+
+    /// The corpus, if it has any inputs yet.
+    pub async fn corpus_dir(&self) -> Result<Option<&Path>> {
+        if has_files(&self.corpus_dir).await? {
+            Ok(Some(&self.corpus_dir))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Record the coverage of `cmd`, which runs the target on `input`, and
+    /// copy `input` to the corpus if it reached any new blocks. Returns
+    /// whether it did.
+    pub async fn check(&mut self, cmd: Command, input: &Path) -> Result<bool> {
+        let cache = self.cache.clone();
+        let timeout = self.timeout;
+        let recorded = spawn_blocking(move || {
+            CoverageRecorder::new(cmd)
+                .debuginfo_cache(cache)
+                .timeout(timeout)
+                .record()
+        })
+        .await??;
+
+        if !reaches_new_blocks(&self.coverage, &recorded.coverage) {
+            return Ok(false);
+        }
+        self.coverage.merge(&recorded.coverage);
+
+        let name = sha256::digest_file(input).await?;
+        let dest = self.corpus_dir.join(name);
+        fs::copy(input, &dest)
+            .await
+            .with_context(|| format!("saving feedback input: {}", dest.display()))?;
+        debug!("kept input with new coverage: {}", dest.display());
+        Ok(true)
+    }
+}
+
+fn reaches_new_blocks(total: &BinaryCoverage, coverage: &BinaryCoverage) -> bool {
+    coverage.modules.iter().any(|(path, module)| {
+        let known = total.modules.get(path);
+        module
+            .offsets
+            .iter()
+            .filter(|(_, count)| count.reached())
+            .any(|(offset, _)| {
+                !known
+                    .and_then(|known| known.offsets.get(offset))
+                    .map_or(false, |count| count.reached())
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use coverage::binary::{Count, ModuleBinaryCoverage};
+    use debuggable_module::path::FilePath;
+    use debuggable_module::Offset;
+
+    use super::*;
+
+    fn coverage(blocks: &[(u64, u32)]) -> Result<BinaryCoverage> {
+        let mut module = ModuleBinaryCoverage::default();
+        for &(offset, count) in blocks {
+            module.offsets.insert(Offset(offset), Count(count));
+        }
+        let mut coverage = BinaryCoverage::default();
+        coverage
+            .modules
+            .insert(FilePath::new("/setup/fuzz.so")?, module);
+        Ok(coverage)
+    }
+
+    #[test]
+    fn test_reaches_new_blocks() -> Result<()> {
+        let total = coverage(&[(0x10, 1), (0x20, 0)])?;
+
+        assert!(!reaches_new_blocks(&total, &coverage(&[(0x10, 5)])?));
+        assert!(!reaches_new_blocks(&total, &coverage(&[(0x30, 0)])?));
+        assert!(reaches_new_blocks(&total, &coverage(&[(0x20, 1)])?));
+        assert!(reaches_new_blocks(&total, &coverage(&[(0x30, 1)])?));
+        assert!(reaches_new_blocks(
+            &BinaryCoverage::default(),
+            &coverage(&[(0x10, 1)])?
+        ));
+
+        Ok(())
+    }
+}
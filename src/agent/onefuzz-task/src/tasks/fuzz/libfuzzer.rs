@@ -1,6 +1,10 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+pub mod archive;
 pub mod common;
 pub mod dotnet;
 pub mod generic;
+pub mod large_input;
+pub mod layout;
+pub mod rr;
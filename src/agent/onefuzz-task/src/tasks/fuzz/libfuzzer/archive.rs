@@ -0,0 +1,86 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Packing a corpus into a single `.tar.zst` archive, which is much faster for
+//! consumers to list and download than one blob per input.
+
+use std::{fs::File, path::Path};
+
+use anyhow::{Context, Result};
+use onefuzz::blob::{store, BlobContainerUrl};
+
+pub const ARCHIVE_EXTENSION: &str = "tar.zst";
+
+/// Archive each file directly within `corpus_dir` as `{name}.tar.zst` in
+/// `container`, replacing any earlier archive of that name.
+pub async fn upload_corpus_archive(
+    corpus_dir: &Path,
+    container: &BlobContainerUrl,
+    name: &str,
+) -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let archive = temp_dir.path().join(format!("{name}.{ARCHIVE_EXTENSION}"));
+
+    let (dir, path) = (corpus_dir.to_owned(), archive.clone());
+    let count = tokio::task::spawn_blocking(move || write_archive(&dir, &path))
+        .await?
+        .with_context(|| format!("archiving corpus: {}", corpus_dir.display()))?;
+
+    let file_name = archive.file_name().unwrap().to_string_lossy().into_owned();
+    store::open(container)?
+        .replace(&archive, &file_name)
+        .await
+        .context("uploading corpus archive failed")?;
+    let blob = container.blob(&file_name);
+    onefuzz::manifest::record_file(&blob, &archive).await;
+
+    info!("uploaded corpus archive of {} inputs: {}", count, blob);
+    Ok(())
+}
+
+/// Write a zstd-compressed tarball of the files directly within `corpus_dir`,
+/// returning the number archived.
+fn write_archive(corpus_dir: &Path, archive: &Path) -> Result<usize> {
+    let encoder = zstd::Encoder::new(File::create(archive)?, 0)?;
+    let mut builder = tar::Builder::new(encoder);
+
+    let mut count = 0;
+    for entry in std::fs::read_dir(corpus_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        builder.append_path_with_name(entry.path(), entry.file_name())?;
+        count += 1;
+    }
+
+    builder.into_inner()?.finish()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_archive() -> Result<()> {
+        let corpus = tempfile::tempdir()?;
+        std::fs::write(corpus.path().join("a"), "input a")?;
+        std::fs::write(corpus.path().join("b"), "input b")?;
+        std::fs::create_dir(corpus.path().join("subdir"))?;
+
+        let out = tempfile::tempdir()?;
+        let archive = out.path().join("corpus.tar.zst");
+        assert_eq!(write_archive(corpus.path(), &archive)?, 2);
+
+        let decoder = zstd::Decoder::new(File::open(&archive)?)?;
+        let mut names = tar::Archive::new(decoder)
+            .entries()?
+            .map(|entry| Ok(entry?.path()?.to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>>>()?;
+        names.sort();
+        assert_eq!(names, ["a", "b"]);
+
+        Ok(())
+    }
+}
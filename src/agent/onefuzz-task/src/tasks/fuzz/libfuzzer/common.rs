@@ -1,45 +1,82 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+use super::archive::upload_corpus_archive;
+use super::large_input::save_large_input;
+use super::layout::CorpusLayout;
+use super::rr;
 use crate::tasks::{
     config::CommonConfig,
+    error::TaskError,
     heartbeat::{HeartbeatSender, TaskHeartbeatClient},
-    utils::default_bool_true,
+    lifecycle,
+    utils::{
+        check_target_nice, check_target_rss_limit, crash_ttl_metadata, default_bool_true,
+        deserialize_file_mode, load_seccomp_profile, normalize_dir_permissions,
+        place_auxiliary_files, remove_checksum_sidecars, remove_unverified_inputs,
+        resolve_target_user, try_resolve_setup_relative_path,
+    },
 };
 use anyhow::{Context, Result};
 use arraydeque::{ArrayDeque, Wrapping};
 use async_trait::async_trait;
 use futures::future::try_join_all;
 use onefuzz::{
+    blob::BlobContainerUrl,
     fs::list_files,
+    jitter::delay_with_jitter,
     libfuzzer::{LibFuzzer, LibFuzzerLine},
     process::ExitStatus,
-    syncdir::{continuous_sync, SyncOperation::Pull, SyncedDir},
+    seccomp::SeccompFilter,
+    sha256,
+    syncdir::{
+        SyncOperation::{self, Pull, Push},
+        SyncedDir, DEFAULT_CONTINUOUS_SYNC_DELAY_SECONDS,
+    },
+    target_user::TargetUser,
 };
 use onefuzz_result::job_result::{JobResultData, JobResultSender, TaskJobResultClient};
 use onefuzz_telemetry::{
-    Event::{new_coverage, new_crashdump, new_result, runtime_stats},
-    EventData,
+    Event::{
+        crash_suppressed, new_coverage, new_crashdump, new_result, runtime_stats,
+        smoke_test_failed, uninstrumented_target,
+    },
+    EventData, LifecycleState,
 };
 use serde::Deserialize;
+use stacktrace_parser::CrashLog;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ffi::{OsStr, OsString},
     fmt::Debug,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
 };
 use tempfile::{tempdir_in, TempDir};
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
-    sync::{mpsc, Notify},
+    process::ChildStdout,
+    sync::{mpsc, watch, Notify},
     time::{sleep, Duration, Instant},
 };
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 // Period of reporting fuzzer-generated runtime stats.
 const RUNTIME_STATS_PERIOD: Duration = Duration::from_secs(60);
 
+// Period of checking the size of the writable corpus against `max_corpus_size`.
+const CORPUS_SIZE_CHECK_PERIOD: Duration = Duration::from_secs(5 * 60);
+
+// Period of uploading the corpus archive, for `corpus_archive_output`.
+const CORPUS_ARCHIVE_PERIOD: Duration = Duration::from_secs(30 * 60);
+
+// Period of uploading a nested corpus, if `corpus_sync_interval_seconds` is unset.
+const NESTED_CORPUS_SYNC_INTERVAL_SECONDS: u64 = 60;
+
 // Period for minimum duration between launches of libFuzzer
 const COOLOFF_PERIOD: Duration = Duration::from_secs(10);
 
@@ -94,6 +131,201 @@ pub struct Config<L: LibFuzzerType + Send + Sync + ?Sized> {
     #[serde(default)]
     pub expect_crash_on_failure: bool,
 
+    /// Only keep one crashing input per unique call stack seen during the run.
+    /// Crashes with a call stack that was already seen are discarded.
+    #[serde(default)]
+    pub only_unique_crashes: bool,
+
+    /// Stop after a fixed number of fuzzing iterations, via `-runs=N`.
+    ///
+    /// A worker whose libFuzzer exits cleanly is not restarted, and the task
+    /// succeeds once every worker has finished. A run that ends in a crash is
+    /// restarted as usual, with a fresh count of iterations.
+    ///
+    /// This does not override `-max_total_time`, which falls back to a default
+    /// if not set in `target_options`; whichever limit is reached first ends
+    /// the run. Pair with `random_seed` for reproducible CI jobs.
+    #[serde(default)]
+    pub max_runs: Option<u64>,
+
+    /// Known-good inputs to replay once before fuzzing, either a single file or a
+    /// directory of files. If any of them crash, the build is assumed to be broken
+    /// and the task fails. Relative paths are resolved against the setup dir.
+    #[serde(default)]
+    pub smoke_test_inputs: Option<PathBuf>,
+
+    /// Run the target once before fuzzing, and fail the task if libFuzzer
+    /// finds no coverage counters in it, as with a build that wasn't compiled
+    /// with `-fsanitize=fuzzer`. Such a target runs, but never finds new
+    /// coverage.
+    #[serde(default)]
+    pub require_instrumentation: bool,
+
+    /// End the task once this many unique crashes have been found.
+    ///
+    /// Crashes are unique by call stack, the same as with `only_unique_crashes`.
+    /// Crashes without a parseable call stack are always counted.
+    #[serde(default)]
+    pub stop_after_crashes: Option<u32>,
+
+    /// Concentrate mutation on inputs which reach this function, via
+    /// `-focus_function`. The name is resolved by libFuzzer.
+    #[serde(default)]
+    pub focus_function: Option<String>,
+
+    /// Mode to set on each input after the initial sync, such as `"0644"`, so
+    /// that a target running with dropped privileges can read it. Unix only.
+    #[serde(default, deserialize_with = "deserialize_file_mode")]
+    pub normalize_input_permissions: Option<u32>,
+
+    /// Upload new corpus inputs in batches every this many seconds, rather than
+    /// as each one is found. `0` uploads only when the task stops. New
+    /// coverage is still reported as each input is found.
+    ///
+    /// Inputs found since the last upload are lost if the node is reclaimed
+    /// without a clean shutdown, which is likely on spot VMs, so large
+    /// intervals trade storage cost for lost coverage.
+    #[serde(default)]
+    pub corpus_sync_interval_seconds: Option<u64>,
+
+    /// Compiled BPF seccomp profile, relative to the setup dir, to confine the
+    /// target with. Linux only.
+    #[serde(default)]
+    pub seccomp_profile: Option<PathBuf>,
+
+    /// Minimize the writable corpus in place, with `-merge=1`, when it holds
+    /// more than this many inputs. Inputs removed by minimization are also
+    /// removed from the corpus container.
+    ///
+    /// Minimization runs alongside the fuzzers, and may not bring the corpus
+    /// under the limit if every input adds coverage.
+    #[serde(default)]
+    pub max_corpus_size: Option<usize>,
+
+    /// Run the target as this user, rather than as the agent's user, so that
+    /// it doesn't run as root. Directories the target writes to are given to
+    /// the user. Linux only.
+    #[serde(default)]
+    pub run_target_as_user: Option<String>,
+
+    /// Set `-use_value_profile`, to use the values of compared operands as
+    /// coverage. Supported since LLVM 4, and off by default.
+    #[serde(default)]
+    pub use_value_profile: Option<bool>,
+
+    /// Set `-entropic`, to schedule inputs by the rarity of the features they
+    /// reach. Supported since LLVM 11, where it is on by default.
+    #[serde(default)]
+    pub entropic: Option<bool>,
+
+    /// Set `-cross_over`, to combine pairs of corpus inputs when mutating.
+    /// Supported by all versions, and on by default.
+    #[serde(default)]
+    pub cross_over: Option<bool>,
+
+    /// After the initial sync, remove inputs which don't match the SHA-256 in
+    /// their `<input>.sha256` sidecar file, such as inputs truncated while
+    /// syncing. Inputs synced later, and inputs without a sidecar, are not
+    /// checked. Either way, sidecars are removed from the corpus after each
+    /// sync, so that they aren't fuzzed as inputs.
+    #[serde(default)]
+    pub verify_input_checksum: bool,
+
+    /// Container to upload the corpus to as a single `<task_id>.tar.zst`
+    /// archive, every 30 minutes and when the task stops, for consumers which
+    /// would otherwise list and download every input.
+    #[serde(default)]
+    pub corpus_archive_output: Option<BlobContainerUrl>,
+
+    /// Only upload the corpus as an archive, rather than also pushing each new
+    /// input to the `inputs` container as it is found. Requires
+    /// `corpus_archive_output`. Inputs found since the last archive are lost if
+    /// the task doesn't stop cleanly.
+    #[serde(default)]
+    pub corpus_archive_only: bool,
+
+    /// Limit the target's virtual memory to this many megabytes, so that a
+    /// runaway allocation fails and is reported as a crash rather than
+    /// invoking the OOM killer. Linux only, and rejected elsewhere. Not usable
+    /// with sanitizer builds, which reserve far more address space than they
+    /// use.
+    #[serde(default)]
+    pub target_rss_limit_mb: Option<u64>,
+
+    /// libFuzzer's own memory limit, via `-rss_limit_mb`, in place of its
+    /// default of 2048. Inputs which exceed it are saved as crashes, so the
+    /// report task should use the same limit for them to reproduce.
+    #[serde(default)]
+    pub rss_limit_mb: Option<u64>,
+
+    /// Scheduling priority to run the target with, as a Unix nice value from
+    /// -20 to 19, so that fuzzing can yield to other work on shared machines.
+    /// Mapped to the nearest priority class on Windows. Unset by default, which
+    /// keeps the agent's own priority.
+    #[serde(default)]
+    pub target_nice: Option<i32>,
+
+    /// Setup-relative files, such as config or license files, which the target
+    /// expects in its working directory. Copied there once at startup. Missing
+    /// files fail the task.
+    #[serde(default)]
+    pub auxiliary_files: Vec<PathBuf>,
+
+    /// Days to keep uploaded crashes and crash dumps before storage lifecycle rules may
+    /// delete them, set as `onefuzz_ttl_days` blob metadata. If unset, no
+    /// metadata is added.
+    #[serde(default)]
+    pub crash_ttl_days: Option<u32>,
+
+    /// Also save crashes larger than this many bytes to `large_crashes`,
+    /// zstd-compressed, and split into numbered chunks if still over 64 MiB,
+    /// with a manifest saying how to reassemble them, so that very large
+    /// inputs download reliably. Requires `large_crashes`. Unset by default.
+    #[serde(default)]
+    pub chunk_large_inputs_over_bytes: Option<u64>,
+
+    /// Container for the crashes saved with `chunk_large_inputs_over_bytes`.
+    #[serde(default)]
+    pub large_crashes: Option<SyncedDir>,
+
+    /// Run each corpus input through the fuzzer once, with `-runs=0`, rather
+    /// than fuzzing. New inputs are not uploaded, the corpus is not minimized,
+    /// and a single worker is used. Crashes are collected as usual. Since
+    /// libFuzzer stops at the first crashing input, it is restarted without
+    /// each crashing input until the rest of the corpus is replayed. Can't be
+    /// combined with `max_runs`.
+    #[serde(default)]
+    pub replay_only: bool,
+
+    /// Minimize the corpus with `-merge=1`, using the same target and options
+    /// as fuzzing, upload the result to `inputs`, and exit, rather than
+    /// fuzzing. Inputs dropped by the merge are deleted from the container.
+    /// Can't be combined with `replay_only`, `max_runs`, `-max_total_time` in
+    /// `target_options`, or corpus_layout `nested`.
+    #[serde(default)]
+    pub corpus_minimize_only: bool,
+
+    /// Rerun each crashing input alone under `rr record`, and save the trace
+    /// to `crashdumps` as `{crash}.rr.tar.zst`, for replay with `rr replay`.
+    /// At most 10 traces of up to 2 GiB are saved. Linux only, and requires
+    /// `crashdumps`, `rr` on the `PATH`, and a `perf_event_paranoid` of at
+    /// most 1.
+    #[serde(default)]
+    pub record_with_rr: bool,
+
+    /// How inputs are organized within `inputs` and `readonly_inputs`. With
+    /// `nested`, each subdirectory is passed to the target as a corpus, and
+    /// new inputs are synced with their subdirectory rather than flattened.
+    /// Can't be combined with `max_corpus_size` or `corpus_archive_output`.
+    /// Defaults to `flat`.
+    #[serde(default)]
+    pub corpus_layout: CorpusLayout,
+
+    /// Log each line the target writes to stdout and stderr at debug level as
+    /// it runs, for debugging a stuck target. Only shown at `log_level=debug`.
+    #[serde(default)]
+    pub tee_target_output: bool,
+
     #[serde(flatten)]
     pub common: CommonConfig,
 
@@ -101,12 +333,190 @@ pub struct Config<L: LibFuzzerType + Send + Sync + ?Sized> {
     pub extra: L::Config,
 }
 
+impl<L: LibFuzzerType + ?Sized> Config<L> {
+    /// The libFuzzer options for the fuzzing target: the user-provided `target_options`,
+    /// plus any options derived from the rest of the config.
+    ///
+    /// Derived options are not added if the user already set them explicitly.
+    pub fn libfuzzer_options(&self) -> Vec<String> {
+        let mut options = self.target_options.clone();
+
+        if self.replay_only {
+            options.retain(|option| !option.starts_with("-runs="));
+            options.push("-runs=0".to_owned());
+        } else if let Some(runs) = self.max_runs {
+            add_option_if_missing(&mut options, "-runs", runs);
+        }
+
+        if let Some(function) = &self.focus_function {
+            add_option_if_missing(&mut options, "-focus_function", function);
+        }
+
+        add_rss_limit(&mut options, self.rss_limit_mb);
+
+        add_flag_if_missing(&mut options, "-use_value_profile", self.use_value_profile);
+        add_flag_if_missing(&mut options, "-entropic", self.entropic);
+        add_flag_if_missing(&mut options, "-cross_over", self.cross_over);
+
+        options
+    }
+
+    pub async fn seccomp_filter(&self) -> Result<Option<SeccompFilter>> {
+        load_seccomp_profile(&self.common.setup_dir, self.seccomp_profile.as_deref()).await
+    }
+
+    pub fn target_user(&self) -> Result<Option<TargetUser>> {
+        resolve_target_user(self.run_target_as_user.as_deref())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if cfg!(not(target_os = "linux")) && self.seccomp_profile.is_some() {
+            return Err(TaskError::Configuration(anyhow!(
+                "seccomp_profile is only supported on Linux"
+            ))
+            .into());
+        }
+
+        if cfg!(not(target_os = "linux")) && self.run_target_as_user.is_some() {
+            return Err(TaskError::Configuration(anyhow!(
+                "run_target_as_user is only supported on Linux"
+            ))
+            .into());
+        }
+
+        if matches!(&self.focus_function, Some(function) if function.trim().is_empty()) {
+            return Err(
+                TaskError::Configuration(anyhow!("focus_function must not be empty")).into(),
+            );
+        }
+
+        check_target_rss_limit(self.target_rss_limit_mb)?;
+        check_rss_limit(self.rss_limit_mb)?;
+        check_target_nice(self.target_nice, false)?;
+
+        if self.replay_only && self.max_runs.is_some() {
+            return Err(TaskError::Configuration(anyhow!(
+                "replay_only can't be combined with max_runs"
+            ))
+            .into());
+        }
+
+        if self.corpus_minimize_only {
+            if self.replay_only {
+                return Err(TaskError::Configuration(anyhow!(
+                    "corpus_minimize_only can't be combined with replay_only"
+                ))
+                .into());
+            }
+            let time_limit = self
+                .target_options
+                .iter()
+                .any(|option| option.starts_with("-max_total_time="));
+            if self.max_runs.is_some() || time_limit {
+                return Err(TaskError::Configuration(anyhow!(
+                    "corpus_minimize_only can't be combined with max_runs or -max_total_time"
+                ))
+                .into());
+            }
+            if self.corpus_layout == CorpusLayout::Nested {
+                return Err(TaskError::Configuration(anyhow!(
+                    "corpus_minimize_only can't be combined with corpus_layout `nested`"
+                ))
+                .into());
+            }
+        }
+
+        if cfg!(not(target_os = "linux")) && self.record_with_rr {
+            return Err(TaskError::Configuration(anyhow!(
+                "record_with_rr is only supported on Linux"
+            ))
+            .into());
+        }
+
+        if self.record_with_rr && self.crashdumps.is_none() {
+            return Err(
+                TaskError::Configuration(anyhow!("record_with_rr requires crashdumps")).into(),
+            );
+        }
+
+        if self.chunk_large_inputs_over_bytes.is_some() && self.large_crashes.is_none() {
+            return Err(TaskError::Configuration(anyhow!(
+                "chunk_large_inputs_over_bytes requires large_crashes"
+            ))
+            .into());
+        }
+
+        if self.corpus_layout == CorpusLayout::Nested {
+            if self.max_corpus_size.is_some() {
+                return Err(TaskError::Configuration(anyhow!(
+                    "max_corpus_size can't be combined with corpus_layout `nested`"
+                ))
+                .into());
+            }
+            if self.corpus_archive_output.is_some() {
+                return Err(TaskError::Configuration(anyhow!(
+                    "corpus_archive_output can't be combined with corpus_layout `nested`"
+                ))
+                .into());
+            }
+        }
+
+        if self.corpus_archive_only && self.corpus_archive_output.is_none() {
+            return Err(TaskError::Configuration(anyhow!(
+                "corpus_archive_only requires corpus_archive_output"
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+fn add_option_if_missing(options: &mut Vec<String>, name: &str, value: impl std::fmt::Display) {
+    let prefix = format!("{name}=");
+    if !options.iter().any(|option| option.starts_with(&prefix)) {
+        options.push(format!("{prefix}{value}"));
+    }
+}
+
+/// Set libFuzzer's `-rss_limit_mb` to `rss_limit_mb`, if set and not already
+/// in `options`.
+pub fn add_rss_limit(options: &mut Vec<String>, rss_limit_mb: Option<u64>) {
+    if let Some(rss_limit_mb) = rss_limit_mb {
+        add_option_if_missing(options, "-rss_limit_mb", rss_limit_mb);
+    }
+}
+
+/// Reject a limit of zero, which libFuzzer takes to mean no limit at all.
+pub fn check_rss_limit(rss_limit_mb: Option<u64>) -> Result<()> {
+    if rss_limit_mb == Some(0) {
+        return Err(TaskError::Configuration(anyhow!("rss_limit_mb must be at least 1")).into());
+    }
+    Ok(())
+}
+
+// Boolean libFuzzer flags are set with `=1` or `=0`. Unset flags are left to
+// the engine default.
+fn add_flag_if_missing(options: &mut Vec<String>, name: &str, value: Option<bool>) {
+    if let Some(value) = value {
+        add_option_if_missing(options, name, u8::from(value));
+    }
+}
+
 pub struct LibFuzzerFuzzTask<L>
 where
     L: LibFuzzerType,
     Config<L>: Debug,
 {
     config: Config<L>,
+    seen_crashes: Mutex<HashSet<String>>,
+    suppressed_crashes: AtomicU64,
+    unique_crashes: AtomicU64,
+    rr_traces: AtomicU64,
+    /// SHA-256 of each input which crashed while replaying the corpus.
+    replay_crashes: Mutex<HashSet<String>>,
+    /// Cancelled once `stop_after_crashes` is reached, to stop all workers.
+    crash_limit_reached: CancellationToken,
 }
 
 impl<L> LibFuzzerFuzzTask<L>
@@ -115,10 +525,22 @@ where
     Config<L>: Debug,
 {
     pub fn new(config: Config<L>) -> Result<Self> {
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            seen_crashes: Mutex::new(HashSet::new()),
+            suppressed_crashes: AtomicU64::new(0),
+            unique_crashes: AtomicU64::new(0),
+            rr_traces: AtomicU64::new(0),
+            replay_crashes: Mutex::new(HashSet::new()),
+            crash_limit_reached: CancellationToken::new(),
+        })
     }
 
     fn workers(&self) -> usize {
+        if self.config.replay_only {
+            return 1;
+        }
+
         match self.config.target_workers {
             0 => default_workers(),
             x => x,
@@ -126,59 +548,167 @@ where
     }
 
     pub async fn run(&self) -> Result<()> {
+        self.config.validate()?;
         self.init_directories().await?;
         L::extra_setup(&self.config).await?;
+        place_auxiliary_files(&self.config.common.setup_dir, &self.config.auxiliary_files).await?;
         self.verify().await?;
+        self.check_instrumentation().await?;
+        self.smoke_test().await?;
+
+        if self.config.corpus_minimize_only {
+            return self.minimize_corpus_only().await;
+        }
 
         let hb_client = self.config.common.init_heartbeat(None).await?;
         let jr_client = self.config.common.init_job_result().await?;
 
-        // To be scheduled.
-        let resync = self.continuous_sync_inputs();
+        // Cancelled either on shutdown, or once all fuzzers have finished their `max_runs`.
+        let cancellation_token = &self.config.common.cancellation_token.child_token();
 
-        let new_inputs = self
-            .config
-            .inputs
-            .monitor_results(new_coverage, true, &jr_client);
-        let new_crashes = self
-            .config
-            .crashes
-            .monitor_results(new_result, true, &jr_client);
+        // To be scheduled.
+        let resync = self.continuous_sync_inputs(cancellation_token);
+
+        let new_inputs = self.sync_new_inputs(&jr_client, cancellation_token);
+        let crash_metadata = crash_ttl_metadata(self.config.crash_ttl_days);
+        let new_crashes = self.config.crashes.monitor_results_with_metadata(
+            new_result,
+            true,
+            &jr_client,
+            cancellation_token,
+            &crash_metadata,
+        );
         let new_crashdumps = async {
             if let Some(crashdumps) = &self.config.crashdumps {
                 crashdumps
-                    .monitor_results(new_crashdump, true, &jr_client)
+                    .monitor_results_with_metadata(
+                        new_crashdump,
+                        true,
+                        &jr_client,
+                        cancellation_token,
+                        &crash_metadata,
+                    )
                     .await
             } else {
                 Ok(())
             }
         };
 
+        let bound_corpus = self.bound_corpus_size(cancellation_token);
+        let archive_corpus = self.archive_corpus_periodically(cancellation_token);
+
         let (stats_sender, stats_receiver) = mpsc::unbounded_channel();
-        let report_stats = report_runtime_stats(stats_receiver, &hb_client, &jr_client);
-        let fuzzers = self.run_fuzzers(Some(&stats_sender));
-        futures::try_join!(
+        let report_stats = report_runtime_stats(
+            stats_receiver,
+            &hb_client,
+            &jr_client,
+            self.config.common.pause.subscribe(),
+            cancellation_token,
+        );
+        let fuzzers = async {
+            self.run_fuzzers(Some(&stats_sender)).await?;
+            info!("all fuzzers finished, stopping task");
+
+            // Upload directly, since the crash monitor stops as soon as we cancel.
+            self.config
+                .crashes
+                .sync_push_with_metadata(&crash_metadata, &self.config.common.cancellation_token)
+                .await?;
+            cancellation_token.cancel();
+            Ok::<_, anyhow::Error>(())
+        };
+        lifecycle::transition(LifecycleState::Ready);
+        let result = futures::try_join!(
             resync,
             new_inputs,
             new_crashes,
             new_crashdumps,
             fuzzers,
-            report_stats
-        )?;
+            report_stats,
+            bound_corpus,
+            archive_corpus
+        );
 
-        Ok(())
+        // Archived even if the task failed, since with `corpus_archive_only`
+        // the archive is the only copy of the inputs found.
+        let archived = self.archive_corpus().await;
+        result?;
+        archived
     }
 
     pub async fn verify(&self) -> Result<()> {
-        let mut directories: Vec<&Path> = vec![&self.config.inputs.local_path];
-        if let Some(readonly_inputs) = &self.config.readonly_inputs {
-            directories.extend(readonly_inputs.iter().map(|x| -> &Path { &x.local_path }));
-        }
+        let corpus_dirs = self.corpus_dirs().await?;
+        let directories: Vec<&Path> = corpus_dirs.iter().map(|x| x.as_path()).collect();
 
         let fuzzer = L::from_config(&self.config).await?;
         fuzzer
             .verify(self.config.check_fuzzer_help, Some(&directories))
             .await
+            .map_err(|err| TaskError::TargetFailure(err).into())
+    }
+
+    /// Fail if `require_instrumentation` is set and the target has no coverage
+    /// counters.
+    pub async fn check_instrumentation(&self) -> Result<()> {
+        if !self.config.require_instrumentation {
+            return Ok(());
+        }
+
+        let fuzzer = L::from_config(&self.config).await?;
+        let counters = fuzzer.coverage_counters().await?;
+        if counters == 0 {
+            let target_exe = self.config.target_exe.display().to_string();
+            event!(uninstrumented_target; EventData::Path = target_exe.clone());
+            metric!(uninstrumented_target; 1.0; EventData::Path = target_exe.clone());
+            return Err(TaskError::TargetFailure(anyhow!(
+                "target {} has no coverage counters, it may not be instrumented for fuzzing",
+                target_exe
+            ))
+            .into());
+        }
+
+        info!("target has {} coverage counters", counters);
+        Ok(())
+    }
+
+    /// Replay `smoke_test_inputs`, failing if any of them crash.
+    pub async fn smoke_test(&self) -> Result<()> {
+        let path = match &self.config.smoke_test_inputs {
+            Some(path) => {
+                try_resolve_setup_relative_path(&self.config.common.setup_dir, path).await?
+            }
+            None => return Ok(()),
+        };
+
+        let inputs = if path.is_dir() {
+            list_files(&path).await?
+        } else {
+            vec![path]
+        };
+
+        info!("replaying {} smoke test inputs", inputs.len());
+        let fuzzer = L::from_config(&self.config).await?;
+        for input in &inputs {
+            let result = fuzzer.repro(input, None, 0).await?;
+
+            let failure = match (&result.crash_log, &result.error) {
+                (Some(crash_log), _) => format!("crashed: {}", crash_log.summary),
+                (None, Some(err)) => format!("failed: {err}"),
+                (None, None) => continue,
+            };
+
+            event!(smoke_test_failed; EventData::Path = input.display().to_string());
+            metric!(smoke_test_failed; 1.0; EventData::Path = input.display().to_string());
+            return Err(TaskError::TargetFailure(anyhow!(
+                "smoke test input {} {}, the target build may be broken",
+                input.display(),
+                failure
+            ))
+            .into());
+        }
+
+        info!("smoke test passed");
+        Ok(())
     }
 
     pub async fn run_fuzzers(&self, stats_sender: Option<&StatsSender>) -> Result<()> {
@@ -191,6 +721,19 @@ where
         Ok(())
     }
 
+    /// The corpus directories to pass to the target, starting with the one it
+    /// writes new inputs to.
+    async fn corpus_dirs(&self) -> Result<Vec<PathBuf>> {
+        let layout = self.config.corpus_layout;
+        let mut dirs = vec![layout.writable_root(&self.config.inputs.local_path).await?];
+        if let Some(readonly_inputs) = &self.config.readonly_inputs {
+            for dir in readonly_inputs {
+                dirs.extend(layout.roots(&dir.local_path).await?);
+            }
+        }
+        Ok(dirs)
+    }
+
     /// Creates a temporary directory in the current task directory
     async fn create_local_temp_dir(&self) -> Result<TempDir> {
         let task_dir = self
@@ -202,6 +745,9 @@ where
         let temp_path = task_dir.join(".temp");
         tokio::fs::create_dir_all(&temp_path).await?;
         let temp_dir = tempdir_in(temp_path)?;
+        if let Some(user) = self.config.target_user()? {
+            user.grant_dir(temp_dir.path())?;
+        }
         Ok(temp_dir)
     }
 
@@ -214,20 +760,31 @@ where
         worker_id: usize,
         stats_sender: Option<&StatsSender>,
     ) -> Result<()> {
+        if self.config.replay_only {
+            return self.replay_corpus(worker_id, stats_sender).await;
+        }
+
         let local_input_dir = self.create_local_temp_dir().await?;
         loop {
             let instant = Instant::now();
-            self.run_fuzzer(&local_input_dir.path(), worker_id, stats_sender)
+            let corpus_dirs = self.corpus_dirs().await?;
+            let exit_status = self
+                .run_fuzzer(
+                    &local_input_dir.path(),
+                    &corpus_dirs,
+                    worker_id,
+                    stats_sender,
+                )
                 .await?;
 
+            let input_dir = self
+                .config
+                .corpus_layout
+                .writable_root(&self.config.inputs.local_path)
+                .await?;
             let mut entries = tokio::fs::read_dir(local_input_dir.path()).await?;
             while let Ok(Some(entry)) = entries.next_entry().await {
-                let destination_path = self
-                    .config
-                    .inputs
-                    .local_path
-                    .clone()
-                    .join(entry.file_name());
+                let destination_path = input_dir.join(entry.file_name());
                 tokio::fs::rename(&entry.path(), &destination_path)
                     .await
                     .with_context(|| {
@@ -239,6 +796,16 @@ where
                     })?;
             }
 
+            if self.config.max_runs.is_some() && exit_status.success {
+                info!("fuzzer {} completed its runs", worker_id);
+                return Ok(());
+            }
+
+            if self.crash_limit_reached.is_cancelled() {
+                info!("fuzzer {} stopped, crash limit reached", worker_id);
+                return Ok(());
+            }
+
             // if libFuzzer is exiting rapidly, give some breathing room to allow the
             // handles to be reaped.
             let runtime = instant.elapsed();
@@ -248,39 +815,100 @@ where
         }
     }
 
+    /// Replay the corpus once, restarting libFuzzer without each input which
+    /// crashes, since it stops at the first one.
+    async fn replay_corpus(
+        &self,
+        worker_id: usize,
+        stats_sender: Option<&StatsSender>,
+    ) -> Result<()> {
+        let mut corpus = Vec::new();
+        for dir in self.corpus_dirs().await? {
+            for input in list_files(&dir).await? {
+                corpus.push((sha256::digest_file(&input).await?, input));
+            }
+        }
+
+        // Nothing is written here with `-runs=0`.
+        let local_input_dir = self.create_local_temp_dir().await?;
+        loop {
+            let replay_dir = self.create_local_temp_dir().await?;
+            let crashes = self.replay_crashes.lock().unwrap().clone();
+            for (digest, input) in &corpus {
+                if !crashes.contains(digest) {
+                    let dest = replay_dir.path().join(digest);
+                    if tokio::fs::hard_link(input, &dest).await.is_err() {
+                        tokio::fs::copy(input, &dest).await?;
+                    }
+                }
+            }
+
+            let exit_status = self
+                .run_fuzzer(
+                    local_input_dir.path(),
+                    &[replay_dir.path().to_owned()],
+                    worker_id,
+                    stats_sender,
+                )
+                .await?;
+
+            let crashed = self.replay_crashes.lock().unwrap().len() > crashes.len();
+            if exit_status.success || !crashed || self.crash_limit_reached.is_cancelled() {
+                info!("fuzzer {} finished replaying the corpus", worker_id);
+                return Ok(());
+            }
+            info!("fuzzer {} resuming replay after crash", worker_id);
+        }
+    }
+
     // Fuzz with a libFuzzer until it exits.
     //
     // While it runs, parse stderr for progress metrics, and report them.
     async fn run_fuzzer(
         &self,
         local_inputs: impl AsRef<Path>,
+        inputs: &[PathBuf],
         worker_id: usize,
         stats_sender: Option<&StatsSender>,
-    ) -> Result<()> {
+    ) -> Result<ExitStatus> {
         let crash_dir = self.create_local_temp_dir().await?;
         let run_id = Uuid::new_v4();
 
         debug!("starting fuzzer run, run_id = {}", run_id);
 
-        let mut inputs = vec![&self.config.inputs.local_path];
-        if let Some(readonly_inputs) = &self.config.readonly_inputs {
-            readonly_inputs
-                .iter()
-                .for_each(|d| inputs.push(&d.local_path));
-        }
-
         info!("config is: {:?}", self.config);
 
-        let fuzzer = L::from_config(&self.config).await?;
-        let mut running = fuzzer.fuzz(crash_dir.path(), local_inputs, &inputs)?;
+        // Offset the configured seed, so that workers don't all make the same
+        // mutations.
+        let seed = self
+            .config
+            .common
+            .random_seed
+            .map(|seed| seed.wrapping_add(worker_id as u64));
+        let fuzzer = L::from_config(&self.config).await?.seed(seed);
+        let mut running = fuzzer.fuzz(crash_dir.path(), local_inputs, inputs)?;
 
         info!("child is: {:?}", running);
 
         #[cfg(target_os = "linux")]
         let pid = running.id();
 
+        // A fuzzer restarted while the task is paused starts out suspended.
+        let child_id = running.id();
+        let mut pause = self.config.common.pause.subscribe();
+        if *pause.borrow_and_update() {
+            set_fuzzer_suspended(worker_id, child_id, true);
+        }
+
         let notify = Arc::new(Notify::new());
 
+        // Only taken to log it, since the target fails on writes to a closed pipe.
+        if self.config.tee_target_output {
+            if let Some(stdout) = running.stdout.take() {
+                tokio::spawn(tee_stdout(worker_id, stdout));
+            }
+        }
+
         // Splitting borrow.
         let stderr = running
             .stderr
@@ -292,8 +920,16 @@ where
         {
             let mut buf = vec![];
             loop {
-                buf.clear();
-                let bytes_read = stderr.read_until(b'\n', &mut buf).await?;
+                // Bytes read before a pause change are kept in `buf`, and the read resumes.
+                let bytes_read = tokio::select! {
+                    read = stderr.read_until(b'\n', &mut buf) => read?,
+                    _ = self.crash_limit_reached.cancelled() => break,
+                    Ok(()) = pause.changed() => {
+                        let paused = *pause.borrow_and_update();
+                        set_fuzzer_suspended(worker_id, child_id, paused);
+                        continue;
+                    }
+                };
                 if bytes_read == 0 && buf.is_empty() {
                     break;
                 }
@@ -304,7 +940,17 @@ where
                         error!("could not parse fuzzing interation update: {}", err);
                     }
                 }
+                if self.config.tee_target_output {
+                    debug!("fuzzer {} stderr: {}", worker_id, line.trim_end());
+                }
                 libfuzzer_output.push_back(line);
+                buf.clear();
+            }
+        }
+
+        if self.crash_limit_reached.is_cancelled() {
+            if let Err(err) = running.kill().await {
+                warn!("unable to stop fuzzer: {:?}", err);
             }
         }
 
@@ -327,19 +973,67 @@ where
 
         info!("found {} crashes", files.len());
 
+        if self.config.replay_only {
+            for file in &files {
+                let digest = sha256::digest_file(file).await?;
+                self.replay_crashes.lock().unwrap().insert(digest);
+            }
+        }
+
+        let is_duplicate = !files.is_empty()
+            && (self.config.only_unique_crashes || self.config.stop_after_crashes.is_some())
+            && self.is_duplicate_crash(libfuzzer_output.iter());
+
+        if is_duplicate && self.config.only_unique_crashes {
+            for file in &files {
+                tokio::fs::remove_file(file).await.with_context(|| {
+                    format!("unable to remove duplicate crash: {}", file.display())
+                })?;
+            }
+
+            // don't let core dumps of discarded crashes accumulate
+            #[cfg(target_os = "linux")]
+            if let Some(pid) = pid {
+                let _ = tokio::fs::remove_file(format!("core.{pid}")).await;
+            }
+
+            let suppressed = self.suppressed_crashes.fetch_add(1, Ordering::Relaxed) + 1;
+            info!(
+                "discarded duplicate crash, {} duplicates suppressed so far",
+                suppressed
+            );
+            event!(crash_suppressed; EventData::Count = suppressed);
+            metric!(crash_suppressed; 1.0; EventData::Count = suppressed);
+            return Ok(exit_status);
+        }
+
+        if !files.is_empty() && !is_duplicate {
+            self.record_unique_crash();
+        }
+
+        if let (true, Some(crashdumps), Some(crash)) = (
+            self.config.record_with_rr,
+            &self.config.crashdumps,
+            files.first(),
+        ) {
+            self.save_rr_trace(crash, crashdumps).await;
+        }
+
         // If the target exits, crashes are required unless
         // 1. Exited cleanly (happens with -runs=N)
         // 2. expect_crash_on_failure is disabled
-        if files.is_empty() && !exit_status.success {
+        // 3. We killed it because the crash limit was reached
+        if files.is_empty() && !exit_status.success && !self.crash_limit_reached.is_cancelled() {
             if self.config.expect_crash_on_failure {
-                bail!(
+                return Err(TaskError::TargetFailure(anyhow!(
                     "libfuzzer exited without generating crashes.  status:{} stderr:{:?}",
                     serde_json::to_string(&exit_status)?,
                     libfuzzer_output
                         .into_iter()
                         .collect::<Vec<String>>()
                         .join("\n")
-                );
+                ))
+                .into());
             } else {
                 warn!(
                     "libfuzzer exited without generating crashes, continuing.  status:{} stderr:{:?}",
@@ -368,6 +1062,7 @@ where
                         bail!(e)
                     }
                 }
+                self.save_large_crash(&dest).await;
             }
         }
 
@@ -434,15 +1129,125 @@ where
             }
         }
 
-        Ok(())
+        Ok(exit_status)
+    }
+
+    /// Save `crash` compressed to `large_crashes`, if it is large, logging
+    /// rather than returning errors, since the crash itself is still saved.
+    async fn save_large_crash(&self, crash: &Path) {
+        let (Some(threshold), Some(large_crashes)) = (
+            self.config.chunk_large_inputs_over_bytes,
+            &self.config.large_crashes,
+        ) else {
+            return;
+        };
+        match save_large_input(crash, threshold, large_crashes).await {
+            Ok(Some(large_input)) => info!(
+                "saved large crash {} as {} blobs",
+                crash.display(),
+                large_input.blobs.len()
+            ),
+            Ok(None) => {}
+            Err(err) => warn!("unable to save large crash: {:?}", err),
+        }
+    }
+
+    /// Rerun `crash` alone under `rr record`, and save the trace to
+    /// `crashdumps`, if fewer than `rr::MAX_TRACES` have been saved. Failures
+    /// are logged, since the crash itself is still saved.
+    async fn save_rr_trace(&self, crash: &Path, crashdumps: &SyncedDir) {
+        if self.rr_traces.fetch_add(1, Ordering::Relaxed) >= rr::MAX_TRACES {
+            return;
+        }
+
+        match self.record_crash(crash, crashdumps).await {
+            Ok(true) => {}
+            Ok(false) => {
+                self.rr_traces.fetch_sub(1, Ordering::Relaxed);
+            }
+            Err(err) => {
+                self.rr_traces.fetch_sub(1, Ordering::Relaxed);
+                warn!("unable to record crash with rr: {:?}", err);
+            }
+        }
+    }
+
+    async fn record_crash(&self, crash: &Path, crashdumps: &SyncedDir) -> Result<bool> {
+        let temp_dir = self.create_local_temp_dir().await?;
+        let trace_dir = temp_dir.path().join("trace");
+        let fuzzer = L::from_config(&self.config).await?;
+        if !fuzzer
+            .record_input(crash, &trace_dir, rr::RECORD_TIMEOUT)
+            .await?
+        {
+            info!("crash did not reproduce under rr: {}", crash.display());
+        }
+
+        let name = crash
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let dest = crashdumps
+            .local_path
+            .join(format!("{name}.{}", rr::TRACE_EXTENSION));
+        let saved = rr::save_trace(&trace_dir, &dest).await?;
+        if saved {
+            info!("saved rr trace: {}", dest.display());
+        }
+        Ok(saved)
+    }
+
+    /// Check the libFuzzer output of a crashing run against the call stacks seen so far,
+    /// recording it if it is new.
+    ///
+    /// Crashes without a parseable call stack are never considered duplicates.
+    fn is_duplicate_crash<'a>(&self, output: impl Iterator<Item = &'a String>) -> bool {
+        let text = output.map(String::as_str).collect::<String>();
+        let crash_log = match CrashLog::parse(text) {
+            Ok(crash_log) if !crash_log.call_stack.is_empty() => crash_log,
+            _ => return false,
+        };
+
+        let mut seen_crashes = self.seen_crashes.lock().unwrap();
+        !seen_crashes.insert(crash_log.call_stack_sha256())
+    }
+
+    /// Count a new unique crash, and signal all workers to stop once
+    /// `stop_after_crashes` is reached.
+    fn record_unique_crash(&self) {
+        let limit = match self.config.stop_after_crashes {
+            Some(limit) => u64::from(limit),
+            None => return,
+        };
+
+        let unique_crashes = self.unique_crashes.fetch_add(1, Ordering::Relaxed) + 1;
+        if unique_crashes >= limit && !self.crash_limit_reached.is_cancelled() {
+            info!(
+                "found {} unique crashes, stopping task (stop_after_crashes = {})",
+                unique_crashes, limit
+            );
+            self.crash_limit_reached.cancel();
+        }
     }
 
     async fn init_directories(&self) -> Result<()> {
         // input directories (init_pull):
-        self.config.inputs.init_pull().await?;
+        let mode = self.config.normalize_input_permissions;
+        let verify = self.config.verify_input_checksum;
+        let layout = self.config.corpus_layout;
+        let cancellation_token = &self.config.common.cancellation_token;
+        self.config.inputs.init_pull(cancellation_token).await?;
+        for root in layout.roots(&self.config.inputs.local_path).await? {
+            remove_unverified_inputs(&root, verify).await?;
+            normalize_dir_permissions(&root, mode).await?;
+        }
         if let Some(readonly_inputs) = &self.config.readonly_inputs {
             for dir in readonly_inputs {
-                dir.init_pull().await?;
+                dir.init_pull(cancellation_token).await?;
+                for root in layout.roots(&dir.local_path).await? {
+                    remove_unverified_inputs(&root, verify).await?;
+                    normalize_dir_permissions(&root, mode).await?;
+                }
             }
         }
 
@@ -451,17 +1256,256 @@ where
         if let Some(crashdumps) = &self.config.crashdumps {
             crashdumps.init().await?;
         }
+        if let Some(large_crashes) = &self.config.large_crashes {
+            large_crashes.init().await?;
+        }
+
+        // The target adds new inputs to the corpus, and may remove them when merging.
+        if let Some(user) = self.config.target_user()? {
+            user.grant_dir(&self.config.inputs.local_path)?;
+            if layout == CorpusLayout::Nested {
+                for root in layout.roots(&self.config.inputs.local_path).await? {
+                    user.grant_dir(&root)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn sync_new_inputs(
+        &self,
+        jr_client: &Option<TaskJobResultClient>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<()> {
+        if self.config.corpus_archive_only || self.config.replay_only {
+            return Ok(());
+        }
+
+        let inputs = &self.config.inputs;
+        // The results monitor uploads new files flat, so a nested corpus is
+        // synced instead, which keeps its subdirectories.
+        let interval = match self.config.corpus_layout {
+            CorpusLayout::Flat => self.config.corpus_sync_interval_seconds,
+            CorpusLayout::Nested => Some(
+                self.config
+                    .corpus_sync_interval_seconds
+                    .unwrap_or(NESTED_CORPUS_SYNC_INTERVAL_SECONDS),
+            ),
+        };
+        match interval {
+            None => {
+                inputs
+                    .monitor_results(new_coverage, true, jr_client, cancellation_token)
+                    .await
+            }
+            Some(interval) => {
+                // Inputs are still reported as they are found.
+                let events =
+                    inputs.monitor_events(new_coverage, true, jr_client, cancellation_token);
+                let sync = async {
+                    // Returns immediately if `interval` is 0.
+                    inputs
+                        .continuous_sync(Push, Some(interval), cancellation_token)
+                        .await?;
+                    cancellation_token.cancelled().await;
+
+                    // Upload the inputs found since the last sync.
+                    inputs
+                        .sync_push(&self.config.common.cancellation_token)
+                        .await
+                };
+                futures::try_join!(events, sync)?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn bound_corpus_size(&self, cancellation_token: &CancellationToken) -> Result<()> {
+        let Some(max_corpus_size) = self.config.max_corpus_size else {
+            return Ok(());
+        };
+        if self.config.replay_only {
+            return Ok(());
+        }
+
+        loop {
+            tokio::select! {
+                _ = cancellation_token.cancelled() => return Ok(()),
+                _ = sleep(CORPUS_SIZE_CHECK_PERIOD) => {}
+            }
+
+            let corpus_size = list_files(&self.config.inputs.local_path).await?.len();
+            if corpus_size <= max_corpus_size {
+                continue;
+            }
+
+            info!(
+                "corpus has {} inputs, more than max_corpus_size of {}, minimizing",
+                corpus_size, max_corpus_size
+            );
+            match self.minimize_corpus().await {
+                Ok(minimized_size) => info!(
+                    "minimized corpus from {} to {} inputs",
+                    corpus_size, minimized_size
+                ),
+                // Fuzzing can continue with the larger corpus, so try again later.
+                Err(err) => warn!("corpus minimization failed: {:?}", err),
+            }
+        }
+    }
+
+    /// Upload the corpus to `corpus_archive_output`, if set, replacing the
+    /// archive last uploaded.
+    async fn archive_corpus(&self) -> Result<()> {
+        let Some(container) = &self.config.corpus_archive_output else {
+            return Ok(());
+        };
+        upload_corpus_archive(
+            &self.config.inputs.local_path,
+            container,
+            &self.config.common.task_id.to_string(),
+        )
+        .await
+        .context("uploading corpus archive failed")
+    }
+
+    async fn archive_corpus_periodically(
+        &self,
+        cancellation_token: &CancellationToken,
+    ) -> Result<()> {
+        if self.config.corpus_archive_output.is_none() {
+            return Ok(());
+        }
+
+        loop {
+            tokio::select! {
+                _ = cancellation_token.cancelled() => return Ok(()),
+                _ = sleep(CORPUS_ARCHIVE_PERIOD) => {}
+            }
+
+            // Archived again when the task stops, so this can wait for then.
+            if let Err(err) = self.archive_corpus().await {
+                warn!("{:?}", err);
+            }
+        }
+    }
 
+    /// Minimize the writable corpus once, for `corpus_minimize_only`.
+    async fn minimize_corpus_only(&self) -> Result<()> {
+        let corpus_size = list_files(&self.config.inputs.local_path).await?.len();
+        if corpus_size == 0 {
+            info!("corpus is empty, nothing to minimize");
+            return Ok(());
+        }
+
+        let minimized_size = self.minimize_corpus().await?;
+        info!(
+            "minimized corpus from {} to {} inputs",
+            corpus_size, minimized_size
+        );
         Ok(())
     }
 
-    async fn continuous_sync_inputs(&self) -> Result<()> {
+    /// Replace the writable corpus with a merge of itself, returning the number
+    /// of inputs kept.
+    ///
+    /// Inputs written by fuzzers during the merge are kept.
+    async fn minimize_corpus(&self) -> Result<usize> {
+        let corpus_dir = &self.config.inputs.local_path;
+        let original = list_files(corpus_dir).await?;
+
+        // Keep the merge output on the same filesystem, so its inputs can be moved.
+        let parent = corpus_dir.parent().unwrap_or_else(|| Path::new("."));
+        let minimized_dir = tempdir_in(parent)?;
+        if let Some(user) = self.config.target_user()? {
+            user.grant_dir(minimized_dir.path())?;
+        }
+
+        let fuzzer = L::from_config(&self.config).await?;
+        fuzzer.merge(minimized_dir.path(), &[corpus_dir]).await?;
+
+        let minimized = list_files(minimized_dir.path()).await?;
+        if minimized.is_empty() {
+            bail!("merge produced an empty corpus");
+        }
+
+        for path in &original {
+            if let Err(err) = tokio::fs::remove_file(path).await {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    return Err(err).context("removing input from corpus");
+                }
+            }
+        }
+        for path in &minimized {
+            if let Some(name) = path.file_name() {
+                tokio::fs::rename(path, corpus_dir.join(name))
+                    .await
+                    .context("moving minimized input into corpus")?;
+            }
+        }
+
+        // Otherwise, the inputs would be pulled back down by the next sync.
+        if self.config.inputs.remote_path.is_some() {
+            self.config
+                .inputs
+                .sync(
+                    SyncOperation::Push,
+                    true,
+                    &self.config.common.cancellation_token,
+                )
+                .await
+                .context("pruning corpus container")?;
+        }
+
+        Ok(minimized.len())
+    }
+
+    async fn continuous_sync_inputs(&self, cancellation_token: &CancellationToken) -> Result<()> {
         let mut dirs = vec![self.config.inputs.clone()];
         if let Some(inputs) = &self.config.readonly_inputs {
             let inputs = inputs.clone();
             dirs.extend(inputs);
         }
-        continuous_sync(&dirs, Pull, self.config.ensemble_sync_delay).await
+
+        // Each pull brings back the checksum sidecars removed from the corpus.
+        let delay = self
+            .config
+            .ensemble_sync_delay
+            .unwrap_or(DEFAULT_CONTINUOUS_SYNC_DELAY_SECONDS);
+        if delay == 0 {
+            return Ok(());
+        }
+        loop {
+            for dir in &dirs {
+                if cancellation_token.is_cancelled() {
+                    return Ok(());
+                }
+                dir.sync(Pull, false, cancellation_token).await?;
+                for root in self.config.corpus_layout.roots(&dir.local_path).await? {
+                    remove_checksum_sidecars(&root).await?;
+                }
+            }
+            tokio::select! {
+                _ = cancellation_token.cancelled() => return Ok(()),
+                _ = delay_with_jitter(Duration::from_secs(delay)) => {}
+            }
+        }
+    }
+}
+
+/// Log the lines of a fuzzer's stdout until it is closed.
+async fn tee_stdout(worker_id: usize, stdout: ChildStdout) {
+    let mut lines = BufReader::new(stdout).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => debug!("fuzzer {} stdout: {}", worker_id, line),
+            Ok(None) => break,
+            Err(err) => {
+                debug!("unable to read fuzzer {} stdout: {}", worker_id, err);
+                break;
+            }
+        }
     }
 }
 
@@ -559,6 +1603,24 @@ impl Timer {
     }
 }
 
+fn set_fuzzer_suspended(worker_id: usize, pid: Option<u32>, suspended: bool) {
+    let Some(pid) = pid else {
+        return;
+    };
+
+    let result = if suspended {
+        onefuzz::suspend::suspend_process(pid)
+    } else {
+        onefuzz::suspend::resume_process(pid)
+    };
+
+    match result {
+        Ok(()) if suspended => info!("fuzzer {} paused", worker_id),
+        Ok(()) => info!("fuzzer {} resumed", worker_id),
+        Err(err) => warn!("unable to pause or resume fuzzer {}: {:?}", worker_id, err),
+    }
+}
+
 // Report runtime stats, as delivered via the `stats` channel, with a periodic trigger to
 // guarantee a minimum reporting frequency.
 //
@@ -570,6 +1632,8 @@ async fn report_runtime_stats(
     mut stats_channel: mpsc::UnboundedReceiver<RuntimeStats>,
     heartbeat_client: &Option<TaskHeartbeatClient>,
     jr_client: &Option<TaskJobResultClient>,
+    mut pause: watch::Receiver<bool>,
+    cancellation_token: &CancellationToken,
 ) -> Result<()> {
     // Cache the last-reported stats for a given worker.
     //
@@ -589,19 +1653,81 @@ async fn report_runtime_stats(
                 total.update(stats);
                 total.report(jr_client).await
             }
+            Ok(()) = pause.changed() => {
+                if *pause.borrow_and_update() {
+                    heartbeat_client.paused();
+                }
+            }
             _ = timer.wait() => {
+                // Paused fuzzers report no stats, so mark the task as paused instead.
+                if *pause.borrow() {
+                    heartbeat_client.paused();
+                }
                 total.report(jr_client).await
             }
+            _ = cancellation_token.cancelled() => {
+                total.report(jr_client).await;
+                return Ok(());
+            }
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{RuntimeStats, TotalStats};
+    use super::{
+        add_flag_if_missing, add_option_if_missing, add_rss_limit, check_rss_limit, RuntimeStats,
+        TotalStats,
+    };
     use anyhow::Result;
     use uuid::Uuid;
 
+    #[test]
+    fn test_add_option_if_missing() {
+        let mut options = vec!["-max_len=10".to_string()];
+        add_option_if_missing(&mut options, "-seed", 1234);
+        assert_eq!(options, vec!["-max_len=10", "-seed=1234"]);
+
+        let mut options = vec!["-seed=1".to_string()];
+        add_option_if_missing(&mut options, "-seed", 1234);
+        assert_eq!(options, vec!["-seed=1"]);
+    }
+
+    #[test]
+    fn test_add_rss_limit() {
+        let mut options = vec![];
+        add_rss_limit(&mut options, Some(512));
+        assert_eq!(options, vec!["-rss_limit_mb=512"]);
+
+        let mut options = vec!["-rss_limit_mb=64".to_string()];
+        add_rss_limit(&mut options, Some(512));
+        assert_eq!(options, vec!["-rss_limit_mb=64"]);
+
+        let mut options: Vec<String> = vec![];
+        add_rss_limit(&mut options, None);
+        assert!(options.is_empty());
+
+        assert!(check_rss_limit(Some(0)).is_err());
+        assert!(check_rss_limit(Some(1)).is_ok());
+        assert!(check_rss_limit(None).is_ok());
+    }
+
+    #[test]
+    fn test_add_flag_if_missing() {
+        let mut options = vec!["-entropic=1".to_string()];
+        add_flag_if_missing(&mut options, "-use_value_profile", Some(true));
+        add_flag_if_missing(&mut options, "-entropic", Some(false));
+        add_flag_if_missing(&mut options, "-cross_over", Some(false));
+        assert_eq!(
+            options,
+            vec!["-entropic=1", "-use_value_profile=1", "-cross_over=0"]
+        );
+
+        let mut options = vec![];
+        add_flag_if_missing(&mut options, "-use_value_profile", None);
+        assert!(options.is_empty());
+    }
+
     #[test]
     fn test_total_stats() -> Result<()> {
         let mut total = TotalStats::default();
@@ -75,7 +75,7 @@ impl common::LibFuzzerType for LibFuzzerDotnet {
             config.extra.target_method.clone(),
         );
 
-        let mut options = config.target_options.clone();
+        let mut options = config.libfuzzer_options();
         options.push(format!(
             "--target_path={}",
             config.extra.loader_path().display()
@@ -93,12 +93,21 @@ impl common::LibFuzzerType for LibFuzzerDotnet {
                 .as_ref()
                 .map(|x| x.local_path.clone()),
             config.common.machine_identity.clone(),
-        ))
+        )
+        .seccomp_filter(config.seccomp_filter().await?)
+        .target_user(config.target_user()?)
+        .memory_limit_mb(config.target_rss_limit_mb)
+        .nice(config.target_nice)
+        .tee_output(config.tee_target_output))
     }
 
     async fn extra_setup(config: &common::Config<Self>) -> Result<()> {
         // Download dotnet fuzzing tools.
-        config.extra.tools.init_pull().await?;
+        config
+            .extra
+            .tools
+            .init_pull(&config.common.cancellation_token)
+            .await?;
 
         // Ensure tools are executable.
         set_executable(&config.extra.tools.local_path).await?;
@@ -27,7 +27,7 @@ impl common::LibFuzzerType for GenericLibFuzzer {
 
         Ok(LibFuzzer::new(
             target_exe,
-            config.target_options.clone(),
+            config.libfuzzer_options(),
             config.target_env.clone(),
             config.common.setup_dir.clone(),
             config.common.extra_setup_dir.clone(),
@@ -37,7 +37,12 @@ impl common::LibFuzzerType for GenericLibFuzzer {
                 .as_ref()
                 .map(|x| x.local_path.clone()),
             config.common.machine_identity.clone(),
-        ))
+        )
+        .seccomp_filter(config.seccomp_filter().await?)
+        .target_user(config.target_user()?)
+        .memory_limit_mb(config.target_rss_limit_mb)
+        .nice(config.target_nice)
+        .tee_output(config.tee_target_output))
     }
 
     async fn extra_setup(config: &common::Config<Self>) -> Result<()> {
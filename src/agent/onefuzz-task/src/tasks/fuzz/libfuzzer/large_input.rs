@@ -0,0 +1,199 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Saving large crashing inputs compressed, and split into chunks if need be,
+//! to their own container, so that pathological inputs can still be
+//! downloaded reliably.
+//!
+//! A crash over `chunk_large_inputs_over_bytes` is zstd-compressed and saved
+//! to `large_crashes` as `{input_sha256}.zst`. If the compressed input is
+//! still over [`CHUNK_BYTES`], it is instead saved as numbered chunks,
+//! `{input_sha256}.zst.000`, `.001` and so on. Either way, a manifest,
+//! `{input_sha256}.manifest.json`, names the blobs and says how to reassemble
+//! the input. The crash itself is still saved to `crashes` as usual, for the
+//! report task.
+
+use std::{fs::File, path::Path};
+
+use anyhow::{Context, Result};
+use onefuzz::{sha256, syncdir::SyncedDir};
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncReadExt};
+
+/// Largest blob a compressed input is saved as before it is split.
+pub const CHUNK_BYTES: u64 = 64 * 1024 * 1024;
+
+const COMPRESSION: &str = "zstd";
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Manifest saved next to the blobs of a large input.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct LargeInput {
+    pub input_sha256: String,
+
+    /// Size of the original input, in bytes.
+    pub size: u64,
+
+    /// Compression applied to the input before it was split.
+    pub compression: String,
+
+    /// Blobs holding the compressed input, in order.
+    pub blobs: Vec<String>,
+
+    /// Shell command which reassembles the input from `blobs`, once they are
+    /// downloaded to the current directory.
+    pub reassembly: String,
+}
+
+/// Save `input` to `container`, if it is over `threshold` bytes, returning
+/// the manifest saved with it.
+pub async fn save_large_input(
+    input: &Path,
+    threshold: u64,
+    container: &SyncedDir,
+) -> Result<Option<LargeInput>> {
+    let size = fs::metadata(input).await?.len();
+    if size <= threshold {
+        return Ok(None);
+    }
+
+    debug!(
+        "saving large input {} ({} bytes) compressed",
+        input.display(),
+        size
+    );
+    let input_sha256 = sha256::digest_file(input).await?;
+    let large_input = upload(input, size, &input_sha256, container, CHUNK_BYTES)
+        .await
+        .with_context(|| format!("saving large input {}", input.display()))?;
+    Ok(Some(large_input))
+}
+
+async fn upload(
+    input: &Path,
+    size: u64,
+    input_sha256: &str,
+    container: &SyncedDir,
+    chunk_bytes: u64,
+) -> Result<LargeInput> {
+    let temp_dir = tempfile::tempdir()?;
+    let compressed = temp_dir.path().join("input.zst");
+    let (from, to) = (input.to_owned(), compressed.clone());
+    tokio::task::spawn_blocking(move || {
+        zstd::stream::copy_encode(File::open(from)?, File::create(to)?, 0)
+    })
+    .await??;
+
+    let name = format!("{input_sha256}.zst");
+    let compressed_size = fs::metadata(&compressed).await?.len();
+    let (blobs, reassembly) = if compressed_size <= chunk_bytes {
+        container.upload_file(&name, &compressed).await?;
+        let reassembly = format!("zstd -d {name} -o {input_sha256}");
+        (vec![name], reassembly)
+    } else {
+        let mut file = fs::File::open(&compressed).await?;
+        let mut chunks = vec![];
+        loop {
+            let mut chunk = vec![];
+            (&mut file)
+                .take(chunk_bytes)
+                .read_to_end(&mut chunk)
+                .await?;
+            if chunk.is_empty() {
+                break;
+            }
+            let chunk_name = format!("{name}.{:03}", chunks.len());
+            container
+                .upload_bytes(&chunk_name, chunk, OCTET_STREAM)
+                .await?;
+            chunks.push(chunk_name);
+        }
+        let reassembly = format!("cat {} | zstd -d -o {input_sha256}", chunks.join(" "));
+        (chunks, reassembly)
+    };
+
+    let large_input = LargeInput {
+        input_sha256: input_sha256.to_owned(),
+        size,
+        compression: COMPRESSION.to_owned(),
+        blobs,
+        reassembly,
+    };
+    container
+        .upload(&format!("{input_sha256}.manifest.json"), &large_input)
+        .await?;
+    Ok(large_input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(dir: &Path) -> SyncedDir {
+        SyncedDir {
+            local_path: dir.to_owned(),
+            remote_path: None,
+        }
+    }
+
+    fn reassemble(dir: &Path, blobs: &[String]) -> Result<Vec<u8>> {
+        let mut compressed = vec![];
+        for blob in blobs {
+            compressed.extend(std::fs::read(dir.join(blob))?);
+        }
+        Ok(zstd::stream::decode_all(&compressed[..])?)
+    }
+
+    #[tokio::test]
+    async fn test_save_large_input() -> Result<()> {
+        let input_dir = tempfile::tempdir()?;
+        let input = input_dir.path().join("crash");
+        let data: Vec<u8> = (0..64 * 1024u32).map(|i| i as u8).collect();
+        std::fs::write(&input, &data)?;
+
+        let large_crashes = tempfile::tempdir()?;
+        let container = container(large_crashes.path());
+        assert_eq!(
+            save_large_input(&input, data.len() as u64, &container).await?,
+            None
+        );
+
+        let large_input = save_large_input(&input, 1024, &container).await?.unwrap();
+        let input_sha256 = sha256::digest_file(&input).await?;
+        assert_eq!(large_input.size, data.len() as u64);
+        assert_eq!(large_input.blobs, [format!("{input_sha256}.zst")]);
+        assert_eq!(reassemble(large_crashes.path(), &large_input.blobs)?, data);
+
+        let manifest = large_crashes
+            .path()
+            .join(format!("{input_sha256}.manifest.json"));
+        let manifest: LargeInput = serde_json::from_slice(&std::fs::read(manifest)?)?;
+        assert_eq!(manifest, large_input);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_chunks() -> Result<()> {
+        let input_dir = tempfile::tempdir()?;
+        let input = input_dir.path().join("crash");
+        // Pseudo-random, so that it doesn't compress below one chunk.
+        let mut state = 1u32;
+        let data: Vec<u8> = (0..16 * 1024)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        std::fs::write(&input, &data)?;
+
+        let large_crashes = tempfile::tempdir()?;
+        let container = container(large_crashes.path());
+        let large_input = upload(&input, data.len() as u64, "abc", &container, 4096).await?;
+
+        assert!(large_input.blobs.len() > 1);
+        assert_eq!(large_input.blobs[0], "abc.zst.000");
+        assert_eq!(reassemble(large_crashes.path(), &large_input.blobs)?, data);
+        assert!(large_crashes.path().join("abc.manifest.json").is_file());
+        Ok(())
+    }
+}
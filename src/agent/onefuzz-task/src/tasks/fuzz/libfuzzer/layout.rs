@@ -0,0 +1,100 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! How inputs are organized within a corpus directory.
+//!
+//! libFuzzer reads corpora recursively, but writes new inputs flat into the
+//! first corpus directory it is given. Harnesses that expect a subdirectory
+//! per input type instead get a nested layout, where each subdirectory of a
+//! corpus is passed to the target as a corpus of its own.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::tasks::error::TaskError;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CorpusLayout {
+    /// Inputs are files directly within the corpus directory.
+    #[default]
+    Flat,
+
+    /// Inputs are files within subdirectories of the corpus directory, which
+    /// are kept when syncing. New inputs are written to the first
+    /// subdirectory, in sorted order.
+    Nested,
+}
+
+impl CorpusLayout {
+    /// The directories within `dir` to pass to the target as corpora.
+    pub async fn roots(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        match self {
+            Self::Flat => Ok(vec![dir.to_owned()]),
+            Self::Nested => list_subdirs(dir).await,
+        }
+    }
+
+    /// The directory within `dir` that the target writes new inputs to.
+    pub async fn writable_root(&self, dir: &Path) -> Result<PathBuf> {
+        self.roots(dir).await?.into_iter().next().ok_or_else(|| {
+            TaskError::Configuration(anyhow!(
+                "corpus_layout `nested` requires a subdirectory in the inputs corpus: {}",
+                dir.display()
+            ))
+            .into()
+        })
+    }
+}
+
+/// The subdirectories directly within `dir`, in sorted order, skipping
+/// hidden ones such as partial downloads.
+async fn list_subdirs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .with_context(|| format!("unable to list corpus subdirectories: {}", dir.display()))?;
+
+    let mut subdirs = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if entry.file_type().await?.is_dir() {
+            subdirs.push(entry.path());
+        }
+    }
+    subdirs.sort();
+    Ok(subdirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_roots() -> Result<()> {
+        let corpus = tempfile::tempdir()?;
+        let dir = corpus.path();
+        std::fs::write(dir.join("flat-input"), "")?;
+
+        assert_eq!(CorpusLayout::Flat.roots(dir).await?, vec![dir.to_owned()]);
+        assert!(CorpusLayout::Nested.roots(dir).await?.is_empty());
+        assert!(CorpusLayout::Nested.writable_root(dir).await.is_err());
+
+        for subdir in ["png", "jpeg", ".azDownload-1"] {
+            std::fs::create_dir(dir.join(subdir))?;
+        }
+        assert_eq!(
+            CorpusLayout::Nested.roots(dir).await?,
+            vec![dir.join("jpeg"), dir.join("png")]
+        );
+        assert_eq!(
+            CorpusLayout::Nested.writable_root(dir).await?,
+            dir.join("jpeg")
+        );
+
+        Ok(())
+    }
+}
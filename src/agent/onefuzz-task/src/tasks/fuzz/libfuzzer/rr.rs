@@ -0,0 +1,106 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Saving `rr record` traces of crashing inputs, so that flaky crashes can be
+//! replayed exactly with `rr replay`.
+//!
+//! Each crashing input is rerun alone under `rr record`, rather than recording
+//! the whole fuzzing session, which would slow fuzzing down and record every
+//! input run before the crash. The rerun may not crash, if the crash is
+//! flaky, but its trace is saved anyway. Traces over `MAX_TRACE_BYTES` are
+//! discarded, and at most `MAX_TRACES` are saved per task.
+
+use std::{fs::File, path::Path, time::Duration};
+
+use anyhow::{Context, Result};
+
+pub const TRACE_EXTENSION: &str = "rr.tar.zst";
+
+/// Most traces to save per task. Later runs are not recorded.
+pub const MAX_TRACES: u64 = 10;
+
+/// Largest uncompressed trace to save.
+pub const MAX_TRACE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// How long a recorded rerun may take before it is killed.
+pub const RECORD_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Archive the trace in `trace_dir` as `dest`, returning whether it was saved.
+pub async fn save_trace(trace_dir: &Path, dest: &Path) -> Result<bool> {
+    let (dir, path) = (trace_dir.to_owned(), dest.to_owned());
+    tokio::task::spawn_blocking(move || -> Result<bool> {
+        if !dir.is_dir() {
+            warn!("no rr trace found in {}", dir.display());
+            return Ok(false);
+        }
+
+        let size = dir_size(&dir)?;
+        if size > MAX_TRACE_BYTES {
+            warn!(
+                "discarding rr trace of {} bytes, over the limit of {} bytes",
+                size, MAX_TRACE_BYTES
+            );
+            return Ok(false);
+        }
+
+        write_archive(&dir, &path)?;
+        Ok(true)
+    })
+    .await?
+    .with_context(|| format!("archiving rr trace: {}", trace_dir.display()))
+}
+
+fn dir_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Write a zstd-compressed tarball of `trace_dir`, rooted at `trace`.
+fn write_archive(trace_dir: &Path, archive: &Path) -> Result<()> {
+    let encoder = zstd::Encoder::new(File::create(archive)?, 0)?;
+    let mut builder = tar::Builder::new(encoder);
+    builder.append_dir_all("trace", trace_dir)?;
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_save_trace() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let trace = temp.path().join("trace");
+        std::fs::create_dir_all(trace.join("mmaps"))?;
+        std::fs::write(trace.join("events"), "events")?;
+        std::fs::write(trace.join("mmaps").join("mmap_1"), "mmap")?;
+        assert_eq!(dir_size(&trace)?, 10);
+
+        let archive = temp.path().join(format!("crash-1.{TRACE_EXTENSION}"));
+        assert!(save_trace(&trace, &archive).await?);
+
+        let decoder = zstd::Decoder::new(File::open(&archive)?)?;
+        let mut names = tar::Archive::new(decoder)
+            .entries()?
+            .map(|entry| Ok(entry?.path()?.to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>>>()?;
+        names.sort();
+        assert!(names.contains(&"trace/events".to_owned()));
+        assert!(names.contains(&"trace/mmaps/mmap_1".to_owned()));
+
+        let missing = temp.path().join("missing");
+        assert!(!save_trace(&missing, &archive).await?);
+
+        Ok(())
+    }
+}
@@ -4,23 +4,28 @@
 #![allow(clippy::too_many_arguments)]
 use crate::tasks::{
     config::{CommonConfig, ContainerType},
+    error::TaskError,
     heartbeat::{HeartbeatSender, TaskHeartbeatClient},
+    lifecycle,
     report::crash_report::monitor_reports,
     stats::common::{monitor_stats, StatsFormat},
     utils::{try_resolve_setup_relative_path, CheckNotify},
 };
 use anyhow::{Context, Error, Result};
 use onefuzz::{
-    expand::Expand,
+    expand::{check_placeholders, Expand},
     fs::{has_files, set_executable, OwnedDir},
     jitter::delay_with_jitter,
-    process::monitor_process,
+    process::{monitor_process, ProcessFailure},
     syncdir::{
         SyncOperation::{Pull, Push},
         SyncedDir,
     },
 };
-use onefuzz_telemetry::Event::{new_coverage, new_crashdump, new_result};
+use onefuzz_telemetry::{
+    Event::{new_coverage, new_crashdump, new_result, supervisor_failed},
+    EventData, LifecycleState,
+};
 use serde::Deserialize;
 use std::{
     collections::HashMap,
@@ -96,17 +101,30 @@ impl SupervisorConfig {
                 |expand, container| expand.crashes_container(container),
             )
     }
+
+    /// Check that the supervisor command only uses known placeholders, such
+    /// as `{input_dir}`, `{crashes_dir}`, `{target_exe}` and `{setup_dir}`,
+    /// so that a typo fails the task before anything is synced.
+    pub fn check_placeholders(&self) -> Result<()> {
+        let templates: Vec<_> = std::iter::once(&self.supervisor_exe)
+            .chain(&self.supervisor_options)
+            .chain(self.supervisor_env.values())
+            .collect();
+        check_placeholders(&templates).map_err(|err| TaskError::Configuration(err).into())
+    }
 }
 
 const HEARTBEAT_PERIOD: Duration = Duration::from_secs(60);
 
 pub async fn spawn(config: SupervisorConfig) -> Result<(), Error> {
+    config.check_placeholders()?;
+
     let runtime_dir = OwnedDir::new(config.common.task_id.to_string());
     runtime_dir.create_if_missing().await?;
 
     // setup tools
     if let Some(tools) = &config.tools {
-        tools.init_pull().await?;
+        tools.init_pull(&config.common.cancellation_token).await?;
         set_executable(&tools.local_path).await?;
     }
 
@@ -119,7 +137,9 @@ pub async fn spawn(config: SupervisorConfig) -> Result<(), Error> {
 
     let jr_client = config.common.init_job_result().await?;
 
-    let monitor_crashes = crashes.monitor_results(new_result, false, &jr_client);
+    let cancellation_token = &config.common.cancellation_token;
+    let monitor_crashes =
+        crashes.monitor_results(new_result, false, &jr_client, cancellation_token);
 
     // setup crashdumps
     let (crashdump_dir, monitor_crashdumps) = {
@@ -139,7 +159,7 @@ pub async fn spawn(config: SupervisorConfig) -> Result<(), Error> {
         let monitor_crashdumps = async move {
             if let Some(crashdumps) = monitor_dir {
                 crashdumps
-                    .monitor_results(new_crashdump, false, &monitor_jr_client)
+                    .monitor_results(new_crashdump, false, &monitor_jr_client, cancellation_token)
                     .await
             } else {
                 Ok(())
@@ -151,14 +171,15 @@ pub async fn spawn(config: SupervisorConfig) -> Result<(), Error> {
 
     // setup coverage
     if let Some(coverage) = &config.coverage {
-        coverage.init_pull().await?;
+        coverage
+            .init_pull(&config.common.cancellation_token)
+            .await?;
     }
 
-    let monitor_coverage_cancellation = CancellationToken::new(); // never actually cancelled, yet
     let monitor_coverage_future = monitor_coverage(
         &config.coverage,
         config.ensemble_sync_delay,
-        &monitor_coverage_cancellation,
+        cancellation_token,
     );
 
     // setup reports
@@ -194,17 +215,17 @@ pub async fn spawn(config: SupervisorConfig) -> Result<(), Error> {
 
         let delay = std::time::Duration::from_secs(10);
         loop {
-            dir.sync_pull().await?;
+            dir.sync_pull(cancellation_token).await?;
             if has_files(&dir.local_path).await? {
                 break;
             }
             delay_with_jitter(delay).await;
         }
     }
-    let monitor_inputs = inputs.monitor_results(new_coverage, false, &jr_client);
-    let inputs_sync_cancellation = CancellationToken::new(); // never actually cancelled
+    let monitor_inputs =
+        inputs.monitor_results(new_coverage, false, &jr_client, cancellation_token);
     let inputs_sync_task =
-        inputs.continuous_sync(Pull, config.ensemble_sync_delay, &inputs_sync_cancellation);
+        inputs.continuous_sync(Pull, config.ensemble_sync_delay, cancellation_token);
 
     let process = start_supervisor(
         &runtime_dir.path(),
@@ -217,8 +238,13 @@ pub async fn spawn(config: SupervisorConfig) -> Result<(), Error> {
     .await?;
 
     let stopped = Notify::new();
-    let monitor_supervisor =
-        monitor_process(process, "supervisor".to_string(), true, Some(&stopped));
+    let monitor_supervisor = async {
+        let result = monitor_process(process, "supervisor".to_string(), true, Some(&stopped)).await;
+        if let Err(e) = &result {
+            report_supervisor_failure(e);
+        }
+        result
+    };
     let hb = config.common.init_heartbeat(None).await?;
 
     let heartbeat_process = heartbeat_process(&stopped, hb);
@@ -237,6 +263,7 @@ pub async fn spawn(config: SupervisorConfig) -> Result<(), Error> {
 
     let monitor_stats = monitor_stats(monitor_path, config.stats_format);
 
+    lifecycle::transition(LifecycleState::Ready);
     futures::try_join!(
         heartbeat_process.map_err(|e| e.context("Failure in heartbeat")),
         monitor_supervisor.map_err(|e| e.context("Failure in monitor_supervisor")),
@@ -252,6 +279,18 @@ pub async fn spawn(config: SupervisorConfig) -> Result<(), Error> {
     Ok(())
 }
 
+fn report_supervisor_failure(err: &Error) {
+    if let Some(failure) = err.downcast_ref::<ProcessFailure>() {
+        event!(supervisor_failed;
+            EventData::ProcessStatus = failure.exit_status.to_string(),
+            EventData::StderrTail = failure.stderr_tail.join("\n")
+        );
+        metric!(supervisor_failed; 1.0;
+            EventData::ProcessStatus = failure.exit_status.to_string()
+        );
+    }
+}
+
 async fn monitor_coverage(
     coverage: &Option<SyncedDir>,
     ensemble_sync_delay: Option<u64>,
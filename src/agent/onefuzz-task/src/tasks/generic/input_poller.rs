@@ -1,22 +1,111 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use std::{fmt, path::PathBuf};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use onefuzz::{blob::BlobUrl, jitter::delay_with_jitter, syncdir::SyncedDir};
+
+use crate::tasks::error::TaskError;
 use reqwest::Url;
 use tempfile::{tempdir, TempDir};
-use tokio::{fs, time::Duration};
+use tokio::{fs, select, time::Duration};
+use tokio_util::sync::CancellationToken;
 
 mod callback;
 pub use callback::*;
 
-const POLL_INTERVAL: Duration = Duration::from_secs(10);
+pub const POLL_INTERVAL: Duration = Duration::from_secs(10);
 
 #[cfg(test)]
 mod tests;
 
+/// Wait between polls which find no new work, doubling after each empty poll
+/// up to a maximum, and reset once work is found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PollInterval {
+    initial: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Default for PollInterval {
+    fn default() -> Self {
+        Self::fixed(POLL_INTERVAL)
+    }
+}
+
+impl PollInterval {
+    fn fixed(interval: Duration) -> Self {
+        Self {
+            initial: interval,
+            max: interval,
+            current: interval,
+        }
+    }
+
+    /// The poll interval set by the `poll_interval_seconds` and
+    /// `max_poll_interval_seconds` of a task config, using `default` if the
+    /// interval is unset. Without a maximum, the interval does not back off.
+    pub fn from_config(
+        default: Duration,
+        interval_seconds: Option<u64>,
+        max_interval_seconds: Option<u64>,
+    ) -> Result<Self> {
+        if interval_seconds == Some(0) {
+            return Err(TaskError::Configuration(anyhow!(
+                "poll_interval_seconds must be at least 1"
+            ))
+            .into());
+        }
+
+        let initial = interval_seconds.map_or(default, Duration::from_secs);
+        let max = max_interval_seconds.map_or(initial, Duration::from_secs);
+        if max < initial {
+            return Err(TaskError::Configuration(anyhow!(
+                "max_poll_interval_seconds must not be less than the poll interval of {}s",
+                initial.as_secs()
+            ))
+            .into());
+        }
+
+        Ok(Self {
+            initial,
+            max,
+            current: initial,
+        })
+    }
+
+    /// The wait before the next poll, after a poll that found no work.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = self.current.saturating_mul(2).min(self.max);
+        delay
+    }
+
+    /// Start again from the initial interval, after a poll that found work.
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
+/// The URL of `input` in the container synced to `dir`, if it has one.
+pub fn input_url(dir: &SyncedDir, input: &Path) -> Result<Option<Url>> {
+    // Compute the file name relative to the synced directory, and thus the
+    // container.
+    let blob_name = {
+        let dir_path = dir.local_path.canonicalize()?;
+        let input_path = input.canonicalize()?;
+        let dir_relative = input_path.strip_prefix(&dir_path)?;
+        dir_relative.display().to_string()
+    };
+    Ok(dir.try_url().map(|x| x.blob(blob_name).url()))
+}
+
 #[derive(Debug)]
 pub enum State<M> {
     Ready,
@@ -104,6 +193,12 @@ pub struct InputPoller<M> {
     batch_dir: Option<SyncedDir>,
 
     name: String,
+
+    poll_interval: PollInterval,
+
+    /// Checked between inputs, so that polling stops without abandoning an input
+    /// part-way through processing.
+    cancellation_token: CancellationToken,
 }
 
 impl<M> InputPoller<M> {
@@ -114,9 +209,26 @@ impl<M> InputPoller<M> {
             state,
             batch_dir: None,
             name,
+            poll_interval: PollInterval::default(),
+            cancellation_token: CancellationToken::new(),
         }
     }
 
+    pub fn with_cancellation(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
+    pub fn set_poll_interval(&mut self, poll_interval: PollInterval) {
+        self.poll_interval = poll_interval;
+    }
+
+    /// Treat inputs in `dir` as already processed, for callers which process
+    /// the directory themselves instead of via [`InputPoller::batch_process`].
+    pub fn set_batch_dir(&mut self, dir: SyncedDir) {
+        self.batch_dir = Some(dir);
+    }
+
     /// Process a given SyncedDir in batch
     pub async fn batch_process(
         &mut self,
@@ -125,7 +237,7 @@ impl<M> InputPoller<M> {
     ) -> Result<()> {
         self.batch_dir = Some(to_process.clone());
         to_process
-            .init_pull()
+            .init_pull(&self.cancellation_token)
             .await
             .with_context(|| format!("pulling to_process {}", to_process.local_path.display()))?;
         info!(
@@ -140,6 +252,11 @@ impl<M> InputPoller<M> {
                 format!("read directory failed: {}", to_process.local_path.display())
             })?;
         while let Some(file) = read_dir.next_entry().await? {
+            if self.cancellation_token.is_cancelled() {
+                info!("batch processing cancelled: {}", self.name);
+                break;
+            }
+
             let path = file.path();
             info!(
                 "processing batch-downloaded input: {} - {}",
@@ -147,15 +264,7 @@ impl<M> InputPoller<M> {
                 path.display()
             );
 
-            // Compute the file name relative to the synced directory, and thus the
-            // container.
-            let blob_name = {
-                let dir_path = to_process.local_path.canonicalize()?;
-                let input_path = path.canonicalize()?;
-                let dir_relative = input_path.strip_prefix(&dir_path)?;
-                dir_relative.display().to_string()
-            };
-            let url = to_process.try_url().map(|x| x.blob(blob_name).url());
+            let url = input_url(to_process, &path)?;
 
             processor
                 .process(url, &path)
@@ -165,6 +274,41 @@ impl<M> InputPoller<M> {
         Ok(())
     }
 
+    /// Process the inputs in `to_process` last modified after `since`, pulling
+    /// only those rather than the whole container.
+    pub async fn batch_process_modified_since(
+        &mut self,
+        processor: &mut dyn Processor,
+        to_process: &SyncedDir,
+        since: DateTime<Utc>,
+    ) -> Result<()> {
+        self.batch_dir = Some(to_process.clone());
+        let inputs = to_process
+            .pull_modified_since(since, &self.cancellation_token)
+            .await?;
+        info!(
+            "batch processing {} inputs modified since {}: {} - {}",
+            inputs.len(),
+            since,
+            self.name,
+            to_process.local_path.display()
+        );
+
+        for path in inputs {
+            if self.cancellation_token.is_cancelled() {
+                info!("batch processing cancelled: {}", self.name);
+                break;
+            }
+
+            let url = input_url(to_process, &path)?;
+            processor
+                .process(url, &path)
+                .await
+                .with_context(|| format!("process input failed: {}", path.display()))?;
+        }
+        Ok(())
+    }
+
     /// Check if an input was already processed via batch-processing its container.
     pub async fn seen_in_batch(&self, url: &Url) -> Result<bool> {
         let result = if let Some(batch_dir) = &self.batch_dir {
@@ -194,10 +338,19 @@ impl<M> InputPoller<M> {
         info!("starting input queue polling: {}", self.name);
         loop {
             match self.state() {
+                State::Ready if self.cancellation_token.is_cancelled() => {
+                    info!("input queue polling cancelled: {}", self.name);
+                    return Ok(());
+                }
                 State::Polled(None) => {
-                    debug!("Input queue empty, sleeping");
-                    delay_with_jitter(POLL_INTERVAL).await;
+                    let delay = self.poll_interval.next_delay();
+                    debug!("Input queue empty, sleeping for {:?}", delay);
+                    select! {
+                        _ = self.cancellation_token.cancelled() => {}
+                        _ = delay_with_jitter(delay) => {}
+                    }
                 }
+                State::Polled(Some(_)) => self.poll_interval.reset(),
                 State::Downloaded(_msg, _url, input, _tempdir) => {
                     // if we can't get the filename, just pass the whole thing to logging
                     let filename = input.file_name().unwrap_or_else(|| input.as_ref());
@@ -251,3 +251,21 @@ async fn test_valid_trigger_failed_action() {
     assert!(result.is_err());
     assert_eq!(task.state(), &State::Ready);
 }
+
+#[test]
+fn test_poll_interval_backoff() -> Result<()> {
+    let mut interval = PollInterval::from_config(POLL_INTERVAL, Some(5), Some(30))?;
+    let delays: Vec<_> = (0..4).map(|_| interval.next_delay().as_secs()).collect();
+    assert_eq!(delays, vec![5, 10, 20, 30]);
+
+    interval.reset();
+    assert_eq!(interval.next_delay().as_secs(), 5);
+
+    let mut interval = PollInterval::from_config(POLL_INTERVAL, None, None)?;
+    assert_eq!(interval.next_delay(), POLL_INTERVAL);
+    assert_eq!(interval.next_delay(), POLL_INTERVAL);
+
+    assert!(PollInterval::from_config(POLL_INTERVAL, Some(0), None).is_err());
+    assert!(PollInterval::from_config(POLL_INTERVAL, Some(30), Some(5)).is_err());
+    Ok(())
+}
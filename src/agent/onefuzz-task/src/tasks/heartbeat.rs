@@ -3,16 +3,34 @@
 
 use anyhow::Result;
 use onefuzz::heartbeat::HeartbeatClient;
+use onefuzz_telemetry::LifecycleState;
 use reqwest::Url;
 use serde::{self, Deserialize, Serialize};
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use storage_queue::QueueClient;
+use tokio::time::timeout;
 use uuid::Uuid;
 
+use crate::tasks::lifecycle;
+
+// Bound on delivering a heartbeat to an extra sink, so that a stalled sink is
+// retried with a later heartbeat rather than skipped for good.
+const EXTRA_SINK_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Deserialize, Serialize, Hash, Eq, PartialEq, Clone)]
 #[serde(tag = "type")]
 pub enum HeartbeatData {
     TaskAlive,
+    TaskStarted,
     MachineAlive,
+    TaskFailed,
+    TaskPaused,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -34,14 +52,77 @@ pub struct TaskContext {
 
 pub type TaskHeartbeatClient = HeartbeatClient<TaskContext, HeartbeatData>;
 
+/// A queue that receives a copy of each heartbeat, on a best-effort basis.
+///
+/// Deliveries run in the background, and at most one is in flight per sink:
+/// a heartbeat is dropped for a sink that is still busy with the previous one.
+#[derive(Clone)]
+struct ExtraSink {
+    client: QueueClient,
+    in_flight: Arc<AtomicBool>,
+}
+
+impl ExtraSink {
+    fn deliver(&self, heartbeat: &Heartbeat) {
+        if self.in_flight.swap(true, Ordering::AcqRel) {
+            debug!("skipping heartbeat to extra sink, previous delivery still in flight");
+            return;
+        }
+
+        let sink = self.clone();
+        let heartbeat = heartbeat.clone();
+        tokio::spawn(async move {
+            match timeout(EXTRA_SINK_TIMEOUT, sink.client.enqueue(heartbeat)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => warn!("failed to send heartbeat to extra sink: {}", err),
+                Err(_) => warn!("timed out sending heartbeat to extra sink"),
+            }
+            sink.in_flight.store(false, Ordering::Release);
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn init_task_heartbeat(
     queue_url: Url,
+    extra_sinks: &[Url],
     task_id: Uuid,
     job_id: Uuid,
     initial_delay: Option<Duration>,
     machine_id: Uuid,
     machine_name: String,
+    heartbeat_on_start: bool,
 ) -> Result<TaskHeartbeatClient> {
+    let extra_sinks = extra_sinks
+        .iter()
+        .map(|url| {
+            Ok(ExtraSink {
+                client: QueueClient::new(url.clone())?,
+                in_flight: Arc::new(AtomicBool::new(false)),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let extra_sinks = Arc::new(extra_sinks);
+
+    if heartbeat_on_start {
+        // Sent in the background, so that a slow queue doesn't delay the task.
+        let started = Heartbeat {
+            task_id,
+            job_id,
+            machine_id,
+            machine_name: machine_name.clone(),
+            data: vec![HeartbeatData::TaskStarted],
+        };
+        let queue_client = QueueClient::new(queue_url.clone())?;
+        let extra_sinks = extra_sinks.clone();
+        tokio::spawn(async move {
+            if let Err(err) = queue_client.enqueue(started.clone()).await {
+                warn!("failed to send started heartbeat: {}", err);
+            }
+            deliver_to_extra_sinks(&extra_sinks, &started);
+        });
+    }
+
     let hb = HeartbeatClient::init_heartbeat(
         TaskContext {
             task_id,
@@ -52,36 +133,74 @@ pub async fn init_task_heartbeat(
         queue_url,
         initial_delay,
         None,
-        |context| async move {
-            let task_id = context.state.task_id;
-            let machine_id = context.state.machine_id;
-            let machine_name = context.state.machine_name.clone();
-            let job_id = context.state.job_id;
-
-            let data = HeartbeatClient::<TaskContext, _>::drain_current_messages(context.clone());
-            let _ = context
-                .queue_client
-                .enqueue(Heartbeat {
+        move |context| {
+            let extra_sinks = extra_sinks.clone();
+            async move {
+                let task_id = context.state.task_id;
+                let machine_id = context.state.machine_id;
+                let machine_name = context.state.machine_name.clone();
+                let job_id = context.state.job_id;
+
+                let data =
+                    HeartbeatClient::<TaskContext, _>::drain_current_messages(context.clone());
+                let heartbeat = Heartbeat {
                     task_id,
                     job_id,
                     machine_id,
                     machine_name,
                     data,
-                })
-                .await;
+                };
+                deliver_to_extra_sinks(&extra_sinks, &heartbeat);
+
+                let _ = context.queue_client.enqueue(heartbeat).await;
+            }
         },
     )?;
     Ok(hb)
 }
 
+/// Send a `TaskFailed` heartbeat directly, rather than through the periodic
+/// heartbeat, for when the task can no longer be relied on to send it.
+pub async fn send_failed_heartbeat(
+    queue_url: Url,
+    task_id: Uuid,
+    job_id: Uuid,
+    machine_id: Uuid,
+    machine_name: String,
+) -> Result<()> {
+    let failed = Heartbeat {
+        task_id,
+        job_id,
+        machine_id,
+        machine_name,
+        data: vec![HeartbeatData::TaskFailed],
+    };
+    QueueClient::new(queue_url)?.enqueue(failed).await
+}
+
+fn deliver_to_extra_sinks(extra_sinks: &[ExtraSink], heartbeat: &Heartbeat) {
+    for sink in extra_sinks {
+        sink.deliver(heartbeat);
+    }
+}
+
 pub trait HeartbeatSender {
     fn send(&self, data: HeartbeatData) -> Result<()>;
 
     fn alive(&self) {
+        // Tasks heartbeat from their main loop, so the first heartbeat marks
+        // the task as running.
+        lifecycle::transition(LifecycleState::Running);
         if let Err(error) = self.send(HeartbeatData::TaskAlive) {
             error!("failed to send heartbeat: {}", error);
         }
     }
+
+    fn paused(&self) {
+        if let Err(error) = self.send(HeartbeatData::TaskPaused) {
+            error!("failed to send heartbeat: {}", error);
+        }
+    }
 }
 
 impl HeartbeatSender for TaskHeartbeatClient {
@@ -0,0 +1,48 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Reporting the lifecycle state of a task, so that the service can tell a
+//! node still downloading its setup from one which is doing work.
+//!
+//! States only move forward, and each is reported at most once. Reporting a
+//! state at or before the one already reached does nothing, so tasks can
+//! report `Ready` each time they enter their main loop, and every heartbeat
+//! can report `Running`.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use onefuzz_telemetry::{Event::task_lifecycle, EventData, LifecycleState};
+
+// The rank of the latest state reached, where 0 is none.
+static REACHED: AtomicU8 = AtomicU8::new(0);
+
+/// Report that the task has reached `state`, unless it already has.
+pub fn transition(state: LifecycleState) {
+    if advance(&REACHED, state) {
+        event!(task_lifecycle; EventData::LifecycleState = state);
+    }
+}
+
+// Record `state` in `reached`, returning whether it is later than the state
+// previously reached.
+fn advance(reached: &AtomicU8, state: LifecycleState) -> bool {
+    let rank = state as u8 + 1;
+    reached.fetch_max(rank, Ordering::AcqRel) < rank
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance() {
+        let reached = AtomicU8::new(0);
+        assert!(advance(&reached, LifecycleState::Initializing));
+        assert!(advance(&reached, LifecycleState::Ready));
+        assert!(!advance(&reached, LifecycleState::Ready));
+        assert!(!advance(&reached, LifecycleState::Downloading));
+        assert!(advance(&reached, LifecycleState::Running));
+        assert!(!advance(&reached, LifecycleState::Running));
+        assert!(advance(&reached, LifecycleState::Finishing));
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! A JSON manifest of the artifacts a task uploaded, written when the task
+//! stops, whether or not it succeeded.
+
+use anyhow::{Context, Result};
+use onefuzz::{blob::BlobClient, manifest::Artifact};
+use reqwest::Url;
+use serde::Serialize;
+use tokio::fs;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct TaskManifest {
+    pub job_id: Uuid,
+    pub task_id: Uuid,
+
+    /// Whether the task stopped without error. If not, the artifacts are only
+    /// those uploaded before it failed.
+    pub complete: bool,
+    pub error: Option<String>,
+    pub artifacts: Vec<Artifact>,
+}
+
+impl TaskManifest {
+    /// Collect the artifacts recorded so far by this process.
+    pub fn collect(job_id: Uuid, task_id: Uuid, error: Option<String>) -> Self {
+        Self {
+            job_id,
+            task_id,
+            complete: error.is_none(),
+            error,
+            artifacts: onefuzz::manifest::artifacts(),
+        }
+    }
+
+    /// Write the manifest to `url`, which is either a `file://` URL or a blob
+    /// URL with a SAS token that allows writes.
+    pub async fn write(&self, url: &Url) -> Result<()> {
+        if url.scheme() == "file" {
+            let path = url
+                .to_file_path()
+                .map_err(|_| format_err!("invalid file URL: {}", url))?;
+            let data = serde_json::to_vec_pretty(self)?;
+            fs::write(&path, data)
+                .await
+                .with_context(|| format!("unable to write manifest: {}", path.display()))?;
+        } else {
+            BlobClient::new()
+                .put_json(url.clone(), self)
+                .await?
+                .error_for_status()
+                .context("unable to upload manifest")?;
+        }
+
+        Ok(())
+    }
+}
@@ -3,7 +3,9 @@
 
 use crate::tasks::{
     config::CommonConfig,
+    generic::input_poller::PollInterval,
     heartbeat::HeartbeatSender,
+    lifecycle,
     utils::{self, try_resolve_setup_relative_path},
 };
 use anyhow::{Context, Result};
@@ -11,6 +13,7 @@ use onefuzz::{
     expand::Expand, fs::set_executable, http::ResponseExt, jitter::delay_with_jitter,
     syncdir::SyncedDir,
 };
+use onefuzz_telemetry::LifecycleState;
 use reqwest::Url;
 use reqwest_retry::SendRetry;
 use serde::Deserialize;
@@ -36,6 +39,16 @@ pub struct Config {
     pub inputs: SyncedDir,
     pub unique_inputs: SyncedDir,
 
+    /// Seconds to wait before polling `input_queue` again after finding it
+    /// empty. Defaults to 10.
+    #[serde(default)]
+    pub poll_interval_seconds: Option<u64>,
+
+    /// If set, the wait doubles after each consecutive empty poll, up to this
+    /// many seconds, to reduce the cost of polling idle queues.
+    #[serde(default)]
+    pub max_poll_interval_seconds: Option<u64>,
+
     #[serde(flatten)]
     pub common: CommonConfig,
 }
@@ -55,19 +68,33 @@ impl Config {
 }
 
 pub async fn spawn(config: &Config) -> Result<()> {
-    config.tools.init_pull().await?;
+    config
+        .tools
+        .init_pull(&config.common.cancellation_token)
+        .await?;
     set_executable(&config.tools.local_path).await?;
 
     config.unique_inputs.init().await?;
     let hb_client = config.common.init_heartbeat(None).await?;
-    loop {
+    let mut poll_interval = PollInterval::from_config(
+        EMPTY_QUEUE_DELAY,
+        config.poll_interval_seconds,
+        config.max_poll_interval_seconds,
+    )?;
+    lifecycle::transition(LifecycleState::Ready);
+    // Only stop between messages, so an in-flight merge is never interrupted.
+    while !config.common.cancellation_token.is_cancelled() {
         hb_client.alive();
         let tmp_dir = PathBuf::from("./tmp");
         debug!("tmp dir reset");
         utils::reset_tmp_dir(&tmp_dir).await?;
-        config.unique_inputs.sync_pull().await?;
+        config
+            .unique_inputs
+            .sync_pull(&config.common.cancellation_token)
+            .await?;
         let queue = QueueClient::new(config.input_queue.clone())?;
         if let Some(msg) = queue.pop().await? {
+            poll_interval.reset();
             let input_url = msg.parse(utils::parse_url_data);
             let input_url = match input_url {
                 Ok(url) => url,
@@ -97,10 +124,14 @@ pub async fn spawn(config: &Config) -> Result<()> {
                 }
             }
         } else {
-            debug!("no new candidate inputs found, sleeping");
-            delay_with_jitter(EMPTY_QUEUE_DELAY).await;
+            let delay = poll_interval.next_delay();
+            debug!("no new candidate inputs found, sleeping for {:?}", delay);
+            delay_with_jitter(delay).await;
         };
     }
+
+    info!("stopping merge task: cancelled");
+    Ok(())
 }
 
 async fn process_message(config: &Config, input_url: &Url, tmp_dir: &Path) -> Result<()> {
@@ -119,7 +150,9 @@ async fn process_message(config: &Config, input_url: &Url, tmp_dir: &Path) -> Re
                 local_path: tmp_dir.to_path_buf(),
                 remote_path: config.unique_inputs.remote_path.clone(),
             };
-            synced_dir.sync_push().await?
+            synced_dir
+                .sync_push(&config.common.cancellation_token)
+                .await?
         }
         Err(e) => error!("Merge failed : {}", e),
     }
@@ -3,7 +3,10 @@
 
 use crate::tasks::{
     config::CommonConfig,
+    error::TaskError,
+    generic::input_poller::PollInterval,
     heartbeat::HeartbeatSender,
+    lifecycle,
     utils::{self, default_bool_true},
 };
 use anyhow::{Context, Result};
@@ -11,16 +14,25 @@ use onefuzz::{
     http::ResponseExt,
     jitter::delay_with_jitter,
     libfuzzer::{LibFuzzer, LibFuzzerMergeOutput},
+    sha256,
     syncdir::{SyncOperation, SyncedDir},
 };
+use onefuzz_telemetry::LifecycleState;
 use reqwest::Url;
 use reqwest_retry::SendRetry;
 use serde::Deserialize;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
 };
 use storage_queue::{QueueClient, EMPTY_QUEUE_DELAY};
+use tokio::fs;
+
+mod corpus_cap;
+mod resume;
+
+const DEDUP_DIR: &str = "./dedup_inputs";
+const MERGE_CONTROL_FILE: &str = "merge-control.txt";
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
@@ -28,6 +40,8 @@ pub struct Config {
     pub target_env: HashMap<String, String>,
     pub target_options: Vec<String>,
     pub input_queue: Option<QueueClient>,
+    /// Corpora to merge into `unique_inputs`. When more than one is given,
+    /// inputs are de-duplicated by content across them before merging.
     pub inputs: Vec<SyncedDir>,
     pub unique_inputs: SyncedDir,
     pub preserve_existing_outputs: bool,
@@ -35,11 +49,49 @@ pub struct Config {
     #[serde(default = "default_bool_true")]
     pub check_fuzzer_help: bool,
 
+    /// Seconds to wait before polling `input_queue` again after finding it
+    /// empty. Defaults to 10.
+    #[serde(default)]
+    pub poll_interval_seconds: Option<u64>,
+
+    /// If set, the wait doubles after each consecutive empty poll, up to this
+    /// many seconds, to reduce the cost of polling idle queues.
+    #[serde(default)]
+    pub max_poll_interval_seconds: Option<u64>,
+
+    /// Keep at most this many inputs in `unique_inputs` after each merge,
+    /// chosen greedily to cover the most features, and delete the rest, even
+    /// with `preserve_existing_outputs`. Unset by default, which keeps every
+    /// input the merge does.
+    #[serde(default)]
+    pub max_output_inputs: Option<usize>,
+
+    /// Container to save libFuzzer's merge control file to while a merge
+    /// runs, so that a merge interrupted by losing the node resumes where it
+    /// left off on the next attempt instead of rerunning every input. Each
+    /// task saves its own blob, `{task_id}-merge-control.txt`, so the
+    /// container can be shared, and deletes it once the merge is done.
+    #[serde(default)]
+    pub merge_control_container: Option<SyncedDir>,
+
     #[serde(flatten)]
     pub common: CommonConfig,
 }
 
+pub struct MergeOutput {
+    pub merge: LibFuzzerMergeOutput,
+
+    /// Inputs removed to keep within `max_output_inputs`.
+    pub dropped_files_count: usize,
+}
+
 pub async fn spawn(config: Config) -> Result<()> {
+    if config.max_output_inputs == Some(0) {
+        return Err(
+            TaskError::Configuration(anyhow!("max_output_inputs must be greater than 0")).into(),
+        );
+    }
+
     let fuzzer = LibFuzzer::new(
         config.target_exe.clone(),
         config.target_options.clone(),
@@ -56,33 +108,71 @@ pub async fn spawn(config: Config) -> Result<()> {
     fuzzer.verify(config.check_fuzzer_help, None).await?;
 
     config.unique_inputs.init().await?;
+    lifecycle::transition(LifecycleState::Ready);
     if let Some(queue) = config.input_queue.clone() {
-        loop {
-            if let Err(error) = process_message(&config, queue.clone()).await {
-                error!(
-                    "failed to process latest message from notification queue: {}",
-                    error
-                );
+        let mut poll_interval = PollInterval::from_config(
+            EMPTY_QUEUE_DELAY,
+            config.poll_interval_seconds,
+            config.max_poll_interval_seconds,
+        )?;
+        // Only stop between messages, so an in-flight merge is never interrupted.
+        while !config.common.cancellation_token.is_cancelled() {
+            match process_message(&config, queue.clone()).await {
+                Ok(false) => {
+                    let delay = poll_interval.next_delay();
+                    debug!("no new candidate inputs found, sleeping for {:?}", delay);
+                    delay_with_jitter(delay).await;
+                }
+                Ok(true) => poll_interval.reset(),
+                Err(error) => {
+                    poll_interval.reset();
+                    error!(
+                        "failed to process latest message from notification queue: {}",
+                        error
+                    );
+                }
             }
         }
+        info!("stopping merge task: cancelled");
+        Ok(())
     } else {
         for input in config.inputs.iter() {
             input.init().await?;
-            input.sync_pull().await?;
+            input.sync_pull(&config.common.cancellation_token).await?;
+        }
+        let input_paths: Vec<_> = config.inputs.iter().map(|i| &i.local_path).collect();
+        if input_paths.len() > 1 {
+            let dedup_dir = Path::new(DEDUP_DIR);
+            utils::reset_tmp_dir(dedup_dir).await?;
+            let count = dedup_inputs(&input_paths, dedup_dir).await?;
+            info!(
+                "merging {} unique inputs from {} input containers",
+                count,
+                input_paths.len()
+            );
+            sync_and_merge(
+                &config,
+                vec![dedup_dir],
+                false,
+                config.preserve_existing_outputs,
+            )
+            .await?;
+        } else {
+            sync_and_merge(
+                &config,
+                input_paths,
+                false,
+                config.preserve_existing_outputs,
+            )
+            .await?;
         }
-        let input_paths = config.inputs.iter().map(|i| &i.local_path).collect();
-        sync_and_merge(
-            &config,
-            input_paths,
-            false,
-            config.preserve_existing_outputs,
-        )
-        .await?;
         Ok(())
     }
 }
 
-async fn process_message(config: &Config, input_queue: QueueClient) -> Result<()> {
+/// Merge the input from the next message in `input_queue`, returning whether
+/// there was one.
+async fn process_message(config: &Config, input_queue: QueueClient) -> Result<bool> {
     let hb_client = config.common.init_heartbeat(None).await?;
     hb_client.alive();
     let tmp_dir = "./tmp";
@@ -98,7 +188,7 @@ async fn process_message(config: &Config, input_queue: QueueClient) -> Result<()
             Ok(url) => url,
             Err(err) => {
                 error!("could not parse input URL from queue message: {}", err);
-                return Ok(());
+                return Ok(true);
             }
         };
 
@@ -118,11 +208,9 @@ async fn process_message(config: &Config, input_queue: QueueClient) -> Result<()
         if let Err(e) = try_delete_blob(input_url.clone()).await {
             error!("Failed to delete blob {}", e)
         }
-        Ok(())
+        Ok(true)
     } else {
-        debug!("no new candidate inputs found, sleeping");
-        delay_with_jitter(EMPTY_QUEUE_DELAY).await;
-        Ok(())
+        Ok(false)
     }
 }
 
@@ -131,17 +219,37 @@ async fn sync_and_merge(
     input_dirs: Vec<impl AsRef<Path>>,
     pull_inputs: bool,
     preserve_existing_outputs: bool,
-) -> Result<LibFuzzerMergeOutput> {
+) -> Result<MergeOutput> {
     if pull_inputs {
-        config.unique_inputs.sync_pull().await?;
+        config
+            .unique_inputs
+            .sync_pull(&config.common.cancellation_token)
+            .await?;
     }
     match merge_inputs(config, input_dirs).await {
         Ok(result) => {
-            if result.added_files_count > 0 {
-                info!("Added {} new files to the corpus", result.added_files_count);
+            if result.dropped_files_count > 0 {
+                info!(
+                    "Dropped {} files to keep the corpus within {} inputs",
+                    result.dropped_files_count,
+                    config.max_output_inputs.unwrap_or_default()
+                );
+            }
+
+            if result.merge.added_files_count > 0 || result.dropped_files_count > 0 {
+                info!(
+                    "Added {} new files to the corpus",
+                    result.merge.added_files_count
+                );
+                // Dropped inputs must also be deleted from the container.
+                let delete = !preserve_existing_outputs || result.dropped_files_count > 0;
                 config
                     .unique_inputs
-                    .sync(SyncOperation::Push, !preserve_existing_outputs)
+                    .sync(
+                        SyncOperation::Push,
+                        delete,
+                        &config.common.cancellation_token,
+                    )
                     .await?;
             } else {
                 info!("No new files added by the merge")
@@ -158,7 +266,7 @@ async fn sync_and_merge(
 pub async fn merge_inputs(
     config: &Config,
     candidates: Vec<impl AsRef<Path>>,
-) -> Result<LibFuzzerMergeOutput> {
+) -> Result<MergeOutput> {
     info!("Merging corpus");
     let merger = LibFuzzer::new(
         config.target_exe.clone(),
@@ -173,9 +281,79 @@ pub async fn merge_inputs(
             .map(|x| x.local_path.clone()),
         config.common.machine_identity.clone(),
     );
-    merger
-        .merge(&config.unique_inputs.local_path, &candidates)
-        .await
+
+    let corpus_dir = &config.unique_inputs.local_path;
+
+    // The control file records the features of each input, to choose which to
+    // keep, and which inputs have been run, to resume.
+    let control_dir = tempfile::tempdir()?;
+    let saved_control_file = match &config.merge_control_container {
+        Some(container) => {
+            let saved = resume::SavedControlFile::new(container, config.common.task_id)?;
+            saved.prepare(corpus_dir, &candidates).await?;
+            Some(saved)
+        }
+        None => None,
+    };
+    let control_file = match &saved_control_file {
+        Some(saved) => Some(saved.path().to_owned()),
+        None => config
+            .max_output_inputs
+            .map(|_| control_dir.path().join(MERGE_CONTROL_FILE)),
+    };
+
+    let merge = merger.merge_with_control_file(corpus_dir, &candidates, control_file.as_deref());
+    let merge = match &saved_control_file {
+        Some(saved) => saved.save_during(merge).await?,
+        None => merge.await?,
+    };
+
+    let dropped_files_count = match (config.max_output_inputs, &control_file) {
+        (Some(max_output_inputs), Some(control_file)) => {
+            corpus_cap::cap_corpus(control_file, corpus_dir, max_output_inputs).await?
+        }
+        _ => 0,
+    };
+
+    if let Some(saved) = &saved_control_file {
+        saved.finish().await?;
+    }
+
+    Ok(MergeOutput {
+        merge,
+        dropped_files_count,
+    })
+}
+
+/// Link each input in `input_dirs` into `dst`, named by its SHA-256, so that
+/// inputs present in several corpora are only merged once.
+///
+/// Returns the number of unique inputs.
+async fn dedup_inputs(input_dirs: &[impl AsRef<Path>], dst: &Path) -> Result<usize> {
+    let mut seen = HashSet::new();
+    for dir in input_dirs {
+        let mut entries = fs::read_dir(dir.as_ref()).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_file() {
+                continue;
+            }
+
+            let path = entry.path();
+            let digest = sha256::digest_file(&path).await?;
+            if !seen.insert(digest.clone()) {
+                continue;
+            }
+
+            let target = dst.join(digest);
+            if fs::hard_link(&path, &target).await.is_err() {
+                fs::copy(&path, &target).await.with_context(|| {
+                    format!("unable to copy input for merge: {}", path.display())
+                })?;
+            }
+        }
+    }
+
+    Ok(seen.len())
 }
 
 async fn try_delete_blob(input_url: Url) -> Result<()> {
@@ -191,3 +369,31 @@ async fn try_delete_blob(input_url: Url) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dedup_inputs() -> Result<()> {
+        let shard_a = tempdir()?;
+        let shard_b = tempdir()?;
+        let dst = tempdir()?;
+
+        fs::write(shard_a.path().join("a"), "shared").await?;
+        fs::write(shard_a.path().join("b"), "only-a").await?;
+        fs::write(shard_b.path().join("c"), "shared").await?;
+        fs::write(shard_b.path().join("d"), "only-b").await?;
+
+        let count = dedup_inputs(&[shard_a.path(), shard_b.path()], dst.path()).await?;
+        assert_eq!(count, 3);
+
+        let shared = dst.path().join(sha256::digest("shared"));
+        assert_eq!(fs::read_to_string(shared).await?, "shared");
+        assert_eq!(std::fs::read_dir(dst.path())?.count(), 3);
+
+        Ok(())
+    }
+}
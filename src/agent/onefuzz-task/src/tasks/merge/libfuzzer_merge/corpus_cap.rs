@@ -0,0 +1,242 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Cap the size of a merged corpus, keeping the inputs which cover the most
+//! features.
+//!
+//! The features of each input are read from the libFuzzer merge control file,
+//! which starts with the number of inputs, the number of them in the output
+//! corpus, and their paths, one per line. It then records each input that was
+//! run, by its index in that list:
+//!
+//! ```text
+//! STARTED 0 1024
+//! FT 0 12 48 96
+//! COV 0 3 4
+//! ```
+//!
+//! An `FT` line only lists the features that no input run before it covered,
+//! so the features of an input are those it was first to reach, in the order
+//! libFuzzer ran them, rather than every feature it reaches. The cap is a
+//! heuristic on top of that: it keeps the inputs which first reached the most
+//! features, and an input whose features were all reached first by another
+//! counts as covering nothing, however much it overlaps with inputs dropped
+//! by the cap.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use onefuzz::{fs::list_files, sha256};
+use tokio::fs;
+
+/// Features of the inputs listed in a merge control file.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct ControlFile {
+    pub inputs: Vec<(PathBuf, Vec<u32>)>,
+}
+
+impl ControlFile {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines();
+        let mut header = |name| -> Result<usize> {
+            lines
+                .next()
+                .ok_or_else(|| format_err!("merge control file missing {}", name))?
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid {} in merge control file", name))
+        };
+        let count = header("input count")?;
+        let _first_corpus_count = header("output corpus input count")?;
+
+        let mut inputs = Vec::with_capacity(count);
+        for _ in 0..count {
+            let path = lines
+                .next()
+                .ok_or_else(|| format_err!("merge control file missing input paths"))?;
+            inputs.push((PathBuf::from(path), Vec::new()));
+        }
+
+        for line in lines {
+            let mut fields = line.split_whitespace();
+            if fields.next() != Some("FT") {
+                continue;
+            }
+
+            let index: usize = fields
+                .next()
+                .and_then(|index| index.parse().ok())
+                .ok_or_else(|| format_err!("invalid merge control file line: {}", line))?;
+            let (_, features) = inputs
+                .get_mut(index)
+                .ok_or_else(|| format_err!("merge control file input out of range: {}", index))?;
+
+            *features = fields
+                .map(|feature| feature.parse())
+                .collect::<Result<_, _>>()
+                .with_context(|| format!("invalid merge control file line: {}", line))?;
+            features.sort_unstable();
+            features.dedup();
+        }
+
+        Ok(Self { inputs })
+    }
+
+    pub async fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .await
+            .with_context(|| format!("reading merge control file {}", path.display()))?;
+        Self::parse(&text)
+    }
+
+    /// Features by the SHA-256 of each input that still exists, to match up
+    /// the copies of new inputs that libFuzzer adds to the output corpus.
+    async fn features_by_digest(self) -> Result<HashMap<String, Vec<u32>>> {
+        let mut by_digest = HashMap::new();
+        for (path, features) in self.inputs {
+            if !fs::metadata(&path)
+                .await
+                .map(|m| m.is_file())
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            let digest = sha256::digest_file(&path).await?;
+            by_digest.insert(digest, features);
+        }
+        Ok(by_digest)
+    }
+}
+
+/// Greedily choose up to `max` of `features` which together cover the most
+/// features, returning their indices in the order chosen.
+pub fn select_greedy(features: &[Vec<u32>], max: usize) -> Vec<usize> {
+    // Gains only shrink as features are covered, so a gain computed earlier is
+    // an upper bound, and an input is only re-scored when it reaches the top.
+    let mut heap: BinaryHeap<(usize, Reverse<usize>)> = features
+        .iter()
+        .enumerate()
+        .map(|(index, features)| (features.len(), Reverse(index)))
+        .collect();
+
+    let mut covered = HashSet::new();
+    let mut selected = Vec::new();
+    while selected.len() < max {
+        let Some((gain, Reverse(index))) = heap.pop() else {
+            break;
+        };
+
+        let current = features[index]
+            .iter()
+            .filter(|feature| !covered.contains(*feature))
+            .count();
+        if current < gain {
+            heap.push((current, Reverse(index)));
+            continue;
+        }
+
+        covered.extend(features[index].iter().copied());
+        selected.push(index);
+    }
+
+    selected
+}
+
+/// Remove all but the `max` inputs in `corpus_dir` which first reached the
+/// most features recorded in `control_file`, returning how many were removed.
+/// Inputs the control file doesn't cover are treated as covering nothing.
+pub async fn cap_corpus(control_file: &Path, corpus_dir: &Path, max: usize) -> Result<usize> {
+    let mut by_digest = ControlFile::load(control_file)
+        .await?
+        .features_by_digest()
+        .await?;
+
+    let corpus = list_files(corpus_dir).await?;
+    if corpus.len() <= max {
+        return Ok(0);
+    }
+
+    let mut features = Vec::with_capacity(corpus.len());
+    for path in &corpus {
+        let digest = sha256::digest_file(path).await?;
+        features.push(by_digest.remove(&digest).unwrap_or_default());
+    }
+
+    let mut keep = vec![false; corpus.len()];
+    for index in select_greedy(&features, max) {
+        keep[index] = true;
+    }
+
+    let mut dropped = 0;
+    for (path, keep) in corpus.iter().zip(keep) {
+        if !keep {
+            fs::remove_file(path)
+                .await
+                .with_context(|| format!("removing input over cap: {}", path.display()))?;
+            dropped += 1;
+        }
+    }
+
+    Ok(dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let text = "2\n1\ncorpus/a\ninputs/b\nSTARTED 0 4\nFT 0 3 1 3\nCOV 0 7\nSTARTED 1 8\n";
+        let control = ControlFile::parse(text)?;
+        assert_eq!(
+            control.inputs,
+            vec![
+                (PathBuf::from("corpus/a"), vec![1, 3]),
+                (PathBuf::from("inputs/b"), vec![]),
+            ]
+        );
+
+        assert!(ControlFile::parse("2\n1\ncorpus/a\n").is_err());
+        assert!(ControlFile::parse("1\n1\ncorpus/a\nFT 1 3\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_greedy() {
+        let features = vec![vec![1, 2], vec![1, 2, 3], vec![4], vec![3, 4], vec![]];
+        assert_eq!(select_greedy(&features, 2), vec![1, 2]);
+        assert_eq!(select_greedy(&features, 0), Vec::<usize>::new());
+        assert_eq!(select_greedy(&features, 10).len(), features.len());
+    }
+
+    #[tokio::test]
+    async fn test_cap_corpus() -> Result<()> {
+        let dir = tempdir()?;
+        let corpus = dir.path().join("corpus");
+        fs::create_dir(&corpus).await?;
+        for (name, data) in [("a", "small"), ("b", "large"), ("c", "other")] {
+            fs::write(corpus.join(name), data).await?;
+        }
+
+        let control_file = dir.path().join("control");
+        let text = format!(
+            "3\n3\n{a}\n{b}\n{c}\nFT 0 1\nFT 1 2 3 4\nFT 2 5 6\n",
+            a = corpus.join("a").display(),
+            b = corpus.join("b").display(),
+            c = corpus.join("c").display(),
+        );
+        fs::write(&control_file, text).await?;
+
+        assert_eq!(cap_corpus(&control_file, &corpus, 2).await?, 1);
+        assert!(!corpus.join("a").exists());
+        assert!(corpus.join("b").exists());
+        assert!(corpus.join("c").exists());
+
+        assert_eq!(cap_corpus(&control_file, &corpus, 2).await?, 0);
+        Ok(())
+    }
+}
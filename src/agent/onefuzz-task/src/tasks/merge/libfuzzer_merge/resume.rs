@@ -0,0 +1,238 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Resuming an interrupted merge from libFuzzer's merge control file.
+//!
+//! The control file lists every input being merged, and records each one as
+//! libFuzzer runs it, so a merge given the same control file again skips the
+//! inputs already run. While a merge runs, its control file is saved to
+//! `merge_control_container` every minute, as a blob named for the task. The
+//! next attempt at the task, perhaps on another node, downloads it, unless it
+//! lists a different set of inputs, and deletes it once the merge is done.
+//! Other blobs in the container, such as those of other tasks, are left alone.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use onefuzz::{
+    blob::store::{self, CorpusStore},
+    fs::{exists, list_files},
+    syncdir::SyncedDir,
+};
+use tokio::fs;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use super::corpus_cap::ControlFile;
+use super::MERGE_CONTROL_FILE;
+
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The control file of a task's merge, and the blob it is saved as.
+pub struct SavedControlFile {
+    // `None` for a container without a remote, where the local file is the
+    // only copy.
+    store: Option<Box<dyn CorpusStore>>,
+    name: String,
+    path: PathBuf,
+}
+
+impl SavedControlFile {
+    pub fn new(container: &SyncedDir, task_id: Uuid) -> Result<Self> {
+        let store = container
+            .remote_path
+            .as_ref()
+            .map(store::open)
+            .transpose()?;
+        let name = format!("{task_id}-{MERGE_CONTROL_FILE}");
+        let path = container.local_path.join(&name);
+        Ok(Self { store, name, path })
+    }
+
+    /// The local path of the control file, to merge with.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Download the control file saved by an interrupted merge of
+    /// `corpus_dir` and `candidates`. A control file for other inputs, or one
+    /// that can't be parsed, is deleted so that the merge starts fresh.
+    pub async fn prepare(&self, corpus_dir: &Path, candidates: &[impl AsRef<Path>]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        if let Some(store) = &self.store {
+            if exists(&self.path).await? {
+                fs::remove_file(&self.path).await?;
+            }
+            let saved = store
+                .list()
+                .await
+                .context("unable to list merge control files")?
+                .contains(&self.name);
+            if saved {
+                store
+                    .download(&self.name, &self.path)
+                    .await
+                    .context("unable to download merge control file")?;
+            }
+        }
+        if !exists(&self.path).await? {
+            return Ok(());
+        }
+
+        let mut dirs = vec![corpus_dir];
+        dirs.extend(candidates.iter().map(|dir| dir.as_ref()));
+        if lists_inputs(&self.path, &dirs).await? {
+            info!("resuming merge from {}", self.name);
+        } else {
+            info!(
+                "starting merge afresh: stale merge control file {}",
+                self.name
+            );
+            fs::remove_file(&self.path).await?;
+        }
+        Ok(())
+    }
+
+    /// Run `merge`, saving the control file periodically until it completes.
+    /// Failing to save it is logged, since it only costs a resumed merge some
+    /// repeated work.
+    pub async fn save_during<T>(&self, merge: impl Future<Output = T>) -> T {
+        let done = CancellationToken::new();
+        let merge = async {
+            let output = merge.await;
+            done.cancel();
+            output
+        };
+        let save = async {
+            loop {
+                tokio::select! {
+                    _ = done.cancelled() => break,
+                    _ = tokio::time::sleep(SAVE_INTERVAL) => {}
+                }
+                if let Err(err) = self.save().await {
+                    warn!("unable to save merge control file: {:?}", err);
+                }
+            }
+        };
+
+        let (output, ()) = tokio::join!(merge, save);
+        output
+    }
+
+    /// Delete the control file of a completed merge, locally and from the
+    /// container, so that the next merge starts fresh.
+    pub async fn finish(&self) -> Result<()> {
+        if exists(&self.path).await? {
+            fs::remove_file(&self.path).await?;
+        }
+        if let Some(store) = &self.store {
+            store
+                .delete(&self.name)
+                .await
+                .context("unable to delete merge control file")?;
+        }
+        Ok(())
+    }
+
+    async fn save(&self) -> Result<()> {
+        // libFuzzer only writes the control file once it has listed the inputs.
+        let (Some(store), true) = (&self.store, exists(&self.path).await?) else {
+            return Ok(());
+        };
+        store.replace(&self.path, &self.name).await
+    }
+}
+
+// Whether `control_file` lists exactly the inputs now in `dirs`.
+async fn lists_inputs(control_file: &Path, dirs: &[&Path]) -> Result<bool> {
+    let control = match ControlFile::load(control_file).await {
+        Ok(control) => control,
+        Err(err) => {
+            debug!("invalid merge control file: {:?}", err);
+            return Ok(false);
+        }
+    };
+    let listed: HashSet<_> = control.inputs.into_iter().map(|(path, _)| path).collect();
+
+    let mut inputs = HashSet::new();
+    for dir in dirs {
+        inputs.extend(list_files(dir).await?);
+    }
+
+    Ok(listed == inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lists_inputs() -> Result<()> {
+        let dir = tempdir()?;
+        let corpus = dir.path().join("corpus");
+        let candidates = dir.path().join("candidates");
+        fs::create_dir(&corpus).await?;
+        fs::create_dir(&candidates).await?;
+        fs::write(corpus.join("a"), "a").await?;
+        fs::write(candidates.join("b"), "b").await?;
+
+        let control_file = dir.path().join(MERGE_CONTROL_FILE);
+        let text = format!(
+            "2\n1\n{}\n{}\nSTARTED 0 1\nFT 0 1 2\n",
+            corpus.join("a").display(),
+            candidates.join("b").display(),
+        );
+        fs::write(&control_file, text).await?;
+        let dirs = [corpus.as_path(), candidates.as_path()];
+        assert!(lists_inputs(&control_file, &dirs).await?);
+
+        fs::write(candidates.join("c"), "c").await?;
+        assert!(!lists_inputs(&control_file, &dirs).await?);
+
+        fs::write(&control_file, "truncated").await?;
+        assert!(!lists_inputs(&control_file, &dirs).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_saved_control_file() -> Result<()> {
+        let dir = tempdir()?;
+        let corpus = dir.path().join("corpus");
+        let remote = dir.path().join("remote");
+        fs::create_dir(&corpus).await?;
+        fs::create_dir(&remote).await?;
+        fs::write(corpus.join("a"), "a").await?;
+
+        let container = SyncedDir {
+            local_path: dir.path().join("local"),
+            remote_path: Some(onefuzz::blob::BlobContainerUrl::Path(remote.clone())),
+        };
+        let task_id = Uuid::new_v4();
+        let name = format!("{task_id}-{MERGE_CONTROL_FILE}");
+        let text = format!("1\n1\n{}\nSTARTED 0 1\n", corpus.join("a").display());
+        fs::write(remote.join(&name), &text).await?;
+        let other = format!("{}-{MERGE_CONTROL_FILE}", Uuid::new_v4());
+        fs::write(remote.join(&other), "other").await?;
+
+        let saved = SavedControlFile::new(&container, task_id)?;
+        saved.prepare(&corpus, &[] as &[PathBuf]).await?;
+        assert_eq!(fs::read_to_string(saved.path()).await?, text);
+
+        fs::write(saved.path(), "updated").await?;
+        saved.save().await?;
+        assert_eq!(fs::read_to_string(remote.join(&name)).await?, "updated");
+
+        saved.finish().await?;
+        assert!(!saved.path().exists());
+        assert!(!remote.join(&name).exists());
+        assert!(remote.join(&other).exists());
+        Ok(())
+    }
+}
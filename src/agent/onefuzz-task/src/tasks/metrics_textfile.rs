@@ -0,0 +1,170 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Task metrics in the Prometheus text exposition format, written to a directory
+//! scraped by the node-exporter textfile collector.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use onefuzz_telemetry::{EventData, LogEvent, LoggingEvent};
+use tokio::{fs, sync::broadcast::error::RecvError, time::interval};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+const WRITE_PERIOD: Duration = Duration::from_secs(30);
+
+pub fn textfile_path(dir: impl AsRef<Path>, task_id: Uuid) -> PathBuf {
+    dir.as_ref().join(format!("onefuzz_{task_id}.prom"))
+}
+
+#[derive(Debug, Default)]
+struct Metrics {
+    events: BTreeMap<&'static str, u64>,
+    execs_sec: Option<f64>,
+}
+
+impl Metrics {
+    fn record(&mut self, event: &LogEvent) {
+        *self.events.entry(event.event.as_str()).or_default() += 1;
+
+        for data in &event.data {
+            if let EventData::ExecsSecond(execs_sec) = data {
+                self.execs_sec = Some(*execs_sec);
+            }
+        }
+    }
+
+    fn render(&self, job_id: Uuid, task_id: Uuid, uptime: Duration) -> String {
+        let labels = format!("job_id=\"{job_id}\",task_id=\"{task_id}\"");
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "# HELP onefuzz_task_uptime_seconds Seconds since the task started."
+        );
+        let _ = writeln!(out, "# TYPE onefuzz_task_uptime_seconds gauge");
+        let _ = writeln!(
+            out,
+            "onefuzz_task_uptime_seconds{{{labels}}} {}",
+            uptime.as_secs()
+        );
+
+        if let Some(execs_sec) = self.execs_sec {
+            let _ = writeln!(
+                out,
+                "# HELP onefuzz_task_execs_per_second Most recently reported executions per second."
+            );
+            let _ = writeln!(out, "# TYPE onefuzz_task_execs_per_second gauge");
+            let _ = writeln!(out, "onefuzz_task_execs_per_second{{{labels}}} {execs_sec}");
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP onefuzz_task_events_total Telemetry events emitted by the task, such as new_result for crashes."
+        );
+        let _ = writeln!(out, "# TYPE onefuzz_task_events_total counter");
+        for (event, count) in &self.events {
+            let _ = writeln!(
+                out,
+                "onefuzz_task_events_total{{{labels},event=\"{event}\"}} {count}"
+            );
+        }
+
+        out
+    }
+}
+
+/// Periodically write task metrics to `dir` until cancelled, then remove the file.
+pub async fn run(
+    dir: &Path,
+    job_id: Uuid,
+    task_id: Uuid,
+    cancellation_token: &CancellationToken,
+) -> Result<()> {
+    fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("unable to create metrics dir: {}", dir.display()))?;
+
+    let path = textfile_path(dir, task_id);
+    let started = Instant::now();
+    let mut metrics = Metrics::default();
+    let mut events = onefuzz_telemetry::subscribe_to_events()?;
+    let mut timer = interval(WRITE_PERIOD);
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(LoggingEvent::Event(event)) => metrics.record(&event),
+                Ok(LoggingEvent::Trace(_)) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+            _ = timer.tick() => {
+                let text = metrics.render(job_id, task_id, started.elapsed());
+                if let Err(err) = write_atomic(&path, text).await {
+                    warn!("failed to write metrics textfile: {:?}", err);
+                }
+            }
+            _ = cancellation_token.cancelled() => break,
+        }
+    }
+
+    // The task has exited cleanly, so don't leave stale metrics to be scraped.
+    if fs::metadata(&path).await.is_ok() {
+        fs::remove_file(&path)
+            .await
+            .with_context(|| format!("unable to remove metrics textfile: {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// The collector only reads files ending in `.prom`, so write to a temporary name
+/// first to ensure it never sees a partial file.
+async fn write_atomic(path: &Path, text: String) -> Result<()> {
+    let temp = path.with_extension("prom.tmp");
+    fs::write(&temp, text).await?;
+    fs::rename(&temp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use onefuzz_telemetry::Event;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut metrics = Metrics::default();
+        for _ in 0..2 {
+            metrics.record(&LogEvent {
+                timestamp: Utc::now(),
+                event: Event::new_result,
+                data: vec![],
+            });
+        }
+        metrics.record(&LogEvent {
+            timestamp: Utc::now(),
+            event: Event::runtime_stats,
+            data: vec![EventData::ExecsSecond(100.5)],
+        });
+
+        let text = metrics.render(Uuid::nil(), Uuid::nil(), Duration::from_secs(42));
+        let labels = format!("job_id=\"{}\",task_id=\"{}\"", Uuid::nil(), Uuid::nil());
+
+        assert!(text.contains(&format!("onefuzz_task_uptime_seconds{{{labels}}} 42\n")));
+        assert!(text.contains(&format!(
+            "onefuzz_task_execs_per_second{{{labels}}} 100.5\n"
+        )));
+        assert!(text.contains(&format!(
+            "onefuzz_task_events_total{{{labels},event=\"new_result\"}} 2\n"
+        )));
+    }
+}
@@ -5,11 +5,21 @@ pub mod analysis;
 pub mod config;
 #[cfg(any(target_os = "linux", target_os = "windows"))]
 pub mod coverage;
+pub mod error;
+pub mod event_socket;
+pub mod fd_watchdog;
 pub mod fuzz;
 pub mod generic;
 pub mod heartbeat;
+pub mod lifecycle;
+pub mod manifest;
 pub mod merge;
+pub mod metrics_textfile;
+pub mod node_lock;
+pub mod pause;
+pub mod preflight;
 pub mod regression;
 pub mod report;
 pub mod stats;
+pub mod task_lease;
 pub mod utils;
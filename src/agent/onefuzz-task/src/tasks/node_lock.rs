@@ -0,0 +1,137 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Named locks shared by the tasks on a node, so that tasks which use an
+//! exclusive resource, such as licensed software or attached hardware, run one
+//! at a time.
+//!
+//! Each lock is an advisory lock on `<onefuzz root>/locks/<name>.lock`. The OS
+//! releases it when the holder exits, even if it crashes, so a dead task never
+//! leaves a lock held. Tasks heartbeat while they wait, so that the service
+//! doesn't take a long wait for a lost task.
+
+use std::{
+    fs::{File, OpenOptions},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use fs4::FileExt;
+use tokio::time::sleep;
+
+use crate::tasks::{
+    error::TaskError,
+    heartbeat::{HeartbeatData, HeartbeatSender},
+};
+
+pub const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+const RETRY_PERIOD: Duration = Duration::from_secs(1);
+
+/// A held node lock, released when dropped.
+#[derive(Debug)]
+pub struct NodeLock {
+    name: String,
+    _file: File,
+}
+
+impl NodeLock {
+    /// Wait up to `timeout` to acquire the lock called `name`, sending
+    /// `TaskAlive` heartbeats to `heartbeat` meanwhile.
+    pub async fn acquire(
+        name: &str,
+        timeout: Duration,
+        heartbeat: &impl HeartbeatSender,
+    ) -> Result<Self> {
+        let path = lock_path(name)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .with_context(|| format!("unable to create lock dir: {}", dir.display()))?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("unable to open lock file: {}", path.display()))?;
+
+        let start = Instant::now();
+        let mut logged = false;
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(()) => break,
+                Err(err) if err.raw_os_error() == fs4::lock_contended_error().raw_os_error() => {}
+                Err(err) => {
+                    return Err(err).with_context(|| format!("unable to lock: {}", path.display()));
+                }
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(TaskError::Transient(anyhow!(
+                    "timed out after {:?} waiting for node lock: {}",
+                    timeout,
+                    name
+                ))
+                .into());
+            }
+            if !logged {
+                info!("waiting for node lock held by another task: {}", name);
+                logged = true;
+            }
+            // Queued heartbeats are sent periodically, so this sends one per
+            // heartbeat period, whatever the retry period. Unlike `alive`, it
+            // doesn't mark the task as running.
+            if let Err(err) = heartbeat.send(HeartbeatData::TaskAlive) {
+                warn!("failed to send heartbeat: {}", err);
+            }
+            sleep(RETRY_PERIOD).await;
+        }
+
+        info!("acquired node lock: {}", name);
+        Ok(Self {
+            name: name.to_owned(),
+            _file: file,
+        })
+    }
+}
+
+impl Drop for NodeLock {
+    fn drop(&mut self) {
+        info!("releasing node lock: {}", self.name);
+    }
+}
+
+fn lock_path(name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !name.starts_with('.');
+    if !valid {
+        return Err(TaskError::Configuration(anyhow!(
+            "node_lock must be non-empty, and only contain letters, digits, '-', '_' and '.', not starting with '.': {:?}",
+            name
+        ))
+        .into());
+    }
+
+    Ok(onefuzz::fs::onefuzz_root()?
+        .join("locks")
+        .join(format!("{name}.lock")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_path_rejects_invalid_names() {
+        assert!(lock_path("gpu-0").is_ok());
+        assert!(lock_path("license_server.v2").is_ok());
+        assert!(lock_path("").is_err());
+        assert!(lock_path("../etc/passwd").is_err());
+        assert!(lock_path(".hidden").is_err());
+    }
+}
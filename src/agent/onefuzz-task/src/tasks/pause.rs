@@ -0,0 +1,63 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Pausing a task at the request of the agent.
+//!
+//! While paused, fuzz tasks suspend their fuzzer processes and report
+//! `TaskPaused` heartbeats, but not `TaskAlive` ones, so a pause longer than
+//! the service's heartbeat timeout may see the task marked as failed.
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// Shared pause state of a task, which can be watched for changes.
+#[derive(Clone, Debug)]
+pub struct PauseSignal(Arc<watch::Sender<bool>>);
+
+impl Default for PauseSignal {
+    fn default() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self(Arc::new(sender))
+    }
+}
+
+impl PauseSignal {
+    pub fn pause(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.0.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Watch for changes to the pause state.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.0.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pause_signal() {
+        let signal = PauseSignal::default();
+        let mut receiver = signal.clone().subscribe();
+        assert!(!signal.is_paused());
+
+        signal.pause();
+        receiver.changed().await.unwrap();
+        assert!(*receiver.borrow_and_update());
+        assert!(signal.is_paused());
+
+        signal.resume();
+        receiver.changed().await.unwrap();
+        assert!(!*receiver.borrow_and_update());
+    }
+}
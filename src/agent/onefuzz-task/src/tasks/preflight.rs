@@ -0,0 +1,89 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Startup reachability checks for the remote endpoints used by a task.
+//!
+//! These are intended to surface misconfigured or expired SAS URLs before the
+//! task does any real work, rather than minutes into a run.
+
+use anyhow::Result;
+use onefuzz::blob::url::redact_query_sas_sig;
+use reqwest::{Client, StatusCode, Url};
+use std::time::Duration;
+
+use crate::tasks::config::Config;
+
+const PREFLIGHT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error code returned by Azure Storage when a SAS signature is invalid or expired.
+const AUTHENTICATION_FAILED: &str = "AuthenticationFailed";
+
+pub async fn check_endpoints(config: &Config) -> Result<()> {
+    let common = config.common();
+
+    let mut endpoints: Vec<(&str, Url)> = vec![];
+    if let Some(url) = &common.heartbeat_queue {
+        endpoints.push(("heartbeat_queue", with_query(url, "comp", "metadata")));
+    }
+    if let Some(url) = &common.logs {
+        endpoints.push(("logs", with_query(url, "restype", "container")));
+    }
+    for (name, dir) in config.primary_containers() {
+        if let Some(url) = dir.remote_path.as_ref().and_then(|u| u.url().ok()) {
+            endpoints.push((name, with_query(&url, "restype", "container")));
+        }
+    }
+
+    let client = Client::builder().timeout(PREFLIGHT_TIMEOUT).build()?;
+    let mut failures = vec![];
+    for (name, url) in endpoints {
+        if let Err(err) = check_endpoint(&client, &url).await {
+            failures.push(format!("{name} ({}): {err}", redact_query_sas_sig(&url)));
+        }
+    }
+
+    if !failures.is_empty() {
+        bail!("preflight checks failed: {}", failures.join("; "));
+    }
+
+    info!("preflight checks passed");
+    Ok(())
+}
+
+/// Queue and container URLs need an extra query parameter to address the
+/// resource's properties, rather than a message or blob within it.
+fn with_query(url: &Url, key: &str, value: &str) -> Url {
+    let mut url = url.clone();
+    url.query_pairs_mut().append_pair(key, value);
+    url
+}
+
+/// Issue a `HEAD` request against `url`.
+///
+/// The SAS tokens we are given are frequently scoped to a subset of operations, so
+/// only errors which indicate the endpoint is unusable are treated as failures:
+/// transport errors, missing resources, and rejected signatures.
+async fn check_endpoint(client: &Client, url: &Url) -> Result<()> {
+    if url.scheme() == "file" {
+        return Ok(());
+    }
+
+    let response = client.head(url.clone()).send().await?;
+    let status = response.status();
+
+    if status == StatusCode::NOT_FOUND {
+        bail!("not found");
+    }
+
+    let error_code = response
+        .headers()
+        .get("x-ms-error-code")
+        .and_then(|v| v.to_str().ok());
+    if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+        && error_code == Some(AUTHENTICATION_FAILED)
+    {
+        bail!("authentication failed ({status}), the SAS token may be invalid or expired");
+    }
+
+    Ok(())
+}
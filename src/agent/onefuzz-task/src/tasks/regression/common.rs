@@ -3,15 +3,21 @@
 
 use crate::tasks::{
     config::CommonConfig,
+    error::TaskError,
     heartbeat::{HeartbeatSender, TaskHeartbeatClient},
-    report::crash_report::{parse_report_file, CrashTestResult, RegressionReport},
+    lifecycle,
+    report::crash_report::{parse_report_file, CrashReport, CrashTestResult, RegressionReport},
 };
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use onefuzz::blob::{store, BlobClient, BlobContainerUrl};
 use onefuzz::syncdir::SyncedDir;
 use onefuzz_result::job_result::TaskJobResultClient;
+use onefuzz_telemetry::LifecycleState;
 use reqwest::Url;
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio_util::sync::CancellationToken;
 
 /// Defines implementation-provided callbacks for all implementers of regression tasks.
 ///
@@ -24,7 +30,109 @@ pub trait RegressionHandler {
     async fn get_crash_result(&self, input: PathBuf, input_url: Url) -> Result<CrashTestResult>;
 }
 
+/// Overall outcome of a regression run, for consumers which need a single pass/fail signal.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct RegressionSummary {
+    /// Number of inputs tested.
+    pub total: u64,
+
+    /// Previously crashing inputs which still crash.
+    pub reproduced: u64,
+
+    /// Previously crashing inputs which no longer crash.
+    pub fixed: u64,
+
+    /// Inputs which crash, but were not previously known to.
+    pub new_failures: u64,
+
+    /// Names of the inputs counted in `new_failures`.
+    pub new_failure_inputs: Vec<String>,
+
+    /// The inputs counted in `reproduced`, with their stacks.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub still_crashing: Vec<StillCrashing>,
+}
+
+/// A previously crashing input which still crashes.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct StillCrashing {
+    pub input: String,
+    pub crash_type: String,
+    pub call_stack: Vec<String>,
+}
+
+impl RegressionSummary {
+    pub fn record(
+        &mut self,
+        input_name: &str,
+        crash_test_result: &CrashTestResult,
+        original_crash_test_result: Option<&CrashTestResult>,
+    ) {
+        self.total += 1;
+
+        let crashed = matches!(
+            original_crash_test_result,
+            Some(CrashTestResult::CrashReport(_))
+        );
+
+        match (crashed, crash_test_result) {
+            (true, CrashTestResult::CrashReport(report)) => {
+                self.record_still_crashing(input_name, report)
+            }
+            (true, CrashTestResult::NoRepro(_)) => self.fixed += 1,
+            (false, CrashTestResult::CrashReport(_)) => {
+                self.new_failures += 1;
+                self.new_failure_inputs.push(input_name.to_owned());
+            }
+            (false, CrashTestResult::NoRepro(_)) => {}
+        }
+    }
+
+    /// Record the result of replaying `input_name`, which is known to have
+    /// crashed.
+    pub fn record_known_crash(&mut self, input_name: &str, crash_test_result: &CrashTestResult) {
+        self.total += 1;
+        match crash_test_result {
+            CrashTestResult::CrashReport(report) => self.record_still_crashing(input_name, report),
+            CrashTestResult::NoRepro(_) => self.fixed += 1,
+        }
+    }
+
+    fn record_still_crashing(&mut self, input_name: &str, report: &CrashReport) {
+        self.reproduced += 1;
+        self.still_crashing.push(StillCrashing {
+            input: input_name.to_owned(),
+            crash_type: report.crash_type.clone(),
+            call_stack: report.call_stack.clone(),
+        });
+    }
+
+    /// Write the summary as JSON to a local `file://` URL, or upload it as a blob.
+    pub async fn save(&self, url: &Url) -> Result<()> {
+        if url.scheme() == "file" {
+            let path = url
+                .to_file_path()
+                .map_err(|_| format_err!("invalid summary_output path: {}", url))?;
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let data = serde_json::to_vec_pretty(self)?;
+            tokio::fs::write(&path, data)
+                .await
+                .with_context(|| format!("unable to write summary: {}", path.display()))?;
+        } else {
+            BlobClient::new()
+                .put_json(url.clone(), self)
+                .await
+                .context("unable to upload regression summary")?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+}
+
 /// Runs the regression task
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     common_config: &CommonConfig,
     regression_reports: &SyncedDir,
@@ -32,6 +140,8 @@ pub async fn run(
     report_dirs: &[&SyncedDir],
     report_list: &Option<Vec<String>>,
     readonly_inputs: &Option<SyncedDir>,
+    verify_fixed_container: Option<&BlobContainerUrl>,
+    summary_output: &Option<Url>,
     handler: &impl RegressionHandler,
 ) -> Result<()> {
     info!("starting regression task");
@@ -39,6 +149,8 @@ pub async fn run(
 
     let heartbeat_client = common_config.init_heartbeat(None).await?;
     let job_result_client = common_config.init_job_result().await?;
+    lifecycle::transition(LifecycleState::Ready);
+    let mut summary = RegressionSummary::default();
 
     handle_crash_reports(
         handler,
@@ -48,6 +160,8 @@ pub async fn run(
         regression_reports,
         &heartbeat_client,
         &job_result_client,
+        &mut summary,
+        &common_config.cancellation_token,
     )
     .await
     .context("handling crash reports")?;
@@ -59,11 +173,46 @@ pub async fn run(
             regression_reports,
             &heartbeat_client,
             &job_result_client,
+            &mut summary,
+            &common_config.cancellation_token,
         )
         .await
         .context("handling inputs")?;
     }
 
+    let still_crashing = match verify_fixed_container {
+        Some(container) => handle_verify_fixed(
+            handler,
+            container,
+            regression_reports,
+            &heartbeat_client,
+            &job_result_client,
+            &mut summary,
+        )
+        .await
+        .context("verifying fixed inputs")?,
+        None => 0,
+    };
+
+    info!(
+        "regression summary: total: {} reproduced: {} fixed: {} new_failures: {}",
+        summary.total, summary.reproduced, summary.fixed, summary.new_failures
+    );
+    if let Some(summary_output) = summary_output {
+        summary
+            .save(summary_output)
+            .await
+            .context("saving regression summary")?;
+    }
+
+    if still_crashing > 0 {
+        return Err(TaskError::TargetFailure(anyhow!(
+            "{} inputs in verify_fixed_container still crash",
+            still_crashing
+        ))
+        .into());
+    }
+
     info!("regression task stopped");
     Ok(())
 }
@@ -73,14 +222,17 @@ pub async fn run(
 /// * `readonly_inputs` - location of the input files
 /// * `regression_reports` - where reports should be saved
 /// * `heartbeat_client` - heartbeat client
+/// * `summary` - accumulates the outcome of each input
 pub async fn handle_inputs(
     handler: &impl RegressionHandler,
     readonly_inputs: &SyncedDir,
     regression_reports: &SyncedDir,
     heartbeat_client: &Option<TaskHeartbeatClient>,
     job_result_client: &Option<TaskJobResultClient>,
+    summary: &mut RegressionSummary,
+    cancellation_token: &CancellationToken,
 ) -> Result<()> {
-    readonly_inputs.init_pull().await?;
+    readonly_inputs.init_pull(cancellation_token).await?;
     let mut input_files = tokio::fs::read_dir(&readonly_inputs.local_path).await?;
     while let Some(file) = input_files.next_entry().await? {
         heartbeat_client.alive();
@@ -99,6 +251,7 @@ pub async fn handle_inputs(
         let input_url = readonly_inputs.remote_url()?.url()?.join(&file_name)?;
 
         let crash_test_result = handler.get_crash_result(file_path, input_url).await?;
+        summary.record(&file_name, &crash_test_result, None);
         RegressionReport {
             crash_test_result,
             original_crash_test_result: None,
@@ -110,6 +263,64 @@ pub async fn handle_inputs(
     Ok(())
 }
 
+/// Replay every input in `container`, all of which are known to have
+/// crashed, returning the number which still crash. Inputs are downloaded one
+/// at a time.
+/// * `handler` - regression handler
+/// * `container` - location of the known crashing inputs
+/// * `regression_reports` - where reports should be saved
+/// * `heartbeat_client` - heartbeat client
+/// * `summary` - accumulates the outcome of each input
+pub async fn handle_verify_fixed(
+    handler: &impl RegressionHandler,
+    container: &BlobContainerUrl,
+    regression_reports: &SyncedDir,
+    heartbeat_client: &Option<TaskHeartbeatClient>,
+    job_result_client: &Option<TaskJobResultClient>,
+    summary: &mut RegressionSummary,
+) -> Result<u64> {
+    let store = store::open(container)?;
+    let temp_dir = tempfile::tempdir()?;
+
+    let mut still_crashing = 0;
+    for name in store
+        .list()
+        .await
+        .context("unable to list inputs to verify")?
+    {
+        heartbeat_client.alive();
+
+        // Keep the file name, since targets may depend on its extension.
+        let file_name = Path::new(&name)
+            .file_name()
+            .ok_or_else(|| format_err!("missing filename: {}", name))?;
+        let input = temp_dir.path().join(file_name);
+        store
+            .download(&name, &input)
+            .await
+            .with_context(|| format!("unable to download input to verify: {name}"))?;
+
+        let input_url = container.blob(&name).url();
+        let crash_test_result = handler.get_crash_result(input.clone(), input_url).await?;
+        if let CrashTestResult::CrashReport(_) = &crash_test_result {
+            warn!("input still crashes: {}", name);
+            still_crashing += 1;
+        }
+        summary.record_known_crash(&name, &crash_test_result);
+        RegressionReport {
+            crash_test_result,
+            original_crash_test_result: None,
+        }
+        .save(None, regression_reports, job_result_client)
+        .await?;
+
+        tokio::fs::remove_file(&input).await?;
+    }
+
+    Ok(still_crashing)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_crash_reports(
     handler: &impl RegressionHandler,
     crashes: &SyncedDir,
@@ -118,16 +329,18 @@ pub async fn handle_crash_reports(
     regression_reports: &SyncedDir,
     heartbeat_client: &Option<TaskHeartbeatClient>,
     job_result_client: &Option<TaskJobResultClient>,
+    summary: &mut RegressionSummary,
+    cancellation_token: &CancellationToken,
 ) -> Result<()> {
     // without crash report containers, skip this method
     if report_dirs.is_empty() {
         return Ok(());
     }
 
-    crashes.init_pull().await?;
+    crashes.init_pull(cancellation_token).await?;
 
     for possible_dir in report_dirs {
-        possible_dir.init_pull().await?;
+        possible_dir.init_pull(cancellation_token).await?;
 
         let mut report_files = tokio::fs::read_dir(&possible_dir.local_path).await?;
         while let Some(file) = report_files.next_entry().await? {
@@ -162,6 +375,11 @@ pub async fn handle_crash_reports(
             let input_url = crashes.remote_url()?.url()?;
             let input = crashes.local_path.join(&input_blob.name);
             let crash_test_result = handler.get_crash_result(input, input_url).await?;
+            summary.record(
+                &input_blob.name,
+                &crash_test_result,
+                Some(&original_crash_test_result),
+            );
 
             RegressionReport {
                 crash_test_result,
@@ -174,3 +392,86 @@ pub async fn handle_crash_reports(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::report::crash_report::NoCrash;
+
+    #[test]
+    fn test_summary_record() {
+        let crash = || {
+            CrashTestResult::CrashReport(Box::new(CrashReport {
+                crash_type: "heap-buffer-overflow".to_owned(),
+                call_stack: vec!["#0 main".to_owned()],
+                ..Default::default()
+            }))
+        };
+        let no_repro = || {
+            CrashTestResult::NoRepro(Box::new(NoCrash {
+                input_sha256: String::new(),
+                input_blob: None,
+                executable: PathBuf::new(),
+                task_id: Default::default(),
+                job_id: Default::default(),
+                tries: 1,
+                error: None,
+                source_revision: None,
+                reproducibility: None,
+            }))
+        };
+
+        let mut summary = RegressionSummary::default();
+        summary.record("still-crashes", &crash(), Some(&crash()));
+        summary.record("fixed", &no_repro(), Some(&crash()));
+        summary.record("new-crash", &crash(), None);
+        summary.record("new-from-no-repro", &crash(), Some(&no_repro()));
+        summary.record("benign", &no_repro(), None);
+
+        assert_eq!(
+            summary,
+            RegressionSummary {
+                total: 5,
+                reproduced: 1,
+                fixed: 1,
+                new_failures: 2,
+                new_failure_inputs: vec!["new-crash".to_owned(), "new-from-no-repro".to_owned()],
+                still_crashing: vec![StillCrashing {
+                    input: "still-crashes".to_owned(),
+                    crash_type: "heap-buffer-overflow".to_owned(),
+                    call_stack: vec!["#0 main".to_owned()],
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_summary_record_known_crash() {
+        let mut summary = RegressionSummary::default();
+        summary.record_known_crash(
+            "still-crashes",
+            &CrashTestResult::CrashReport(Box::default()),
+        );
+        summary.record_known_crash(
+            "fixed",
+            &CrashTestResult::NoRepro(Box::new(NoCrash {
+                input_sha256: String::new(),
+                input_blob: None,
+                executable: PathBuf::new(),
+                task_id: Default::default(),
+                job_id: Default::default(),
+                tries: 1,
+                error: None,
+                source_revision: None,
+                reproducibility: None,
+            })),
+        );
+
+        assert_eq!(summary.total, 2);
+        assert_eq!(summary.reproduced, 1);
+        assert_eq!(summary.fixed, 1);
+        assert_eq!(summary.new_failures, 0);
+        assert_eq!(summary.still_crashing.len(), 1);
+        assert_eq!(summary.still_crashing[0].input, "still-crashes");
+    }
+}
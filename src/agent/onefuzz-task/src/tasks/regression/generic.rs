@@ -8,7 +8,7 @@ use crate::tasks::{
 };
 use anyhow::Result;
 use async_trait::async_trait;
-use onefuzz::syncdir::SyncedDir;
+use onefuzz::{blob::BlobContainerUrl, syncdir::SyncedDir};
 use reqwest::Url;
 use serde::Deserialize;
 use std::{collections::HashMap, path::PathBuf};
@@ -45,6 +45,17 @@ pub struct Config {
     #[serde(default)]
     pub minimized_stack_depth: Option<usize>,
 
+    /// Where to write a JSON summary of the run, for use as a single pass/fail signal.
+    #[serde(default)]
+    pub summary_output: Option<Url>,
+
+    /// Container of inputs which crashed earlier builds, to confirm that none
+    /// of them still crash, such as before a release. Every input is replayed,
+    /// and those which still crash are reported as regressions and listed,
+    /// with their stacks, in `summary_output`. The task fails if any do.
+    #[serde(default)]
+    pub verify_fixed_container: Option<BlobContainerUrl>,
+
     #[serde(flatten)]
     pub common: CommonConfig,
 }
@@ -64,6 +75,7 @@ impl RegressionHandler for GenericRegressionTask {
         let args = generic::TestInputArgs {
             input_url: Some(input_url),
             input: &input,
+            transformed_input: None,
             target_exe: &target_exe,
             target_options: &self.config.target_options,
             target_env: &self.config.target_env,
@@ -76,7 +88,20 @@ impl RegressionHandler for GenericRegressionTask {
             check_asan_log: self.config.check_asan_log,
             check_debugger: self.config.check_debugger,
             minimized_stack_depth: self.config.minimized_stack_depth,
+            stack_hash_frame_count: None,
             machine_identity: self.config.common.machine_identity.clone(),
+            input_via: Default::default(),
+            seccomp_filter: None,
+            target_user: None,
+            fresh_state: false,
+            working_dir: None,
+            auxiliary_files: &[],
+            expected_exit_codes: &[],
+            memory_limit_mb: None,
+            nice: None,
+            input_placeholder: None,
+            tee_output: false,
+            capture_module_list: false,
         };
         generic::test_input(args).await
     }
@@ -108,6 +133,8 @@ impl GenericRegressionTask {
             &report_dirs,
             &self.config.report_list,
             &self.config.readonly_inputs,
+            self.config.verify_fixed_container.as_ref(),
+            &self.config.summary_output,
             self,
         )
         .await?;
@@ -44,6 +44,10 @@ pub struct Config {
     #[serde(default)]
     pub minimized_stack_depth: Option<usize>,
 
+    /// Where to write a JSON summary of the run, for use as a single pass/fail signal.
+    #[serde(default)]
+    pub summary_output: Option<Url>,
+
     #[serde(flatten)]
     pub common: CommonConfig,
 }
@@ -62,6 +66,7 @@ impl RegressionHandler for LibFuzzerRegressionTask {
         let args = libfuzzer_report::TestInputArgs {
             input_url: Some(input_url),
             input: &input,
+            transformed_input: None,
             target_exe: &target_exe,
             target_options: &self.config.target_options,
             target_env: &self.config.target_env,
@@ -78,7 +83,16 @@ impl RegressionHandler for LibFuzzerRegressionTask {
             target_timeout: self.config.target_timeout,
             check_retry_count: self.config.check_retry_count,
             minimized_stack_depth: self.config.minimized_stack_depth,
+            stack_hash_frame_count: None,
             machine_identity: self.config.common.machine_identity.clone(),
+            seccomp_filter: None,
+            target_user: None,
+            fresh_state: false,
+            auxiliary_files: &[],
+            memory_limit_mb: None,
+            nice: None,
+            tee_output: false,
+            capture_module_list: false,
         };
 
         libfuzzer_report::test_input(args).await
@@ -110,6 +124,8 @@ impl LibFuzzerRegressionTask {
             &report_dirs,
             &self.config.report_list,
             &self.config.readonly_inputs,
+            None,
+            &self.config.summary_output,
             self,
         )
         .await
@@ -0,0 +1,174 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+
+use super::crash_report::{CrashReport, CrashTestResult};
+
+const DEFAULT_LABEL: &str = "unclassified";
+
+fn default_label() -> String {
+    DEFAULT_LABEL.to_owned()
+}
+
+#[derive(Debug, Deserialize)]
+struct RulesFile {
+    #[serde(default = "default_label")]
+    default_label: String,
+    rules: Vec<RuleEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleEntry {
+    pattern: String,
+    label: String,
+}
+
+#[derive(Debug)]
+struct Rule {
+    pattern: Regex,
+    label: String,
+}
+
+/// User-supplied rules mapping crash text to a label, such as a severity or owner.
+///
+/// Rules are loaded from a JSON file of the form:
+///
+/// ```json
+/// {
+///   "default_label": "unclassified",
+///   "rules": [
+///     { "pattern": "heap-buffer-overflow", "label": "high" },
+///     { "pattern": "stack-overflow", "label": "low" }
+///   ]
+/// }
+/// ```
+///
+/// Each pattern is matched against the crash type, crash site, call stack, and
+/// sanitizer log. The first matching rule wins; crashes which match no rule get
+/// the default label.
+#[derive(Debug)]
+pub struct ClassificationRules {
+    default_label: String,
+    rules: Vec<Rule>,
+}
+
+impl ClassificationRules {
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: RulesFile = serde_json::from_str(text).context("invalid classification rules")?;
+
+        let rules = file
+            .rules
+            .into_iter()
+            .map(|entry| {
+                let pattern = Regex::new(&entry.pattern).with_context(|| {
+                    format!("invalid classification rule pattern: {}", entry.pattern)
+                })?;
+                Ok(Rule {
+                    pattern,
+                    label: entry.label,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            default_label: file.default_label,
+            rules,
+        })
+    }
+
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("unable to read classification rules: {}", path.display()))?;
+        Self::parse(&text)
+            .with_context(|| format!("unable to load classification rules: {}", path.display()))
+    }
+
+    /// Load the rules file, if one is configured.
+    pub async fn load_optional(path: Option<impl AsRef<Path>>) -> Result<Option<Self>> {
+        match path {
+            Some(path) => Ok(Some(Self::load(path).await?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn classify(&self, report: &CrashReport) -> &str {
+        let texts = [&report.crash_type, &report.crash_site]
+            .into_iter()
+            .chain(report.call_stack.iter())
+            .chain(report.asan_log.iter());
+
+        let texts: Vec<&String> = texts.collect();
+
+        self.rules
+            .iter()
+            .find(|rule| texts.iter().any(|text| rule.pattern.is_match(text)))
+            .map(|rule| rule.label.as_str())
+            .unwrap_or(self.default_label.as_str())
+    }
+
+    /// Label the report in `result`, if it reproduced.
+    pub fn apply(&self, result: &mut CrashTestResult) {
+        if let CrashTestResult::CrashReport(report) = result {
+            report.classification = Some(self.classify(report).to_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = r#"{
+        "default_label": "unknown",
+        "rules": [
+            { "pattern": "heap-buffer-overflow", "label": "high" },
+            { "pattern": "^#0 .* in parse_header", "label": "parser-team" },
+            { "pattern": "overflow", "label": "medium" }
+        ]
+    }"#;
+
+    fn report(crash_type: &str, call_stack: &[&str]) -> CrashReport {
+        CrashReport {
+            crash_type: crash_type.to_owned(),
+            call_stack: call_stack.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() -> Result<()> {
+        let rules = ClassificationRules::parse(RULES)?;
+
+        assert_eq!(rules.classify(&report("heap-buffer-overflow", &[])), "high");
+        assert_eq!(
+            rules.classify(&report("stack-overflow", &["#0 0x1 in parse_header"])),
+            "parser-team"
+        );
+        assert_eq!(rules.classify(&report("stack-overflow", &[])), "medium");
+        assert_eq!(rules.classify(&report("SEGV", &[])), "unknown");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(
+            ClassificationRules::parse(r#"{ "rules": [{ "pattern": "(", "label": "x" }] }"#)
+                .is_err()
+        );
+        assert!(ClassificationRules::parse("not json").is_err());
+    }
+
+    #[test]
+    fn test_default_label() -> Result<()> {
+        let rules = ClassificationRules::parse(r#"{ "rules": [] }"#)?;
+        assert_eq!(rules.classify(&report("SEGV", &[])), DEFAULT_LABEL);
+        Ok(())
+    }
+}
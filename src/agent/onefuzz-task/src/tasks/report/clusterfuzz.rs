@@ -0,0 +1,146 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! ClusterFuzz-compatible crash states, so that reports can be matched against
+//! testcases deduplicated by ClusterFuzz.
+//!
+//! ClusterFuzz identifies a crash by its type and its "crash state": the top
+//! three frames of the crashing stack which are not part of the sanitizer or
+//! fuzzing runtimes, with function arguments removed. Frames are skipped using
+//! ClusterFuzz's own ignore list, from `libclusterfuzz`, and, as ClusterFuzz
+//! does, `LLVMFuzzerTestOneInput` is replaced by the fuzzer's source file name.
+
+use stacktrace_parser::{clusterfuzz_frames, parse_call_stack};
+
+use super::crash_report::CrashReport;
+
+const CRASH_STATE_FRAMES: usize = 3;
+
+/// The state of a crash without any symbolized frames.
+const NULL_CRASH_STATE: &str = "NULL";
+
+impl CrashReport {
+    /// The ClusterFuzz crash state of the report's call stack.
+    pub fn clusterfuzz_crash_state(&self) -> String {
+        crash_state(&self.call_stack)
+    }
+}
+
+/// Compute the ClusterFuzz crash state of `call_stack`, one frame per line.
+pub fn crash_state(call_stack: &[String]) -> String {
+    let stack = parse_call_stack(&call_stack.join("\n")).unwrap_or_default();
+    let frames: Vec<String> = clusterfuzz_frames(&stack)
+        .into_iter()
+        .filter_map(|frame| frame.function_name)
+        .map(|name| normalize_function_name(&name))
+        .filter(|name| !name.is_empty())
+        .take(CRASH_STATE_FRAMES)
+        .collect();
+
+    if frames.is_empty() {
+        return NULL_CRASH_STATE.to_owned();
+    }
+
+    // ClusterFuzz terminates each frame, including the last, with a newline.
+    frames.into_iter().map(|frame| frame + "\n").collect()
+}
+
+/// Remove the argument list and qualifiers from a symbolized function name,
+/// e.g. `ns::parse(char const*, int) const` becomes `ns::parse`.
+pub fn normalize_function_name(name: &str) -> String {
+    let mut name = name.trim();
+    name = name.strip_suffix(" const").unwrap_or(name);
+
+    if let Some(args_start) = argument_list_start(name) {
+        name = name[..args_start].trim_end();
+
+        // Keep the call operator's own parentheses.
+        if name.ends_with("operator") {
+            return format!("{name}()");
+        }
+    }
+
+    name.to_owned()
+}
+
+/// Byte offset of the `(` opening the trailing argument list of `name`, if any.
+fn argument_list_start(name: &str) -> Option<usize> {
+    if !name.ends_with(')') {
+        return None;
+    }
+
+    let mut depth = 0;
+    for (offset, c) in name.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(offset);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEAP_OVERFLOW_STACK: &[&str] = &[
+        "#0 0x4f8a21 in __asan_memcpy /src/llvm/compiler-rt/lib/asan/asan_interceptors_memintrinsics.cpp:22:3",
+        "#1 0x551f05 in png::read_chunk(png::Reader&, unsigned long) const /src/png/read.cc:120:5",
+        "#2 0x552a11 in png::Reader::operator()(char const*) /src/png/read.cc:200:10",
+        "#3 0x553b20 in (anonymous namespace)::decode(unsigned char const*, unsigned long) /src/png/decode.cc:41:3",
+        "#4 0x554c30 in png::run_decoder /src/png/decode.cc:80:3",
+        "#5 0x555d40 in LLVMFuzzerTestOneInput /src/png/fuzz.cc:10:3",
+        "#6 0x556e50 in fuzzer::Fuzzer::ExecuteCallback(unsigned char const*, unsigned long) /src/libfuzzer/FuzzerLoop.cpp:611:15",
+    ];
+
+    fn stack(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_normalize_function_name() {
+        assert_eq!(normalize_function_name("parse"), "parse");
+        assert_eq!(
+            normalize_function_name("ns::parse(char const*, int) const"),
+            "ns::parse"
+        );
+        assert_eq!(
+            normalize_function_name("ns::Reader::operator()(char const*)"),
+            "ns::Reader::operator()"
+        );
+        assert_eq!(
+            normalize_function_name("std::vector<int>::at(unsigned long)"),
+            "std::vector<int>::at"
+        );
+        assert_eq!(normalize_function_name("run(void (*)(int))"), "run");
+    }
+
+    #[test]
+    fn test_crash_state() {
+        assert_eq!(
+            crash_state(&stack(HEAP_OVERFLOW_STACK)),
+            "png::read_chunk\npng::Reader::operator()\n(anonymous namespace)::decode\n"
+        );
+    }
+
+    #[test]
+    fn test_crash_state_short_stack() {
+        let call_stack = stack(&HEAP_OVERFLOW_STACK[4..]);
+        assert_eq!(crash_state(&call_stack), "png::run_decoder\nfuzz.cc\n");
+    }
+
+    #[test]
+    fn test_crash_state_no_frames() {
+        assert_eq!(crash_state(&[]), NULL_CRASH_STATE);
+
+        let runtime_only = stack(&[HEAP_OVERFLOW_STACK[0], HEAP_OVERFLOW_STACK[6]]);
+        assert_eq!(crash_state(&runtime_only), NULL_CRASH_STATE);
+    }
+}
@@ -1,8 +1,14 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+use super::reproducibility::Reproducibility;
+use super::sarif::ReportFormat;
+use super::severity::Severity;
 use anyhow::{Context, Result};
-use onefuzz::{blob::BlobUrl, monitor::DirectoryMonitor, syncdir::SyncedDir};
+use chrono::{DateTime, NaiveDate, Utc};
+use onefuzz::{
+    blob::BlobUrl, loaded_modules::LoadedModule, monitor::DirectoryMonitor, syncdir::SyncedDir,
+};
 use onefuzz_result::job_result::{JobResultData, JobResultSender, TaskJobResultClient};
 use onefuzz_telemetry::{
     Event::{
@@ -11,12 +17,15 @@ use onefuzz_telemetry::{
     },
     EventData,
 };
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use stacktrace_parser::CrashLog;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+const DATE_PARTITION_FORMAT: &str = "%Y/%m/%d/";
+
 #[derive(Debug, Deserialize, Serialize, Default)]
 pub struct CrashReport {
     pub input_sha256: String,
@@ -47,6 +56,11 @@ pub struct CrashReport {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub minimized_stack_function_lines_sha256: Option<String>,
 
+    /// Hash of the top `stack_hash_frame_count` normalized frames, which
+    /// replaces `call_stack_sha256` in deduplicating reports when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalized_stack_sha256: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub asan_log: Option<String>,
 
@@ -68,6 +82,36 @@ pub struct CrashReport {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_version: Option<String>,
+
+    /// Label assigned by the task's `classification_rules`, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification: Option<String>,
+
+    /// The task's `source_revision`, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_revision: Option<String>,
+
+    /// The ClusterFuzz crash state, if the task's `clusterfuzz_signature` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clusterfuzz_crash_state: Option<String>,
+
+    /// Triage priority assigned from the crash type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+
+    /// Likely CWE ID of the crash, if the task's `classify_cwe` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwe: Option<u32>,
+
+    /// How reliably the crash reproduced on reruns, if the task's
+    /// `repro_count` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reproducibility: Option<Reproducibility>,
+
+    /// Modules loaded by the target when it crashed, if the task's
+    /// `capture_module_list` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loaded_modules: Option<Vec<LoadedModule>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -81,6 +125,11 @@ pub struct NoCrash {
     pub tries: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_revision: Option<String>,
+    /// Set to `non_reproducing` when reproducibility is being checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reproducibility: Option<Reproducibility>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -90,6 +139,17 @@ pub enum CrashTestResult {
     NoRepro(Box<NoCrash>),
 }
 
+impl CrashTestResult {
+    /// Record the revision of the code under test in the report.
+    pub fn set_source_revision(&mut self, source_revision: Option<&str>) {
+        let source_revision = source_revision.map(str::to_owned);
+        match self {
+            Self::CrashReport(report) => report.source_revision = source_revision,
+            Self::NoRepro(no_repro) => no_repro.source_revision = source_revision,
+        }
+    }
+}
+
 impl From<CrashReport> for CrashTestResult {
     fn from(report: CrashReport) -> Self {
         Self::CrashReport(Box::new(report))
@@ -126,7 +186,7 @@ impl RegressionReport {
             }
         };
 
-        if upload_or_save_local(&self, &name, regression_reports).await? {
+        if upload_or_save_local(&self, &name, regression_reports, &HashMap::new()).await? {
             event!(event; EventData::Path = name.clone());
             metric!(event; 1.0; EventData::Path = name.clone());
 
@@ -147,8 +207,36 @@ async fn upload_or_save_local<T: Serialize>(
     report: &T,
     dest_name: &str,
     container: &SyncedDir,
+    metadata: &HashMap<String, String>,
+) -> Result<bool> {
+    container
+        .upload_with_metadata(dest_name, report, metadata)
+        .await
+}
+
+/// Save `report` as `name` prefixed with `partition`, unless it was already
+/// saved, whether flat or under any date partition, so that a known report is
+/// not saved again each day.
+async fn save_partitioned<T: Serialize>(
+    report: &T,
+    partition: &str,
+    name: &str,
+    container: &SyncedDir,
+    metadata: &HashMap<String, String>,
 ) -> Result<bool> {
-    container.upload(dest_name, report).await
+    if !partition.is_empty() && saved_in_any_partition(container, name).await? {
+        return Ok(false);
+    }
+    upload_or_save_local(report, &format!("{partition}{name}"), container, metadata).await
+}
+
+async fn saved_in_any_partition(container: &SyncedDir, name: &str) -> Result<bool> {
+    let saved = container
+        .list()
+        .await?
+        .into_iter()
+        .any(|blob| blob == name || blob.strip_suffix(name).map_or(false, is_date_partition));
+    Ok(saved)
 }
 
 impl CrashTestResult {
@@ -163,6 +251,47 @@ impl CrashTestResult {
         no_repro: &Option<SyncedDir>,
         jr_client: &Option<TaskJobResultClient>,
     ) -> Result<()> {
+        self.save_with_format(
+            ReportFormat::Native,
+            unique_reports,
+            reports,
+            no_repro,
+            jr_client,
+            &HashMap::new(),
+            false,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Saves the crash result, writing reproduced crashes natively and, if
+    /// `report_format` is SARIF, as SARIF next to it, and setting `metadata`
+    /// on each uploaded report blob. With
+    /// `date_partitioning`, each blob name is prefixed with the current UTC
+    /// date, as `YYYY/MM/DD/`, unless the report is already saved, flat or
+    /// under any date.
+    ///
+    /// Returns the URL of the crash report saved, preferring the unique
+    /// report, or `None` if no crash report was saved.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn save_with_format(
+        &self,
+        report_format: ReportFormat,
+        unique_reports: &Option<SyncedDir>,
+        reports: &Option<SyncedDir>,
+        no_repro: &Option<SyncedDir>,
+        jr_client: &Option<TaskJobResultClient>,
+        metadata: &HashMap<String, String>,
+        date_partitioning: bool,
+    ) -> Result<Option<Url>> {
+        // Read the clock once, so that every blob of the report has the same date.
+        let partition = if date_partitioning {
+            date_partition(Utc::now())
+        } else {
+            String::new()
+        };
+
+        let mut report_url = None;
         match self {
             Self::CrashReport(report) => {
                 // Use SHA-256 of call stack as dedupe key.
@@ -178,7 +307,17 @@ impl CrashTestResult {
                 }
                 if let Some(unique_reports) = unique_reports {
                     let name = report.unique_blob_name();
-                    if upload_or_save_local(&report, &name, unique_reports).await? {
+                    let saved =
+                        save_partitioned(&report, &partition, &name, unique_reports, metadata)
+                            .await?;
+                    if report_format == ReportFormat::Sarif {
+                        let name = report.unique_sarif_blob_name();
+                        let sarif = report.to_sarif();
+                        save_partitioned(&sarif, &partition, &name, unique_reports, metadata)
+                            .await?;
+                    }
+                    if saved {
+                        report_url = saved_blob_url(unique_reports, &format!("{partition}{name}"));
                         event!(new_unique_report; EventData::Path = report.unique_blob_name());
                         metric!(new_unique_report; 1.0; EventData::Path = report.unique_blob_name());
 
@@ -195,7 +334,16 @@ impl CrashTestResult {
 
                 if let Some(reports) = reports {
                     let name = report.blob_name();
-                    if upload_or_save_local(&report, &name, reports).await? {
+                    let saved =
+                        save_partitioned(&report, &partition, &name, reports, metadata).await?;
+                    if report_format == ReportFormat::Sarif {
+                        let name = report.sarif_blob_name();
+                        let sarif = report.to_sarif();
+                        save_partitioned(&sarif, &partition, &name, reports, metadata).await?;
+                    }
+                    if saved {
+                        report_url = report_url
+                            .or_else(|| saved_blob_url(reports, &format!("{partition}{name}")));
                         event!(new_report; EventData::Path = report.blob_name());
                         metric!(new_report; 1.0; EventData::Path = report.blob_name());
 
@@ -214,7 +362,7 @@ impl CrashTestResult {
             Self::NoRepro(report) => {
                 if let Some(no_repro) = no_repro {
                     let name = report.blob_name();
-                    if upload_or_save_local(&report, &name, no_repro).await? {
+                    if save_partitioned(&report, &partition, &name, no_repro, metadata).await? {
                         event!(new_unable_to_reproduce; EventData::Path = report.blob_name());
                         metric!(new_unable_to_reproduce; 1.0; EventData::Path = report.blob_name());
 
@@ -230,10 +378,32 @@ impl CrashTestResult {
                 }
             }
         }
-        Ok(())
+        Ok(report_url)
+    }
+}
+
+/// URL of the blob `name` saved to `container`, without any SAS token.
+fn saved_blob_url(container: &SyncedDir, name: &str) -> Option<Url> {
+    match container.try_url() {
+        Some(remote) => {
+            let mut url = remote.blob(name).url();
+            url.set_query(None);
+            Some(url)
+        }
+        None => Url::from_file_path(container.local_path.join(name)).ok(),
     }
 }
 
+/// The `YYYY/MM/DD/` prefix of reports saved at `time`.
+fn date_partition(time: DateTime<Utc>) -> String {
+    time.format(DATE_PARTITION_FORMAT).to_string()
+}
+
+fn is_date_partition(prefix: &str) -> bool {
+    prefix.len() == "YYYY/MM/DD/".len()
+        && NaiveDate::parse_from_str(prefix, DATE_PARTITION_FORMAT).is_ok()
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct InputBlob {
     pub account: Option<String>,
@@ -261,11 +431,14 @@ impl CrashReport {
         input_blob: Option<InputBlob>,
         input_sha256: String,
         minimized_stack_depth: Option<usize>,
+        stack_hash_frame_count: Option<usize>,
         tool_name: String,
         tool_version: String,
         onefuzz_version: String,
     ) -> Self {
         let call_stack_sha256 = crash_log.call_stack_sha256();
+        let normalized_stack_sha256 =
+            stack_hash_frame_count.map(|count| crash_log.normalized_stack_sha256(count));
         let minimized_stack_sha256 = if crash_log.minimized_stack.is_empty() {
             None
         } else {
@@ -313,6 +486,7 @@ impl CrashReport {
             minimized_stack_function_names_sha256,
             minimized_stack_function_lines,
             minimized_stack_function_lines_sha256,
+            normalized_stack_sha256,
             call_stack: crash_log.call_stack,
             asan_log: crash_log.text,
             scariness_score: crash_log.scariness_score,
@@ -322,6 +496,13 @@ impl CrashReport {
             onefuzz_version: Some(onefuzz_version),
             tool_name: Some(tool_name),
             tool_version: Some(tool_version),
+            classification: None,
+            source_revision: None,
+            clusterfuzz_crash_state: None,
+            severity: None,
+            cwe: None,
+            reproducibility: None,
+            loaded_modules: None,
         }
     }
 
@@ -329,8 +510,15 @@ impl CrashReport {
         format!("{}.json", self.input_sha256)
     }
 
+    /// Hash identifying reports of the same crash.
+    pub fn unique_sha256(&self) -> &str {
+        self.normalized_stack_sha256
+            .as_deref()
+            .unwrap_or(&self.call_stack_sha256)
+    }
+
     pub fn unique_blob_name(&self) -> String {
-        format!("{}.json", self.call_stack_sha256)
+        format!("{}.json", self.unique_sha256())
     }
 }
 
@@ -418,4 +606,120 @@ mod tests {
 
         Ok(())
     }
+    #[test]
+    fn test_date_partition() -> Result<()> {
+        let time = DateTime::parse_from_rfc3339("2024-03-07T23:59:59Z")?.with_timezone(&Utc);
+        assert_eq!(date_partition(time), "2024/03/07/");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_date_partitioned() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let reports = Some(SyncedDir {
+            local_path: dir.path().to_owned(),
+            remote_path: None,
+        });
+        let report = CrashTestResult::from(CrashReport {
+            input_sha256: "abc".into(),
+            ..Default::default()
+        });
+
+        let before = date_partition(Utc::now());
+        let url = report
+            .save_with_format(
+                ReportFormat::Native,
+                &None,
+                &reports,
+                &None,
+                &None,
+                &HashMap::new(),
+                true,
+            )
+            .await?;
+        let after = date_partition(Utc::now());
+
+        let saved = |partition: &str| dir.path().join(partition).join("abc.json").is_file();
+        assert!(saved(&before) || saved(&after));
+        let url = url.expect("report url").to_file_path().unwrap();
+        assert!(url.ends_with("abc.json") && url.is_file());
+
+        // Reports saved on an earlier day, or before partitioning, are not
+        // saved again.
+        std::fs::create_dir_all(dir.path().join("2020/01/01"))?;
+        std::fs::write(dir.path().join("2020/01/01/def.json"), "{}")?;
+        std::fs::write(dir.path().join("ghi.json"), "{}")?;
+        for input_sha256 in ["abc", "def", "ghi"] {
+            let report = CrashTestResult::from(CrashReport {
+                input_sha256: input_sha256.into(),
+                ..Default::default()
+            });
+            let url = report
+                .save_with_format(
+                    ReportFormat::Native,
+                    &None,
+                    &reports,
+                    &None,
+                    &None,
+                    &HashMap::new(),
+                    true,
+                )
+                .await?;
+            assert_eq!(url, None, "{input_sha256}");
+        }
+        let today = date_partition(Utc::now());
+        assert!(!dir.path().join(&today).join("def.json").exists());
+        assert!(!dir.path().join(&today).join("ghi.json").exists());
+
+        assert!(is_date_partition("2024/03/07/"));
+        assert!(!is_date_partition("crashes/2024/03/07/"));
+        assert!(!is_date_partition("misc/"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_sarif_with_native() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let reports = Some(SyncedDir {
+            local_path: dir.path().to_owned(),
+            remote_path: None,
+        });
+        let crash_report = CrashReport {
+            input_sha256: "abc".into(),
+            ..Default::default()
+        };
+        let sarif_name = crash_report.sarif_blob_name();
+        let report = CrashTestResult::from(crash_report);
+
+        let url = report
+            .save_with_format(
+                ReportFormat::Sarif,
+                &None,
+                &reports,
+                &None,
+                &None,
+                &HashMap::new(),
+                false,
+            )
+            .await?;
+
+        assert!(dir.path().join("abc.json").is_file());
+        assert!(dir.path().join(sarif_name).is_file());
+        let url = url.expect("report url").to_file_path().unwrap();
+        assert!(url.ends_with("abc.json"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_blob_name() {
+        let mut report = CrashReport {
+            call_stack_sha256: "abc".into(),
+            ..Default::default()
+        };
+        assert_eq!(report.unique_blob_name(), "abc.json");
+
+        report.normalized_stack_sha256 = Some("def".into());
+        assert_eq!(report.unique_blob_name(), "def.json");
+        assert_eq!(report.unique_sarif_blob_name(), "def.sarif");
+    }
 }
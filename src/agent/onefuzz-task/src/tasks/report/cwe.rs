@@ -0,0 +1,154 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Likely CWE IDs of reproduced crashes, from their sanitizer crash types, so
+//! that reports can be filed in vulnerability management pre-classified.
+//!
+//! | Crash type                                   | CWE | Weakness                              |
+//! |----------------------------------------------|-----|---------------------------------------|
+//! | `heap-buffer-overflow`                       | 122 | Heap-based Buffer Overflow            |
+//! | `stack-buffer-overflow`                      | 121 | Stack-based Buffer Overflow           |
+//! | `dynamic-stack-buffer-overflow`              | 121 | Stack-based Buffer Overflow           |
+//! | `stack-buffer-underflow`                     | 124 | Buffer Underwrite                     |
+//! | `global-buffer-overflow`                     | 788 | Access After End of Buffer            |
+//! | `container-overflow`                         | 119 | Improper Restriction of Buffer Bounds |
+//! | `heap-use-after-free`, `use-after-free`      | 416 | Use After Free                        |
+//! | `stack-use-after-return`                     | 562 | Return of Stack Variable Address      |
+//! | `stack-use-after-scope`, `use-after-poison`  | 825 | Expired Pointer Dereference           |
+//! | `double-free`, `attempting double-free`      | 415 | Double Free                           |
+//! | `bad-free`                                   | 763 | Release of Invalid Pointer            |
+//! | `alloc-dealloc-mismatch`                     | 762 | Mismatched Memory Management Routines |
+//! | `new-delete-type-mismatch`                   | 762 | Mismatched Memory Management Routines |
+//! | `use-of-uninitialized-value`                 | 457 | Use of Uninitialized Variable         |
+//! | `signed-integer-overflow`, `calloc-overflow` | 190 | Integer Overflow or Wraparound        |
+//! | `integer-divide-by-zero`                     | 369 | Divide By Zero                        |
+//! | `stack-overflow`                             | 674 | Uncontrolled Recursion                |
+//! | `detected memory leaks`, `leak`              | 401 | Missing Release of Memory             |
+//! | `out-of-memory`, `allocation-size-too-big`   | 789 | Memory Allocation with Excessive Size |
+//! | `data race`                                  | 362 | Race Condition                        |
+//!
+//! Other crash types, such as `SEGV`, whose cause can't be told from the type
+//! alone, have no CWE unless overridden.
+
+use std::collections::HashMap;
+
+use super::crash_report::{CrashReport, CrashTestResult};
+
+/// Built-in CWE of each crash type, as documented above.
+pub const CWE_BY_CRASH_TYPE: &[(&str, u32)] = &[
+    ("heap-buffer-overflow", 122),
+    ("stack-buffer-overflow", 121),
+    ("dynamic-stack-buffer-overflow", 121),
+    ("stack-buffer-underflow", 124),
+    ("global-buffer-overflow", 788),
+    ("container-overflow", 119),
+    ("heap-use-after-free", 416),
+    ("use-after-free", 416),
+    ("stack-use-after-return", 562),
+    ("stack-use-after-scope", 825),
+    ("use-after-poison", 825),
+    ("double-free", 415),
+    ("attempting double-free", 415),
+    ("bad-free", 763),
+    ("alloc-dealloc-mismatch", 762),
+    ("new-delete-type-mismatch", 762),
+    ("use-of-uninitialized-value", 457),
+    ("signed-integer-overflow", 190),
+    ("calloc-overflow", 190),
+    ("integer-divide-by-zero", 369),
+    ("stack-overflow", 674),
+    ("detected memory leaks", 401),
+    ("leak", 401),
+    ("out-of-memory", 789),
+    ("allocation-size-too-big", 789),
+    ("data race", 362),
+];
+
+/// Assigns a CWE ID to each reproduced crash from its crash type, using the
+/// built-in table unless the crash type is overridden.
+#[derive(Debug, Default)]
+pub struct CweClassifier {
+    overrides: HashMap<String, u32>,
+}
+
+impl CweClassifier {
+    pub fn new(overrides: HashMap<String, u32>) -> Self {
+        Self { overrides }
+    }
+
+    pub fn classify(&self, report: &CrashReport) -> Option<u32> {
+        let crash_type = report.crash_type.as_str();
+        if let Some(cwe) = self.overrides.get(crash_type) {
+            return Some(*cwe);
+        }
+
+        CWE_BY_CRASH_TYPE
+            .iter()
+            .find(|(known, _)| *known == crash_type)
+            .map(|(_, cwe)| *cwe)
+    }
+
+    /// Set the CWE of the report in `result`, if it reproduced.
+    pub fn apply(&self, result: &mut CrashTestResult) {
+        if let CrashTestResult::CrashReport(report) = result {
+            report.cwe = self.classify(report);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn report(crash_type: &str) -> CrashReport {
+        CrashReport {
+            crash_type: crash_type.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_builtin_cwe() {
+        let classifier = CweClassifier::default();
+        assert_eq!(
+            classifier.classify(&report("heap-buffer-overflow")),
+            Some(122)
+        );
+        assert_eq!(
+            classifier.classify(&report("heap-use-after-free")),
+            Some(416)
+        );
+        assert_eq!(classifier.classify(&report("double-free")), Some(415));
+        assert_eq!(classifier.classify(&report("stack-overflow")), Some(674));
+        assert_eq!(classifier.classify(&report("SEGV")), None);
+    }
+
+    #[test]
+    fn test_cwe_table_has_unique_crash_types() {
+        let crash_types: HashSet<_> = CWE_BY_CRASH_TYPE.iter().map(|(t, _)| t).collect();
+        assert_eq!(crash_types.len(), CWE_BY_CRASH_TYPE.len());
+    }
+
+    #[test]
+    fn test_cwe_overrides() {
+        let classifier = CweClassifier::new(HashMap::from([
+            ("heap-buffer-overflow".to_owned(), 787),
+            ("SEGV".to_owned(), 476),
+        ]));
+        assert_eq!(
+            classifier.classify(&report("heap-buffer-overflow")),
+            Some(787)
+        );
+        assert_eq!(classifier.classify(&report("SEGV")), Some(476));
+        assert_eq!(classifier.classify(&report("double-free")), Some(415));
+
+        let mut result = CrashTestResult::from(report("heap-use-after-free"));
+        classifier.apply(&mut result);
+        let CrashTestResult::CrashReport(report) = result else {
+            panic!("expected a crash report");
+        };
+        assert_eq!(report.cwe, Some(416));
+    }
+}
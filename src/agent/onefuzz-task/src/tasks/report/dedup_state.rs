@@ -0,0 +1,193 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Stack hashes already reported by earlier runs, so that a long-running
+//! campaign only saves one unique report per bug across every job.
+//!
+//! The state is a container of shards, each a JSON array of hashes. A task
+//! reads every shard when it starts and writes the hashes it saw for the first
+//! time to its own shard, `{task_id}.json`, every few minutes and when it
+//! stops. Since no two tasks write the same shard, concurrent tasks never
+//! overwrite each other's state, and a task can replace its own shard with
+//! everything it has seen so far, including the hashes of the shard it
+//! started with.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use onefuzz::{blob::store, syncdir::SyncedDir};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+const SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+pub struct DedupState {
+    container: SyncedDir,
+    shard: String,
+    seen: HashSet<String>,
+    new: BTreeSet<String>,
+    unsaved: bool,
+    last_saved: Instant,
+}
+
+impl DedupState {
+    /// Load the hashes in every shard of `container`.
+    pub async fn load(
+        container: SyncedDir,
+        task_id: Uuid,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Self> {
+        container
+            .init_pull(cancellation_token)
+            .await
+            .context("unable to pull dedup state")?;
+        let shard = format!("{task_id}.json");
+        let (seen, new) = load_shards(&container.local_path, &shard).await?;
+        info!("loaded {} previously reported stack hashes", seen.len());
+
+        Ok(Self {
+            container,
+            shard,
+            seen,
+            new,
+            unsaved: false,
+            last_saved: Instant::now(),
+        })
+    }
+
+    pub async fn load_optional(
+        container: Option<&SyncedDir>,
+        task_id: Uuid,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Option<Self>> {
+        match container {
+            Some(container) => Ok(Some(
+                Self::load(container.clone(), task_id, cancellation_token).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Record `hash` as seen, returning whether an earlier run reported it.
+    ///
+    /// Hashes first seen by this task are not previously reported, so repeat
+    /// crashes within a run are still deduplicated by the unique reports
+    /// container alone.
+    pub fn check(&mut self, hash: &str) -> bool {
+        if self.seen.contains(hash) {
+            return true;
+        }
+        self.unsaved |= self.new.insert(hash.to_owned());
+        false
+    }
+
+    /// Save the hashes first seen by this task, if it has seen any since the
+    /// last save and the last save was at least `SAVE_INTERVAL` ago.
+    pub async fn save_if_due(&mut self) -> Result<()> {
+        if self.last_saved.elapsed() < SAVE_INTERVAL {
+            return Ok(());
+        }
+        self.save().await
+    }
+
+    /// Replace the task's shard with the hashes first seen by this task, if it
+    /// has seen any since the last save.
+    pub async fn save(&mut self) -> Result<()> {
+        if !self.unsaved {
+            return Ok(());
+        }
+
+        let path = self.container.local_path.join(&self.shard);
+        tokio::fs::write(&path, serde_json::to_vec(&self.new)?).await?;
+        if let Some(remote_path) = &self.container.remote_path {
+            store::open(remote_path)?
+                .replace(&path, &self.shard)
+                .await
+                .context("unable to save dedup state")?;
+        }
+
+        self.unsaved = false;
+        self.last_saved = Instant::now();
+        Ok(())
+    }
+}
+
+/// The hashes in every shard in `dir`, and those in the shard `own` alone.
+async fn load_shards(dir: &Path, own: &str) -> Result<(HashSet<String>, BTreeSet<String>)> {
+    let mut seen = HashSet::new();
+    let mut own_hashes = BTreeSet::new();
+
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+
+        let data = tokio::fs::read(&path).await?;
+        match serde_json::from_slice::<Vec<String>>(&data) {
+            Ok(hashes) => {
+                if entry.file_name() == own {
+                    own_hashes.extend(hashes.iter().cloned());
+                }
+                seen.extend(hashes);
+            }
+            Err(err) => warn!("skipping invalid dedup state {}: {}", path.display(), err),
+        }
+    }
+
+    Ok((seen, own_hashes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_shards() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        tokio::fs::write(dir.path().join("a.json"), r#"["1", "2"]"#).await?;
+        tokio::fs::write(dir.path().join("b.json"), r#"["2", "3"]"#).await?;
+        tokio::fs::write(dir.path().join("c.json"), "not json").await?;
+        tokio::fs::write(dir.path().join("notes.txt"), r#"["4"]"#).await?;
+
+        let (seen, own) = load_shards(dir.path(), "b.json").await?;
+        assert_eq!(seen, HashSet::from(["1", "2", "3"].map(String::from)));
+        assert_eq!(own, BTreeSet::from(["2", "3"].map(String::from)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_replaces_own_shard() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let container = SyncedDir {
+            local_path: dir.path().to_owned(),
+            remote_path: None,
+        };
+        let task_id = Uuid::new_v4();
+        let shard = dir.path().join(format!("{task_id}.json"));
+        tokio::fs::write(&shard, r#"["1"]"#).await?;
+        tokio::fs::write(dir.path().join("other.json"), r#"["2"]"#).await?;
+
+        let token = CancellationToken::new();
+        let mut state = DedupState::load(container.clone(), task_id, &token).await?;
+        assert!(state.check("1"));
+        assert!(state.check("2"));
+        assert!(!state.check("3"));
+        state.save().await?;
+
+        // A restarted task keeps the hashes saved by its earlier run.
+        let mut state = DedupState::load(container, task_id, &token).await?;
+        assert!(!state.check("4"));
+        state.save().await?;
+
+        let saved: Vec<String> = serde_json::from_slice(&tokio::fs::read(&shard).await?)?;
+        assert_eq!(saved, ["1", "3", "4"]);
+        Ok(())
+    }
+}
@@ -8,12 +8,14 @@ use std::{
     sync::Arc,
 };
 
+use crate::tasks::report::classification::ClassificationRules;
 use crate::tasks::report::crash_report::*;
 use crate::tasks::report::dotnet::common::collect_exception_info;
 use crate::tasks::{
     config::CommonConfig,
     generic::input_poller::*,
     heartbeat::{HeartbeatSender, TaskHeartbeatClient},
+    lifecycle,
     utils::{default_bool_true, try_resolve_setup_relative_path},
 };
 use anyhow::{Context, Result};
@@ -22,6 +24,7 @@ use onefuzz::expand::Expand;
 use onefuzz::fs::set_executable;
 use onefuzz::{blob::BlobUrl, sha256, syncdir::SyncedDir};
 use onefuzz_result::job_result::TaskJobResultClient;
+use onefuzz_telemetry::LifecycleState;
 use reqwest::Url;
 use serde::Deserialize;
 use storage_queue::{Message, QueueClient};
@@ -55,6 +58,10 @@ pub struct Config {
     #[serde(default = "default_bool_true")]
     pub check_queue: bool,
 
+    /// JSON file of regex-to-label rules used to classify crash reports.
+    #[serde(default)]
+    pub classification_rules: Option<PathBuf>,
+
     #[serde(flatten)]
     pub common: CommonConfig,
 }
@@ -92,7 +99,8 @@ pub struct DotnetCrashReportTask {
 
 impl DotnetCrashReportTask {
     pub fn new(config: Config) -> Self {
-        let poller = InputPoller::new("libfuzzer-dotnet-crash-report");
+        let poller = InputPoller::new("libfuzzer-dotnet-crash-report")
+            .with_cancellation(config.common.cancellation_token.clone());
         let config = Arc::new(config);
 
         Self { config, poller }
@@ -101,7 +109,10 @@ impl DotnetCrashReportTask {
     pub async fn run(&mut self) -> Result<()> {
         info!("starting dotnet crash report task");
 
-        self.config.tools.init_pull().await?;
+        self.config
+            .tools
+            .init_pull(&self.config.common.cancellation_token)
+            .await?;
 
         set_executable(&self.config.tools.local_path).await?;
 
@@ -123,6 +134,7 @@ impl DotnetCrashReportTask {
 
         let mut processor = AsanProcessor::new(self.config.clone()).await?;
 
+        lifecycle::transition(LifecycleState::Ready);
         if let Some(crashes) = &self.config.crashes {
             self.poller.batch_process(&mut processor, crashes).await?;
         }
@@ -141,10 +153,13 @@ pub struct AsanProcessor {
     config: Arc<Config>,
     heartbeat_client: Option<TaskHeartbeatClient>,
     job_result_client: Option<TaskJobResultClient>,
+    classification_rules: Option<ClassificationRules>,
 }
 
 impl AsanProcessor {
     pub async fn new(config: Arc<Config>) -> Result<Self> {
+        let classification_rules =
+            ClassificationRules::load_optional(config.classification_rules.as_ref()).await?;
         let heartbeat_client = config.common.init_heartbeat(None).await?;
         let job_result_client = config.common.init_job_result().await?;
 
@@ -152,6 +167,7 @@ impl AsanProcessor {
             config,
             heartbeat_client,
             job_result_client,
+            classification_rules,
         })
     }
 
@@ -240,6 +256,7 @@ impl AsanProcessor {
                     minimized_stack_function_names_sha256: None,
                     minimized_stack_function_lines: None,
                     minimized_stack_function_lines_sha256: None,
+                    normalized_stack_sha256: None,
                     asan_log: None,
                     task_id,
                     job_id,
@@ -248,6 +265,13 @@ impl AsanProcessor {
                     onefuzz_version: Some(env!("ONEFUZZ_VERSION").to_owned()),
                     tool_name: Some(DOTNET_DUMP_TOOL_NAME.to_owned()),
                     tool_version: None,
+                    classification: None,
+                    source_revision: None,
+                    clusterfuzz_crash_state: None,
+                    severity: None,
+                    cwe: None,
+                    reproducibility: None,
+                    loaded_modules: None,
                 };
 
                 crash_report.into()
@@ -260,6 +284,8 @@ impl AsanProcessor {
                     task_id,
                     tries: 1,
                     error: None,
+                    source_revision: None,
+                    reproducibility: None,
                 };
 
                 no_repro.into()
@@ -274,7 +300,11 @@ impl Processor for AsanProcessor {
     async fn process(&mut self, url: Option<Url>, input: &Path) -> Result<()> {
         debug!("processing dotnet crash url:{:?} path:{:?}", url, input);
 
-        let crash_test_result = self.test_input(input, url).await?;
+        let mut crash_test_result = self.test_input(input, url).await?;
+        if let Some(rules) = &self.classification_rules {
+            rules.apply(&mut crash_test_result);
+        }
+        crash_test_result.set_source_revision(self.config.common.source_revision.as_deref());
 
         let saved = crash_test_result
             .save(
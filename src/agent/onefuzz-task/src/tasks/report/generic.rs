@@ -1,26 +1,63 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+use super::classification::ClassificationRules;
 use super::crash_report::{CrashReport, CrashTestResult, InputBlob, NoCrash};
+use super::cwe::CweClassifier;
+use super::dedup_state::DedupState;
+use super::known_crashes::{skip_known_crash, KnownCrashes};
+use super::notification::ReportNotifier;
+use super::report_metrics::{input_saved_time, ReportMetrics};
+use super::repro_script::ReproScript;
+use super::reproducibility::{ReproCheck, Reproducibility};
+use super::sarif::ReportFormat;
+use super::severity::{Severity, SeverityClassifier};
+use super::symbols::{apply_to_debugger, symbol_cache_dir, with_symbol_server};
+use super::upload_queue::{upload_queue, ReportSender};
+use super::webhook::ReportWebhook;
 use crate::tasks::{
     config::CommonConfig,
-    generic::input_poller::{CallbackImpl, InputPoller, Processor},
+    error::TaskError,
+    generic::input_poller::{
+        input_url, CallbackImpl, InputPoller, PollInterval, Processor, POLL_INTERVAL,
+    },
     heartbeat::{HeartbeatSender, TaskHeartbeatClient},
-    utils::{default_bool_true, try_resolve_setup_relative_path},
+    lifecycle,
+    utils::{
+        check_expected_exit_codes, check_input_via, check_pre_exec_transform,
+        check_stack_hash_frame_count, check_target_nice, check_target_rss_limit,
+        crash_ttl_metadata, default_bool_true, deserialize_file_mode, grant_input_dir,
+        load_seccomp_profile, normalize_permissions, place_auxiliary_files, reject_oversized_input,
+        resolve_target_user, transform_input, try_resolve_setup_relative_path,
+        verify_input_checksum,
+    },
 };
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use onefuzz::{
-    blob::BlobUrl, input_tester::Tester, machine_id::MachineIdentity, sha256, syncdir::SyncedDir,
+    auth::Secret,
+    blob::{BlobContainerUrl, BlobUrl},
+    fs::{clean_dir_except, copy_into_dir, list_files},
+    input_tester::{InputDelivery, TestResult, Tester},
+    machine_id::MachineIdentity,
+    seccomp::SeccompFilter,
+    sha256,
+    syncdir::SyncedDir,
+    target_user::TargetUser,
 };
 use onefuzz_result::job_result::TaskJobResultClient;
+use onefuzz_telemetry::LifecycleState;
 use reqwest::Url;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
+    future::Future,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 use storage_queue::{Message, QueueClient};
+use tempfile::{tempdir, TempDir};
 use uuid::Uuid;
 
 const GENERIC_TOOL_NAME: &str = "generic";
@@ -35,7 +72,17 @@ pub struct Config {
     #[serde(default)]
     pub target_env: HashMap<String, String>,
 
+    /// Queue of crashing input URLs to report as they are found, in addition
+    /// to the inputs already in `crashes`.
     pub input_queue: Option<QueueClient>,
+
+    /// Queue that inputs which could not be reported or whose reports could
+    /// not be uploaded are sent to, along with the error. If unset, a failed
+    /// report stops the task. If set, each report is uploaded before the next
+    /// input is reported, so that failed uploads are seen.
+    #[serde(default)]
+    pub dead_letter_queue: Option<QueueClient>,
+
     pub crashes: Option<SyncedDir>,
     pub reports: Option<SyncedDir>,
     pub unique_reports: Option<SyncedDir>,
@@ -56,10 +103,277 @@ pub struct Config {
     #[serde(default)]
     pub minimized_stack_depth: Option<usize>,
 
+    /// Deduplicate reports by the top this many frames of the crash's stack,
+    /// rather than by the whole stack. Fewer frames make coarser buckets.
+    /// Frames are normalized to be stable across builds, by keeping only the
+    /// function name without arguments, or, for frames without symbols, the
+    /// module file name and offset into the module.
+    #[serde(default)]
+    pub stack_hash_frame_count: Option<usize>,
+
+    /// How each input is passed to the target.
+    #[serde(default)]
+    pub input_via: InputDelivery,
+
+    /// Symbol server (symsrv or debuginfod) to fetch symbols from when
+    /// symbolizing stacks, for targets whose symbols aren't in the setup dir.
+    /// With `check_debugger`, only used on Windows.
+    #[serde(default)]
+    pub symbol_server_url: Option<Url>,
+
+    /// JSON file of regex-to-label rules used to classify crash reports.
+    #[serde(default)]
+    pub classification_rules: Option<PathBuf>,
+
+    /// Format of reproduced crash reports, in addition to the native report,
+    /// which is always written, as are no-repro reports.
+    #[serde(default)]
+    pub report_format: ReportFormat,
+
+    /// Maximum number of generated reports waiting to be uploaded. Generating
+    /// further reports blocks until there is space. Defaults to 64.
+    #[serde(default)]
+    pub upload_queue_depth: Option<usize>,
+
+    /// Prefix each uploaded report with the UTC date it is saved, as
+    /// `YYYY/MM/DD/`, so that storage lifecycle rules and queries can work per
+    /// day. Reports already saved, flat or under any date, are not saved
+    /// again. Off by default, which keeps the flat layout.
+    #[serde(default)]
+    pub report_date_partitioning: bool,
+
+    /// Mode to set on each input before it is used, such as `"0644"`, so that a
+    /// target running with dropped privileges can read it. Unix only.
+    #[serde(default, deserialize_with = "deserialize_file_mode")]
+    pub normalize_input_permissions: Option<u32>,
+
+    /// Compiled BPF seccomp profile, relative to the setup dir, to confine the
+    /// target with. Targets killed for a blocked syscall are reported as
+    /// not reproducing, with an error saying so. Linux only.
+    #[serde(default)]
+    pub seccomp_profile: Option<PathBuf>,
+
+    /// Include the ClusterFuzz crash state in reports, to match them against
+    /// testcases deduplicated by ClusterFuzz.
+    #[serde(default)]
+    pub clusterfuzz_signature: bool,
+
+    /// Run the target as this user, rather than as the agent's user, so that
+    /// it doesn't run as root. Directories the target writes to are given to
+    /// the user. Linux only.
+    #[serde(default)]
+    pub run_target_as_user: Option<String>,
+
+    /// Reproduce each crash with a new, empty working directory and temp
+    /// directory for every run of the target, so that files left behind by
+    /// earlier inputs can't mask or cause a crash. A new target process is
+    /// always started for each run. Creating and removing the directories
+    /// slows reproduction, most noticeably for fast targets with many retries.
+    #[serde(default)]
+    pub fresh_process_per_crash: bool,
+
+    /// Run the target in a working directory of its own, removing any files
+    /// it leaves there before the next input, so that files leaked by one
+    /// input can't affect later ones. `auxiliary_files` are kept. The
+    /// directory is reused rather than recreated, so this is cheaper than
+    /// `fresh_process_per_crash`, which takes precedence.
+    #[serde(default)]
+    pub clean_cwd_between_inputs: bool,
+
+    /// Seconds to wait before polling `input_queue` again after finding it
+    /// empty. Defaults to 10.
+    #[serde(default)]
+    pub poll_interval_seconds: Option<u64>,
+
+    /// If set, the wait doubles after each consecutive empty poll, up to this
+    /// many seconds, to reduce the cost of polling idle queues.
+    #[serde(default)]
+    pub max_poll_interval_seconds: Option<u64>,
+
+    /// Skip inputs which don't match the SHA-256 in their `<input>.sha256`
+    /// sidecar file, such as inputs truncated while syncing, rather than
+    /// reporting crashes on corrupt data. Inputs without a sidecar are
+    /// reported as usual, and sidecars themselves are never reported.
+    #[serde(default)]
+    pub verify_input_checksum: bool,
+
+    /// Skip inputs larger than this many bytes, rather than passing them to
+    /// the target, for targets which can't handle larger inputs and would
+    /// otherwise produce spurious crashes. Unlike libFuzzer's `-max_len`, this
+    /// applies to inputs from any source. Unset by default.
+    #[serde(default)]
+    pub reject_inputs_over_bytes: Option<u64>,
+
+    /// Run the target on up to this many of the existing `crashes` at once,
+    /// with `{input}` set to a directory holding the batch, for targets with
+    /// a high startup cost which accept a directory of inputs. A batch which
+    /// crashes is bisected to find the crashing inputs, which are reported as
+    /// usual. Inputs from `input_queue` are always run one at a time.
+    #[serde(default)]
+    pub batch_size: Option<usize>,
+
+    /// URL to POST each report's JSON to as soon as it is generated, such as
+    /// for alerting, in addition to saving it. Failed posts are retried and
+    /// logged, but never delay or fail saving reports.
+    #[serde(default)]
+    pub report_webhook: Option<Url>,
+
+    /// Key for the HMAC-SHA256 of each webhook request body, sent in the
+    /// `X-Onefuzz-Signature` header as `sha256=<hex>` so that the receiver can
+    /// authenticate reports. If unset, requests are unsigned.
+    #[serde(default)]
+    pub report_webhook_secret: Option<Secret<String>>,
+
+    /// Queue to send a message to for each crash report saved, holding the
+    /// report URL, crash type and stack hash, so that triage needn't poll the
+    /// reports container. Failed sends are retried and logged, but never
+    /// delay or fail saving reports.
+    #[serde(default)]
+    pub report_notification_queue: Option<QueueClient>,
+
+    /// Severity to assign to crashes of a type, such as
+    /// `"heap-buffer-overflow"`, or of a type and access, such as
+    /// `"heap-buffer-overflow READ"`, instead of the built-in policy.
+    #[serde(default)]
+    pub severity_overrides: HashMap<String, Severity>,
+
+    /// Include the likely CWE ID of each crash, from its crash type, in its
+    /// report's `cwe`, such as 122 for `heap-buffer-overflow`. The built-in
+    /// mapping is documented in `report::cwe`.
+    #[serde(default)]
+    pub classify_cwe: bool,
+
+    /// CWE ID to assign to crashes of a type, such as `"SEGV"`, instead of
+    /// the built-in mapping, with `classify_cwe`.
+    #[serde(default)]
+    pub cwe_overrides: HashMap<String, u32>,
+
+    /// Limit the target's virtual memory to this many megabytes, so that a
+    /// runaway allocation fails and is reported as a crash rather than
+    /// invoking the OOM killer. Linux only, and rejected elsewhere. Not usable
+    /// with sanitizer builds, which reserve far more address space than they
+    /// use.
+    #[serde(default)]
+    pub target_rss_limit_mb: Option<u64>,
+
+    /// Scheduling priority to run the target with, as a Unix nice value from
+    /// -20 to 19, so that fuzzing can yield to other work on shared machines.
+    /// Mapped to the nearest priority class on Windows. Unset by default, which
+    /// keeps the agent's own priority.
+    #[serde(default)]
+    pub target_nice: Option<i32>,
+
+    /// Exit codes the target uses to reject inputs, which are treated as clean
+    /// exits rather than crashes. Sanitizers exit with 1 by default, so listing
+    /// 1 also hides their reports unless `exitcode` is set in their options.
+    #[serde(default)]
+    pub expected_exit_codes: Vec<i32>,
+
+    /// Setup-relative files, such as config or license files, which the target
+    /// expects in its working directory. Copied into it before each run with
+    /// `fresh_process_per_crash`, and once at startup otherwise. Missing files
+    /// fail the task.
+    #[serde(default)]
+    pub auxiliary_files: Vec<PathBuf>,
+
+    /// Container of stack hashes reported by earlier runs, shared across
+    /// jobs. Crashes already seen there are saved to `reports` but not
+    /// `unique_reports`, and the hashes this task sees first are added to it
+    /// when the task stops.
+    #[serde(default)]
+    pub dedup_state_container: Option<SyncedDir>,
+
+    /// Days to keep uploaded reports before storage lifecycle rules may
+    /// delete them, set as `onefuzz_ttl_days` blob metadata. If unset, no
+    /// metadata is added.
+    #[serde(default)]
+    pub crash_ttl_days: Option<u32>,
+
+    /// Save a script that reruns the target on each reproduced crash next to
+    /// its report, as `repro.sh` on Linux or `repro.ps1` on Windows.
+    /// Environment variables that look like secrets are left for the caller
+    /// to set, rather than written to the script.
+    #[serde(default)]
+    pub generate_repro_script: bool,
+
+    /// Token in `target_options` to replace with the input path, for targets
+    /// that take `{input}` literally. If no argument contains it, the input
+    /// path is appended. Defaults to `{input}`.
+    #[serde(default)]
+    pub input_placeholder: Option<String>,
+
+    /// Seconds over which report latency and reproduction rate are
+    /// aggregated before being sent as a `report_stats` event. Defaults to
+    /// five minutes.
+    #[serde(default)]
+    pub report_metrics_window_seconds: Option<u64>,
+
+    /// Rerun each reproduced crash this many more times, without retries, and
+    /// set its report's `reproducibility` to `reproducible`, `flaky` or
+    /// `non_reproducing` from the share of reruns which crash, as described
+    /// in `report/reproducibility.rs`. Inputs which don't crash at all are
+    /// still saved to `no_repro`, flagged `non_reproducing`. Unset by default.
+    #[serde(default)]
+    pub repro_count: Option<u64>,
+
+    /// Share of `repro_count` reruns, from 0 to 1, which must crash for a
+    /// crash to be `reproducible`. Defaults to 1, every rerun.
+    #[serde(default)]
+    pub reproducible_min_ratio: Option<f64>,
+
+    /// Share of `repro_count` reruns, from 0 to 1, which must crash for a
+    /// crash which isn't `reproducible` to be `flaky` rather than
+    /// `non_reproducing`. Defaults to 0, so any crashing rerun is enough.
+    #[serde(default)]
+    pub flaky_min_ratio: Option<f64>,
+
+    /// Log each line the target writes to stdout and stderr at debug level as
+    /// it runs, as well as capturing it. Only shown at `log_level=debug`.
+    #[serde(default)]
+    pub tee_target_output: bool,
+
+    /// Record the modules, with their hashes, that the target had loaded when
+    /// it crashed, in each report's `loaded_modules`, to pin crashes to exact
+    /// dependency versions. Exact with `check_debugger` on Linux; otherwise
+    /// sampled while the target runs, so that modules loaded just before the
+    /// crash may be missed.
+    #[serde(default)]
+    pub capture_module_list: bool,
+
+    /// Container of inputs for already-filed bugs. Crashing inputs with the
+    /// same contents as one of them are skipped rather than reported. The
+    /// container is read once, when the task starts.
+    #[serde(default)]
+    pub known_crashes_container: Option<BlobContainerUrl>,
+
+    /// Only report the existing `crashes` last modified after this time, such
+    /// as `"2024-01-31T12:00:00Z"`, downloading just those, to catch up after
+    /// an outage without rescanning the whole container. Inputs from
+    /// `input_queue` are always reported.
+    #[serde(default)]
+    pub inputs_modified_since: Option<DateTime<Utc>>,
+
+    /// Command, and its arguments, to pipe each input through before passing
+    /// the output to the target, such as a decompressor for a corpus stored
+    /// compressed. Reports still name the original input. Inputs for which
+    /// the command fails or runs for over a minute are skipped. Can't be
+    /// combined with `batch_size`.
+    #[serde(default)]
+    pub pre_exec_transform: Option<Vec<String>>,
+
     #[serde(flatten)]
     pub common: CommonConfig,
 }
 
+/// Dead-lettered input, as written to `dead_letter_queue`.
+#[derive(Debug, Serialize)]
+pub struct DeadLetter {
+    pub input_url: Option<Url>,
+    /// The URL of the input, or its local path if it has none.
+    pub input: String,
+    pub error: String,
+}
+
 pub struct ReportTask {
     config: Config,
     poller: InputPoller<Message>,
@@ -67,16 +381,109 @@ pub struct ReportTask {
 
 impl ReportTask {
     pub fn new(config: Config) -> Self {
-        let poller = InputPoller::new("crash-report");
+        let poller = InputPoller::new("crash-report")
+            .with_cancellation(config.common.cancellation_token.clone());
         Self { config, poller }
     }
 
     pub async fn managed_run(&mut self) -> Result<()> {
         info!("Starting generic crash report task");
+        if let Some(batch_size) = self.config.batch_size {
+            if batch_size == 0 {
+                return Err(
+                    TaskError::Configuration(anyhow!("batch_size must be at least 1")).into(),
+                );
+            }
+            if self.config.input_via != InputDelivery::FileArg {
+                return Err(TaskError::Configuration(anyhow!(
+                    "batch_size requires inputs to be passed as a file argument"
+                ))
+                .into());
+            }
+            if self.config.pre_exec_transform.is_some() {
+                return Err(TaskError::Configuration(anyhow!(
+                    "batch_size can't be combined with pre_exec_transform"
+                ))
+                .into());
+            }
+        }
+        if self.config.input_placeholder.as_deref() == Some("") {
+            return Err(
+                TaskError::Configuration(anyhow!("input_placeholder must not be empty")).into(),
+            );
+        }
+        check_target_rss_limit(self.config.target_rss_limit_mb)?;
+        check_target_nice(self.config.target_nice, self.config.check_debugger)?;
+        check_input_via(self.config.input_via, self.config.check_debugger)?;
+        check_expected_exit_codes(&self.config.expected_exit_codes)?;
+        check_stack_hash_frame_count(self.config.stack_hash_frame_count)?;
+        check_pre_exec_transform(self.config.pre_exec_transform.as_deref())?;
+        self.poller.set_poll_interval(PollInterval::from_config(
+            POLL_INTERVAL,
+            self.config.poll_interval_seconds,
+            self.config.max_poll_interval_seconds,
+        )?);
+        let classification_rules =
+            ClassificationRules::load_optional(self.config.classification_rules.as_ref()).await?;
+        let seccomp_filter = load_seccomp_profile(
+            &self.config.common.setup_dir,
+            self.config.seccomp_profile.as_deref(),
+        )
+        .await?;
+        let target_user = resolve_target_user(self.config.run_target_as_user.as_deref())?;
+        let auxiliary_files =
+            place_auxiliary_files(&self.config.common.setup_dir, &self.config.auxiliary_files)
+                .await?;
         let heartbeat_client = self.config.common.init_heartbeat(None).await?;
         let job_result_client = self.config.common.init_job_result().await?;
+        let (report_sender, uploader) = upload_queue(
+            self.config.upload_queue_depth,
+            self.config.report_format,
+            &self.config.unique_reports,
+            &self.config.reports,
+            &self.config.no_repro,
+            self.config.common.init_job_result().await?,
+        );
+        let uploader = uploader
+            .blob_metadata(crash_ttl_metadata(self.config.crash_ttl_days))
+            .date_partitioning(self.config.report_date_partitioning)
+            .notifier(ReportNotifier::from_config(
+                self.config.report_notification_queue.as_ref(),
+            ))
+            .dedup_state(
+                DedupState::load_optional(
+                    self.config.dedup_state_container.as_ref(),
+                    self.config.common.task_id,
+                    &self.config.common.cancellation_token,
+                )
+                .await?,
+            )
+            .report_metrics(Some(ReportMetrics::from_config(
+                self.config.report_metrics_window_seconds,
+            )?));
         let mut processor =
-            GenericReportProcessor::new(&self.config, heartbeat_client, job_result_client);
+            GenericReportProcessor::new(&self.config, heartbeat_client, job_result_client)
+                .classification_rules(classification_rules)
+                .upload_queue(Some(report_sender))
+                .dead_letter_queue(self.config.dead_letter_queue.clone())
+                // Upload failures are only dead-lettered if the processor sees them.
+                .wait_for_upload(self.config.dead_letter_queue.is_some())
+                .seccomp_filter(seccomp_filter)
+                .target_user(target_user)
+                .auxiliary_files(auxiliary_files)
+                .repro_check(ReproCheck::from_config(
+                    self.config.repro_count,
+                    self.config.reproducible_min_ratio,
+                    self.config.flaky_min_ratio,
+                )?)
+                .known_crashes(
+                    KnownCrashes::load_optional(self.config.known_crashes_container.as_ref())
+                        .await?,
+                )
+                .symbol_server(self.config.symbol_server_url.as_ref())
+                .await?
+                .clean_working_dir(self.config.clean_cwd_between_inputs)
+                .await?;
 
         #[allow(clippy::manual_flatten)]
         for entry in [
@@ -89,22 +496,51 @@ impl ReportTask {
             }
         }
 
-        info!("processing existing crashes");
-        if let Some(crashes) = &self.config.crashes {
-            self.poller
-                .batch_process(&mut processor, crashes)
-                .await
-                .context("batch processing failed")?;
-        }
+        let config = &self.config;
+        let poller = &mut self.poller;
 
-        info!("processing crashes from queue");
-        if self.config.check_queue {
-            if let Some(queue) = &self.config.input_queue {
-                let callback = CallbackImpl::new(queue.clone(), processor)
-                    .context("processing from queue failed")?;
-                self.poller.run(callback).await.context("poller failed")?;
+        // The processor owns the sending half of the upload queue, so the
+        // uploader stops once the processor is dropped at the end of this block.
+        let generate_reports = async move {
+            info!("processing existing crashes");
+            match (&config.crashes, config.batch_size) {
+                (Some(crashes), Some(batch_size)) if batch_size > 1 => {
+                    poller.set_batch_dir(crashes.clone());
+                    processor
+                        .batch_process(crashes, batch_size, config.inputs_modified_since)
+                        .await
+                        .context("batch processing failed")?;
+                }
+                (Some(crashes), _) => {
+                    match config.inputs_modified_since {
+                        Some(since) => {
+                            poller
+                                .batch_process_modified_since(&mut processor, crashes, since)
+                                .await
+                        }
+                        None => poller.batch_process(&mut processor, crashes).await,
+                    }
+                    .context("batch processing failed")?;
+                }
+                (None, _) => {}
             }
-        }
+
+            info!("processing crashes from queue");
+            if config.check_queue {
+                if let Some(queue) = &config.input_queue {
+                    // The poller deletes each message once it is processed, so
+                    // its report must be uploaded by then.
+                    let processor = processor.wait_for_upload(true);
+                    let callback = CallbackImpl::new(queue.clone(), processor)
+                        .context("processing from queue failed")?;
+                    poller.run(callback).await.context("poller failed")?;
+                }
+            }
+            Ok::<_, anyhow::Error>(())
+        };
+
+        lifecycle::transition(LifecycleState::Ready);
+        futures::try_join!(generate_reports, uploader.run())?;
         Ok(())
     }
 }
@@ -112,6 +548,9 @@ impl ReportTask {
 pub struct TestInputArgs<'a> {
     pub input_url: Option<Url>,
     pub input: &'a Path,
+    /// Passed to the target instead of `input`, if set. Reports still name
+    /// `input`.
+    pub transformed_input: Option<&'a Path>,
     pub target_exe: &'a Path,
     pub target_options: &'a [String],
     pub target_env: &'a HashMap<String, String>,
@@ -124,25 +563,55 @@ pub struct TestInputArgs<'a> {
     pub check_asan_log: bool,
     pub check_debugger: bool,
     pub minimized_stack_depth: Option<usize>,
+    pub stack_hash_frame_count: Option<usize>,
     pub machine_identity: MachineIdentity,
+    pub input_via: InputDelivery,
+    pub seccomp_filter: Option<&'a SeccompFilter>,
+    pub target_user: Option<&'a TargetUser>,
+    pub fresh_state: bool,
+    pub working_dir: Option<&'a Path>,
+    pub auxiliary_files: &'a [PathBuf],
+    pub expected_exit_codes: &'a [i32],
+    pub memory_limit_mb: Option<u64>,
+    pub nice: Option<i32>,
+    pub input_placeholder: Option<&'a str>,
+    pub tee_output: bool,
+    pub capture_module_list: bool,
+}
+
+impl<'a> TestInputArgs<'a> {
+    fn tester(&self) -> Tester<'a> {
+        Tester::new(
+            self.setup_dir,
+            self.extra_setup_dir,
+            self.target_exe,
+            self.target_options,
+            self.target_env,
+            self.machine_identity.clone(),
+        )
+        .check_asan_log(self.check_asan_log)
+        .check_debugger(self.check_debugger)
+        .check_retry_count(self.check_retry_count)
+        .input_delivery(self.input_via)
+        .set_optional(self.seccomp_filter.cloned(), Tester::seccomp_filter)
+        .set_optional(self.target_user.cloned(), Tester::target_user)
+        .fresh_state(self.fresh_state)
+        .set_optional(self.working_dir, Tester::working_dir)
+        .auxiliary_files(self.auxiliary_files)
+        .expected_exit_codes(self.expected_exit_codes)
+        .set_optional(self.memory_limit_mb, Tester::memory_limit_mb)
+        .set_optional(self.nice, Tester::nice)
+        .set_optional(self.input_placeholder, Tester::input_placeholder)
+        .tee_output(self.tee_output)
+        .capture_module_list(self.capture_module_list)
+        .set_optional(self.target_timeout, |tester, timeout| {
+            tester.timeout(timeout)
+        })
+    }
 }
 
 pub async fn test_input(args: TestInputArgs<'_>) -> Result<CrashTestResult> {
-    let extra_setup_dir = args.extra_setup_dir;
-    let tester = Tester::new(
-        args.setup_dir,
-        extra_setup_dir,
-        args.target_exe,
-        args.target_options,
-        args.target_env,
-        args.machine_identity.clone(),
-    )
-    .check_asan_log(args.check_asan_log)
-    .check_debugger(args.check_debugger)
-    .check_retry_count(args.check_retry_count)
-    .set_optional(args.target_timeout, |tester, timeout| {
-        tester.timeout(timeout)
-    });
+    let tester = args.tester();
 
     let input_sha256 = sha256::digest_file(args.input).await?;
     let task_id = args.task_id;
@@ -152,10 +621,12 @@ pub async fn test_input(args: TestInputArgs<'_>) -> Result<CrashTestResult> {
         .and_then(|u| BlobUrl::new(u).ok())
         .map(InputBlob::from);
 
-    let test_report = tester.test_input(args.input).await?;
+    let test_report = tester
+        .test_input(args.transformed_input.unwrap_or(args.input))
+        .await?;
 
     if let Some(crash_log) = test_report.crash_log {
-        let crash_report = CrashReport::new(
+        let mut crash_report = CrashReport::new(
             crash_log,
             task_id,
             job_id,
@@ -163,10 +634,12 @@ pub async fn test_input(args: TestInputArgs<'_>) -> Result<CrashTestResult> {
             input_blob,
             input_sha256,
             args.minimized_stack_depth,
+            args.stack_hash_frame_count,
             GENERIC_TOOL_NAME.into(),
             env!("ONEFUZZ_VERSION").to_string(),
             env!("ONEFUZZ_VERSION").to_string(),
         );
+        crash_report.loaded_modules = test_report.loaded_modules;
         Ok(CrashTestResult::CrashReport(Box::new(crash_report)))
     } else {
         let no_repro = NoCrash {
@@ -177,6 +650,8 @@ pub async fn test_input(args: TestInputArgs<'_>) -> Result<CrashTestResult> {
             job_id,
             tries: 1 + args.check_retry_count,
             error: test_report.error.map(|e| format!("{e}")),
+            source_revision: None,
+            reproducibility: None,
         };
 
         Ok(CrashTestResult::NoRepro(Box::new(no_repro)))
@@ -187,6 +662,21 @@ pub struct GenericReportProcessor<'a> {
     config: &'a Config,
     heartbeat_client: Option<TaskHeartbeatClient>,
     job_result_client: Option<TaskJobResultClient>,
+    classification_rules: Option<ClassificationRules>,
+    severity: SeverityClassifier,
+    cwe: Option<CweClassifier>,
+    upload_queue: Option<ReportSender>,
+    webhook: Option<ReportWebhook>,
+    notifier: Option<ReportNotifier>,
+    dead_letter_queue: Option<QueueClient>,
+    seccomp_filter: Option<SeccompFilter>,
+    target_user: Option<TargetUser>,
+    auxiliary_files: Vec<PathBuf>,
+    target_env: HashMap<String, String>,
+    report_metrics: Option<ReportMetrics>,
+    repro_check: Option<ReproCheck>,
+    known_crashes: Option<KnownCrashes>,
+    working_dir: Option<TempDir>,
 }
 
 impl<'a> GenericReportProcessor<'a> {
@@ -199,13 +689,139 @@ impl<'a> GenericReportProcessor<'a> {
             config,
             heartbeat_client,
             job_result_client,
+            classification_rules: None,
+            severity: SeverityClassifier::new(config.severity_overrides.clone()),
+            cwe: config
+                .classify_cwe
+                .then(|| CweClassifier::new(config.cwe_overrides.clone())),
+            upload_queue: None,
+            webhook: ReportWebhook::from_config(
+                config.report_webhook.as_ref(),
+                config.report_webhook_secret.as_ref(),
+            ),
+            notifier: ReportNotifier::from_config(config.report_notification_queue.as_ref()),
+            dead_letter_queue: None,
+            seccomp_filter: None,
+            target_user: None,
+            auxiliary_files: Vec::new(),
+            target_env: config.target_env.clone(),
+            report_metrics: None,
+            repro_check: None,
+            known_crashes: None,
+            working_dir: None,
         }
     }
 
+    pub fn classification_rules(mut self, rules: Option<ClassificationRules>) -> Self {
+        self.classification_rules = rules;
+        self
+    }
+
+    /// Queue reports for upload, rather than saving each one before returning.
+    pub fn upload_queue(mut self, upload_queue: Option<ReportSender>) -> Self {
+        self.upload_queue = upload_queue;
+        self
+    }
+
+    /// Wait for each queued report to be uploaded before returning. See
+    /// [`ReportSender::wait_for_upload`].
+    pub fn wait_for_upload(mut self, wait_for_upload: bool) -> Self {
+        self.upload_queue = self
+            .upload_queue
+            .map(|upload_queue| upload_queue.wait_for_upload(wait_for_upload));
+        self
+    }
+
+    /// Send inputs which fail to be reported to `dead_letter_queue`, rather
+    /// than returning the error. With `wait_for_upload`, so are inputs whose
+    /// reports fail to upload.
+    pub fn dead_letter_queue(mut self, dead_letter_queue: Option<QueueClient>) -> Self {
+        self.dead_letter_queue = dead_letter_queue;
+        self
+    }
+
+    pub fn seccomp_filter(mut self, seccomp_filter: Option<SeccompFilter>) -> Self {
+        self.seccomp_filter = seccomp_filter;
+        self
+    }
+
+    pub fn target_user(mut self, target_user: Option<TargetUser>) -> Self {
+        self.target_user = target_user;
+        self
+    }
+
+    /// Copy the resolved `auxiliary_files` into each fresh working directory.
+    pub fn auxiliary_files(mut self, auxiliary_files: Vec<PathBuf>) -> Self {
+        self.auxiliary_files = auxiliary_files;
+        self
+    }
+
+    /// Aggregate report latency and reproduction rate in `report_metrics`, for
+    /// reports saved directly. The uploader records reports sent to the upload
+    /// queue.
+    pub fn report_metrics(mut self, report_metrics: Option<ReportMetrics>) -> Self {
+        self.report_metrics = report_metrics;
+        self
+    }
+
+    /// Rerun reproduced crashes to set their `reproducibility`.
+    pub fn repro_check(mut self, repro_check: Option<ReproCheck>) -> Self {
+        self.repro_check = repro_check;
+        self
+    }
+
+    /// Skip inputs in `known_crashes`.
+    pub fn known_crashes(mut self, known_crashes: Option<KnownCrashes>) -> Self {
+        self.known_crashes = known_crashes;
+        self
+    }
+
+    /// Run the target in a directory of its own, holding only the
+    /// `auxiliary_files`, which is cleaned before each input. Call after
+    /// setting the `auxiliary_files` and `target_user`.
+    pub async fn clean_working_dir(mut self, clean: bool) -> Result<GenericReportProcessor<'a>> {
+        if clean {
+            let dir = tempdir()?;
+            if let Some(user) = &self.target_user {
+                user.grant_dir(dir.path())?;
+            }
+            copy_into_dir(&self.auxiliary_files, dir.path()).await?;
+            self.working_dir = Some(dir);
+        }
+        Ok(self)
+    }
+
+    /// Remove files left in the target's working directory by earlier inputs.
+    async fn reset_working_dir(&self) -> Result<()> {
+        if let Some(dir) = &self.working_dir {
+            clean_dir_except(dir.path(), &self.auxiliary_files)
+                .await
+                .context("cleaning target working directory failed")?;
+        }
+        Ok(())
+    }
+
+    /// Fetch symbols for the target from `symbol_server`, if set.
+    pub async fn symbol_server(
+        mut self,
+        symbol_server: Option<&Url>,
+    ) -> Result<GenericReportProcessor<'a>> {
+        if symbol_server.is_some() {
+            let cache_dir = symbol_cache_dir()?;
+            tokio::fs::create_dir_all(&cache_dir).await?;
+            self.target_env = with_symbol_server(&self.target_env, symbol_server, &cache_dir);
+            if self.config.check_debugger {
+                apply_to_debugger(&self.target_env);
+            }
+        }
+        Ok(self)
+    }
+
     pub async fn test_input(
         &self,
         input_url: Option<Url>,
         input: &Path,
+        transformed_input: Option<&Path>,
     ) -> Result<CrashTestResult> {
         self.heartbeat_client.alive();
 
@@ -213,13 +829,77 @@ impl<'a> GenericReportProcessor<'a> {
             try_resolve_setup_relative_path(&self.config.common.setup_dir, &self.config.target_exe)
                 .await?;
 
+        self.reset_working_dir().await?;
+        let mut args = self.test_input_args(input_url, input, &target_exe);
+        args.transformed_input = transformed_input;
+        test_input(args).await.context("test input failed")
+    }
+
+    /// Rerun the crash in `report`, if it reproduced, as many times as
+    /// `repro_check` says, and set its `reproducibility`. Inputs which didn't
+    /// reproduce are flagged `non_reproducing` without being rerun.
+    async fn check_reproducibility(
+        &self,
+        report: &mut CrashTestResult,
+        input: &Path,
+        transformed_input: Option<&Path>,
+    ) -> Result<()> {
+        let Some(repro_check) = self.repro_check else {
+            return Ok(());
+        };
+        let crash_report = match report {
+            CrashTestResult::CrashReport(crash_report) => crash_report,
+            CrashTestResult::NoRepro(no_repro) => {
+                no_repro.reproducibility = Some(Reproducibility::NonReproducing);
+                return Ok(());
+            }
+        };
+
+        let target_exe =
+            try_resolve_setup_relative_path(&self.config.common.setup_dir, &self.config.target_exe)
+                .await?;
+        let mut crashes = 0;
+        for _ in 0..repro_check.attempts {
+            self.heartbeat_client.alive();
+            self.reset_working_dir().await?;
+            let mut args = self.test_input_args(None, input, &target_exe);
+            args.transformed_input = transformed_input;
+            args.check_retry_count = 0;
+            if args
+                .tester()
+                .test_input(args.transformed_input.unwrap_or(input))
+                .await?
+                .crash_log
+                .is_some()
+            {
+                crashes += 1;
+            }
+        }
+
+        crash_report.reproducibility = Some(repro_check.verdict(crashes));
+        debug!(
+            "{} crashed {} of {} reruns",
+            input.display(),
+            crashes,
+            repro_check.attempts
+        );
+        Ok(())
+    }
+
+    fn test_input_args<'b>(
+        &'b self,
+        input_url: Option<Url>,
+        input: &'b Path,
+        target_exe: &'b Path,
+    ) -> TestInputArgs<'b> {
         let extra_setup_dir = self.config.common.extra_setup_dir.as_deref();
-        let args = TestInputArgs {
+        TestInputArgs {
             input_url,
             input,
-            target_exe: &target_exe,
+            transformed_input: None,
+            target_exe,
             target_options: &self.config.target_options,
-            target_env: &self.config.target_env,
+            target_env: &self.target_env,
             setup_dir: &self.config.common.setup_dir,
             extra_setup_dir,
             task_id: self.config.common.task_id,
@@ -229,28 +909,459 @@ impl<'a> GenericReportProcessor<'a> {
             check_asan_log: self.config.check_asan_log,
             check_debugger: self.config.check_debugger,
             minimized_stack_depth: self.config.minimized_stack_depth,
+            stack_hash_frame_count: self.config.stack_hash_frame_count,
             machine_identity: self.config.common.machine_identity.clone(),
-        };
-        test_input(args).await.context("test input failed")
+            input_via: self.config.input_via,
+            seccomp_filter: self.seccomp_filter.as_ref(),
+            target_user: self.target_user.as_ref(),
+            fresh_state: self.config.fresh_process_per_crash,
+            working_dir: self.working_dir.as_ref().map(|dir| dir.path()),
+            auxiliary_files: &self.auxiliary_files,
+            expected_exit_codes: &self.config.expected_exit_codes,
+            memory_limit_mb: self.config.target_rss_limit_mb,
+            nice: self.config.target_nice,
+            input_placeholder: self.config.input_placeholder.as_deref(),
+            tee_output: self.config.tee_target_output,
+            capture_module_list: self.config.capture_module_list,
+        }
     }
 }
 
 #[async_trait]
 impl<'a> Processor for GenericReportProcessor<'a> {
     async fn process(&mut self, url: Option<Url>, input: &Path) -> Result<()> {
+        let result = self.report(url.clone(), input).await;
+        match (result, &self.dead_letter_queue) {
+            (Err(err), Some(dead_letter_queue)) => {
+                let source = match &url {
+                    Some(url) => url.to_string(),
+                    None => input.to_string_lossy().into_owned(),
+                };
+                warn!(
+                    "dead-lettering input {} after failed report: {:?}",
+                    source, err
+                );
+                let dead_letter = DeadLetter {
+                    input_url: url,
+                    input: source,
+                    error: format!("{err:?}"),
+                };
+                dead_letter_queue
+                    .enqueue(dead_letter)
+                    .await
+                    .context("dead-lettering input failed")
+            }
+            (result, _) => result,
+        }
+    }
+}
+
+impl<'a> GenericReportProcessor<'a> {
+    async fn report(&mut self, url: Option<Url>, input: &Path) -> Result<()> {
         debug!("generating crash report for: {}", input.display());
-        let report = self
-            .test_input(url, input)
+        let input_saved = input_saved_time(input).await;
+        if !self.prepare_input(url.as_ref(), input).await? {
+            return Ok(());
+        }
+        let transformed = match &self.config.pre_exec_transform {
+            Some(transform) => {
+                match transform_input(&self.config.common.setup_dir, transform, input).await? {
+                    Some(transformed) => {
+                        grant_input_dir(self.target_user.as_ref(), transformed.path())?;
+                        Some(transformed)
+                    }
+                    None => return Ok(()),
+                }
+            }
+            None => None,
+        };
+        let mut report = self
+            .test_input(url, input, transformed.as_ref().map(|t| t.path()))
             .await
             .context("test input failed")?;
-        report
-            .save(
+        self.check_reproducibility(&mut report, input, transformed.as_ref().map(|t| t.path()))
+            .await?;
+        self.save_report(report, input_saved).await
+    }
+
+    /// Get `input`, downloaded from `url` if set, ready to pass to the target,
+    /// returning `false` if it should be skipped instead.
+    async fn prepare_input(&self, url: Option<&Url>, input: &Path) -> Result<bool> {
+        if !verify_input_checksum(input, url, self.config.verify_input_checksum).await? {
+            return Ok(false);
+        }
+        if reject_oversized_input(input, self.config.reject_inputs_over_bytes).await? {
+            return Ok(false);
+        }
+        if skip_known_crash(self.known_crashes.as_ref(), input).await? {
+            return Ok(false);
+        }
+        normalize_permissions(input, self.config.normalize_input_permissions).await?;
+        grant_input_dir(self.target_user.as_ref(), input)?;
+        Ok(true)
+    }
+
+    /// Save `report`, of an input saved at `input_saved`, recording it in the
+    /// report metrics once it is uploaded.
+    async fn save_report(
+        &mut self,
+        mut report: CrashTestResult,
+        input_saved: Option<SystemTime>,
+    ) -> Result<()> {
+        if let Some(rules) = &self.classification_rules {
+            rules.apply(&mut report);
+        }
+        self.severity.apply(&mut report);
+        if let Some(cwe) = &self.cwe {
+            cwe.apply(&mut report);
+        }
+        report.set_source_revision(self.config.common.source_revision.as_deref());
+        if self.config.clusterfuzz_signature {
+            if let CrashTestResult::CrashReport(crash_report) = &mut report {
+                crash_report.clusterfuzz_crash_state = Some(crash_report.clusterfuzz_crash_state());
+            }
+        }
+        if let Some(webhook) = &self.webhook {
+            webhook.notify(&report);
+        }
+        if let CrashTestResult::CrashReport(crash_report) = &report {
+            self.upload_repro_script(crash_report).await;
+        }
+        if let Some(upload_queue) = &self.upload_queue {
+            return upload_queue.send(report, input_saved).await;
+        }
+        let report_url = report
+            .save_with_format(
+                self.config.report_format,
                 &self.config.unique_reports,
                 &self.config.reports,
                 &self.config.no_repro,
                 &self.job_result_client,
+                &crash_ttl_metadata(self.config.crash_ttl_days),
+                self.config.report_date_partitioning,
             )
             .await
-            .context("saving report failed")
+            .context("saving report failed")?;
+        if let Some(notifier) = &self.notifier {
+            notifier.notify(&report, report_url);
+        }
+        if let Some(report_metrics) = &mut self.report_metrics {
+            let reproduced = matches!(report, CrashTestResult::CrashReport(_));
+            report_metrics.record(input_saved, reproduced);
+        }
+        Ok(())
+    }
+
+    async fn upload_repro_script(&self, report: &CrashReport) {
+        if !self.config.generate_repro_script {
+            return;
+        }
+
+        let target_options = match self.config.input_placeholder.as_deref() {
+            Some(placeholder) => {
+                let mut options: Vec<_> = self
+                    .config
+                    .target_options
+                    .iter()
+                    .map(|arg| arg.replace(placeholder, "{input}"))
+                    .collect();
+                if !self
+                    .config
+                    .target_options
+                    .iter()
+                    .any(|arg| arg.contains(placeholder))
+                {
+                    options.push("{input}".to_owned());
+                }
+                options
+            }
+            None => self.config.target_options.clone(),
+        };
+        let script = ReproScript {
+            target_exe: &report.executable,
+            target_options: &target_options,
+            target_env: &self.target_env,
+            setup_dir: &self.config.common.setup_dir,
+            extra_setup_dir: self.config.common.extra_setup_dir.as_deref(),
+            machine_identity: &self.config.common.machine_identity,
+        };
+        if let Err(err) = script
+            .upload(report, &self.config.reports, &self.config.unique_reports)
+            .await
+        {
+            warn!("unable to save repro script: {:?}", err);
+        }
+    }
+
+    /// Report the inputs directly within `crashes`, running the target on up
+    /// to `batch_size` of them at once. If `modified_since` is set, only the
+    /// inputs last modified after it are pulled and reported.
+    pub async fn batch_process(
+        &mut self,
+        crashes: &SyncedDir,
+        batch_size: usize,
+        modified_since: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let files = match modified_since {
+            Some(since) => {
+                crashes
+                    .pull_modified_since(since, &self.config.common.cancellation_token)
+                    .await?
+            }
+            None => {
+                crashes
+                    .init_pull(&self.config.common.cancellation_token)
+                    .await
+                    .with_context(|| format!("pulling crashes {}", crashes.local_path.display()))?;
+                list_files(&crashes.local_path).await?
+            }
+        };
+
+        let mut inputs = Vec::new();
+        for input in files {
+            // Sidecars are pulled along with the inputs.
+            if self.prepare_input(None, &input).await? {
+                inputs.push(input);
+            }
+        }
+        inputs.sort();
+
+        info!(
+            "batch processing {} inputs in batches of {}: {}",
+            inputs.len(),
+            batch_size,
+            crashes.local_path.display()
+        );
+        for batch in inputs.chunks(batch_size) {
+            if self.config.common.cancellation_token.is_cancelled() {
+                info!("batch processing cancelled");
+                break;
+            }
+            self.report_batch(crashes, batch.to_vec()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Report `batch`, bisecting it until each input that crashes the target
+    /// has been run on its own. Inputs in a part of the batch which doesn't
+    /// crash are reported as not reproducing, without being run alone.
+    async fn report_batch(&mut self, crashes: &SyncedDir, batch: Vec<PathBuf>) -> Result<()> {
+        let this = &*self;
+        let bisection =
+            bisect_batch(batch, |part| async move { this.test_batch(&part).await }).await?;
+
+        for input in &bisection.alone {
+            let url = input_url(crashes, input)?;
+            self.process(url, input).await?;
+        }
+        for (part, error) in &bisection.no_crash {
+            for input in part {
+                let url = input_url(crashes, input)?;
+                self.save_batch_no_repro(url, input, error.clone()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn test_batch(&self, batch: &[PathBuf]) -> Result<TestResult> {
+        self.heartbeat_client.alive();
+
+        let batch_dir = tempdir()?;
+        for input in batch {
+            if let Some(file_name) = input.file_name() {
+                tokio::fs::copy(input, batch_dir.path().join(file_name)).await?;
+            }
+        }
+        if let Some(user) = &self.target_user {
+            user.grant_dir(batch_dir.path())?;
+        }
+
+        let target_exe =
+            try_resolve_setup_relative_path(&self.config.common.setup_dir, &self.config.target_exe)
+                .await?;
+        self.reset_working_dir().await?;
+        let args = self.test_input_args(None, batch_dir.path(), &target_exe);
+        args.tester()
+            .test_input(batch_dir.path())
+            .await
+            .with_context(|| format!("testing batch of {} inputs failed", batch.len()))
+    }
+
+    async fn save_batch_no_repro(
+        &mut self,
+        input_url: Option<Url>,
+        input: &Path,
+        error: Option<String>,
+    ) -> Result<()> {
+        let input_saved = input_saved_time(input).await;
+        let target_exe =
+            try_resolve_setup_relative_path(&self.config.common.setup_dir, &self.config.target_exe)
+                .await?;
+        let no_repro = NoCrash {
+            input_blob: input_url
+                .and_then(|u| BlobUrl::new(u).ok())
+                .map(InputBlob::from),
+            input_sha256: sha256::digest_file(input).await?,
+            executable: target_exe,
+            task_id: self.config.common.task_id,
+            job_id: self.config.common.job_id,
+            tries: 1 + self.config.check_retry_count,
+            error,
+            source_revision: None,
+            reproducibility: self.repro_check.map(|_| Reproducibility::NonReproducing),
+        };
+        self.save_report(CrashTestResult::NoRepro(Box::new(no_repro)), input_saved)
+            .await
+    }
+}
+
+/// The parts of a batch of inputs, once bisected.
+#[derive(Debug, PartialEq)]
+struct Bisection<T> {
+    /// Inputs which may crash the target, to run on their own.
+    alone: Vec<T>,
+
+    /// Parts of the batch which didn't crash the target, with the error from
+    /// testing each, if any.
+    no_crash: Vec<(Vec<T>, Option<String>)>,
+}
+
+/// Split `batch` in half, testing each part with `test`, until each part
+/// either doesn't crash the target or is a single input. Single inputs are not
+/// tested, since they are then run on their own anyway.
+async fn bisect_batch<T, F, Fut>(batch: Vec<T>, mut test: F) -> Result<Bisection<T>>
+where
+    T: Clone,
+    F: FnMut(Vec<T>) -> Fut,
+    Fut: Future<Output = Result<TestResult>>,
+{
+    let mut bisection = Bisection {
+        alone: vec![],
+        no_crash: vec![],
+    };
+    let mut pending = vec![batch];
+    while let Some(batch) = pending.pop() {
+        if batch.len() == 1 {
+            bisection.alone.extend(batch);
+            continue;
+        }
+        if batch.is_empty() {
+            continue;
+        }
+
+        let result = test(batch.clone()).await?;
+        if result.crash_log.is_some() {
+            debug!("batch of {} inputs crashed, bisecting", batch.len());
+            let (left, right) = batch.split_at(batch.len() / 2);
+            pending.push(right.to_vec());
+            pending.push(left.to_vec());
+            continue;
+        }
+
+        let error = result.error.map(|e| format!("{e}"));
+        bisection.no_crash.push((batch, error));
+    }
+
+    Ok(bisection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stacktrace_parser::CrashLog;
+    use std::cell::RefCell;
+
+    /// Bisect `batch` against a target which crashes on any of `culprits`,
+    /// returning the bisection and the parts tested.
+    async fn bisect(batch: &[u32], culprits: &[u32]) -> Result<(Bisection<u32>, Vec<Vec<u32>>)> {
+        let tested = RefCell::new(vec![]);
+        let bisection = bisect_batch(batch.to_vec(), |part| {
+            let crashed = part.iter().any(|input| culprits.contains(input));
+            tested.borrow_mut().push(part);
+            async move {
+                Ok(TestResult {
+                    crash_log: crashed.then(CrashLog::default),
+                    error: None,
+                    loaded_modules: None,
+                })
+            }
+        })
+        .await?;
+        Ok((bisection, tested.into_inner()))
+    }
+
+    #[tokio::test]
+    async fn test_bisect_batch_without_crash() -> Result<()> {
+        let (bisection, tested) = bisect(&[1, 2, 3, 4], &[]).await?;
+        assert_eq!(bisection.alone, Vec::<u32>::new());
+        assert_eq!(bisection.no_crash, [(vec![1, 2, 3, 4], None)]);
+        assert_eq!(tested, [vec![1, 2, 3, 4]]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bisect_batch_finds_culprits() -> Result<()> {
+        let (bisection, tested) = bisect(&[1, 2, 3, 4], &[2]).await?;
+        assert_eq!(bisection.alone, [1, 2]);
+        assert_eq!(bisection.no_crash, [(vec![3, 4], None)]);
+        assert_eq!(tested, [vec![1, 2, 3, 4], vec![1, 2], vec![3, 4]]);
+
+        let (bisection, tested) = bisect(&[1, 2, 3, 4, 5], &[5]).await?;
+        assert_eq!(bisection.alone, [3, 4, 5]);
+        assert_eq!(bisection.no_crash, [(vec![1, 2], None)]);
+        assert_eq!(
+            tested,
+            [vec![1, 2, 3, 4, 5], vec![1, 2], vec![3, 4, 5], vec![4, 5]]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bisect_batch_reports_each_input_once() -> Result<()> {
+        let batch: Vec<u32> = (0..37).collect();
+        let (bisection, _) = bisect(&batch, &[0, 13, 14, 36]).await?;
+        for culprit in [0, 13, 14, 36] {
+            assert!(bisection.alone.contains(&culprit));
+        }
+
+        let mut inputs = bisection.alone.clone();
+        for (part, _) in &bisection.no_crash {
+            inputs.extend(part);
+        }
+        inputs.sort();
+        assert_eq!(inputs, batch);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bisect_batch_single_input() -> Result<()> {
+        let (bisection, tested) = bisect(&[7], &[7]).await?;
+        assert_eq!(bisection.alone, [7]);
+        assert!(bisection.no_crash.is_empty());
+        assert!(tested.is_empty());
+
+        let (bisection, tested) = bisect(&[], &[]).await?;
+        assert_eq!(bisection.alone, Vec::<u32>::new());
+        assert!(bisection.no_crash.is_empty() && tested.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bisect_batch_keeps_error() -> Result<()> {
+        let bisection = bisect_batch(vec![1, 2], |_| async {
+            Ok(TestResult {
+                crash_log: None,
+                error: Some(anyhow!("target timed out")),
+                loaded_modules: None,
+            })
+        })
+        .await?;
+        assert_eq!(
+            bisection.no_crash,
+            [(vec![1, 2], Some("target timed out".to_owned()))]
+        );
+        Ok(())
     }
 }
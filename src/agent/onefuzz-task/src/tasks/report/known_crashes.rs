@@ -0,0 +1,93 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Inputs for bugs that are already filed, so that crashes on them aren't
+//! reported again. This complements stack hash deduplication, catching repeat
+//! inputs even when their stacks differ between builds.
+//!
+//! Only the SHA-256 of each known input is kept, so memory use doesn't grow
+//! with the size of the inputs.
+
+use std::{collections::HashSet, path::Path};
+
+use anyhow::{Context, Result};
+use onefuzz::{
+    blob::{store, BlobContainerUrl},
+    sha256,
+};
+
+pub struct KnownCrashes {
+    hashes: HashSet<String>,
+}
+
+impl KnownCrashes {
+    /// Hash every input in `container`, downloading them one at a time.
+    pub async fn load(container: &BlobContainerUrl) -> Result<Self> {
+        let store = store::open(container)?;
+        let temp_dir = tempfile::tempdir()?;
+        let temp = temp_dir.path().join("input");
+
+        let mut hashes = HashSet::new();
+        for name in store.list().await.context("unable to list known crashes")? {
+            store
+                .download(&name, &temp)
+                .await
+                .with_context(|| format!("unable to download known crash: {name}"))?;
+            hashes.insert(sha256::digest_file(&temp).await?);
+        }
+        info!("loaded {} known crashing inputs", hashes.len());
+
+        Ok(Self { hashes })
+    }
+
+    pub async fn load_optional(container: Option<&BlobContainerUrl>) -> Result<Option<Self>> {
+        match container {
+            Some(container) => Ok(Some(Self::load(container).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether `input` has the same contents as a known crashing input.
+    pub async fn contains(&self, input: &Path) -> Result<bool> {
+        let hash = sha256::digest_file(input).await?;
+        Ok(self.hashes.contains(&hash))
+    }
+}
+
+/// Whether `input` should be skipped, as a known crash.
+pub async fn skip_known_crash(known: Option<&KnownCrashes>, input: &Path) -> Result<bool> {
+    let Some(known) = known else {
+        return Ok(false);
+    };
+    if known.contains(input).await? {
+        info!("skipping known crashing input: {}", input.display());
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_known_crashes() -> Result<()> {
+        let known = tempfile::tempdir()?;
+        std::fs::create_dir(known.path().join("issue-1"))?;
+        std::fs::write(known.path().join("issue-1").join("crash"), "known")?;
+        let container = BlobContainerUrl::Path(known.path().to_owned());
+        let known = KnownCrashes::load(&container).await?;
+
+        let inputs = tempfile::tempdir()?;
+        let renamed = inputs.path().join("crash-abc");
+        std::fs::write(&renamed, "known")?;
+        let new = inputs.path().join("crash-def");
+        std::fs::write(&new, "new")?;
+
+        assert!(skip_known_crash(Some(&known), &renamed).await?);
+        assert!(!skip_known_crash(Some(&known), &new).await?);
+        assert!(!skip_known_crash(None, &renamed).await?);
+
+        Ok(())
+    }
+}
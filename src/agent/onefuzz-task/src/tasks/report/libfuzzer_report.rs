@@ -1,19 +1,48 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+use super::classification::ClassificationRules;
 use super::crash_report::*;
+use super::cwe::CweClassifier;
+use super::dedup_state::DedupState;
+use super::known_crashes::{skip_known_crash, KnownCrashes};
+use super::notification::ReportNotifier;
+use super::report_metrics::{input_saved_time, ReportMetrics};
+use super::repro_script::ReproScript;
+use super::reproducibility::{ReproCheck, Reproducibility};
+use super::sarif::ReportFormat;
+use super::severity::{Severity, SeverityClassifier};
+use super::symbols::{symbol_cache_dir, with_symbol_server};
+use super::upload_queue::{upload_queue, ReportSender};
+use super::webhook::ReportWebhook;
 use crate::tasks::{
     config::CommonConfig,
+    fuzz::libfuzzer::common::{add_rss_limit, check_rss_limit},
     generic::input_poller::*,
     heartbeat::{HeartbeatSender, TaskHeartbeatClient},
-    utils::{default_bool_true, try_resolve_setup_relative_path},
+    lifecycle,
+    utils::{
+        check_pre_exec_transform, check_stack_hash_frame_count, check_target_nice,
+        check_target_rss_limit, crash_ttl_metadata, default_bool_true, deserialize_file_mode,
+        grant_input_dir, load_seccomp_profile, normalize_permissions, place_auxiliary_files,
+        reject_oversized_input, resolve_target_user, transform_input,
+        try_resolve_setup_relative_path, verify_input_checksum,
+    },
 };
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use onefuzz::{
-    blob::BlobUrl, libfuzzer::LibFuzzer, machine_id::MachineIdentity, sha256, syncdir::SyncedDir,
+    auth::Secret,
+    blob::{BlobContainerUrl, BlobUrl},
+    libfuzzer::LibFuzzer,
+    machine_id::MachineIdentity,
+    seccomp::SeccompFilter,
+    sha256,
+    syncdir::SyncedDir,
+    target_user::TargetUser,
 };
 use onefuzz_result::job_result::TaskJobResultClient;
+use onefuzz_telemetry::LifecycleState;
 use reqwest::Url;
 use serde::Deserialize;
 use std::{
@@ -47,9 +76,232 @@ pub struct Config {
     #[serde(default)]
     pub minimized_stack_depth: Option<usize>,
 
+    /// Deduplicate reports by the top this many frames of the crash's stack,
+    /// rather than by the whole stack. Fewer frames make coarser buckets.
+    /// Frames are normalized to be stable across builds, by keeping only the
+    /// function name without arguments, or, for frames without symbols, the
+    /// module file name and offset into the module.
+    #[serde(default)]
+    pub stack_hash_frame_count: Option<usize>,
+
     #[serde(default = "default_bool_true")]
     pub check_queue: bool,
 
+    /// Symbol server (symsrv or debuginfod) to fetch symbols from when
+    /// symbolizing stacks, for targets whose symbols aren't in the setup dir.
+    #[serde(default)]
+    pub symbol_server_url: Option<Url>,
+
+    /// JSON file of regex-to-label rules used to classify crash reports.
+    #[serde(default)]
+    pub classification_rules: Option<PathBuf>,
+
+    /// Format of reproduced crash reports, in addition to the native report,
+    /// which is always written, as are no-repro reports.
+    #[serde(default)]
+    pub report_format: ReportFormat,
+
+    /// Maximum number of generated reports waiting to be uploaded. Generating
+    /// further reports blocks until there is space. Defaults to 64.
+    #[serde(default)]
+    pub upload_queue_depth: Option<usize>,
+
+    /// Prefix each uploaded report with the UTC date it is saved, as
+    /// `YYYY/MM/DD/`, so that storage lifecycle rules and queries can work per
+    /// day. Reports already saved, flat or under any date, are not saved
+    /// again. Off by default, which keeps the flat layout.
+    #[serde(default)]
+    pub report_date_partitioning: bool,
+
+    /// Mode to set on each input before it is used, such as `"0644"`, so that a
+    /// target running with dropped privileges can read it. Unix only.
+    #[serde(default, deserialize_with = "deserialize_file_mode")]
+    pub normalize_input_permissions: Option<u32>,
+
+    /// Compiled BPF seccomp profile, relative to the setup dir, to confine the
+    /// target with. Targets killed for a blocked syscall are reported as
+    /// not reproducing, with an error saying so. Linux only.
+    #[serde(default)]
+    pub seccomp_profile: Option<PathBuf>,
+
+    /// Include the ClusterFuzz crash state in reports, to match them against
+    /// testcases deduplicated by ClusterFuzz.
+    #[serde(default)]
+    pub clusterfuzz_signature: bool,
+
+    /// Run the target as this user, rather than as the agent's user, so that
+    /// it doesn't run as root. Directories the target writes to are given to
+    /// the user. Linux only.
+    #[serde(default)]
+    pub run_target_as_user: Option<String>,
+
+    /// Reproduce each crash with a new, empty working directory and temp
+    /// directory for every run of the target, so that files left behind by
+    /// earlier inputs can't mask or cause a crash. A new target process is
+    /// always started for each run. Creating and removing the directories
+    /// slows reproduction, most noticeably for fast targets with many retries.
+    #[serde(default)]
+    pub fresh_process_per_crash: bool,
+
+    /// Seconds to wait before polling `input_queue` again after finding it
+    /// empty. Defaults to 10.
+    #[serde(default)]
+    pub poll_interval_seconds: Option<u64>,
+
+    /// If set, the wait doubles after each consecutive empty poll, up to this
+    /// many seconds, to reduce the cost of polling idle queues.
+    #[serde(default)]
+    pub max_poll_interval_seconds: Option<u64>,
+
+    /// Skip inputs which don't match the SHA-256 in their `<input>.sha256`
+    /// sidecar file, such as inputs truncated while syncing, rather than
+    /// reporting crashes on corrupt data. Inputs without a sidecar are
+    /// reported as usual, and sidecars themselves are never reported.
+    #[serde(default)]
+    pub verify_input_checksum: bool,
+
+    /// Skip inputs larger than this many bytes, rather than passing them to
+    /// the target, for targets which can't handle larger inputs and would
+    /// otherwise produce spurious crashes. Unlike libFuzzer's `-max_len`, this
+    /// applies to inputs from any source. Unset by default.
+    #[serde(default)]
+    pub reject_inputs_over_bytes: Option<u64>,
+
+    /// URL to POST each report's JSON to as soon as it is generated, such as
+    /// for alerting, in addition to saving it. Failed posts are retried and
+    /// logged, but never delay or fail saving reports.
+    #[serde(default)]
+    pub report_webhook: Option<Url>,
+
+    /// Key for the HMAC-SHA256 of each webhook request body, sent in the
+    /// `X-Onefuzz-Signature` header as `sha256=<hex>` so that the receiver can
+    /// authenticate reports. If unset, requests are unsigned.
+    #[serde(default)]
+    pub report_webhook_secret: Option<Secret<String>>,
+
+    /// Queue to send a message to for each crash report saved, holding the
+    /// report URL, crash type and stack hash, so that triage needn't poll the
+    /// reports container. Failed sends are retried and logged, but never
+    /// delay or fail saving reports.
+    #[serde(default)]
+    pub report_notification_queue: Option<QueueClient>,
+
+    /// Severity to assign to crashes of a type, such as
+    /// `"heap-buffer-overflow"`, or of a type and access, such as
+    /// `"heap-buffer-overflow READ"`, instead of the built-in policy.
+    #[serde(default)]
+    pub severity_overrides: HashMap<String, Severity>,
+
+    /// Include the likely CWE ID of each crash, from its crash type, in its
+    /// report's `cwe`, such as 122 for `heap-buffer-overflow`. The built-in
+    /// mapping is documented in `report::cwe`.
+    #[serde(default)]
+    pub classify_cwe: bool,
+
+    /// CWE ID to assign to crashes of a type, such as `"SEGV"`, instead of
+    /// the built-in mapping, with `classify_cwe`.
+    #[serde(default)]
+    pub cwe_overrides: HashMap<String, u32>,
+
+    /// Limit the target's virtual memory to this many megabytes, so that a
+    /// runaway allocation fails and is reported as a crash rather than
+    /// invoking the OOM killer. Linux only, and rejected elsewhere. Not usable
+    /// with sanitizer builds, which reserve far more address space than they
+    /// use.
+    #[serde(default)]
+    pub target_rss_limit_mb: Option<u64>,
+
+    /// libFuzzer's memory limit when reproducing, via `-rss_limit_mb`. Set to
+    /// the fuzz task's `rss_limit_mb`, so that out-of-memory crashes found
+    /// under it reproduce.
+    #[serde(default)]
+    pub rss_limit_mb: Option<u64>,
+
+    /// Scheduling priority to run the target with, as a Unix nice value from
+    /// -20 to 19, so that fuzzing can yield to other work on shared machines.
+    /// Mapped to the nearest priority class on Windows. Unset by default, which
+    /// keeps the agent's own priority.
+    #[serde(default)]
+    pub target_nice: Option<i32>,
+
+    /// Setup-relative files, such as config or license files, which the target
+    /// expects in its working directory. Copied into it before each run with
+    /// `fresh_process_per_crash`, and once at startup otherwise. Missing files
+    /// fail the task.
+    #[serde(default)]
+    pub auxiliary_files: Vec<PathBuf>,
+
+    /// Container of stack hashes reported by earlier runs, shared across
+    /// jobs. Crashes already seen there are saved to `reports` but not
+    /// `unique_reports`, and the hashes this task sees first are added to it
+    /// when the task stops.
+    #[serde(default)]
+    pub dedup_state_container: Option<SyncedDir>,
+
+    /// Days to keep uploaded reports before storage lifecycle rules may
+    /// delete them, set as `onefuzz_ttl_days` blob metadata. If unset, no
+    /// metadata is added.
+    #[serde(default)]
+    pub crash_ttl_days: Option<u32>,
+
+    /// Save a script that reruns the target on each reproduced crash next to
+    /// its report, as `repro.sh` on Linux or `repro.ps1` on Windows.
+    /// Environment variables that look like secrets are left for the caller
+    /// to set, rather than written to the script.
+    #[serde(default)]
+    pub generate_repro_script: bool,
+
+    /// Seconds over which report latency and reproduction rate are
+    /// aggregated before being sent as a `report_stats` event. Defaults to
+    /// five minutes.
+    #[serde(default)]
+    pub report_metrics_window_seconds: Option<u64>,
+
+    /// Rerun each reproduced crash this many more times, without retries, and
+    /// set its report's `reproducibility` to `reproducible`, `flaky` or
+    /// `non_reproducing` from the share of reruns which crash, as described
+    /// in `report/reproducibility.rs`. Inputs which don't crash at all are
+    /// still saved to `no_repro`, flagged `non_reproducing`. Unset by default.
+    #[serde(default)]
+    pub repro_count: Option<u64>,
+
+    /// Share of `repro_count` reruns, from 0 to 1, which must crash for a
+    /// crash to be `reproducible`. Defaults to 1, every rerun.
+    #[serde(default)]
+    pub reproducible_min_ratio: Option<f64>,
+
+    /// Share of `repro_count` reruns, from 0 to 1, which must crash for a
+    /// crash which isn't `reproducible` to be `flaky` rather than
+    /// `non_reproducing`. Defaults to 0, so any crashing rerun is enough.
+    #[serde(default)]
+    pub flaky_min_ratio: Option<f64>,
+
+    /// Log each line the target writes to stdout and stderr at debug level as
+    /// it runs, as well as capturing it. Only shown at `log_level=debug`.
+    #[serde(default)]
+    pub tee_target_output: bool,
+
+    /// Record the modules, with their hashes, that the target had loaded when
+    /// it crashed, in each report's `loaded_modules`, to pin crashes to exact
+    /// dependency versions. Exact with `check_debugger` on Linux; otherwise
+    /// sampled while the target runs, so that modules loaded just before the
+    /// crash may be missed.
+    #[serde(default)]
+    pub capture_module_list: bool,
+
+    /// Container of inputs for already-filed bugs. Crashing inputs with the
+    /// same contents as one of them are skipped rather than reported. The
+    /// container is read once, when the task starts.
+    #[serde(default)]
+    pub known_crashes_container: Option<BlobContainerUrl>,
+
+    /// Command, and its arguments, to pipe each input through before passing
+    /// the output to the fuzzer, such as a decompressor for a corpus stored
+    /// compressed. Reports still name the original input. Inputs for which
+    /// the command fails or runs for over a minute are skipped.
+    #[serde(default)]
+    pub pre_exec_transform: Option<Vec<String>>,
+
     #[serde(flatten)]
     pub common: CommonConfig,
 }
@@ -61,7 +313,8 @@ pub struct ReportTask {
 
 impl ReportTask {
     pub fn new(config: Config) -> Self {
-        let poller = InputPoller::new("libfuzzer-crash-report");
+        let poller = InputPoller::new("libfuzzer-crash-report")
+            .with_cancellation(config.common.cancellation_token.clone());
         let config = Arc::new(config);
 
         Self { config, poller }
@@ -84,7 +337,10 @@ impl ReportTask {
                 .as_ref()
                 .map(|x| x.local_path.clone()),
             self.config.common.machine_identity.clone(),
-        );
+        )
+        .target_user(resolve_target_user(
+            self.config.run_target_as_user.as_deref(),
+        )?);
 
         fuzzer.verify(self.config.check_fuzzer_help, None).await
     }
@@ -92,6 +348,11 @@ impl ReportTask {
     pub async fn managed_run(&mut self) -> Result<()> {
         info!("Starting libFuzzer crash report task");
         self.verify().await?;
+        self.poller.set_poll_interval(PollInterval::from_config(
+            POLL_INTERVAL,
+            self.config.poll_interval_seconds,
+            self.config.max_poll_interval_seconds,
+        )?);
 
         if let Some(unique_reports) = &self.config.unique_reports {
             unique_reports.init().await?;
@@ -103,18 +364,67 @@ impl ReportTask {
             no_repro.init().await?;
         }
 
-        let mut processor = AsanProcessor::new(self.config.clone()).await?;
+        let (report_sender, uploader) = upload_queue(
+            self.config.upload_queue_depth,
+            self.config.report_format,
+            &self.config.unique_reports,
+            &self.config.reports,
+            &self.config.no_repro,
+            self.config.common.init_job_result().await?,
+        );
+        let uploader = uploader
+            .blob_metadata(crash_ttl_metadata(self.config.crash_ttl_days))
+            .date_partitioning(self.config.report_date_partitioning)
+            .notifier(ReportNotifier::from_config(
+                self.config.report_notification_queue.as_ref(),
+            ))
+            .dedup_state(
+                DedupState::load_optional(
+                    self.config.dedup_state_container.as_ref(),
+                    self.config.common.task_id,
+                    &self.config.common.cancellation_token,
+                )
+                .await?,
+            )
+            .report_metrics(Some(ReportMetrics::from_config(
+                self.config.report_metrics_window_seconds,
+            )?));
+        let mut processor = AsanProcessor::new(self.config.clone())
+            .await?
+            .upload_queue(Some(report_sender))
+            .repro_check(ReproCheck::from_config(
+                self.config.repro_count,
+                self.config.reproducible_min_ratio,
+                self.config.flaky_min_ratio,
+            )?)
+            .known_crashes(
+                KnownCrashes::load_optional(self.config.known_crashes_container.as_ref()).await?,
+            );
 
-        if let Some(crashes) = &self.config.crashes {
-            self.poller.batch_process(&mut processor, crashes).await?;
-        }
+        let config = &self.config;
+        let poller = &mut self.poller;
 
-        if self.config.check_queue {
-            if let Some(url) = &self.config.input_queue {
-                let callback = CallbackImpl::new(url.clone(), processor)?;
-                self.poller.run(callback).await?;
+        // The processor owns the sending half of the upload queue, so the
+        // uploader stops once the processor is dropped at the end of this block.
+        let generate_reports = async move {
+            if let Some(crashes) = &config.crashes {
+                poller.batch_process(&mut processor, crashes).await?;
             }
-        }
+
+            if config.check_queue {
+                if let Some(url) = &config.input_queue {
+                    // The poller deletes each message once it is processed, so
+                    // its report must be uploaded by then.
+                    let processor = processor.wait_for_upload(true);
+                    let callback = CallbackImpl::new(url.clone(), processor)?;
+                    poller.run(callback).await?;
+                }
+            }
+            Ok::<_, anyhow::Error>(())
+        };
+
+        lifecycle::transition(LifecycleState::Ready);
+        futures::try_join!(generate_reports, uploader.run())?;
         Ok(())
     }
 }
@@ -122,6 +432,9 @@ impl ReportTask {
 pub struct TestInputArgs<'a> {
     pub input_url: Option<Url>,
     pub input: &'a Path,
+    /// Passed to the fuzzer instead of `input`, if set. Reports still name
+    /// `input`.
+    pub transformed_input: Option<&'a Path>,
     pub target_exe: &'a Path,
     pub target_options: &'a [String],
     pub target_env: &'a HashMap<String, String>,
@@ -133,7 +446,16 @@ pub struct TestInputArgs<'a> {
     pub target_timeout: Option<u64>,
     pub check_retry_count: u64,
     pub minimized_stack_depth: Option<usize>,
+    pub stack_hash_frame_count: Option<usize>,
     pub machine_identity: MachineIdentity,
+    pub seccomp_filter: Option<&'a SeccompFilter>,
+    pub target_user: Option<&'a TargetUser>,
+    pub fresh_state: bool,
+    pub auxiliary_files: &'a [PathBuf],
+    pub memory_limit_mb: Option<u64>,
+    pub nice: Option<i32>,
+    pub tee_output: bool,
+    pub capture_module_list: bool,
 }
 
 pub async fn test_input(args: TestInputArgs<'_>) -> Result<CrashTestResult> {
@@ -145,7 +467,15 @@ pub async fn test_input(args: TestInputArgs<'_>) -> Result<CrashTestResult> {
         args.extra_setup_dir.map(PathBuf::from),
         args.extra_output_dir.map(PathBuf::from),
         args.machine_identity,
-    );
+    )
+    .seccomp_filter(args.seccomp_filter.cloned())
+    .target_user(args.target_user.cloned())
+    .fresh_state(args.fresh_state)
+    .auxiliary_files(args.auxiliary_files.to_vec())
+    .memory_limit_mb(args.memory_limit_mb)
+    .nice(args.nice)
+    .tee_output(args.tee_output)
+    .capture_module_list(args.capture_module_list);
 
     let task_id = args.task_id;
     let job_id = args.job_id;
@@ -159,12 +489,16 @@ pub async fn test_input(args: TestInputArgs<'_>) -> Result<CrashTestResult> {
         .with_context(|| format_err!("unable to sha256 digest input file: {}", input.display()))?;
 
     let test_report = fuzzer
-        .repro(args.input, args.target_timeout, args.check_retry_count)
+        .repro(
+            args.transformed_input.unwrap_or(args.input),
+            args.target_timeout,
+            args.check_retry_count,
+        )
         .await?;
 
     match test_report.crash_log {
         Some(crash_log) => {
-            let crash_report = CrashReport::new(
+            let mut crash_report = CrashReport::new(
                 crash_log,
                 task_id,
                 job_id,
@@ -172,10 +506,12 @@ pub async fn test_input(args: TestInputArgs<'_>) -> Result<CrashTestResult> {
                 input_blob,
                 input_sha256,
                 args.minimized_stack_depth,
+                args.stack_hash_frame_count,
                 LIBFUZZER_TOOL_NAME.into(),
                 env!("ONEFUZZ_VERSION").to_string(),
                 env!("ONEFUZZ_VERSION").to_string(),
             );
+            crash_report.loaded_modules = test_report.loaded_modules;
             Ok(CrashTestResult::CrashReport(Box::new(crash_report)))
         }
         None => {
@@ -187,6 +523,8 @@ pub async fn test_input(args: TestInputArgs<'_>) -> Result<CrashTestResult> {
                 job_id,
                 tries: 1 + args.check_retry_count,
                 error: test_report.error.map(|e| format!("{e}")),
+                source_revision: None,
+                reproducibility: None,
             };
 
             Ok(CrashTestResult::NoRepro(Box::new(no_repro)))
@@ -198,24 +536,133 @@ pub struct AsanProcessor {
     config: Arc<Config>,
     heartbeat_client: Option<TaskHeartbeatClient>,
     job_result_client: Option<TaskJobResultClient>,
+    classification_rules: Option<ClassificationRules>,
+    severity: SeverityClassifier,
+    cwe: Option<CweClassifier>,
+    upload_queue: Option<ReportSender>,
+    webhook: Option<ReportWebhook>,
+    notifier: Option<ReportNotifier>,
+    seccomp_filter: Option<SeccompFilter>,
+    target_user: Option<TargetUser>,
+    auxiliary_files: Vec<PathBuf>,
+    target_env: HashMap<String, String>,
+    report_metrics: Option<ReportMetrics>,
+    repro_check: Option<ReproCheck>,
+    known_crashes: Option<KnownCrashes>,
 }
 
 impl AsanProcessor {
     pub async fn new(config: Arc<Config>) -> Result<Self> {
+        let classification_rules =
+            ClassificationRules::load_optional(config.classification_rules.as_ref()).await?;
+        let seccomp_filter =
+            load_seccomp_profile(&config.common.setup_dir, config.seccomp_profile.as_deref())
+                .await?;
+        let target_user = resolve_target_user(config.run_target_as_user.as_deref())?;
+        check_target_rss_limit(config.target_rss_limit_mb)?;
+        check_rss_limit(config.rss_limit_mb)?;
+        check_target_nice(config.target_nice, false)?;
+        check_stack_hash_frame_count(config.stack_hash_frame_count)?;
+        check_pre_exec_transform(config.pre_exec_transform.as_deref())?;
+        let auxiliary_files =
+            place_auxiliary_files(&config.common.setup_dir, &config.auxiliary_files).await?;
         let heartbeat_client = config.common.init_heartbeat(None).await?;
         let job_result_client = config.common.init_job_result().await?;
 
+        let target_env = if config.symbol_server_url.is_some() {
+            let cache_dir = symbol_cache_dir()?;
+            tokio::fs::create_dir_all(&cache_dir).await?;
+            with_symbol_server(
+                &config.target_env,
+                config.symbol_server_url.as_ref(),
+                &cache_dir,
+            )
+        } else {
+            config.target_env.clone()
+        };
+
         Ok(Self {
-            config,
             heartbeat_client,
             job_result_client,
+            classification_rules,
+            severity: SeverityClassifier::new(config.severity_overrides.clone()),
+            cwe: config
+                .classify_cwe
+                .then(|| CweClassifier::new(config.cwe_overrides.clone())),
+            upload_queue: None,
+            webhook: ReportWebhook::from_config(
+                config.report_webhook.as_ref(),
+                config.report_webhook_secret.as_ref(),
+            ),
+            notifier: ReportNotifier::from_config(config.report_notification_queue.as_ref()),
+            seccomp_filter,
+            target_user,
+            auxiliary_files,
+            target_env,
+            report_metrics: None,
+            repro_check: None,
+            known_crashes: None,
+            // Last, since the fields above are built from it.
+            config,
         })
     }
 
+    /// Queue reports for upload, rather than saving each one before returning.
+    pub fn upload_queue(mut self, upload_queue: Option<ReportSender>) -> Self {
+        self.upload_queue = upload_queue;
+        self
+    }
+
+    /// Wait for each queued report to be uploaded before returning. See
+    /// [`ReportSender::wait_for_upload`].
+    pub fn wait_for_upload(mut self, wait_for_upload: bool) -> Self {
+        self.upload_queue = self
+            .upload_queue
+            .map(|upload_queue| upload_queue.wait_for_upload(wait_for_upload));
+        self
+    }
+
+    /// Aggregate report latency and reproduction rate in `report_metrics`, for
+    /// reports saved directly. The uploader records reports sent to the upload
+    /// queue.
+    pub fn report_metrics(mut self, report_metrics: Option<ReportMetrics>) -> Self {
+        self.report_metrics = report_metrics;
+        self
+    }
+
+    /// Rerun reproduced crashes to set their `reproducibility`.
+    pub fn repro_check(mut self, repro_check: Option<ReproCheck>) -> Self {
+        self.repro_check = repro_check;
+        self
+    }
+
+    /// Skip inputs in `known_crashes`.
+    pub fn known_crashes(mut self, known_crashes: Option<KnownCrashes>) -> Self {
+        self.known_crashes = known_crashes;
+        self
+    }
+
     pub async fn test_input(
         &self,
         input_url: Option<Url>,
         input: &Path,
+        transformed_input: Option<&Path>,
+    ) -> Result<CrashTestResult> {
+        self.test_input_with_retries(
+            input_url,
+            input,
+            transformed_input,
+            self.config.check_retry_count,
+        )
+        .await
+    }
+
+    async fn test_input_with_retries(
+        &self,
+        input_url: Option<Url>,
+        input: &Path,
+        transformed_input: Option<&Path>,
+        check_retry_count: u64,
     ) -> Result<CrashTestResult> {
         self.heartbeat_client.alive();
 
@@ -223,12 +670,16 @@ impl AsanProcessor {
             try_resolve_setup_relative_path(&self.config.common.setup_dir, &self.config.target_exe)
                 .await?;
 
+        let mut target_options = self.config.target_options.clone();
+        add_rss_limit(&mut target_options, self.config.rss_limit_mb);
+
         let args = TestInputArgs {
             input_url,
             input,
+            transformed_input,
             target_exe: &target_exe,
-            target_options: &self.config.target_options,
-            target_env: &self.config.target_env,
+            target_options: &target_options,
+            target_env: &self.target_env,
             setup_dir: &self.config.common.setup_dir,
             extra_setup_dir: self.config.common.extra_setup_dir.as_deref(),
             extra_output_dir: self
@@ -240,29 +691,162 @@ impl AsanProcessor {
             task_id: self.config.common.task_id,
             job_id: self.config.common.job_id,
             target_timeout: self.config.target_timeout,
-            check_retry_count: self.config.check_retry_count,
+            check_retry_count,
             minimized_stack_depth: self.config.minimized_stack_depth,
+            stack_hash_frame_count: self.config.stack_hash_frame_count,
             machine_identity: self.config.common.machine_identity.clone(),
+            seccomp_filter: self.seccomp_filter.as_ref(),
+            target_user: self.target_user.as_ref(),
+            fresh_state: self.config.fresh_process_per_crash,
+            auxiliary_files: &self.auxiliary_files,
+            memory_limit_mb: self.config.target_rss_limit_mb,
+            nice: self.config.target_nice,
+            tee_output: self.config.tee_target_output,
+            capture_module_list: self.config.capture_module_list,
         };
 
         let result = test_input(args).await?;
 
         Ok(result)
     }
+    /// Rerun the crash in `report`, if it reproduced, as many times as
+    /// `repro_check` says, and set its `reproducibility`. Inputs which didn't
+    /// reproduce are flagged `non_reproducing` without being rerun.
+    async fn check_reproducibility(
+        &self,
+        report: &mut CrashTestResult,
+        input: &Path,
+        transformed_input: Option<&Path>,
+    ) -> Result<()> {
+        let Some(repro_check) = self.repro_check else {
+            return Ok(());
+        };
+        let crash_report = match report {
+            CrashTestResult::CrashReport(crash_report) => crash_report,
+            CrashTestResult::NoRepro(no_repro) => {
+                no_repro.reproducibility = Some(Reproducibility::NonReproducing);
+                return Ok(());
+            }
+        };
+
+        let mut crashes = 0;
+        for _ in 0..repro_check.attempts {
+            let rerun = self
+                .test_input_with_retries(None, input, transformed_input, 0)
+                .await?;
+            if matches!(rerun, CrashTestResult::CrashReport(_)) {
+                crashes += 1;
+            }
+        }
+
+        crash_report.reproducibility = Some(repro_check.verdict(crashes));
+        debug!(
+            "{} crashed {} of {} reruns",
+            input.display(),
+            crashes,
+            repro_check.attempts
+        );
+        Ok(())
+    }
+
+    async fn upload_repro_script(&self, report: &CrashReport) {
+        if !self.config.generate_repro_script {
+            return;
+        }
+
+        let mut target_options = self.config.target_options.clone();
+        target_options.push("{input}".to_owned());
+        let script = ReproScript {
+            target_exe: &report.executable,
+            target_options: &target_options,
+            target_env: &self.target_env,
+            setup_dir: &self.config.common.setup_dir,
+            extra_setup_dir: self.config.common.extra_setup_dir.as_deref(),
+            machine_identity: &self.config.common.machine_identity,
+        };
+        if let Err(err) = script
+            .upload(report, &self.config.reports, &self.config.unique_reports)
+            .await
+        {
+            warn!("unable to save repro script: {:?}", err);
+        }
+    }
 }
 
 #[async_trait]
 impl Processor for AsanProcessor {
     async fn process(&mut self, url: Option<Url>, input: &Path) -> Result<()> {
         debug!("processing libfuzzer crash url:{:?} path:{:?}", url, input);
-        let report = self.test_input(url, input).await?;
-        report
-            .save(
+        let input_saved = input_saved_time(input).await;
+        if !verify_input_checksum(input, url.as_ref(), self.config.verify_input_checksum).await? {
+            return Ok(());
+        }
+        if reject_oversized_input(input, self.config.reject_inputs_over_bytes).await? {
+            return Ok(());
+        }
+        if skip_known_crash(self.known_crashes.as_ref(), input).await? {
+            return Ok(());
+        }
+        normalize_permissions(input, self.config.normalize_input_permissions).await?;
+        grant_input_dir(self.target_user.as_ref(), input)?;
+        let transformed = match &self.config.pre_exec_transform {
+            Some(transform) => {
+                match transform_input(&self.config.common.setup_dir, transform, input).await? {
+                    Some(transformed) => {
+                        grant_input_dir(self.target_user.as_ref(), transformed.path())?;
+                        Some(transformed)
+                    }
+                    None => return Ok(()),
+                }
+            }
+            None => None,
+        };
+        let mut report = self
+            .test_input(url, input, transformed.as_ref().map(|t| t.path()))
+            .await?;
+        self.check_reproducibility(&mut report, input, transformed.as_ref().map(|t| t.path()))
+            .await?;
+        if let Some(rules) = &self.classification_rules {
+            rules.apply(&mut report);
+        }
+        self.severity.apply(&mut report);
+        if let Some(cwe) = &self.cwe {
+            cwe.apply(&mut report);
+        }
+        report.set_source_revision(self.config.common.source_revision.as_deref());
+        if self.config.clusterfuzz_signature {
+            if let CrashTestResult::CrashReport(crash_report) = &mut report {
+                crash_report.clusterfuzz_crash_state = Some(crash_report.clusterfuzz_crash_state());
+            }
+        }
+        if let Some(webhook) = &self.webhook {
+            webhook.notify(&report);
+        }
+        if let CrashTestResult::CrashReport(crash_report) = &report {
+            self.upload_repro_script(crash_report).await;
+        }
+        if let Some(upload_queue) = &self.upload_queue {
+            // The uploader records the report metrics.
+            return upload_queue.send(report, input_saved).await;
+        }
+        let report_url = report
+            .save_with_format(
+                self.config.report_format,
                 &self.config.unique_reports,
                 &self.config.reports,
                 &self.config.no_repro,
                 &self.job_result_client,
+                &crash_ttl_metadata(self.config.crash_ttl_days),
+                self.config.report_date_partitioning,
             )
-            .await
+            .await?;
+        if let Some(notifier) = &self.notifier {
+            notifier.notify(&report, report_url);
+        }
+        if let Some(report_metrics) = &mut self.report_metrics {
+            let reproduced = matches!(report, CrashTestResult::CrashReport(_));
+            report_metrics.record(input_saved, reproduced);
+        }
+        Ok(())
     }
 }
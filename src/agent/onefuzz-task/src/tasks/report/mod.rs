@@ -1,7 +1,21 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+pub mod classification;
+pub mod clusterfuzz;
 pub mod crash_report;
+pub mod cwe;
+pub mod dedup_state;
 pub mod dotnet;
 pub mod generic;
+pub mod known_crashes;
 pub mod libfuzzer_report;
+pub mod notification;
+pub mod report_metrics;
+pub mod repro_script;
+pub mod reproducibility;
+pub mod sarif;
+pub mod severity;
+pub mod symbols;
+pub mod upload_queue;
+pub mod webhook;
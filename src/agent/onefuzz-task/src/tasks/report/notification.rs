@@ -0,0 +1,130 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Sending a message to a queue for each crash report saved, so that
+//! consumers such as triage services needn't poll the reports container.
+//!
+//! Each message is sent in the background, with retries, so that a slow or
+//! failing queue never delays saving reports. Messages still being sent when
+//! the task exits are lost.
+
+use std::time::Duration;
+
+use backoff::{future::retry_notify, ExponentialBackoff};
+use reqwest::Url;
+use serde::Serialize;
+use storage_queue::QueueClient;
+
+use super::crash_report::CrashTestResult;
+
+const MAX_SEND_TIME: Duration = Duration::from_secs(300);
+
+/// Message sent to `report_notification_queue`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct ReportNotification {
+    /// The saved report, preferring the unique report. Unset if the reports
+    /// container is neither remote nor a local path.
+    pub report_url: Option<Url>,
+    pub crash_type: String,
+    /// The stack hash that unique reports are named by.
+    pub stack_sha256: String,
+}
+
+impl ReportNotification {
+    /// The message for `report`, if it is a crash report.
+    pub fn new(report: &CrashTestResult, report_url: Option<Url>) -> Option<Self> {
+        match report {
+            CrashTestResult::CrashReport(report) => Some(Self {
+                report_url,
+                crash_type: report.crash_type.clone(),
+                stack_sha256: report.unique_sha256().to_owned(),
+            }),
+            CrashTestResult::NoRepro(_) => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ReportNotifier {
+    queue: QueueClient,
+}
+
+impl ReportNotifier {
+    pub fn new(queue: QueueClient) -> Self {
+        Self { queue }
+    }
+
+    pub fn from_config(queue: Option<&QueueClient>) -> Option<Self> {
+        queue.map(|queue| Self::new(queue.clone()))
+    }
+
+    /// Send a message for `report`, saved at `report_url`, in the background,
+    /// logging rather than returning errors. Reports that didn't reproduce are
+    /// skipped.
+    pub fn notify(&self, report: &CrashTestResult, report_url: Option<Url>) {
+        let Some(notification) = ReportNotification::new(report, report_url) else {
+            return;
+        };
+
+        let queue = self.queue.clone();
+        tokio::spawn(async move {
+            let send = || async {
+                queue
+                    .enqueue(&notification)
+                    .await
+                    .map_err(backoff::Error::transient)
+            };
+            let backoff = ExponentialBackoff {
+                max_elapsed_time: Some(MAX_SEND_TIME),
+                ..ExponentialBackoff::default()
+            };
+            let notify = |err, delay| {
+                debug!("retrying report notification in {:?}: {:?}", delay, err);
+            };
+            if let Err(err) = retry_notify(backoff, send, notify).await {
+                warn!("unable to send report notification: {:?}", err);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::super::crash_report::{CrashReport, NoCrash};
+    use super::*;
+
+    #[test]
+    fn test_notification() -> Result<()> {
+        let report = CrashTestResult::from(CrashReport {
+            crash_type: "heap-buffer-overflow".into(),
+            call_stack_sha256: "abc".into(),
+            ..Default::default()
+        });
+        let url = Url::parse("https://example.com/unique-reports/abc.json")?;
+
+        assert_eq!(
+            ReportNotification::new(&report, Some(url.clone())),
+            Some(ReportNotification {
+                report_url: Some(url),
+                crash_type: "heap-buffer-overflow".into(),
+                stack_sha256: "abc".into(),
+            })
+        );
+
+        let no_repro = CrashTestResult::from(NoCrash {
+            input_blob: None,
+            input_sha256: "abc".into(),
+            executable: "fuzz.exe".into(),
+            task_id: Default::default(),
+            job_id: Default::default(),
+            tries: 1,
+            error: None,
+            source_revision: None,
+            reproducibility: None,
+        });
+        assert_eq!(ReportNotification::new(&no_repro, None), None);
+        Ok(())
+    }
+}
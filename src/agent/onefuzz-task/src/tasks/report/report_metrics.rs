@@ -0,0 +1,213 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Aggregate metrics on how quickly crashes are reported, and how often they
+//! reproduce, sent as a `report_stats` event once per window.
+//!
+//! An input's latency is the time from when it was saved, which is the
+//! Last-Modified time of its blob for inputs from a queue, or the modification
+//! time of a local file, until its report was uploaded. Reports which fail to
+//! upload are not counted.
+
+use std::{
+    path::Path,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Result;
+use onefuzz_telemetry::{Event::report_stats, EventData};
+
+use crate::tasks::error::TaskError;
+
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+pub struct ReportMetrics {
+    window: Duration,
+    start: Instant,
+    latencies: Vec<Duration>,
+    reproduced: u64,
+    not_reproduced: u64,
+}
+
+impl ReportMetrics {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            start: Instant::now(),
+            latencies: Vec::new(),
+            reproduced: 0,
+            not_reproduced: 0,
+        }
+    }
+
+    /// Use a window of `window_seconds`, or `DEFAULT_WINDOW` if unset.
+    pub fn from_config(window_seconds: Option<u64>) -> Result<Self> {
+        let window = match window_seconds {
+            Some(0) => {
+                return Err(TaskError::Configuration(anyhow!(
+                    "report_metrics_window_seconds must be at least 1"
+                ))
+                .into());
+            }
+            Some(seconds) => Duration::from_secs(seconds),
+            None => DEFAULT_WINDOW,
+        };
+        Ok(Self::new(window))
+    }
+
+    /// Record that an input saved at `saved`, if known, was reported, sending
+    /// the aggregate event if the current window has ended.
+    pub fn record(&mut self, saved: Option<SystemTime>, reproduced: bool) {
+        let latency = saved.and_then(|saved| SystemTime::now().duration_since(saved).ok());
+        self.record_latency(latency, reproduced);
+
+        if self.start.elapsed() >= self.window {
+            self.flush();
+        }
+    }
+
+    fn record_latency(&mut self, latency: Option<Duration>, reproduced: bool) {
+        if let Some(latency) = latency {
+            self.latencies.push(latency);
+        }
+        if reproduced {
+            self.reproduced += 1;
+        } else {
+            self.not_reproduced += 1;
+        }
+    }
+
+    /// Send the aggregate event for the current window, if anything was
+    /// reported in it, and start a new window.
+    pub fn flush(&mut self) {
+        if let Some(stats) = self.stats() {
+            event!(
+                report_stats;
+                EventData::Count = stats.count,
+                EventData::ReproRate = stats.repro_rate,
+                EventData::LatencyP50Seconds = stats.p50.as_secs_f64(),
+                EventData::LatencyP95Seconds = stats.p95.as_secs_f64()
+            );
+            metric!(
+                report_stats;
+                1.0;
+                EventData::Count = stats.count,
+                EventData::ReproRate = stats.repro_rate,
+                EventData::LatencyP50Seconds = stats.p50.as_secs_f64(),
+                EventData::LatencyP95Seconds = stats.p95.as_secs_f64()
+            );
+        }
+
+        self.start = Instant::now();
+        self.latencies.clear();
+        self.reproduced = 0;
+        self.not_reproduced = 0;
+    }
+
+    fn stats(&mut self) -> Option<WindowStats> {
+        let count = self.reproduced + self.not_reproduced;
+        if count == 0 {
+            return None;
+        }
+
+        self.latencies.sort();
+        Some(WindowStats {
+            count,
+            repro_rate: self.reproduced as f64 / count as f64,
+            p50: percentile(&self.latencies, 50),
+            p95: percentile(&self.latencies, 95),
+        })
+    }
+}
+
+/// When `input` was saved, from its modification time, which downloaded
+/// inputs keep from their blob.
+pub async fn input_saved_time(input: &Path) -> Option<SystemTime> {
+    match tokio::fs::metadata(input).await.and_then(|m| m.modified()) {
+        Ok(modified) => Some(modified),
+        Err(err) => {
+            debug!(
+                "unable to read modification time of {}: {}",
+                input.display(),
+                err
+            );
+            None
+        }
+    }
+}
+
+impl Drop for ReportMetrics {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct WindowStats {
+    count: u64,
+    repro_rate: f64,
+    p50: Duration,
+    p95: Duration,
+}
+
+/// The nearest-rank percentile of `sorted`, or zero if it is empty.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (percent * sorted.len() + 99) / 100;
+    sorted[rank.saturating_sub(1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: &[u64]) -> Vec<Duration> {
+        seconds.iter().map(|s| Duration::from_secs(*s)).collect()
+    }
+
+    #[test]
+    fn test_percentile() {
+        let latencies = secs(&(1..=20).collect::<Vec<_>>());
+        assert_eq!(percentile(&latencies, 50), Duration::from_secs(10));
+        assert_eq!(percentile(&latencies, 95), Duration::from_secs(19));
+        assert_eq!(percentile(&secs(&[7]), 95), Duration::from_secs(7));
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_window_stats() {
+        let mut metrics = ReportMetrics::new(DEFAULT_WINDOW);
+        assert_eq!(metrics.stats(), None);
+
+        metrics.record_latency(Some(Duration::from_secs(30)), true);
+        metrics.record_latency(Some(Duration::from_secs(10)), true);
+        metrics.record_latency(None, false);
+        metrics.record_latency(Some(Duration::from_secs(20)), false);
+
+        assert_eq!(
+            metrics.stats(),
+            Some(WindowStats {
+                count: 4,
+                repro_rate: 0.5,
+                p50: Duration::from_secs(20),
+                p95: Duration::from_secs(30),
+            })
+        );
+
+        metrics.flush();
+        assert_eq!(metrics.stats(), None);
+    }
+
+    #[test]
+    fn test_from_config() -> Result<()> {
+        assert_eq!(ReportMetrics::from_config(None)?.window, DEFAULT_WINDOW);
+        assert_eq!(
+            ReportMetrics::from_config(Some(60))?.window,
+            Duration::from_secs(60)
+        );
+        assert!(ReportMetrics::from_config(Some(0)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,533 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Scripts that rerun the target on a reported crash, saved alongside the
+//! report so that a developer can reproduce it without rebuilding the task
+//! configuration by hand.
+//!
+//! The script is `repro.sh` on Linux and `repro.ps1` on Windows. The input
+//! and setup directory are parameters, defaulting to the blob name of the
+//! input and the setup directory on the node. Environment variables and
+//! target arguments that look like secrets are never written out: the script
+//! requires the caller to set them instead, arguments as `SECRET_ARG_0`,
+//! `SECRET_ARG_1` and so on. Every other value is quoted, and environment
+//! variables whose names are not identifiers are left out.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Result;
+use onefuzz::{
+    expand::{Expand, ExpandedValue, PlaceHolder},
+    machine_id::MachineIdentity,
+    syncdir::SyncedDir,
+};
+
+use super::crash_report::CrashReport;
+
+const MARKER: char = '\u{1}';
+const INPUT_MARKER: &str = "\u{1}input\u{1}";
+const SETUP_DIR_MARKER: &str = "\u{1}setup_dir\u{1}";
+const SECRET_ARG_PREFIX: &str = "SECRET_ARG_";
+
+// Case-insensitive substrings of variable names whose values are not saved.
+const SECRET_NAMES: &[&str] = &[
+    "KEY",
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "CREDENTIAL",
+    "SAS",
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScriptKind {
+    Sh,
+    PowerShell,
+}
+
+impl ScriptKind {
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            Self::PowerShell
+        } else {
+            Self::Sh
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Sh => "sh",
+            Self::PowerShell => "ps1",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Sh => "text/x-shellscript",
+            Self::PowerShell => "text/plain",
+        }
+    }
+}
+
+pub struct ReproScript<'a> {
+    pub target_exe: &'a Path,
+    pub target_options: &'a [String],
+    pub target_env: &'a HashMap<String, String>,
+    pub setup_dir: &'a Path,
+    pub extra_setup_dir: Option<&'a Path>,
+    pub machine_identity: &'a MachineIdentity,
+}
+
+/// Part of a script word: literal text, or a reference to a script parameter.
+#[derive(Debug, Eq, PartialEq)]
+enum Piece<'s> {
+    Literal(&'s str),
+    Input,
+    SetupDir,
+    SecretArg(usize),
+}
+
+impl<'a> ReproScript<'a> {
+    /// Render the script that reruns the target on the input of `report`.
+    pub fn render(&self, kind: ScriptKind, report: &CrashReport) -> Result<String> {
+        let target_exe = self.target_exe.to_string_lossy();
+        let setup_dir = self.setup_dir.to_string_lossy();
+        let target_exe = match target_exe.strip_prefix(setup_dir.as_ref()) {
+            Some(relative) => format!("{SETUP_DIR_MARKER}{relative}"),
+            None => target_exe.into_owned(),
+        };
+
+        let expand = Expand::new(self.machine_identity)
+            .machine_id()
+            .input_marker(INPUT_MARKER)
+            .set_value(
+                PlaceHolder::SetupDir,
+                ExpandedValue::Scalar(SETUP_DIR_MARKER.to_owned()),
+            )
+            .set_value(
+                PlaceHolder::TargetExe,
+                ExpandedValue::Scalar(target_exe.clone()),
+            )
+            .target_options(self.target_options)
+            .set_optional(self.extra_setup_dir, Expand::extra_setup_dir);
+
+        let args = expand.evaluate(self.target_options)?;
+        let mut env = Vec::new();
+        for (name, value) in self.target_env {
+            let value = expand.evaluate_value(value)?;
+            env.push((name.as_str(), value));
+        }
+        env.sort();
+
+        let input_name = report
+            .input_blob
+            .as_ref()
+            .map(|blob| blob.name.clone())
+            .unwrap_or_else(|| report.input_sha256.clone());
+
+        let mut command = vec![target_exe];
+        command.extend(redact_args(args));
+
+        Ok(match kind {
+            ScriptKind::Sh => render_sh(report, &input_name, &setup_dir, &env, &command),
+            ScriptKind::PowerShell => render_ps1(report, &input_name, &setup_dir, &env, &command),
+        })
+    }
+
+    /// Upload the script for `report` next to it, in `reports` and
+    /// `unique_reports`.
+    pub async fn upload(
+        &self,
+        report: &CrashReport,
+        reports: &Option<SyncedDir>,
+        unique_reports: &Option<SyncedDir>,
+    ) -> Result<()> {
+        let kind = ScriptKind::native();
+        let script = self.render(kind, report)?.into_bytes();
+
+        let names = [
+            (reports, report.input_sha256.as_str()),
+            (unique_reports, report.unique_sha256()),
+        ];
+        for (container, stem) in names {
+            if let Some(container) = container {
+                let name = format!("{stem}.repro.{}", kind.extension());
+                container
+                    .upload_bytes(&name, script.clone(), kind.content_type())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    SECRET_NAMES.iter().any(|s| name.contains(s))
+}
+
+fn is_secret(name: &str, value: &str) -> bool {
+    is_secret_name(name) || value.contains("sig=")
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replace secret arguments with references to `SECRET_ARG_{n}`: the value
+/// of a secret flag, as in `-token=...` or `--token ...`, and any argument
+/// holding a SAS signature.
+fn redact_args(args: Vec<String>) -> Vec<String> {
+    let mut secrets = 0;
+    let mut secret_arg = || {
+        secrets += 1;
+        format!("{MARKER}{SECRET_ARG_PREFIX}{}{MARKER}", secrets - 1)
+    };
+
+    let mut redacted = Vec::with_capacity(args.len());
+    let mut value_is_secret = false;
+    for arg in args {
+        let flag = arg
+            .strip_prefix('-')
+            .map(|flag| flag.trim_start_matches('-'));
+        let (name, value) = match flag.and_then(|flag| flag.split_once('=')) {
+            Some((name, value)) => (Some(name), Some(value)),
+            None => (flag, None),
+        };
+
+        let whole_is_secret =
+            std::mem::take(&mut value_is_secret) || (value.is_none() && arg.contains("sig="));
+        let arg = if whole_is_secret {
+            secret_arg()
+        } else if let (Some(name), Some(value)) = (name, value) {
+            if is_secret(name, value) {
+                let prefix = &arg[..arg.len() - value.len()];
+                format!("{prefix}{}", secret_arg())
+            } else {
+                arg
+            }
+        } else {
+            value_is_secret = name.map_or(false, is_secret_name);
+            arg
+        };
+        redacted.push(arg);
+    }
+    redacted
+}
+
+/// `text`, with control characters replaced, for use in a script comment.
+fn comment(text: &str) -> String {
+    text.replace(|c: char| c.is_control(), " ")
+}
+
+fn marker_piece(name: &str) -> Option<Piece<'static>> {
+    match name {
+        "input" => Some(Piece::Input),
+        "setup_dir" => Some(Piece::SetupDir),
+        _ => name
+            .strip_prefix(SECRET_ARG_PREFIX)?
+            .parse()
+            .ok()
+            .map(Piece::SecretArg),
+    }
+}
+
+fn pieces(word: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = word;
+    let mut literal = 0;
+    while let Some(start) = rest[literal..].find(MARKER).map(|at| literal + at) {
+        let marker = rest[start + 1..].find(MARKER).and_then(|len| {
+            let piece = marker_piece(&rest[start + 1..start + 1 + len])?;
+            Some((piece, start + len + 2))
+        });
+        match marker {
+            Some((piece, end)) => {
+                if start > 0 {
+                    pieces.push(Piece::Literal(&rest[..start]));
+                }
+                pieces.push(piece);
+                rest = &rest[end..];
+                literal = 0;
+            }
+            None => literal = start + 1,
+        }
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Literal(rest));
+    }
+    pieces
+}
+
+fn secret_arg_names(command: &[String]) -> Vec<String> {
+    let mut names: Vec<_> = command
+        .iter()
+        .flat_map(|word| pieces(word))
+        .filter_map(|piece| match piece {
+            Piece::SecretArg(n) => Some(n),
+            _ => None,
+        })
+        .collect();
+    names.sort_unstable();
+    names
+        .into_iter()
+        .map(|n| format!("{SECRET_ARG_PREFIX}{n}"))
+        .collect()
+}
+
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn sh_word(word: &str) -> String {
+    let word: String = pieces(word)
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Literal(text) => sh_quote(text),
+            Piece::Input => "\"$INPUT\"".to_owned(),
+            Piece::SetupDir => "\"$SETUP_DIR\"".to_owned(),
+            Piece::SecretArg(n) => format!("\"${SECRET_ARG_PREFIX}{n}\""),
+        })
+        .collect();
+    if word.is_empty() {
+        "''".to_owned()
+    } else {
+        word
+    }
+}
+
+fn render_sh(
+    report: &CrashReport,
+    input_name: &str,
+    setup_dir: &str,
+    env: &[(&str, String)],
+    command: &[String],
+) -> String {
+    let mut script = String::new();
+    script.push_str("#!/bin/sh\n");
+    script.push_str(&format!(
+        "# Reproduces {} in {}.\n",
+        comment(&report.crash_type),
+        comment(&report.call_stack_sha256)
+    ));
+    script.push_str("# Usage: repro.sh [INPUT]\n");
+    script.push_str("# Set SETUP_DIR to run from a copy of the setup container.\n");
+    script.push_str("set -eu\n\n");
+
+    script.push_str(&format!("INPUT={}\n", sh_quote(input_name)));
+    script.push_str("if [ $# -gt 0 ]; then INPUT=\"$1\"; fi\n");
+    script.push_str(&format!(
+        "if [ -z \"${{SETUP_DIR:-}}\" ]; then SETUP_DIR={}; fi\n\n",
+        sh_quote(setup_dir)
+    ));
+
+    for (name, value) in env {
+        if !is_identifier(name) {
+            warn!("leaving {:?} out of repro script, not an identifier", name);
+        } else if is_secret(name, value) {
+            script.push_str(&format!(
+                ": \"${{{name}:?{name} is a secret and must be set}}\"\nexport {name}\n"
+            ));
+        } else {
+            script.push_str(&format!("export {name}={}\n", sh_word(value)));
+        }
+    }
+    for name in secret_arg_names(command) {
+        script.push_str(&format!(
+            ": \"${{{name}:?{name} is a secret argument and must be set}}\"\n"
+        ));
+    }
+    script
+        .push_str("export LD_LIBRARY_PATH=\"$SETUP_DIR${LD_LIBRARY_PATH:+:$LD_LIBRARY_PATH}\"\n\n");
+
+    let command: Vec<_> = command.iter().map(|word| sh_word(word)).collect();
+    script.push_str(&format!("exec {}\n", command.join(" ")));
+    script
+}
+
+fn ps1_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn ps1_word(word: &str) -> String {
+    let pieces: Vec<String> = pieces(word)
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Literal(text) => ps1_quote(text),
+            Piece::Input => "$InputPath".to_owned(),
+            Piece::SetupDir => "$SetupDir".to_owned(),
+            Piece::SecretArg(n) => format!("$env:{SECRET_ARG_PREFIX}{n}"),
+        })
+        .collect();
+    match pieces.len() {
+        0 => "''".to_owned(),
+        1 => pieces.into_iter().next().unwrap_or_default(),
+        _ => format!("({})", pieces.join(" + ")),
+    }
+}
+
+fn render_ps1(
+    report: &CrashReport,
+    input_name: &str,
+    setup_dir: &str,
+    env: &[(&str, String)],
+    command: &[String],
+) -> String {
+    let mut script = String::new();
+    script.push_str(&format!(
+        "# Reproduces {} in {}.\n",
+        comment(&report.crash_type),
+        comment(&report.call_stack_sha256)
+    ));
+    script.push_str("# Usage: repro.ps1 [-InputPath INPUT] [-SetupDir DIR]\n");
+    script.push_str(&format!(
+        "param([string]$InputPath = {}, [string]$SetupDir = {})\n",
+        ps1_quote(input_name),
+        ps1_quote(setup_dir)
+    ));
+    script.push_str("$ErrorActionPreference = 'Stop'\n\n");
+
+    for (name, value) in env {
+        if !is_identifier(name) {
+            warn!("leaving {:?} out of repro script, not an identifier", name);
+        } else if is_secret(name, value) {
+            script.push_str(&format!(
+                "if (-not $env:{name}) {{ throw '{name} is a secret and must be set' }}\n"
+            ));
+        } else {
+            script.push_str(&format!("$env:{name} = {}\n", ps1_word(value)));
+        }
+    }
+    for name in secret_arg_names(command) {
+        script.push_str(&format!(
+            "if (-not $env:{name}) {{ throw '{name} is a secret argument and must be set' }}\n"
+        ));
+    }
+    script.push_str("$env:PATH = \"$SetupDir;$env:PATH\"\n\n");
+
+    let (exe, args) = command.split_first().expect("command includes target_exe");
+    let args: Vec<_> = args.iter().map(|word| ps1_word(word)).collect();
+    script.push_str(&format!("& {} {}\n", ps1_word(exe), args.join(" ")));
+    script.push_str("exit $LASTEXITCODE\n");
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pieces() {
+        let word = format!("{SETUP_DIR_MARKER}/fuzz -input={INPUT_MARKER}");
+        assert_eq!(
+            pieces(&word),
+            vec![
+                Piece::SetupDir,
+                Piece::Literal("/fuzz -input="),
+                Piece::Input
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(sh_word("it's"), r"'it'\''s'");
+        assert_eq!(sh_word(&format!("-a={INPUT_MARKER}")), "'-a='\"$INPUT\"");
+        assert_eq!(ps1_word("it's"), "'it''s'");
+        assert_eq!(
+            ps1_word(&format!("-a={INPUT_MARKER}")),
+            "('-a=' + $InputPath)"
+        );
+    }
+
+    #[test]
+    fn test_redact_args() {
+        let args = [
+            "-timeout=25",
+            "-api_key=hunter2",
+            "--token",
+            "hunter2",
+            "-url=https://x.blob.core.windows.net/c?sig=hunter2",
+            "https://x.blob.core.windows.net/c?sig=hunter2",
+            INPUT_MARKER,
+        ];
+        let redacted = redact_args(args.iter().map(|arg| arg.to_string()).collect());
+        assert_eq!(
+            redacted,
+            [
+                "-timeout=25".to_owned(),
+                format!("-api_key={MARKER}SECRET_ARG_0{MARKER}"),
+                "--token".to_owned(),
+                format!("{MARKER}SECRET_ARG_1{MARKER}"),
+                format!("-url={MARKER}SECRET_ARG_2{MARKER}"),
+                format!("{MARKER}SECRET_ARG_3{MARKER}"),
+                INPUT_MARKER.to_owned(),
+            ]
+        );
+        assert_eq!(
+            pieces(&redacted[1]),
+            vec![Piece::Literal("-api_key="), Piece::SecretArg(0)]
+        );
+    }
+
+    #[test]
+    fn test_secrets_are_not_written() {
+        let report = CrashReport {
+            crash_type: "heap-buffer-overflow".to_owned(),
+            ..Default::default()
+        };
+        let env = vec![
+            ("ASAN_OPTIONS", "detect_leaks=0".to_owned()),
+            ("API_TOKEN", "hunter2".to_owned()),
+            (
+                "DATA",
+                "https://x.blob.core.windows.net/c?sig=hunter2".to_owned(),
+            ),
+        ];
+        let mut command = vec![format!("{SETUP_DIR_MARKER}/fuzz")];
+        command.extend(redact_args(vec![
+            "-password=hunter2".to_owned(),
+            INPUT_MARKER.to_owned(),
+        ]));
+
+        let sh = render_sh(&report, "crash-1", "/setup", &env, &command);
+        assert!(sh.contains("export ASAN_OPTIONS='detect_leaks=0'\n"));
+        assert!(sh.contains(": \"${API_TOKEN:?"));
+        assert!(sh.contains(": \"${SECRET_ARG_0:?"));
+        assert!(
+            sh.contains("exec \"$SETUP_DIR\"'/fuzz' '-password='\"$SECRET_ARG_0\" \"$INPUT\"\n")
+        );
+
+        let ps1 = render_ps1(&report, "crash-1", "/setup", &env, &command);
+        assert!(ps1.contains("$env:ASAN_OPTIONS = 'detect_leaks=0'\n"));
+        assert!(ps1.contains("if (-not $env:DATA)"));
+        assert!(ps1.contains("('-password=' + $env:SECRET_ARG_0)"));
+
+        for script in [sh, ps1] {
+            assert!(!script.contains("hunter2"));
+        }
+    }
+
+    #[test]
+    fn test_untrusted_text_is_contained() {
+        let report = CrashReport {
+            crash_type: "overflow\nrm -rf /".to_owned(),
+            ..Default::default()
+        };
+        let env = vec![("X; rm -rf /", "1".to_owned())];
+        let command = vec!["fuzz".to_owned()];
+
+        for script in [
+            render_sh(&report, "crash-1", "/setup", &env, &command),
+            render_ps1(&report, "crash-1", "/setup", &env, &command),
+        ] {
+            assert!(script.contains("# Reproduces overflow rm -rf / in"));
+            assert!(!script.contains("X;"));
+        }
+    }
+}
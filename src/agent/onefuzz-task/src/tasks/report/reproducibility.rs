@@ -0,0 +1,137 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! A verdict on how reliably each reproduced crash reproduces, for triage
+//! rules which treat flaky crashes differently.
+//!
+//! With `repro_count` set, a crash which reproduced is rerun that many more
+//! times, without retries, and its report's `reproducibility` is set from the
+//! share of reruns which crashed:
+//!
+//! - `reproducible`, if at least `reproducible_min_ratio` crashed, by default
+//!   every rerun.
+//! - `flaky`, if some crashed, and at least `flaky_min_ratio`, by default any.
+//! - `non_reproducing`, otherwise. The report is still saved, since the crash
+//!   was seen once.
+//!
+//! Inputs which don't crash at all, even with retries, are saved as no-repro
+//! reports, with their `reproducibility` set to `non_reproducing` without
+//! being rerun.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::tasks::error::TaskError;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reproducibility {
+    Reproducible,
+    Flaky,
+    NonReproducing,
+}
+
+/// How many times to rerun reproduced crashes, and the shares of reruns
+/// which make each verdict.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReproCheck {
+    pub attempts: u64,
+    reproducible_min_ratio: f64,
+    flaky_min_ratio: f64,
+}
+
+impl ReproCheck {
+    /// The check for `repro_count` reruns, if set, with the ratios defaulting
+    /// to 1 and 0.
+    pub fn from_config(
+        repro_count: Option<u64>,
+        reproducible_min_ratio: Option<f64>,
+        flaky_min_ratio: Option<f64>,
+    ) -> Result<Option<Self>> {
+        let Some(attempts) = repro_count else {
+            if reproducible_min_ratio.is_some() || flaky_min_ratio.is_some() {
+                return Err(TaskError::Configuration(anyhow!(
+                    "reproducible_min_ratio and flaky_min_ratio require repro_count"
+                ))
+                .into());
+            }
+            return Ok(None);
+        };
+        if attempts == 0 {
+            return Err(TaskError::Configuration(anyhow!("repro_count must be at least 1")).into());
+        }
+
+        let reproducible_min_ratio = reproducible_min_ratio.unwrap_or(1.0);
+        let flaky_min_ratio = flaky_min_ratio.unwrap_or(0.0);
+        for (name, ratio) in [
+            ("reproducible_min_ratio", reproducible_min_ratio),
+            ("flaky_min_ratio", flaky_min_ratio),
+        ] {
+            if !(0.0..=1.0).contains(&ratio) {
+                return Err(TaskError::Configuration(anyhow!(
+                    "{} must be between 0 and 1, got {}",
+                    name,
+                    ratio
+                ))
+                .into());
+            }
+        }
+        if flaky_min_ratio > reproducible_min_ratio {
+            return Err(TaskError::Configuration(anyhow!(
+                "flaky_min_ratio must not be greater than reproducible_min_ratio"
+            ))
+            .into());
+        }
+
+        Ok(Some(Self {
+            attempts,
+            reproducible_min_ratio,
+            flaky_min_ratio,
+        }))
+    }
+
+    /// The verdict for a crash which crashed `crashes` of `attempts` reruns.
+    pub fn verdict(&self, crashes: u64) -> Reproducibility {
+        let ratio = crashes as f64 / self.attempts as f64;
+        if crashes > 0 && ratio >= self.reproducible_min_ratio {
+            Reproducibility::Reproducible
+        } else if crashes > 0 && ratio >= self.flaky_min_ratio {
+            Reproducibility::Flaky
+        } else {
+            Reproducibility::NonReproducing
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdict() -> Result<()> {
+        let check = ReproCheck::from_config(Some(10), None, None)?.unwrap();
+        assert_eq!(check.verdict(10), Reproducibility::Reproducible);
+        assert_eq!(check.verdict(9), Reproducibility::Flaky);
+        assert_eq!(check.verdict(1), Reproducibility::Flaky);
+        assert_eq!(check.verdict(0), Reproducibility::NonReproducing);
+
+        let check = ReproCheck::from_config(Some(10), Some(0.8), Some(0.3))?.unwrap();
+        assert_eq!(check.verdict(8), Reproducibility::Reproducible);
+        assert_eq!(check.verdict(3), Reproducibility::Flaky);
+        assert_eq!(check.verdict(2), Reproducibility::NonReproducing);
+
+        // Even a threshold of 0 needs a crashing rerun.
+        let check = ReproCheck::from_config(Some(5), Some(0.0), None)?.unwrap();
+        assert_eq!(check.verdict(0), Reproducibility::NonReproducing);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert_eq!(ReproCheck::from_config(None, None, None).unwrap(), None);
+        assert!(ReproCheck::from_config(Some(0), None, None).is_err());
+        assert!(ReproCheck::from_config(None, Some(0.5), None).is_err());
+        assert!(ReproCheck::from_config(Some(5), Some(1.5), None).is_err());
+        assert!(ReproCheck::from_config(Some(5), Some(0.5), Some(0.6)).is_err());
+    }
+}
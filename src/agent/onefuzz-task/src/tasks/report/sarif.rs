@@ -0,0 +1,169 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Conversion of crash reports to [SARIF](https://sarifweb.azurewebsites.net/) 2.1.0,
+//! for ingestion by code scanning tools.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use stacktrace_parser::{parse_call_stack, StackEntry};
+
+use super::crash_report::CrashReport;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+/// Serialization format for crash reports.
+///
+/// The native report is always written, since other tasks read it. No-repro
+/// reports are only written natively, since they do not describe a finding.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// The OneFuzz crash report JSON.
+    #[default]
+    #[serde(alias = "native")]
+    Native,
+    /// A SARIF log with a single result, next to the native report.
+    #[serde(alias = "sarif")]
+    Sarif,
+}
+
+impl CrashReport {
+    pub fn sarif_blob_name(&self) -> String {
+        format!("{}.sarif", self.input_sha256)
+    }
+
+    pub fn unique_sarif_blob_name(&self) -> String {
+        format!("{}.sarif", self.unique_sha256())
+    }
+
+    /// The report as a SARIF log.
+    ///
+    /// The rule ID is derived from the crash type, and the location from the top
+    /// stack frame with source information.
+    pub fn to_sarif(&self) -> Value {
+        let rule_id = sarif_rule_id(&self.crash_type);
+
+        let mut result = json!({
+            "ruleId": rule_id,
+            "level": "error",
+            "message": { "text": self.crash_site },
+            "partialFingerprints": { "callStackSha256/v1": self.call_stack_sha256 },
+        });
+
+        if let Some(location) = top_frame_location(&self.call_stack) {
+            result["locations"] = json!([location]);
+        }
+
+        if let Some(classification) = &self.classification {
+            result["properties"]["classification"] = json!(classification);
+        }
+
+        if let Some(severity) = &self.severity {
+            result["properties"]["severity"] = json!(severity);
+        }
+
+        json!({
+            "$schema": SARIF_SCHEMA,
+            "version": SARIF_VERSION,
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": self.tool_name.as_deref().unwrap_or("onefuzz"),
+                        "version": self.tool_version,
+                        "rules": [{
+                            "id": rule_id,
+                            "shortDescription": { "text": self.crash_type },
+                        }],
+                    },
+                },
+                "results": [result],
+            }],
+        })
+    }
+}
+
+/// Rule IDs must be stable identifiers, so normalize the free-form crash type,
+/// e.g. `heap-buffer-overflow READ 4` becomes `heap-buffer-overflow-read-4`.
+fn sarif_rule_id(crash_type: &str) -> String {
+    let id = crash_type
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+
+    if id.is_empty() {
+        "unknown".to_owned()
+    } else {
+        id
+    }
+}
+
+fn top_frame_location(call_stack: &[String]) -> Option<Value> {
+    let stack = parse_call_stack(&call_stack.join("\n")).ok()?;
+    let frame = stack
+        .iter()
+        .find(|frame| frame.source_file_path.is_some())
+        .or_else(|| stack.iter().find(|frame| frame.function_name.is_some()))?;
+
+    let mut location = json!({});
+    if let Some(path) = &frame.source_file_path {
+        location["physicalLocation"] = physical_location(path, frame);
+    }
+    if let Some(function_name) = &frame.function_name {
+        location["logicalLocations"] = json!([{
+            "fullyQualifiedName": function_name,
+            "kind": "function",
+        }]);
+    }
+    Some(location)
+}
+
+fn physical_location(path: &str, frame: &StackEntry) -> Value {
+    let mut location = json!({
+        "artifactLocation": { "uri": path },
+    });
+    if let Some(line) = frame.source_file_line {
+        location["region"] = json!({ "startLine": line });
+    }
+    location
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sarif_rule_id() {
+        assert_eq!(
+            sarif_rule_id("heap-buffer-overflow READ 4"),
+            "heap-buffer-overflow-read-4"
+        );
+        assert_eq!(sarif_rule_id("SEGV"), "segv");
+        assert_eq!(sarif_rule_id(""), "unknown");
+    }
+
+    #[test]
+    fn test_to_sarif() {
+        let report = CrashReport {
+            crash_type: "heap-buffer-overflow".to_owned(),
+            crash_site: "AddressSanitizer: heap-buffer-overflow in parse".to_owned(),
+            call_stack: vec![
+                "#0 0x4f8a21 in parse /src/fuzz.c:10:5".to_owned(),
+                "#1 0x4f8b00 in LLVMFuzzerTestOneInput /src/fuzz.c:20:3".to_owned(),
+            ],
+            call_stack_sha256: "abc".to_owned(),
+            ..Default::default()
+        };
+
+        let sarif = report.to_sarif();
+        let result = &sarif["runs"][0]["results"][0];
+
+        assert_eq!(sarif["version"], SARIF_VERSION);
+        assert_eq!(result["ruleId"], "heap-buffer-overflow");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "/src/fuzz.c");
+        assert_eq!(location["region"]["startLine"], 10);
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::collections::HashMap;
+
+use onefuzz_telemetry::{Event::crash_severity, EventData};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::crash_report::{CrashReport, CrashTestResult};
+
+/// Triage priority of a reproduced crash.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+}
+
+/// Whether the faulting access was a read or a write, if the sanitizer said.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "READ",
+            Self::Write => "WRITE",
+        }
+    }
+}
+
+// Memory corruption, which is high severity for writes and medium for reads.
+const MEMORY_CORRUPTION: &[&str] = &[
+    "access-violation",
+    "container-overflow",
+    "dynamic-stack-buffer-overflow",
+    "global-buffer-overflow",
+    "heap-buffer-overflow",
+    "heap-use-after-free",
+    "SEGV",
+    "stack-buffer-overflow",
+    "stack-buffer-underflow",
+    "stack-use-after-return",
+    "stack-use-after-scope",
+    "use-after-poison",
+];
+
+const HIGH: &[&str] = &["attempting double-free", "double-free", "bad-free"];
+
+const LOW: &[&str] = &[
+    "ABRT",
+    "deadly signal",
+    "detected memory leaks",
+    "leak",
+    "out-of-memory",
+    "stack-overflow",
+    "timeout",
+];
+
+/// Assigns a severity to each reproduced crash from its sanitizer crash type.
+///
+/// Memory corruption is `high` for writes and `medium` for reads or when the
+/// access is unknown, double and invalid frees are `high`, and leaks, stack
+/// exhaustion, OOMs, timeouts and aborts are `low`. Other crash types are
+/// `medium`.
+///
+/// Overrides are keyed by crash type, such as `"heap-buffer-overflow"`, or by
+/// crash type and access, such as `"heap-buffer-overflow READ"`, which takes
+/// precedence.
+#[derive(Debug, Default)]
+pub struct SeverityClassifier {
+    overrides: HashMap<String, Severity>,
+}
+
+impl SeverityClassifier {
+    pub fn new(overrides: HashMap<String, Severity>) -> Self {
+        Self { overrides }
+    }
+
+    pub fn classify(&self, report: &CrashReport) -> Severity {
+        let crash_type = report.crash_type.as_str();
+        let access = report.asan_log.as_deref().and_then(access);
+
+        if let Some(access) = access {
+            let key = format!("{crash_type} {}", access.as_str());
+            if let Some(severity) = self.overrides.get(&key) {
+                return *severity;
+            }
+        }
+        if let Some(severity) = self.overrides.get(crash_type) {
+            return *severity;
+        }
+
+        if MEMORY_CORRUPTION.contains(&crash_type) {
+            match access {
+                Some(Access::Write) => Severity::High,
+                _ => Severity::Medium,
+            }
+        } else if HIGH.contains(&crash_type) {
+            Severity::High
+        } else if LOW.contains(&crash_type) {
+            Severity::Low
+        } else {
+            Severity::Medium
+        }
+    }
+
+    /// Set the severity of the report in `result`, if it reproduced, and count
+    /// it in telemetry.
+    pub fn apply(&self, result: &mut CrashTestResult) {
+        if let CrashTestResult::CrashReport(report) = result {
+            let severity = self.classify(report);
+            report.severity = Some(severity);
+
+            event!(crash_severity; EventData::Severity = severity.as_str());
+            metric!(crash_severity; 1.0; EventData::Severity = severity.as_str());
+        }
+    }
+}
+
+fn access(asan_log: &str) -> Option<Access> {
+    lazy_static::lazy_static! {
+        // Matches both `READ of size 4` and `caused by a WRITE memory access`.
+        static ref ACCESS_RE: Regex = Regex::new(r"\b(READ|WRITE) (of size|memory access)").unwrap();
+    }
+
+    let captures = ACCESS_RE.captures(asan_log)?;
+    match &captures[1] {
+        "READ" => Some(Access::Read),
+        _ => Some(Access::Write),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(crash_type: &str, asan_log: Option<&str>) -> CrashReport {
+        CrashReport {
+            crash_type: crash_type.to_owned(),
+            asan_log: asan_log.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_builtin_severity() {
+        let classifier = SeverityClassifier::default();
+
+        let write = Some("WRITE of size 4 at 0x602000000011 thread T0");
+        let read = Some("READ of size 1 at 0x602000000011 thread T0");
+        let segv = Some("==1==The signal is caused by a WRITE memory access.");
+
+        let high = Severity::High;
+        let medium = Severity::Medium;
+        let low = Severity::Low;
+        assert_eq!(
+            classifier.classify(&report("heap-buffer-overflow", write)),
+            high
+        );
+        assert_eq!(
+            classifier.classify(&report("heap-buffer-overflow", read)),
+            medium
+        );
+        assert_eq!(
+            classifier.classify(&report("heap-buffer-overflow", None)),
+            medium
+        );
+        assert_eq!(classifier.classify(&report("SEGV", segv)), high);
+        assert_eq!(classifier.classify(&report("double-free", None)), high);
+        assert_eq!(
+            classifier.classify(&report("detected memory leaks", None)),
+            low
+        );
+        assert_eq!(classifier.classify(&report("something-new", None)), medium);
+    }
+
+    #[test]
+    fn test_severity_overrides() {
+        let classifier = SeverityClassifier::new(HashMap::from([
+            ("heap-buffer-overflow".to_owned(), Severity::Low),
+            ("heap-buffer-overflow WRITE".to_owned(), Severity::Medium),
+        ]));
+
+        let write = Some("WRITE of size 4 at 0x602000000011 thread T0");
+        let read = Some("READ of size 1 at 0x602000000011 thread T0");
+        assert_eq!(
+            classifier.classify(&report("heap-buffer-overflow", write)),
+            Severity::Medium
+        );
+        assert_eq!(
+            classifier.classify(&report("heap-buffer-overflow", read)),
+            Severity::Low
+        );
+        assert_eq!(
+            classifier.classify(&report("double-free", None)),
+            Severity::High
+        );
+    }
+}
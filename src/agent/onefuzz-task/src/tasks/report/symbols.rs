@@ -0,0 +1,95 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! On-demand symbol fetching for stripped targets.
+//!
+//! Rather than downloading symbols ourselves, we point the symbolizers at the
+//! server: `llvm-symbolizer` reads `DEBUGINFOD_URLS`, and DbgHelp reads
+//! `_NT_SYMBOL_PATH`. Both cache what they fetch, and report raw addresses
+//! for frames whose symbols can't be found.
+//!
+//! With `check_debugger` on Windows, DbgHelp runs in the agent's own process
+//! rather than the target's, so `_NT_SYMBOL_PATH` is also set on the agent.
+//! The Linux debugger symbolizes frames from the target's own symbol tables,
+//! so a symbol server can't help there.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use reqwest::Url;
+
+const SYMBOL_CACHE_DIR: &str = "symbol_cache";
+
+const DEBUGINFOD_URLS: &str = "DEBUGINFOD_URLS";
+const DEBUGINFOD_CACHE_PATH: &str = "DEBUGINFOD_CACHE_PATH";
+const NT_SYMBOL_PATH: &str = "_NT_SYMBOL_PATH";
+
+/// Where fetched symbols are cached, within the task working dir.
+pub fn symbol_cache_dir() -> Result<PathBuf> {
+    Ok(std::env::current_dir()?.join(SYMBOL_CACHE_DIR))
+}
+
+/// `target_env`, plus the variables needed to fetch symbols from `symbol_server`.
+///
+/// Variables already set in `target_env` take precedence.
+pub fn with_symbol_server(
+    target_env: &HashMap<String, String>,
+    symbol_server: Option<&Url>,
+    cache_dir: &Path,
+) -> HashMap<String, String> {
+    let mut env = target_env.clone();
+    let symbol_server = match symbol_server {
+        Some(url) => url,
+        None => return env,
+    };
+
+    let cache = cache_dir.to_string_lossy();
+    let vars = [
+        (DEBUGINFOD_URLS, symbol_server.to_string()),
+        (DEBUGINFOD_CACHE_PATH, cache.to_string()),
+        (NT_SYMBOL_PATH, format!("srv*{cache}*{symbol_server}")),
+    ];
+    for (name, value) in vars {
+        env.entry(name.to_owned()).or_insert(value);
+    }
+
+    env
+}
+
+/// Set the symbol path from `target_env` on the agent, for the debugger.
+/// Does nothing except on Windows.
+pub fn apply_to_debugger(target_env: &HashMap<String, String>) {
+    if !cfg!(target_os = "windows") {
+        return;
+    }
+    if let Some(symbol_path) = target_env.get(NT_SYMBOL_PATH) {
+        std::env::set_var(NT_SYMBOL_PATH, symbol_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_symbol_server() -> Result<()> {
+        let url = Url::parse("https://symbols.example.com/")?;
+        let cache_dir = Path::new("/task/symbol_cache");
+
+        let target_env = HashMap::from([(DEBUGINFOD_URLS.to_owned(), "custom".to_owned())]);
+        let env = with_symbol_server(&target_env, Some(&url), cache_dir);
+
+        assert_eq!(env[DEBUGINFOD_URLS], "custom");
+        assert_eq!(env[DEBUGINFOD_CACHE_PATH], "/task/symbol_cache");
+        assert_eq!(
+            env[NT_SYMBOL_PATH],
+            "srv*/task/symbol_cache*https://symbols.example.com/"
+        );
+
+        assert_eq!(with_symbol_server(&target_env, None, cache_dir), target_env);
+        Ok(())
+    }
+}
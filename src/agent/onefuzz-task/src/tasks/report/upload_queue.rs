@@ -0,0 +1,301 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Bounded queue between crash report generation and upload.
+//!
+//! When uploads fall behind, generating the next report blocks until there is
+//! space in the queue, rather than buffering reports in memory without limit.
+//!
+//! Reports of inputs from a queue are only known to be saved once they are
+//! uploaded, so their senders wait for the upload, and the input's message is
+//! only deleted if it succeeded. See [`ReportSender::wait_for_upload`].
+
+use std::{collections::HashMap, time::SystemTime};
+
+use anyhow::{Context, Result};
+use onefuzz::syncdir::SyncedDir;
+use onefuzz_result::job_result::TaskJobResultClient;
+use tokio::sync::{mpsc, oneshot};
+
+use super::{
+    crash_report::CrashTestResult, dedup_state::DedupState, notification::ReportNotifier,
+    report_metrics::ReportMetrics, sarif::ReportFormat,
+};
+
+pub const DEFAULT_UPLOAD_QUEUE_DEPTH: usize = 64;
+
+struct QueuedReport {
+    report: CrashTestResult,
+    // When the input was saved, for report metrics.
+    input_saved: Option<SystemTime>,
+    // Told the result of the upload, if the sender waits for it.
+    uploaded: Option<oneshot::Sender<Result<()>>>,
+}
+
+#[derive(Clone)]
+pub struct ReportSender {
+    sender: mpsc::Sender<QueuedReport>,
+    wait_for_upload: bool,
+}
+
+impl ReportSender {
+    /// Wait for each report to be uploaded before `send` returns, and return
+    /// the upload's error if it fails. A failed upload then no longer stops the
+    /// uploader, since the caller handles it.
+    pub fn wait_for_upload(self, wait_for_upload: bool) -> Self {
+        Self {
+            wait_for_upload,
+            ..self
+        }
+    }
+
+    /// Queue a report for upload, waiting if the queue is full. `input_saved`
+    /// is when the input was saved, if known, for report metrics.
+    pub async fn send(
+        &self,
+        report: CrashTestResult,
+        input_saved: Option<SystemTime>,
+    ) -> Result<()> {
+        let (uploaded, upload_result) = if self.wait_for_upload {
+            let (uploaded, upload_result) = oneshot::channel();
+            (Some(uploaded), Some(upload_result))
+        } else {
+            (None, None)
+        };
+
+        self.sender
+            .send(QueuedReport {
+                report,
+                input_saved,
+                uploaded,
+            })
+            .await
+            .map_err(|_| format_err!("report uploader stopped"))?;
+
+        match upload_result {
+            Some(upload_result) => upload_result
+                .await
+                .map_err(|_| format_err!("report uploader stopped"))?,
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct ReportUploader<'a> {
+    receiver: mpsc::Receiver<QueuedReport>,
+    report_format: ReportFormat,
+    unique_reports: &'a Option<SyncedDir>,
+    reports: &'a Option<SyncedDir>,
+    no_repro: &'a Option<SyncedDir>,
+    job_result_client: Option<TaskJobResultClient>,
+    dedup_state: Option<DedupState>,
+    blob_metadata: HashMap<String, String>,
+    date_partitioning: bool,
+    notifier: Option<ReportNotifier>,
+    report_metrics: Option<ReportMetrics>,
+}
+
+impl<'a> ReportUploader<'a> {
+    /// Set `metadata` on each uploaded report blob.
+    pub fn blob_metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.blob_metadata = metadata;
+        self
+    }
+
+    /// Prefix each uploaded report blob with the date it is saved.
+    pub fn date_partitioning(mut self, date_partitioning: bool) -> Self {
+        self.date_partitioning = date_partitioning;
+        self
+    }
+
+    /// Skip saving unique reports for stack hashes that `dedup_state` says an
+    /// earlier run already reported.
+    pub fn dedup_state(mut self, dedup_state: Option<DedupState>) -> Self {
+        self.dedup_state = dedup_state;
+        self
+    }
+
+    /// Send a message to `notifier` for each crash report uploaded.
+    pub fn notifier(mut self, notifier: Option<ReportNotifier>) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Record each report uploaded in `report_metrics`.
+    pub fn report_metrics(mut self, report_metrics: Option<ReportMetrics>) -> Self {
+        self.report_metrics = report_metrics;
+        self
+    }
+
+    /// Upload queued reports until every `ReportSender` has been dropped, then
+    /// save the dedup state. The dedup state is also saved periodically, so
+    /// that it survives the task being killed.
+    pub async fn run(mut self) -> Result<()> {
+        while let Some(QueuedReport {
+            report,
+            input_saved,
+            uploaded,
+        }) = self.receiver.recv().await
+        {
+            let result = self.upload(&report).await;
+            if let (Ok(()), Some(report_metrics)) = (&result, &mut self.report_metrics) {
+                let reproduced = matches!(report, CrashTestResult::CrashReport(_));
+                report_metrics.record(input_saved, reproduced);
+            }
+            match uploaded {
+                Some(uploaded) => {
+                    // The sender may have stopped waiting, if it was cancelled.
+                    let _ = uploaded.send(result);
+                }
+                None => result?,
+            }
+
+            if let Some(dedup_state) = &mut self.dedup_state {
+                if let Err(err) = dedup_state.save_if_due().await {
+                    warn!("{:?}", err);
+                }
+            }
+        }
+
+        if let Some(dedup_state) = &mut self.dedup_state {
+            dedup_state.save().await?;
+        }
+        Ok(())
+    }
+
+    async fn upload(&mut self, report: &CrashTestResult) -> Result<()> {
+        let no_unique_reports = None;
+        let unique_reports = if self.previously_reported(report) {
+            &no_unique_reports
+        } else {
+            self.unique_reports
+        };
+
+        let report_url = report
+            .save_with_format(
+                self.report_format,
+                unique_reports,
+                self.reports,
+                self.no_repro,
+                &self.job_result_client,
+                &self.blob_metadata,
+                self.date_partitioning,
+            )
+            .await
+            .context("saving report failed")?;
+        if let Some(notifier) = &self.notifier {
+            notifier.notify(report, report_url);
+        }
+        Ok(())
+    }
+
+    fn previously_reported(&mut self, report: &CrashTestResult) -> bool {
+        match (&mut self.dedup_state, report) {
+            (Some(dedup_state), CrashTestResult::CrashReport(report)) => {
+                let seen = dedup_state.check(report.unique_sha256());
+                if seen {
+                    info!(
+                        "skipping unique report for crash reported by an earlier run: {}",
+                        report.unique_blob_name()
+                    );
+                }
+                seen
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Create a queue holding up to `depth` reports, at least one.
+pub fn upload_queue<'a>(
+    depth: Option<usize>,
+    report_format: ReportFormat,
+    unique_reports: &'a Option<SyncedDir>,
+    reports: &'a Option<SyncedDir>,
+    no_repro: &'a Option<SyncedDir>,
+    job_result_client: Option<TaskJobResultClient>,
+) -> (ReportSender, ReportUploader<'a>) {
+    let depth = depth.unwrap_or(DEFAULT_UPLOAD_QUEUE_DEPTH).max(1);
+    let (sender, receiver) = mpsc::channel(depth);
+    let uploader = ReportUploader {
+        receiver,
+        report_format,
+        unique_reports,
+        reports,
+        no_repro,
+        job_result_client,
+        dedup_state: None,
+        blob_metadata: HashMap::new(),
+        date_partitioning: false,
+        notifier: None,
+        report_metrics: None,
+    };
+    let sender = ReportSender {
+        sender,
+        wait_for_upload: false,
+    };
+    (sender, uploader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::report::crash_report::CrashReport;
+
+    fn report() -> CrashTestResult {
+        CrashTestResult::from(CrashReport {
+            input_sha256: "abc".into(),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_upload() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let reports = Some(SyncedDir {
+            local_path: dir.path().to_owned(),
+            remote_path: None,
+        });
+        // Saving reports under a file, rather than a directory, fails.
+        let file = dir.path().join("file");
+        tokio::fs::write(&file, "").await?;
+        let broken = Some(SyncedDir {
+            local_path: file,
+            remote_path: None,
+        });
+
+        let (sender, uploader) =
+            upload_queue(Some(1), ReportFormat::Native, &None, &reports, &None, None);
+        let sender = sender.wait_for_upload(true);
+        let saved = dir.path().join("abc.json");
+        let send = async move {
+            sender.send(report(), None).await?;
+            // Sending waits until the report is saved.
+            assert!(saved.is_file());
+            anyhow::Ok(())
+        };
+        let (sent, uploaded) = tokio::join!(send, uploader.run());
+        sent?;
+        uploaded?;
+
+        let (sender, uploader) =
+            upload_queue(Some(1), ReportFormat::Native, &None, &broken, &None, None);
+        let sender = sender.wait_for_upload(true);
+        let send = async move {
+            assert!(sender.send(report(), None).await.is_err());
+            // The uploader keeps running after the failure it returned.
+            assert!(sender.send(report(), None).await.is_err());
+        };
+        let ((), uploaded) = tokio::join!(send, uploader.run());
+        uploaded?;
+
+        // Without a waiting sender, a failed upload stops the uploader.
+        let (sender, uploader) =
+            upload_queue(Some(1), ReportFormat::Native, &None, &broken, &None, None);
+        let send = async move { sender.send(report(), None).await };
+        let (sent, uploaded) = tokio::join!(send, uploader.run());
+        sent?;
+        assert!(uploaded.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Posting crash reports to a webhook as they are generated.
+//!
+//! Each report is posted in the background, with retries, so that a slow or
+//! failing webhook never delays saving reports to storage. At most
+//! `MAX_PENDING` reports are posted at once, and further reports are dropped
+//! until one finishes. Reports still being posted when the task exits are
+//! lost.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use onefuzz::{auth::Secret, sha256};
+use reqwest::{header::CONTENT_TYPE, Client, Url};
+use reqwest_retry::SendRetry;
+use tokio::{sync::Semaphore, task::JoinHandle};
+
+use super::crash_report::CrashTestResult;
+
+/// Header holding `sha256=<hex>`, the HMAC-SHA256 of the request body keyed
+/// with the webhook secret.
+pub const SIGNATURE_HEADER: &str = "X-Onefuzz-Signature";
+
+/// Most reports posted at once.
+pub const MAX_PENDING: usize = 16;
+
+/// Timeout of each attempt to post a report.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+pub struct ReportWebhook {
+    client: Client,
+    url: Url,
+    secret: Option<Secret<String>>,
+    pending: Arc<Semaphore>,
+}
+
+impl ReportWebhook {
+    pub fn new(url: Url, secret: Option<Secret<String>>) -> Self {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("webhook client");
+        Self {
+            client,
+            url,
+            secret,
+            pending: Arc::new(Semaphore::new(MAX_PENDING)),
+        }
+    }
+
+    pub fn from_config(url: Option<&Url>, secret: Option<&Secret<String>>) -> Option<Self> {
+        url.map(|url| Self::new(url.clone(), secret.cloned()))
+    }
+
+    /// Post `report` in the background, logging rather than returning errors,
+    /// and return the handle of the post, unless `report` was dropped.
+    pub fn notify(&self, report: &CrashTestResult) -> Option<JoinHandle<()>> {
+        let Ok(permit) = self.pending.clone().try_acquire_owned() else {
+            warn!(
+                "dropping report, {} already being sent to webhook",
+                MAX_PENDING
+            );
+            return None;
+        };
+
+        let body = match serde_json::to_vec(report) {
+            Ok(body) => body,
+            Err(err) => {
+                warn!("unable to serialize report for webhook: {:?}", err);
+                return None;
+            }
+        };
+
+        let webhook = self.clone();
+        Some(tokio::spawn(async move {
+            if let Err(err) = webhook.post(body).await {
+                warn!("unable to send report to webhook: {:?}", err);
+            }
+            drop(permit);
+        }))
+    }
+
+    async fn post(&self, body: Vec<u8>) -> Result<()> {
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret.expose_ref(), &body));
+        }
+
+        request
+            .body(body)
+            .send_retry_default()
+            .await
+            .context("posting report to webhook failed")?
+            .error_for_status()
+            .context("webhook rejected report")?;
+
+        Ok(())
+    }
+}
+
+/// Value of the [`SIGNATURE_HEADER`] for `body`.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    format!("sha256={}", sha256::hmac(secret, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::tasks::report::crash_report::NoCrash;
+
+    // Accept one request, and return its head and body.
+    async fn receive(listener: TcpListener) -> Result<(String, Vec<u8>)> {
+        let (mut stream, _) = listener.accept().await?;
+        let mut request = vec![];
+        let mut buf = [0; 4096];
+        loop {
+            let len = stream.read(&mut buf).await?;
+            if len == 0 {
+                bail!("connection closed before the request was complete");
+            }
+            request.extend_from_slice(&buf[..len]);
+
+            let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+                continue;
+            };
+            let head = String::from_utf8(request[..end].to_vec())?.to_lowercase();
+            let content_length: usize = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .context("missing content-length")?
+                .trim()
+                .parse()?;
+            if request.len() >= end + 4 + content_length {
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                    .await?;
+                return Ok((head, request[end + 4..].to_vec()));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notify_signs_report() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = Url::parse(&format!("http://{}/reports", listener.local_addr()?))?;
+        let server = tokio::spawn(receive(listener));
+
+        let webhook = ReportWebhook::new(url, Some("secret".to_owned().into()));
+        let report = CrashTestResult::NoRepro(Box::new(NoCrash {
+            input_sha256: "abc".to_owned(),
+            input_blob: None,
+            executable: PathBuf::from("fuzz.exe"),
+            task_id: Uuid::nil(),
+            job_id: Uuid::nil(),
+            tries: 1,
+            error: None,
+            source_revision: None,
+            reproducibility: None,
+        }));
+        webhook.notify(&report).unwrap().await?;
+
+        let (head, body) = server.await??;
+        assert_eq!(body, serde_json::to_vec(&report)?);
+        let expected = format!(
+            "{}: {}",
+            SIGNATURE_HEADER.to_lowercase(),
+            signature("secret", &body)
+        );
+        assert!(head.lines().any(|line| line == expected), "{head}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
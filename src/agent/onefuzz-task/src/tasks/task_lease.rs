@@ -0,0 +1,258 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Detecting a task that is running on more than one machine at once.
+//!
+//! Each machine registers the task by taking a lease on
+//! `{job_id}/{task_id}/task.lease` in the task's `logs` container, and renews
+//! it while the task runs. The blob names the machine holding the lease. A
+//! machine that finds the lease held by another machine applies the
+//! `on_duplicate_task` policy. A machine that stops renewing, for instance
+//! because it crashed, loses the lease once it expires, so a rerun of the task
+//! elsewhere isn't mistaken for a duplicate for long.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use onefuzz::blob::{BlobContainerUrl, BlobUrl};
+use reqwest::{RequestBuilder, StatusCode, Url};
+use reqwest_retry::{RetryCheck, SendRetry, DEFAULT_RETRY_PERIOD, MAX_RETRY_ATTEMPTS};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::tasks::{config::CommonConfig, error::TaskError};
+
+const LEASE_DURATION: Duration = Duration::from_secs(60);
+
+// Well within `LEASE_DURATION`, so that a slow renewal doesn't lose the lease.
+const RENEW_PERIOD: Duration = Duration::from_secs(20);
+
+// Leases need a storage service version header on each request.
+const STORAGE_VERSION: &str = "2021-08-06";
+
+/// What to do when the task is already running on another machine.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum DuplicateTaskPolicy {
+    /// Log a warning, and run the task anyway.
+    Warn,
+
+    /// Fail the task, without running it.
+    Fail,
+}
+
+/// The machine holding the lease, saved in the lease blob.
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+struct Registration {
+    task_id: Uuid,
+    machine_id: Uuid,
+    machine_name: String,
+}
+
+/// The lease on the task, renewed in the background until dropped.
+#[derive(Debug)]
+pub struct TaskLease {
+    renewal: JoinHandle<()>,
+}
+
+impl Drop for TaskLease {
+    fn drop(&mut self) {
+        // Left to expire, rather than released, since the task may be exiting.
+        self.renewal.abort();
+    }
+}
+
+impl TaskLease {
+    /// Register the task in its `logs` container, applying `policy` if another
+    /// machine already holds it. Returns the held lease, if any.
+    ///
+    /// Only failing the task is an error: if the check itself fails, such as
+    /// when the storage account can't be reached, it is logged and skipped.
+    pub async fn register(
+        config: &CommonConfig,
+        policy: DuplicateTaskPolicy,
+    ) -> Result<Option<Self>> {
+        let Some(logs) = &config.logs else {
+            return Err(
+                TaskError::Configuration(anyhow!("on_duplicate_task requires logs")).into(),
+            );
+        };
+        let container = BlobContainerUrl::new(logs.clone())?;
+        if !matches!(container, BlobContainerUrl::BlobContainer(_)) {
+            warn!("skipping duplicate task check, logs is not an Azure blob container");
+            return Ok(None);
+        }
+
+        let registration = Registration {
+            task_id: config.task_id,
+            machine_id: config.machine_identity.machine_id,
+            machine_name: config.machine_name().to_owned(),
+        };
+        let blob = container.blob(format!("{}/{}/task.lease", config.job_id, config.task_id));
+
+        let holder = match acquire(&blob, &registration).await {
+            Ok(Acquired::Held(lease_id)) => {
+                let renewal = tokio::spawn(renew(blob, lease_id));
+                return Ok(Some(Self { renewal }));
+            }
+            Ok(Acquired::HeldBy(holder)) => holder,
+            Err(err) => {
+                warn!("unable to check for duplicate task: {:?}", err);
+                return Ok(None);
+            }
+        };
+
+        let message = format!(
+            "task {} is already running on machine {} ({})",
+            config.task_id, holder.machine_id, holder.machine_name
+        );
+        match policy {
+            DuplicateTaskPolicy::Warn => {
+                warn!("{}", message);
+                Ok(None)
+            }
+            DuplicateTaskPolicy::Fail => Err(TaskError::Configuration(anyhow!(message)).into()),
+        }
+    }
+}
+
+enum Acquired {
+    /// This machine holds the lease, with the given id.
+    Held(String),
+
+    /// Another machine holds the lease.
+    HeldBy(Registration),
+}
+
+async fn acquire(blob: &BlobUrl, registration: &Registration) -> Result<Acquired> {
+    let client = reqwest::Client::new();
+    let body = serde_json::to_vec(registration)?;
+
+    // The lease is on a blob, so create it first, if it isn't there yet.
+    client
+        .put(blob.url())
+        .header("x-ms-blob-type", "BlockBlob")
+        .header("If-None-Match", "*")
+        .body(body.clone())
+        .send_retry(
+            |code| match code {
+                StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED => RetryCheck::Succeed,
+                _ => RetryCheck::Retry,
+            },
+            DEFAULT_RETRY_PERIOD,
+            MAX_RETRY_ATTEMPTS,
+        )
+        .await
+        .context("creating task lease blob")?
+        .error_for_status()
+        .context("creating task lease blob")?;
+
+    let response = lease_request(&client, blob, "acquire")
+        .header("x-ms-lease-duration", LEASE_DURATION.as_secs().to_string())
+        .send_retry(
+            |code| match code {
+                StatusCode::CONFLICT => RetryCheck::Succeed,
+                _ => RetryCheck::Retry,
+            },
+            DEFAULT_RETRY_PERIOD,
+            MAX_RETRY_ATTEMPTS,
+        )
+        .await
+        .context("acquiring task lease")?;
+
+    if response.status() == StatusCode::CONFLICT {
+        let holder: Registration = client
+            .get(blob.url())
+            .header("x-ms-version", STORAGE_VERSION)
+            .send_retry_default()
+            .await
+            .context("reading task lease blob")?
+            .error_for_status()
+            .context("reading task lease blob")?
+            .json()
+            .await
+            .context("parsing task lease blob")?;
+
+        // A restart on the same machine, before its old lease expired.
+        if holder.machine_id == registration.machine_id {
+            return Err(anyhow!(
+                "task lease is still held by an earlier run on this machine"
+            ));
+        }
+        return Ok(Acquired::HeldBy(holder));
+    }
+
+    let lease_id = response
+        .error_for_status()
+        .context("acquiring task lease")?
+        .headers()
+        .get("x-ms-lease-id")
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("no lease id in task lease response"))?;
+
+    // Name this machine as the holder, for the machines that find it held.
+    client
+        .put(blob.url())
+        .header("x-ms-blob-type", "BlockBlob")
+        .header("x-ms-version", STORAGE_VERSION)
+        .header("x-ms-lease-id", &lease_id)
+        .body(body)
+        .send_retry_default()
+        .await
+        .context("saving task lease blob")?
+        .error_for_status()
+        .context("saving task lease blob")?;
+
+    Ok(Acquired::Held(lease_id))
+}
+
+/// Renew the lease on `blob` until aborted.
+async fn renew(blob: BlobUrl, lease_id: String) {
+    let client = reqwest::Client::new();
+    loop {
+        tokio::time::sleep(RENEW_PERIOD).await;
+
+        let renewed = lease_request(&client, &blob, "renew")
+            .header("x-ms-lease-id", &lease_id)
+            .send_retry_default()
+            .await
+            .and_then(|response| Ok(response.error_for_status()?));
+        if let Err(err) = renewed {
+            warn!("unable to renew task lease: {:?}", err);
+        }
+    }
+}
+
+fn lease_request(client: &reqwest::Client, blob: &BlobUrl, action: &str) -> RequestBuilder {
+    client
+        .put(lease_url(blob))
+        .header("x-ms-version", STORAGE_VERSION)
+        .header("x-ms-lease-action", action)
+}
+
+fn lease_url(blob: &BlobUrl) -> Url {
+    let mut url = blob.url();
+    url.query_pairs_mut().append_pair("comp", "lease");
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_url_keeps_sas() -> Result<()> {
+        let container = BlobContainerUrl::parse(
+            "https://account.blob.core.windows.net/logs?sv=2021-08-06&sig=secret",
+        )?;
+        let blob = container.blob("job/task/task.lease");
+        let url = lease_url(&blob);
+
+        assert_eq!(url.path(), "/logs/job/task/task.lease");
+        let query: Vec<_> = url.query_pairs().into_owned().collect();
+        assert!(query.contains(&("sig".to_owned(), "secret".to_owned())));
+        assert!(query.contains(&("comp".to_owned(), "lease".to_owned())));
+        Ok(())
+    }
+}
@@ -1,24 +1,42 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+use crate::tasks::error::TaskError;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use onefuzz::{http::ResponseExt, jitter::delay_with_jitter};
-use reqwest::{Client, Url};
-use reqwest_retry::SendRetry;
+use onefuzz::{
+    http::ResponseExt, input_tester::InputDelivery, jitter::delay_with_jitter,
+    seccomp::SeccompFilter, target_user::TargetUser,
+};
+use onefuzz_telemetry::{Event::input_rejected, EventData};
+use reqwest::{Client, StatusCode, Url};
+use reqwest_retry::{RetryCheck, SendRetry, DEFAULT_RETRY_PERIOD, MAX_RETRY_ATTEMPTS};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
-use tokio::{fs, io};
-
+use std::process::Stdio;
+use std::time::{Duration, Instant, SystemTime};
+use tempfile::TempDir;
+use tokio::{
+    fs,
+    io::{self, AsyncReadExt, AsyncWriteExt},
+};
+use tokio_util::sync::CancellationToken;
+
+/// Download `input_url` to `dst`. The downloaded file keeps the modification
+/// time of the source file or blob, so that it records when the input was
+/// saved rather than when it was downloaded.
 pub async fn download_input(input_url: Url, dst: impl AsRef<Path>) -> Result<PathBuf> {
     let file_name = input_url.path_segments().unwrap().last().unwrap();
     let file_path = dst.as_ref().join(file_name);
 
-    if input_url.scheme().to_lowercase() == "file" {
+    let modified = if input_url.scheme().to_lowercase() == "file" {
         let input_file_path = input_url
             .to_file_path()
             .map_err(|_| anyhow!("Invalid file Url"))?;
         fs::copy(&input_file_path, &file_path).await?;
+        fs::metadata(&input_file_path).await?.modified().ok()
     } else {
         let resp = Client::new()
             .get(input_url)
@@ -28,6 +46,12 @@ pub async fn download_input(input_url: Url, dst: impl AsRef<Path>) -> Result<Pat
             .error_for_status_with_body()
             .await
             .context("download_input status body")?;
+        let last_modified = resp
+            .headers()
+            .get(reqwest::header::LAST_MODIFIED)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| chrono::DateTime::parse_from_rfc2822(value).ok())
+            .map(SystemTime::from);
 
         let body = resp.bytes().await?;
         let mut body = body.as_ref();
@@ -40,6 +64,12 @@ pub async fn download_input(input_url: Url, dst: impl AsRef<Path>) -> Result<Pat
         let mut writer = io::BufWriter::new(file);
 
         io::copy(&mut body, &mut writer).await?;
+        last_modified
+    };
+
+    if let Some(modified) = modified {
+        filetime::set_file_mtime(&file_path, filetime::FileTime::from_system_time(modified))
+            .with_context(|| format!("setting modification time of {}", file_path.display()))?;
     }
     Ok(file_path)
 }
@@ -97,6 +127,499 @@ pub fn default_bool_true() -> bool {
     true
 }
 
+/// Deserialize an optional file mode, given either as an octal string such as
+/// `"0644"`, or as an integer.
+pub fn deserialize_file_mode<'de, D>(deserializer: D) -> std::result::Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum FileMode {
+        Octal(String),
+        Integer(u32),
+    }
+
+    let mode = match Option::<FileMode>::deserialize(deserializer)? {
+        Some(FileMode::Octal(mode)) => {
+            let digits = mode.strip_prefix("0o").unwrap_or(&mode);
+            u32::from_str_radix(digits, 8).map_err(serde::de::Error::custom)?
+        }
+        Some(FileMode::Integer(mode)) => mode,
+        None => return Ok(None),
+    };
+
+    if mode > 0o7777 {
+        return Err(serde::de::Error::custom(format!(
+            "invalid file mode: {mode:o}"
+        )));
+    }
+
+    Ok(Some(mode))
+}
+
+/// Set the permissions of `path` to `mode`, if set.
+///
+/// Only supported on Unix, and otherwise a no-op.
+pub async fn normalize_permissions(path: impl AsRef<Path>, mode: Option<u32>) -> Result<()> {
+    let path = path.as_ref();
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .await
+            .with_context(|| format!("unable to set permissions of {}", path.display()))?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+
+    Ok(())
+}
+
+/// Set the permissions of each file directly within `dir` to `mode`, if set.
+pub async fn normalize_dir_permissions(dir: impl AsRef<Path>, mode: Option<u32>) -> Result<()> {
+    if mode.is_none() {
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(dir.as_ref()).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            normalize_permissions(entry.path(), mode).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Load the setup-relative seccomp `profile`, if set.
+pub async fn load_seccomp_profile(
+    setup_dir: impl AsRef<Path>,
+    profile: Option<&Path>,
+) -> Result<Option<SeccompFilter>> {
+    let Some(profile) = profile else {
+        return Ok(None);
+    };
+
+    let path = try_resolve_setup_relative_path(setup_dir, profile)
+        .await
+        .map_err(TaskError::Configuration)?;
+    let filter = SeccompFilter::load(path).map_err(TaskError::Configuration)?;
+    Ok(Some(filter))
+}
+
+/// Resolve the setup-relative `auxiliary_files`, and copy them into the task's
+/// working directory, where targets run without fresh state.
+pub async fn place_auxiliary_files(
+    setup_dir: impl AsRef<Path>,
+    files: &[PathBuf],
+) -> Result<Vec<PathBuf>> {
+    let setup_dir = setup_dir.as_ref();
+
+    let mut resolved = Vec::with_capacity(files.len());
+    for file in files {
+        let path = try_resolve_setup_relative_path(setup_dir, file)
+            .await
+            .map_err(|err| TaskError::Configuration(err.context("invalid auxiliary_files")))?;
+        if !fs::metadata(&path).await?.is_file() {
+            return Err(TaskError::Configuration(anyhow!(
+                "auxiliary file is not a file: {}",
+                path.display()
+            ))
+            .into());
+        }
+        resolved.push(path);
+    }
+
+    let cwd = std::env::current_dir()?;
+    onefuzz::fs::copy_into_dir(&resolved, cwd).await?;
+    Ok(resolved)
+}
+
+/// Look up the user named `name` to run targets as, if set.
+pub fn resolve_target_user(name: Option<&str>) -> Result<Option<TargetUser>> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+
+    let user = TargetUser::lookup(name).map_err(TaskError::Configuration)?;
+    Ok(Some(user))
+}
+
+/// Check that `target_nice` is a valid nice value, which can be applied with
+/// `check_debugger`.
+pub fn check_target_nice(nice: Option<i32>, check_debugger: bool) -> Result<()> {
+    let Some(nice) = nice else {
+        return Ok(());
+    };
+    // The Windows crash detector starts the target itself, at normal priority.
+    if cfg!(target_os = "windows") && check_debugger {
+        return Err(TaskError::Configuration(anyhow!(
+            "target_nice is not supported with check_debugger on Windows"
+        ))
+        .into());
+    }
+    onefuzz::priority::check_nice(nice)
+        .map_err(|err| TaskError::Configuration(err.context("invalid target_nice")).into())
+}
+
+/// Check that inputs can be delivered `input_via` with `check_debugger`.
+pub fn check_input_via(input_via: InputDelivery, check_debugger: bool) -> Result<()> {
+    // The Windows crash detector starts the target itself, without stdin.
+    if cfg!(target_os = "windows") && check_debugger && input_via == InputDelivery::Stdin {
+        return Err(TaskError::Configuration(anyhow!(
+            "input_via Stdin is not supported with check_debugger on Windows"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Check that `expected_exit_codes` doesn't list 0, which is always clean.
+pub fn check_expected_exit_codes(codes: &[i32]) -> Result<()> {
+    if codes.contains(&0) {
+        return Err(TaskError::Configuration(anyhow!(
+            "expected_exit_codes must not include 0, which is always a clean exit"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Check `stack_hash_frame_count`, which would put every crash in one bucket
+/// if it were 0.
+pub fn check_stack_hash_frame_count(count: Option<usize>) -> Result<()> {
+    if count == Some(0) {
+        return Err(
+            TaskError::Configuration(anyhow!("stack_hash_frame_count must be at least 1")).into(),
+        );
+    }
+    Ok(())
+}
+
+/// Check `target_rss_limit_mb`, which is only supported on Linux.
+pub fn check_target_rss_limit(limit_mb: Option<u64>) -> Result<Option<u64>> {
+    match limit_mb {
+        Some(_) if cfg!(not(target_os = "linux")) => Err(TaskError::Configuration(anyhow!(
+            "target_rss_limit_mb is only supported on Linux"
+        ))
+        .into()),
+        Some(0) => {
+            Err(TaskError::Configuration(anyhow!("target_rss_limit_mb must be at least 1")).into())
+        }
+        limit_mb => Ok(limit_mb),
+    }
+}
+
+/// Blob metadata holding the number of days that a crash artifact should be
+/// kept after it is uploaded, for storage lifecycle rules to act on.
+pub const CRASH_TTL_METADATA: &str = "onefuzz_ttl_days";
+
+/// Metadata to set on uploaded crash artifacts, empty if `ttl_days` is unset.
+pub fn crash_ttl_metadata(ttl_days: Option<u32>) -> HashMap<String, String> {
+    ttl_days
+        .map(|days| (CRASH_TTL_METADATA.to_owned(), days.to_string()))
+        .into_iter()
+        .collect()
+}
+
+/// Give `user` ownership of the directory containing `input`, if set, so that
+/// a target running as that user can read it.
+pub fn grant_input_dir(user: Option<&TargetUser>, input: &Path) -> Result<()> {
+    if let (Some(user), Some(dir)) = (user, input.parent()) {
+        user.grant_dir(dir)?;
+    }
+
+    Ok(())
+}
+
+/// Whether `input` is larger than `max_bytes`, if set, and so should not be
+/// passed to the target. Rejected inputs are logged and counted.
+pub async fn reject_oversized_input(input: &Path, max_bytes: Option<u64>) -> Result<bool> {
+    let Some(max_bytes) = max_bytes else {
+        return Ok(false);
+    };
+
+    let size = fs::metadata(input)
+        .await
+        .with_context(|| format!("unable to get size of input: {}", input.display()))?
+        .len();
+    if size <= max_bytes {
+        return Ok(false);
+    }
+
+    warn!(
+        "skipping input of {} bytes, over the limit of {} bytes: {}",
+        size,
+        max_bytes,
+        input.display()
+    );
+    event!(input_rejected;
+        EventData::Path = input.display().to_string(),
+        EventData::InputSize = size
+    );
+    metric!(input_rejected; 1.0; EventData::InputSize = size);
+    Ok(true)
+}
+
+/// Extension of the sidecar file holding the expected SHA-256 of an input, in
+/// the format written by `sha256sum`.
+pub const CHECKSUM_SIDECAR_EXTENSION: &str = "sha256";
+
+fn is_checksum_sidecar(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new(CHECKSUM_SIDECAR_EXTENSION))
+}
+
+/// Whether `input` should be processed, if `verify` is set: it isn't itself a
+/// checksum sidecar, and it matches the checksum in its sidecar, if it has one.
+/// The sidecar is read from next to `input`, or else next to `input_url`, for
+/// inputs downloaded on their own. Inputs without a sidecar can't be
+/// verified, and are processed as usual.
+pub async fn verify_input_checksum(
+    input: &Path,
+    input_url: Option<&Url>,
+    verify: bool,
+) -> Result<bool> {
+    if !verify {
+        return Ok(true);
+    }
+
+    if is_checksum_sidecar(input) {
+        debug!("skipping checksum sidecar: {}", input.display());
+        return Ok(false);
+    }
+
+    let Some(expected) = read_checksum_sidecar(input, input_url)
+        .await
+        .with_context(|| format!("unable to read checksum of input: {}", input.display()))?
+    else {
+        return Ok(true);
+    };
+    let expected = expected.split_whitespace().next().unwrap_or_default();
+
+    let actual = onefuzz::sha256::digest_file(input).await?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        warn!(
+            "skipping input which doesn't match its checksum, expected {} but got {}: {}",
+            expected,
+            actual,
+            input.display()
+        );
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// The contents of the checksum sidecar of `input`, if it has one.
+async fn read_checksum_sidecar(input: &Path, input_url: Option<&Url>) -> Result<Option<String>> {
+    let mut sidecar = input.as_os_str().to_owned();
+    sidecar.push(".");
+    sidecar.push(CHECKSUM_SIDECAR_EXTENSION);
+    match fs::read_to_string(&sidecar).await {
+        Ok(contents) => return Ok(Some(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    let Some(input_url) = input_url else {
+        return Ok(None);
+    };
+    let mut url = input_url.clone();
+    url.set_path(&format!(
+        "{}.{CHECKSUM_SIDECAR_EXTENSION}",
+        input_url.path()
+    ));
+    if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| anyhow!("invalid file URL: {}", url))?;
+        return match fs::read_to_string(path).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        };
+    }
+
+    let response = Client::new()
+        .get(url)
+        .send_retry(
+            |code| match code {
+                StatusCode::NOT_FOUND => RetryCheck::Succeed,
+                _ => RetryCheck::Retry,
+            },
+            DEFAULT_RETRY_PERIOD,
+            MAX_RETRY_ATTEMPTS,
+        )
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(response.error_for_status()?.text().await?))
+}
+
+/// Remove each input directly within `dir` which doesn't match its checksum,
+/// if `verify` is set, and then the checksum sidecars, so that the target
+/// doesn't take them for inputs.
+pub async fn remove_unverified_inputs(dir: impl AsRef<Path>, verify: bool) -> Result<()> {
+    if !verify {
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(dir.as_ref()).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if is_checksum_sidecar(&path) {
+            continue;
+        }
+        if entry.file_type().await?.is_file() && !verify_input_checksum(&path, None, verify).await?
+        {
+            fs::remove_file(&path).await?;
+        }
+    }
+
+    remove_checksum_sidecars(dir).await
+}
+
+/// Remove the checksum sidecars directly within `dir`.
+pub async fn remove_checksum_sidecars(dir: impl AsRef<Path>) -> Result<()> {
+    let mut entries = fs::read_dir(dir.as_ref()).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if is_checksum_sidecar(&path) && entry.file_type().await?.is_file() {
+            fs::remove_file(&path).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Bound on running the `pre_exec_transform` command on one input.
+pub const PRE_EXEC_TRANSFORM_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Bound on the `pre_exec_transform` stderr kept to log a failure.
+const PRE_EXEC_TRANSFORM_STDERR_LIMIT: u64 = 64 * 1024;
+
+/// Check `pre_exec_transform`, which must name a command if set.
+pub fn check_pre_exec_transform(transform: Option<&[String]>) -> Result<()> {
+    if transform.is_some_and(|transform| transform.is_empty()) {
+        return Err(
+            TaskError::Configuration(anyhow!("pre_exec_transform must not be empty")).into(),
+        );
+    }
+    Ok(())
+}
+
+/// An input piped through `pre_exec_transform`, in a temporary directory that
+/// is removed when this is dropped.
+#[derive(Debug)]
+pub struct TransformedInput {
+    _dir: TempDir,
+    path: PathBuf,
+}
+
+impl TransformedInput {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Pipe `input` through the `transform` command, returning the output saved
+/// under the input's file name, or `None` if the command fails or times out.
+/// The command is resolved in `setup_dir`, or else looked up on the `PATH`.
+pub async fn transform_input(
+    setup_dir: &Path,
+    transform: &[String],
+    input: &Path,
+) -> Result<Option<TransformedInput>> {
+    let (program, args) = transform
+        .split_first()
+        .ok_or_else(|| format_err!("pre_exec_transform must not be empty"))?;
+    let program = resolve_setup_relative_path(setup_dir, program)
+        .await?
+        .unwrap_or_else(|| PathBuf::from(program));
+
+    let mut data = fs::File::open(input)
+        .await
+        .with_context(|| format!("unable to open input to transform: {}", input.display()))?;
+
+    let dir = tempfile::tempdir()?;
+    let path = dir
+        .path()
+        .join(input.file_name().unwrap_or_else(|| OsStr::new("input")));
+    let mut transformed = fs::File::create(&path)
+        .await
+        .with_context(|| format!("unable to create transformed input: {}", path.display()))?;
+
+    let mut child = tokio::process::Command::new(&program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("unable to start pre_exec_transform: {}", program.display()))?;
+    let (Some(mut stdin), Some(mut stdout), Some(mut stderr)) =
+        (child.stdin.take(), child.stdout.take(), child.stderr.take())
+    else {
+        bail!("pre_exec_transform stdio not captured");
+    };
+
+    // Streamed concurrently, so that a transform which writes its output as it
+    // reads can't block on a full pipe. The transform may exit without reading
+    // the whole input, which is up to it.
+    let write = async move {
+        if let Err(err) = io::copy(&mut data, &mut stdin).await {
+            debug!("pre_exec_transform stopped reading input: {}", err);
+        }
+    };
+    let read = async {
+        io::copy(&mut stdout, &mut transformed).await?;
+        transformed.flush().await
+    };
+    // Only the start of stderr is kept for the log, the rest is drained.
+    let mut errors = Vec::new();
+    let read_errors = async {
+        io::copy(
+            &mut (&mut stderr).take(PRE_EXEC_TRANSFORM_STDERR_LIMIT),
+            &mut errors,
+        )
+        .await?;
+        io::copy(&mut stderr, &mut io::sink()).await
+    };
+    let run = async {
+        let ((), read, read_errors, status) = tokio::join!(write, read, read_errors, child.wait());
+        read.with_context(|| format!("unable to save transformed input: {}", path.display()))?;
+        read_errors.context("reading pre_exec_transform stderr")?;
+        status.context("running pre_exec_transform")
+    };
+
+    let status = match tokio::time::timeout(PRE_EXEC_TRANSFORM_TIMEOUT, run).await {
+        Ok(status) => status?,
+        Err(_) => {
+            warn!(
+                "skipping input, pre_exec_transform timed out after {:?}: {}",
+                PRE_EXEC_TRANSFORM_TIMEOUT,
+                input.display()
+            );
+            return Ok(None);
+        }
+    };
+    if !status.success() {
+        warn!(
+            "skipping input, pre_exec_transform failed with {}: {}: {}",
+            status,
+            input.display(),
+            String::from_utf8_lossy(&errors).trim_end()
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(TransformedInput { _dir: dir, path }))
+}
+
 /// Try to resolve an ambiguous setup-relative subpath, returning an error if not found.
 pub async fn try_resolve_setup_relative_path(
     setup_dir: impl AsRef<Path>,
@@ -162,6 +685,45 @@ pub async fn resolve_setup_relative_path(
     Ok(None)
 }
 
+pub const DEFAULT_WAIT_FOR_FILE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+const WAIT_FOR_FILE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Wait up to `timeout` for `path` to exist, returning `false` if the task is
+/// cancelled first.
+pub async fn wait_for_file(
+    path: &Path,
+    timeout: Duration,
+    cancellation_token: &CancellationToken,
+) -> Result<bool> {
+    let start = Instant::now();
+    let mut logged = false;
+    loop {
+        if exists(path).await {
+            info!("found file: {}", path.display());
+            return Ok(true);
+        }
+
+        if start.elapsed() >= timeout {
+            return Err(TaskError::Transient(anyhow!(
+                "timed out after {:?} waiting for file: {}",
+                timeout,
+                path.display()
+            ))
+            .into());
+        }
+        if !logged {
+            info!("waiting for file: {}", path.display());
+            logged = true;
+        }
+
+        tokio::select! {
+            _ = cancellation_token.cancelled() => return Ok(false),
+            _ = tokio::time::sleep(WAIT_FOR_FILE_PERIOD) => {}
+        }
+    }
+}
+
 async fn exists(path: impl AsRef<Path>) -> bool {
     fs::metadata(path).await.is_ok()
 }
@@ -249,4 +811,137 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_deserialize_file_mode() -> Result<()> {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(default, deserialize_with = "deserialize_file_mode")]
+            mode: Option<u32>,
+        }
+
+        let parse = |json: &str| serde_json::from_str::<Config>(json).map(|c| c.mode);
+
+        assert_eq!(parse(r#"{"mode": "0644"}"#)?, Some(0o644));
+        assert_eq!(parse(r#"{"mode": "0o755"}"#)?, Some(0o755));
+        assert_eq!(parse(r#"{"mode": 420}"#)?, Some(0o644));
+        assert_eq!(parse(r#"{"mode": null}"#)?, None);
+        assert_eq!(parse(r#"{}"#)?, None);
+        assert!(parse(r#"{"mode": "0999"}"#).is_err());
+        assert!(parse(r#"{"mode": "77777"}"#).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_input_checksum() -> Result<()> {
+        let dir = TempDir::new()?;
+        let intact = dir.path().join("intact");
+        let truncated = dir.path().join("truncated");
+        let unchecked = dir.path().join("unchecked");
+        let digest = onefuzz::sha256::digest("input");
+
+        std::fs::write(&intact, "input")?;
+        std::fs::write(
+            dir.path().join("intact.sha256"),
+            format!("{digest}  intact\n"),
+        )?;
+        std::fs::write(&truncated, "inp")?;
+        std::fs::write(dir.path().join("truncated.sha256"), &digest)?;
+        std::fs::write(&unchecked, "input")?;
+
+        assert!(verify_input_checksum(&intact, None, true).await?);
+        assert!(!verify_input_checksum(&truncated, None, true).await?);
+        assert!(verify_input_checksum(&truncated, None, false).await?);
+        assert!(verify_input_checksum(&unchecked, None, true).await?);
+        assert!(!verify_input_checksum(&dir.path().join("intact.sha256"), None, true).await?);
+
+        // A downloaded input is checked against the sidecar next to its URL.
+        let download_dir = TempDir::new()?;
+        for (name, verified) in [("truncated", false), ("intact", true), ("unchecked", true)] {
+            let url = Url::from_file_path(dir.path().join(name)).unwrap();
+            let input = download_input(url.clone(), download_dir.path()).await?;
+            assert_eq!(
+                verify_input_checksum(&input, Some(&url), true).await?,
+                verified
+            );
+        }
+
+        remove_unverified_inputs(dir.path(), true).await?;
+        assert!(intact.exists());
+        assert!(!truncated.exists());
+        assert!(unchecked.exists());
+        assert!(!dir.path().join("intact.sha256").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_input_via() {
+        assert!(check_input_via(InputDelivery::FileArg, true).is_ok());
+        assert!(check_input_via(InputDelivery::Stdin, false).is_ok());
+        assert_eq!(
+            check_input_via(InputDelivery::Stdin, true).is_err(),
+            cfg!(target_os = "windows")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_file() -> Result<()> {
+        let temp = TempDir::new()?;
+        let path = temp.path().join("ready");
+        let token = CancellationToken::new();
+
+        assert!(wait_for_file(&path, Duration::ZERO, &token).await.is_err());
+
+        std::fs::write(&path, "")?;
+        assert!(wait_for_file(&path, Duration::ZERO, &token).await?);
+
+        let missing = temp.path().join("missing");
+        token.cancel();
+        assert!(!wait_for_file(&missing, Duration::from_secs(60), &token).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_input_keeps_modified_time() -> Result<()> {
+        let temp = TempDir::new()?;
+        let source = temp.path().join("crash-1");
+        std::fs::write(&source, "input")?;
+        let saved = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        filetime::set_file_mtime(&source, filetime::FileTime::from_system_time(saved))?;
+
+        let dst = TempDir::new()?;
+        let url = Url::from_file_path(&source).unwrap();
+        let input = download_input(url, dst.path()).await?;
+        assert_eq!(input, dst.path().join("crash-1"));
+        assert_eq!(std::fs::metadata(&input)?.modified()?, saved);
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_transform_input() -> Result<()> {
+        let temp = TempDir::new()?;
+        let input = temp.path().join("input.txt");
+        std::fs::write(&input, "packed")?;
+
+        let transform = ["tr".to_owned(), "a-z".to_owned(), "A-Z".to_owned()];
+        let transformed = transform_input(temp.path(), &transform, &input)
+            .await?
+            .expect("transform succeeded");
+        assert_eq!(transformed.path().file_name(), input.file_name());
+        assert_eq!(std::fs::read_to_string(transformed.path())?, "PACKED");
+
+        let failing = ["false".to_owned()];
+        assert!(transform_input(temp.path(), &failing, &input)
+            .await?
+            .is_none());
+
+        assert!(check_pre_exec_transform(Some(&[])).is_err());
+        assert!(check_pre_exec_transform(Some(&transform)).is_ok());
+
+        Ok(())
+    }
 }
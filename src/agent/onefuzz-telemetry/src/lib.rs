@@ -4,6 +4,7 @@
 use chrono::DateTime;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{LockResult, RwLockReadGuard, RwLockWriteGuard};
 use std::time::Duration;
 use uuid::Uuid;
@@ -70,44 +71,111 @@ impl Role {
     }
 }
 
+/// Where a task is in its lifecycle, in the order the states are reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LifecycleState {
+    /// Validating the config and waiting for any prerequisites.
+    Initializing,
+    /// Downloading the setup container and inputs.
+    Downloading,
+    /// Initialized, and entering the task's main loop.
+    Ready,
+    /// Doing the task's work, such as fuzzing or processing inputs.
+    Running,
+    /// Stopped running, and flushing results.
+    Finishing,
+}
+
+impl LifecycleState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Initializing => "initializing",
+            Self::Downloading => "downloading",
+            Self::Ready => "ready",
+            Self::Running => "running",
+            Self::Finishing => "finishing",
+        }
+    }
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Clone, Debug)]
 pub enum Event {
     task_start,
+    task_stop,
+    task_config,
+    task_lifecycle,
+    task_panic,
     coverage_data,
     coverage_failed,
     coverage_empty,
+    coverage_below_minimum,
+    coverage_diff,
     new_result,
     new_crashdump,
+    new_generated_crash,
     new_coverage,
     runtime_stats,
     new_report,
     new_unique_report,
     crash_reported,
+    crash_suppressed,
+    crash_severity,
+    input_rejected,
+    smoke_test_failed,
+    uninstrumented_target,
+    supervisor_failed,
+    fd_exhausted,
     new_unable_to_reproduce,
     regression_report,
     regression_unable_to_reproduce,
+    report_stats,
 }
 
 impl Event {
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::task_start => "task_start",
+            Self::task_stop => "task_stop",
+            Self::task_config => "task_config",
+            Self::task_lifecycle => "task_lifecycle",
+            Self::task_panic => "task_panic",
             Self::coverage_data => "coverage_data",
             Self::coverage_failed => "coverage_failed",
             Self::coverage_empty => "coverage_empty",
+            Self::coverage_below_minimum => "coverage_below_minimum",
+            Self::coverage_diff => "coverage_diff",
             Self::new_coverage => "new_coverage",
             Self::new_crashdump => "new_crashdump",
+            Self::new_generated_crash => "new_generated_crash",
             Self::new_result => "new_result",
             Self::runtime_stats => "runtime_stats",
             Self::new_report => "new_report",
             Self::new_unique_report => "new_unique_report",
             Self::crash_reported => "crash_reported",
+            Self::crash_suppressed => "crash_suppressed",
+            Self::crash_severity => "crash_severity",
+            Self::input_rejected => "input_rejected",
+            Self::smoke_test_failed => "smoke_test_failed",
+            Self::uninstrumented_target => "uninstrumented_target",
+            Self::supervisor_failed => "supervisor_failed",
+            Self::fd_exhausted => "fd_exhausted",
             Self::new_unable_to_reproduce => "new_unable_to_reproduce",
             Self::regression_report => "regression_report",
             Self::regression_unable_to_reproduce => "regression_unable_to_reproduce",
+            Self::report_stats => "report_stats",
         }
     }
+
+    /// Whether the event reports routine progress, and so is subject to the
+    /// sample rate set by [`set_sample_rate`]. Lifecycle events, results,
+    /// crashes, and failures are always sent.
+    pub fn is_sampled(&self) -> bool {
+        matches!(
+            self,
+            Self::coverage_data | Self::coverage_empty | Self::new_coverage | Self::runtime_stats
+        )
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -118,6 +186,7 @@ pub enum EventData {
     TaskId(Uuid),
     ScalesetId(String),
     MachineId(Uuid),
+    MachineName(String),
     Version(String),
     CommandLine(String),
     Type(String),
@@ -136,6 +205,8 @@ pub enum EventData {
     PhysicalMemory(u64),
     CpuUsage(f32),
     Coverage(f64),
+    MinCoverage(f64),
+    CoverageGained(u64),
     CoveragePaths(u64),
     CoveragePathsFavored(u64),
     CoveragePathsFound(u64),
@@ -144,6 +215,18 @@ pub enum EventData {
     ToolName(String),
     Region(String),
     Role(Role),
+    ConfigHash(String),
+    ErrorCategory(String),
+    StderrTail(String),
+    SourceRevision(String),
+    InputSize(u64),
+    Severity(String),
+    LatencyP50Seconds(f64),
+    LatencyP95Seconds(f64),
+    ReproRate(f64),
+    PanicMessage(String),
+    Backtrace(String),
+    LifecycleState(LifecycleState),
 }
 
 impl EventData {
@@ -155,6 +238,7 @@ impl EventData {
             Self::TaskId(x) => ("task_id", x.to_string()),
             Self::ScalesetId(x) => ("scaleset_id", x.to_string()),
             Self::MachineId(x) => ("machine_id", x.to_string()),
+            Self::MachineName(x) => ("machine_name", x.to_owned()),
             Self::CommandLine(x) => ("command_line", x.to_owned()),
             Self::Type(x) => ("event_type", x.to_owned()),
             Self::Mode(x) => ("mode", x.to_owned()),
@@ -178,9 +262,23 @@ impl EventData {
             Self::CoveragePathsImported(x) => ("coverage_paths_imported", x.to_string()),
             Self::CoverageMaxDepth(x) => ("coverage_paths_depth", x.to_string()),
             Self::Coverage(x) => ("coverage", x.to_string()),
+            Self::MinCoverage(x) => ("min_coverage", x.to_string()),
+            Self::CoverageGained(x) => ("coverage_gained", x.to_string()),
             Self::ToolName(x) => ("tool_name", x.to_owned()),
             Self::Region(x) => ("region", x.to_owned()),
             Self::Role(x) => ("role", x.as_str().to_owned()),
+            Self::ConfigHash(x) => ("config_hash", x.to_owned()),
+            Self::ErrorCategory(x) => ("error_category", x.to_owned()),
+            Self::StderrTail(x) => ("stderr_tail", x.to_owned()),
+            Self::SourceRevision(x) => ("source_revision", x.to_owned()),
+            Self::InputSize(x) => ("input_size", x.to_string()),
+            Self::Severity(x) => ("severity", x.to_owned()),
+            Self::LatencyP50Seconds(x) => ("latency_p50_seconds", x.to_string()),
+            Self::LatencyP95Seconds(x) => ("latency_p95_seconds", x.to_string()),
+            Self::ReproRate(x) => ("repro_rate", x.to_string()),
+            Self::PanicMessage(x) => ("panic_message", x.to_owned()),
+            Self::Backtrace(x) => ("backtrace", x.to_owned()),
+            Self::LifecycleState(x) => ("lifecycle_state", x.as_str().to_owned()),
         }
     }
 
@@ -191,6 +289,7 @@ impl EventData {
             Self::TaskId(_) => true,
             Self::JobId(_) => true,
             Self::MachineId(_) => true,
+            Self::MachineName(_) => false,
             Self::ScalesetId(_) => false,
             Self::CommandLine(_) => false,
             Self::Path(_) => false,
@@ -215,9 +314,23 @@ impl EventData {
             Self::CoveragePathsImported(_) => true,
             Self::CoverageMaxDepth(_) => true,
             Self::Coverage(_) => true,
+            Self::MinCoverage(_) => true,
+            Self::CoverageGained(_) => true,
             Self::ToolName(_) => true,
             Self::Region(_) => false,
             Self::Role(_) => true,
+            Self::ConfigHash(_) => false,
+            Self::ErrorCategory(_) => true,
+            Self::StderrTail(_) => false,
+            Self::SourceRevision(_) => false,
+            Self::InputSize(_) => true,
+            Self::Severity(_) => true,
+            Self::LatencyP50Seconds(_) => true,
+            Self::LatencyP95Seconds(_) => true,
+            Self::ReproRate(_) => true,
+            Self::PanicMessage(_) => false,
+            Self::Backtrace(_) => false,
+            Self::LifecycleState(_) => true,
         }
     }
 }
@@ -437,6 +550,49 @@ pub fn set_property(entry: EventData) {
     }
 }
 
+// Sampled events are sent if their hash is below this threshold. `u64::MAX`
+// sends all of them.
+static SAMPLE_THRESHOLD: AtomicU64 = AtomicU64::new(u64::MAX);
+static SAMPLE_SEED: AtomicU64 = AtomicU64::new(0);
+static SAMPLE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Send only `rate` (0.0 to 1.0) of the events for which [`Event::is_sampled`]
+/// is true.
+///
+/// Which events are sent is determined by `seed`, typically the task ID, and
+/// the order in which events are tracked, so a rerun of the same task samples
+/// consistently.
+pub fn set_sample_rate(rate: f64, seed: Uuid) {
+    let threshold = if rate >= 1.0 {
+        u64::MAX
+    } else {
+        (rate.max(0.0) * u64::MAX as f64) as u64
+    };
+    let (high, low) = seed.as_u64_pair();
+
+    SAMPLE_SEED.store(high ^ low, Ordering::Relaxed);
+    SAMPLE_COUNT.store(0, Ordering::Relaxed);
+    SAMPLE_THRESHOLD.store(threshold, Ordering::Relaxed);
+}
+
+fn should_send(event: &Event) -> bool {
+    let threshold = SAMPLE_THRESHOLD.load(Ordering::Relaxed);
+    if threshold == u64::MAX || !event.is_sampled() {
+        return true;
+    }
+
+    let count = SAMPLE_COUNT.fetch_add(1, Ordering::Relaxed);
+    splitmix64(SAMPLE_SEED.load(Ordering::Relaxed).wrapping_add(count)) < threshold
+}
+
+// Finalizer of the SplitMix64 generator, to spread sequential inputs uniformly.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
 pub fn format_events(events: &[EventData]) -> String {
     events
         .iter()
@@ -494,36 +650,65 @@ pub fn subscribe_to_events() -> Result<Receiver<LoggingEvent>> {
 }
 
 pub fn track_event(event: &Event, properties: &[EventData]) {
-    use appinsights::telemetry::Telemetry;
+    // Sampling only throttles what is sent upstream, not local subscribers.
+    if !should_send(event) {
+        try_broadcast_event(chrono::Utc::now(), event, properties);
+        return;
+    }
 
     if let Some(client) = client(ClientType::Instance) {
-        let mut evt = appinsights::telemetry::EventTelemetry::new(event.as_str());
-        let props = evt.properties_mut();
-        for property in properties {
-            let (name, val) = property.as_values();
-            props.insert(name.to_string(), val);
-        }
-        client.track(evt);
+        client.track(event_telemetry(event, properties, false));
     }
 
     if let Some(client) = client(ClientType::Microsoft) {
-        let mut evt = appinsights::telemetry::EventTelemetry::new(event.as_str());
-        let props = evt.properties_mut();
+        client.track(event_telemetry(event, properties, true));
+    }
+    try_broadcast_event(chrono::Utc::now(), event, properties);
+}
 
-        for property in properties {
-            if property.can_share_with_microsoft() {
-                let (name, val) = property.as_values();
-                props.insert(name.to_string(), val);
-            }
+/// Send an event and flush it, without blocking on or panicking over the
+/// telemetry client locks. Clients which are in use are skipped.
+///
+/// Meant for use in a panic hook, where the panicking thread may hold a lock,
+/// or have poisoned one. Local subscribers are not notified.
+pub fn try_track_event_and_flush(event: &Event, properties: &[EventData]) {
+    for (client_type, shared_only) in [(ClientType::Instance, false), (ClientType::Microsoft, true)]
+    {
+        let Some(lock) = global::client_lock(client_type) else {
+            continue;
+        };
+        if let Ok(client) = lock.try_read() {
+            client.track(event_telemetry(event, properties, shared_only));
+            client.flush_channel();
         }
-        client.track(evt);
     }
-    try_broadcast_event(chrono::Utc::now(), event, properties);
+}
+
+fn event_telemetry(
+    event: &Event,
+    properties: &[EventData],
+    shared_only: bool,
+) -> appinsights::telemetry::EventTelemetry {
+    use appinsights::telemetry::Telemetry;
+
+    let mut evt = appinsights::telemetry::EventTelemetry::new(event.as_str());
+    let props = evt.properties_mut();
+    for property in properties {
+        if !shared_only || property.can_share_with_microsoft() {
+            let (name, val) = property.as_values();
+            props.insert(name.to_string(), val);
+        }
+    }
+    evt
 }
 
 pub fn track_metric(metric: &Event, value: f64, properties: &[EventData]) {
     use appinsights::telemetry::Telemetry;
 
+    if !should_send(metric) {
+        return;
+    }
+
     if let Some(client) = client(ClientType::Instance) {
         let mut mtr = appinsights::telemetry::MetricTelemetry::new(metric.as_str(), value);
         let props = mtr.properties_mut();
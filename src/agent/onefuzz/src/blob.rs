@@ -2,7 +2,9 @@
 // Licensed under the MIT License.
 
 pub mod client;
+pub mod store;
 pub mod url;
 
 pub use self::client::BlobClient;
+pub use self::store::CorpusStore;
 pub use self::url::{BlobContainerUrl, BlobUrl};
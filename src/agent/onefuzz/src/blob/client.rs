@@ -8,7 +8,7 @@ use futures::stream::TryStreamExt;
 use reqwest::{Body, RequestBuilder, Response, Url};
 use reqwest_retry::SendRetry;
 use serde::Serialize;
-use tokio::{fs, io};
+use tokio::{fs, io, io::AsyncWriteExt};
 use tokio_util::codec;
 
 #[derive(Clone)]
@@ -54,8 +54,8 @@ impl BlobClient {
     pub async fn get_file(&self, url: &Url, dst: impl AsRef<Path>) -> Result<PathBuf> {
         let dst = dst.as_ref();
 
-        let data = self.get_data(url).await?;
-        fs::write(dst, &data).await?;
+        let response = self.get(url).await?;
+        write_body(response, dst).await?;
 
         Ok(dst.to_owned())
     }
@@ -105,3 +105,15 @@ impl BlobClient {
             .context("BlobClient.put_file")
     }
 }
+
+/// Write the body of `response` to the file `dst` as it arrives, rather than
+/// holding it all in memory.
+pub(crate) async fn write_body(response: Response, dst: &Path) -> Result<()> {
+    let mut file = fs::File::create(dst).await?;
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.try_next().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(())
+}
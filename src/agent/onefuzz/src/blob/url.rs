@@ -14,10 +14,18 @@ use serde::{de, Serialize, Serializer};
 pub enum BlobUrl {
     AzureBlob(Url),
     LocalFile(PathBuf),
+    S3Object(Url),
 }
 
 impl BlobUrl {
     pub fn new(url: Url) -> Result<Self> {
+        if url.scheme() == S3_SCHEME {
+            if s3_bucket(&url).is_some() && !s3_key(&url).is_empty() {
+                return Ok(Self::S3Object(url));
+            }
+            bail!("Invalid S3 object URL: {}", url)
+        }
+
         if possible_blob_storage_url(&url, false) {
             if let Ok(path) = url.to_file_path() {
                 return Ok(Self::LocalFile(path));
@@ -47,7 +55,7 @@ impl BlobUrl {
             Self::LocalFile(path) => {
                 Url::from_file_path(path).expect("Could not convert path to url")
             }
-            Self::AzureBlob(url) => url.clone(),
+            Self::AzureBlob(url) | Self::S3Object(url) => url.clone(),
         }
     }
 
@@ -57,7 +65,7 @@ impl BlobUrl {
                 // Ctor checks that domain has at least one subdomain.
                 Some(url.domain().unwrap().split('.').next().unwrap().to_owned())
             }
-            Self::LocalFile(_) => None,
+            Self::LocalFile(_) | Self::S3Object(_) => None,
         }
     }
 
@@ -67,6 +75,7 @@ impl BlobUrl {
                 // Segment existence checked in ctor, so we can unwrap.
                 Some(url.path_segments().unwrap().next().unwrap().to_owned())
             }
+            Self::S3Object(url) => s3_bucket(url).map(ToOwned::to_owned),
             Self::LocalFile(_) => None,
         }
     }
@@ -82,6 +91,7 @@ impl BlobUrl {
                     .collect();
                 name_segments.join("/")
             }
+            Self::S3Object(url) => s3_key(url),
             Self::LocalFile(path) => path
                 .file_name()
                 .expect("Invalid file path")
@@ -91,6 +101,19 @@ impl BlobUrl {
                 .into(),
         }
     }
+
+    /// The container holding this blob, for blobs that can be addressed by
+    /// name within it.
+    pub fn container_url(&self) -> Option<BlobContainerUrl> {
+        match self {
+            Self::S3Object(url) => {
+                let mut container = url.clone();
+                container.set_path("");
+                Some(BlobContainerUrl::S3(container))
+            }
+            Self::AzureBlob(_) | Self::LocalFile(_) => None,
+        }
+    }
 }
 
 impl fmt::Debug for BlobUrl {
@@ -109,22 +132,33 @@ impl fmt::Display for BlobUrl {
                 self.container().unwrap_or_default(),
                 self.name()
             ),
+            Self::S3Object(url) => write!(f, "{url}"),
             Self::LocalFile(path) => write!(f, "{}", path.display()),
         }
     }
 }
 
-/// URL for accessing an Azure Blob Storage container.
+/// URL for accessing an Azure Blob Storage container, an S3 bucket with an
+/// optional key prefix, or a local directory.
 ///
 /// Use to validate a URL and address contained blobs.
 #[derive(Clone, Eq, PartialEq)]
 pub enum BlobContainerUrl {
     BlobContainer(Url),
     Path(PathBuf),
+    /// `s3://bucket[/prefix]`.
+    S3(Url),
 }
 
 impl BlobContainerUrl {
     pub fn new(url: Url) -> Result<Self> {
+        if url.scheme() == S3_SCHEME {
+            if s3_bucket(&url).is_none() || url.query().is_some() {
+                bail!("Invalid S3 container URL: {}", url);
+            }
+            return Ok(Self::S3(url));
+        }
+
         if !possible_blob_container_url(&url) {
             bail!("Invalid container URL: {}", url);
         }
@@ -152,7 +186,7 @@ impl BlobContainerUrl {
 
     pub fn url(&self) -> Result<Url> {
         match self {
-            Self::BlobContainer(url) => Ok(url.clone()),
+            Self::BlobContainer(url) | Self::S3(url) => Ok(url.clone()),
             Self::Path(p) => Ok(Url::from_file_path(p)
                 .map_err(|err| anyhow!("invalid path.  path:{} error:{:?}", p.display(), err))?),
         }
@@ -164,7 +198,7 @@ impl BlobContainerUrl {
                 // Ctor checks that domain has at least one subdomain.
                 Some(url.domain().unwrap().split('.').next().unwrap().to_owned())
             }
-            Self::Path(_p) => None,
+            Self::Path(_) | Self::S3(_) => None,
         }
     }
 
@@ -173,6 +207,7 @@ impl BlobContainerUrl {
             Self::BlobContainer(url) => {
                 Some(url.path_segments().unwrap().next().unwrap().to_owned())
             }
+            Self::S3(url) => s3_bucket(url).map(ToOwned::to_owned),
             Self::Path(_p) => None,
         }
     }
@@ -187,6 +222,18 @@ impl BlobContainerUrl {
                 );
                 BlobUrl::AzureBlob(url)
             }
+            Self::S3(url) => {
+                let mut url = url.clone();
+                {
+                    // Checked in ctor: S3 URLs always have a host.
+                    let mut segments = url.path_segments_mut().unwrap();
+                    segments.pop_if_empty();
+                    for segment in name.as_ref().split('/') {
+                        segments.push(segment);
+                    }
+                }
+                BlobUrl::S3Object(url)
+            }
             Self::Path(p) => BlobUrl::LocalFile(p.join(name.as_ref())),
         }
     }
@@ -208,6 +255,7 @@ impl fmt::Debug for BlobContainerUrl {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::BlobContainer(url) => write!(f, "{}", redact_query_sas_sig(url)),
+            Self::S3(url) => write!(f, "{url}"),
             Self::Path(p) => write!(f, "{}", p.display()),
         }
     }
@@ -217,6 +265,8 @@ impl fmt::Display for BlobContainerUrl {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let Some(file_path) = self.as_file_path() {
             write!(f, "{file_path:?}")
+        } else if let Self::S3(url) = self {
+            write!(f, "{url}")
         } else if let (Some(account), Some(container)) = (self.account(), self.container()) {
             write!(f, "{account}:{container}")
         } else {
@@ -238,6 +288,23 @@ pub fn redact_query_sas_sig(url: &Url) -> Url {
     redacted
 }
 
+const S3_SCHEME: &str = "s3";
+
+fn s3_bucket(url: &Url) -> Option<&str> {
+    url.host_str().filter(|bucket| !bucket.is_empty())
+}
+
+/// The decoded object key of an `s3://bucket/key` URL.
+fn s3_key(url: &Url) -> String {
+    let segments: Vec<_> = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .map(url_escape::decode)
+        .collect();
+    segments.join("/").trim_matches('/').to_owned()
+}
+
 // Weak check of necessary conditions for a storage blob or container URL.
 fn possible_blob_storage_url(url: &Url, container: bool) -> bool {
     if url.scheme() == "file" {
@@ -310,7 +377,7 @@ impl Serialize for BlobContainerUrl {
     {
         match self {
             Self::Path(p) => serializer.serialize_str(p.to_str().unwrap_or_default()),
-            Self::BlobContainer(url) => {
+            Self::BlobContainer(url) | Self::S3(url) => {
                 let url = url.to_string();
                 serializer.serialize_str(&url)
             }
@@ -532,6 +599,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_s3_container_url() -> Result<()> {
+        let bucket = BlobContainerUrl::parse("s3://mybucket")?;
+        assert_eq!(bucket.account(), None);
+        assert_eq!(bucket.container(), Some("mybucket".into()));
+        assert_eq!(
+            bucket.blob("mydir/myblob").url().as_str(),
+            "s3://mybucket/mydir/myblob"
+        );
+
+        let prefixed = BlobContainerUrl::parse("s3://mybucket/corpus/")?;
+        assert_eq!(
+            prefixed.blob("myblob").url().as_str(),
+            "s3://mybucket/corpus/myblob"
+        );
+        assert_eq!(prefixed.to_string(), "s3://mybucket/corpus/");
+
+        assert!(BlobContainerUrl::parse("s3:///corpus").is_err());
+        assert!(BlobContainerUrl::parse("s3://mybucket?x=1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_s3_blob_url() -> Result<()> {
+        let blob = BlobUrl::parse("s3://mybucket/corpus/myblob")?;
+        assert_eq!(blob.account(), None);
+        assert_eq!(blob.container(), Some("mybucket".into()));
+        assert_eq!(blob.name(), "corpus/myblob");
+
+        let container = blob.container_url().expect("no container for S3 object");
+        assert_eq!(container.blob(blob.name()), blob);
+
+        let escaped = BlobUrl::parse("s3://mybucket/id%3A000000%2Csig%3A06")?;
+        assert_eq!(escaped.name(), "id:000000,sig:06");
+
+        assert!(BlobUrl::parse("s3://mybucket").is_err());
+        assert!(BlobUrl::parse("s3://mybucket/").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_as_path() -> Result<()> {
         let root = PathBuf::from(r"/onefuzz");
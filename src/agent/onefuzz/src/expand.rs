@@ -18,14 +18,39 @@ pub enum ExpandedValue<'a> {
     Mapping(MappingFn<'a>),
 }
 
+lazy_static::lazy_static! {
+    static ref VAR_RE: Regex = Regex::new(r"\{[^}]+?\}").unwrap();
+}
+
+/// Check that `args` only use known placeholders, so that a typo can be
+/// reported before any values are available to evaluate them with.
+pub fn check_placeholders(args: &[impl AsRef<str>]) -> Result<()> {
+    let unknown: Vec<_> = args
+        .iter()
+        .flat_map(|arg| VAR_RE.find_iter(arg.as_ref()))
+        .map(|m| m.as_str())
+        .filter(|matched| !PlaceHolder::iter().any(|v| v.get_string() == *matched))
+        .collect();
+
+    if !unknown.is_empty() {
+        bail!("unknown variable replacements: {}", unknown.join(", "));
+    }
+
+    Ok(())
+}
+
 type MappingFn<'a> = Box<dyn Fn(&Expand<'a>) -> Result<ExpandedValue<'a>> + Send>;
 
 #[derive(PartialEq, Eq, Hash, EnumIter)]
 pub enum PlaceHolder {
     Input,
     Crashes,
+    /// Alias of `Crashes`.
+    CrashesDir,
     Crashdumps,
     InputCorpus,
+    /// Alias of `InputCorpus`.
+    InputDir,
     GeneratedInputs,
     TargetExe,
     TargetOptions,
@@ -60,8 +85,10 @@ impl PlaceHolder {
         match self {
             Self::Input => "{input}",
             Self::Crashes => "{crashes}",
+            Self::CrashesDir => "{crashes_dir}",
             Self::Crashdumps => "{crashdumps}",
             Self::InputCorpus => "{input_corpus}",
+            Self::InputDir => "{input_dir}",
             Self::GeneratedInputs => "{generated_inputs}",
             Self::TargetExe => "{target_exe}",
             Self::TargetOptions => "{target_options}",
@@ -236,7 +263,8 @@ impl<'a> Expand<'a> {
     pub fn crashes(self, arg: impl AsRef<Path>) -> Self {
         let arg = arg.as_ref();
         let path = String::from(arg.to_string_lossy());
-        self.set_value(PlaceHolder::Crashes, ExpandedValue::Path(path))
+        self.set_value(PlaceHolder::Crashes, ExpandedValue::Path(path.clone()))
+            .set_value(PlaceHolder::CrashesDir, ExpandedValue::Path(path))
     }
 
     pub fn crashdumps(self, arg: impl AsRef<Path>) -> Self {
@@ -258,7 +286,8 @@ impl<'a> Expand<'a> {
     pub fn input_corpus(self, arg: impl AsRef<Path>) -> Self {
         let arg = arg.as_ref();
         let path = String::from(arg.to_string_lossy());
-        self.set_value(PlaceHolder::InputCorpus, ExpandedValue::Path(path))
+        self.set_value(PlaceHolder::InputCorpus, ExpandedValue::Path(path.clone()))
+            .set_value(PlaceHolder::InputDir, ExpandedValue::Path(path))
     }
 
     pub fn generator_exe(self, arg: impl AsRef<Path>) -> Self {
@@ -420,10 +449,6 @@ impl<'a> Expand<'a> {
         arg: impl AsRef<str>,
         eval_stack: &mut Vec<&'static str>,
     ) -> Result<String> {
-        lazy_static::lazy_static! {
-            static ref VAR_RE: Regex = Regex::new(r"\{[^}]+?\}").unwrap();
-        }
-
         let arg = arg.as_ref().to_owned();
         let mut errors = Vec::new();
 
@@ -677,6 +702,31 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_expand_dir_aliases() -> Result<()> {
+        let result = Expand::new(&test_machine_identity())
+            .input_corpus("src")
+            .crashes("src")
+            .evaluate(&["{input_dir}", "{crashes_dir}"])?;
+
+        let expected = dunce::canonicalize("src")?;
+        let expected = expected.to_string_lossy();
+        assert_eq!(result, vec![expected.clone(), expected]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_placeholders() {
+        assert!(super::check_placeholders(&["-i", "{input_dir}", "{target_exe}"]).is_ok());
+        assert_eq!(
+            format!(
+                "{}",
+                super::check_placeholders(&["{input_dri}", "x{crash_dir}"]).unwrap_err()
+            ),
+            "unknown variable replacements: {input_dri}, {crash_dir}"
+        );
+    }
+
     #[test]
     fn test_expand_in_string() -> Result<()> {
         let result = Expand::new(&test_machine_identity())
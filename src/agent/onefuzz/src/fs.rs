@@ -130,6 +130,48 @@ pub async fn write_file(path: impl AsRef<Path>, content: &str) -> Result<()> {
     Ok(())
 }
 
+/// Copy each of `files` into `dir`, keeping its file name and replacing any
+/// existing file.
+pub async fn copy_into_dir(files: &[PathBuf], dir: impl AsRef<Path>) -> Result<()> {
+    let dir = dir.as_ref();
+    for file in files {
+        let name = file
+            .file_name()
+            .ok_or_else(|| format_err!("no file name for: {}", file.display()))?;
+        fs::copy(file, dir.join(name))
+            .await
+            .with_context(|| format!("unable to copy {} to {}", file.display(), dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Remove everything in `dir` except the entries with the same file name as
+/// one of `keep`, such as files placed by [`copy_into_dir`].
+pub async fn clean_dir_except(dir: impl AsRef<Path>, keep: &[PathBuf]) -> Result<()> {
+    let dir = dir.as_ref();
+    let mut entries = fs::read_dir(dir)
+        .await
+        .with_context(|| format!("unable to list directory: {}", dir.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        if keep
+            .iter()
+            .any(|file| file.file_name() == Some(name.as_os_str()))
+        {
+            continue;
+        }
+
+        let path = entry.path();
+        let removed = if entry.file_type().await?.is_dir() {
+            fs::remove_dir_all(&path).await
+        } else {
+            fs::remove_file(&path).await
+        };
+        removed.with_context(|| format!("unable to remove: {}", path.display()))?;
+    }
+    Ok(())
+}
+
 pub async fn reset_dir(dir: impl AsRef<Path>) -> Result<()> {
     let dir = dir.as_ref();
 
@@ -369,6 +411,56 @@ mod tests {
         assert_eq!(0, dir_len(&dir_path).await);
     }
 
+    #[tokio::test]
+    async fn test_copy_into_dir() {
+        let setup = tempdir().unwrap();
+        let target = tempdir().unwrap();
+
+        let config = setup.path().join("target.conf");
+        fs::write(&config, "new").await.unwrap();
+        fs::write(target.path().join("target.conf"), "old")
+            .await
+            .unwrap();
+
+        copy_into_dir(&[config], target.path()).await.unwrap();
+
+        let copied = fs::read_to_string(target.path().join("target.conf"))
+            .await
+            .unwrap();
+        assert_eq!(copied, "new");
+
+        let missing = setup.path().join("missing.conf");
+        assert!(copy_into_dir(&[missing], target.path()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clean_dir_except() {
+        let setup = tempdir().unwrap();
+        let target = tempdir().unwrap();
+
+        let config = setup.path().join("target.conf");
+        fs::write(&config, "config").await.unwrap();
+        copy_into_dir(&[config.clone()], target.path())
+            .await
+            .unwrap();
+        fs::write(target.path().join("leaked.tmp"), "")
+            .await
+            .unwrap();
+        fs::create_dir(target.path().join("leaked-dir"))
+            .await
+            .unwrap();
+        fs::write(target.path().join("leaked-dir").join("file"), "")
+            .await
+            .unwrap();
+
+        clean_dir_except(target.path(), &[config]).await.unwrap();
+
+        assert_eq!(1, dir_len(target.path()).await);
+        assert!(fs::metadata(target.path().join("target.conf"))
+            .await
+            .is_ok());
+    }
+
     #[tokio::test]
     async fn test_create_if_missing_missing() {
         let parent = tempdir().unwrap();
@@ -0,0 +1,47 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use anyhow::Result;
+
+/// Number of file descriptors open in the current process.
+#[cfg(target_os = "linux")]
+pub fn open_count() -> Result<u64> {
+    let count = std::fs::read_dir("/proc/self/fd")?.count();
+
+    // Reading the directory itself holds a descriptor open.
+    Ok(count.saturating_sub(1) as u64)
+}
+
+/// Number of handles open in the current process.
+#[cfg(target_os = "windows")]
+pub fn open_count() -> Result<u64> {
+    use anyhow::Context;
+    use windows::Win32::System::Threading::{GetCurrentProcess, GetProcessHandleCount};
+
+    let mut count = 0u32;
+
+    unsafe { GetProcessHandleCount(GetCurrentProcess(), &mut count) }
+        .ok()
+        .context("error querying process handle count")?;
+
+    Ok(u64::from(count))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn open_count() -> Result<u64> {
+    bail!("querying open file descriptors is not supported on this platform")
+}
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_count() -> Result<()> {
+        let before = open_count()?;
+        let _file = tempfile::tempfile()?;
+        assert!(open_count()? > before);
+        Ok(())
+    }
+}
@@ -6,9 +6,12 @@
 use crate::{
     asan::{add_asan_log_env, check_asan_path, check_asan_string},
     env::{get_path_with_directory, update_path, LD_LIBRARY_PATH, PATH},
-    expand::Expand,
+    expand::{Expand, PlaceHolder},
+    loaded_modules::{hash_modules, LoadedModule},
     machine_id::MachineIdentity,
-    process::run_cmd,
+    process::{build_cmd, run_command, run_command_sampling_modules, run_command_tee, Output},
+    seccomp::{self, SeccompFilter},
+    target_user::TargetUser,
 };
 use anyhow::{Context, Error, Result};
 #[cfg(target_os = "linux")]
@@ -17,13 +20,30 @@ use stacktrace_parser::CrashLog;
 #[cfg(any(target_os = "linux", target_family = "windows"))]
 use stacktrace_parser::StackEntry;
 use std::ffi::OsStr;
-#[cfg(target_os = "linux")]
 use std::process::Stdio;
-use std::{collections::HashMap, path::Path, time::Duration};
-use tempfile::tempdir;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tempfile::{tempdir, TempDir};
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// How a test case is passed to the target.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum InputDelivery {
+    /// Substitute the path of the input for the `{input}` placeholder in the
+    /// target arguments.
+    #[default]
+    FileArg,
+
+    /// Write the contents of the input to the target's stdin. Placeholder-only
+    /// `{input}` arguments are dropped.
+    Stdin,
+}
+
 pub struct Tester<'a> {
     setup_dir: &'a Path,
     extra_setup_dir: Option<&'a Path>,
@@ -37,6 +57,18 @@ pub struct Tester<'a> {
     check_retry_count: u64,
     add_setup_to_ld_library_path: bool,
     add_setup_to_path: bool,
+    input_delivery: InputDelivery,
+    input_placeholder: Option<&'a str>,
+    seccomp_filter: Option<SeccompFilter>,
+    target_user: Option<TargetUser>,
+    fresh_state: bool,
+    working_dir: Option<&'a Path>,
+    capture_module_list: bool,
+    auxiliary_files: &'a [PathBuf],
+    expected_exit_codes: &'a [i32],
+    memory_limit_mb: Option<u64>,
+    nice: Option<i32>,
+    tee_output: bool,
     machine_identity: MachineIdentity,
 }
 
@@ -51,6 +83,9 @@ pub struct Crash {
 pub struct TestResult {
     pub crash_log: Option<CrashLog>,
     pub error: Option<Error>,
+
+    /// Modules loaded by the target when it crashed, with `capture_module_list`.
+    pub loaded_modules: Option<Vec<LoadedModule>>,
 }
 
 impl<'a> Tester<'a> {
@@ -75,6 +110,18 @@ impl<'a> Tester<'a> {
             check_retry_count: 0,
             add_setup_to_ld_library_path: false,
             add_setup_to_path: false,
+            input_delivery: InputDelivery::FileArg,
+            input_placeholder: None,
+            seccomp_filter: None,
+            target_user: None,
+            fresh_state: false,
+            working_dir: None,
+            capture_module_list: false,
+            auxiliary_files: &[],
+            expected_exit_codes: &[],
+            memory_limit_mb: None,
+            nice: None,
+            tee_output: false,
             machine_identity,
         }
     }
@@ -128,6 +175,121 @@ impl<'a> Tester<'a> {
         }
     }
 
+    pub fn input_delivery(self, value: InputDelivery) -> Self {
+        Self {
+            input_delivery: value,
+            ..self
+        }
+    }
+
+    /// Substitute the input path for `placeholder` in the target arguments
+    /// instead of for `{input}`, which is then passed to the target literally,
+    /// appending the path if no argument contains `placeholder`. Placeholders
+    /// derived from the input, such as `{input_file_name}`, are not available.
+    pub fn input_placeholder(self, placeholder: &'a str) -> Self {
+        Self {
+            input_placeholder: Some(placeholder),
+            ..self
+        }
+    }
+
+    /// Confine the target with `filter`. Only supported on Linux, and ignored
+    /// elsewhere.
+    pub fn seccomp_filter(self, filter: SeccompFilter) -> Self {
+        Self {
+            seccomp_filter: Some(filter),
+            ..self
+        }
+    }
+
+    /// Run the target as `user`. Only supported on Linux, and ignored
+    /// elsewhere.
+    pub fn target_user(self, user: TargetUser) -> Self {
+        Self {
+            target_user: Some(user),
+            ..self
+        }
+    }
+
+    /// Give each run of the target a new, empty working directory and temp
+    /// directory, removed after the run, so that files left by earlier runs
+    /// can't affect it. With `check_debugger` on Windows, only the temp
+    /// directory is isolated.
+    pub fn fresh_state(self, value: bool) -> Self {
+        Self {
+            fresh_state: value,
+            ..self
+        }
+    }
+
+    /// Run the target in `dir`, rather than in the agent's working directory.
+    /// Ignored with `fresh_state`, which gives each run its own directory, and
+    /// with `check_debugger` on Windows.
+    pub fn working_dir(self, dir: &'a Path) -> Self {
+        Self {
+            working_dir: Some(dir),
+            ..self
+        }
+    }
+
+    /// Record the modules loaded by the target when it crashes. With
+    /// `check_debugger` on Linux, they are listed while the crashed target is
+    /// stopped. Otherwise, they are sampled while it runs, and modules loaded
+    /// just before the crash may be missed. Not supported with
+    /// `check_debugger` on Windows.
+    pub fn capture_module_list(self, value: bool) -> Self {
+        Self {
+            capture_module_list: value,
+            ..self
+        }
+    }
+
+    /// Copy `files` into each fresh working directory before the target runs
+    /// in it. Only used with `fresh_state`.
+    pub fn auxiliary_files(self, files: &'a [PathBuf]) -> Self {
+        Self {
+            auxiliary_files: files,
+            ..self
+        }
+    }
+
+    /// Treat exits with any of `codes` as clean, rather than crashes, even if
+    /// the target logged something that parses as one. For targets that use
+    /// exit codes to reject inputs.
+    pub fn expected_exit_codes(self, codes: &'a [i32]) -> Self {
+        Self {
+            expected_exit_codes: codes,
+            ..self
+        }
+    }
+
+    /// Limit the target's address space to `limit_mb` megabytes. See
+    /// [`crate::rlimit`]. Only supported on Linux, and ignored elsewhere.
+    pub fn memory_limit_mb(self, limit_mb: u64) -> Self {
+        Self {
+            memory_limit_mb: Some(limit_mb),
+            ..self
+        }
+    }
+
+    /// Run the target with the scheduling priority `nice`. See
+    /// [`crate::priority`]. Not supported with `check_debugger` on Windows.
+    pub fn nice(self, nice: i32) -> Self {
+        Self {
+            nice: Some(nice),
+            ..self
+        }
+    }
+
+    /// Log the target's stdout and stderr at debug level as it runs, as well
+    /// as capturing them. Not supported with `check_debugger`.
+    pub fn tee_output(self, value: bool) -> Self {
+        Self {
+            tee_output: value,
+            ..self
+        }
+    }
+
     pub fn set_optional<T>(self, value: Option<T>, setter: impl FnOnce(Self, T) -> Self) -> Self {
         if let Some(value) = value {
             setter(self, value)
@@ -139,18 +301,36 @@ impl<'a> Tester<'a> {
     #[cfg(target_family = "windows")]
     async fn test_input_debugger(
         &self,
+        exe_path: &Path,
         argv: &[impl AsRef<OsStr>],
         env: &HashMap<String, String>,
-    ) -> Result<Option<CrashLog>> {
+        stdin: Option<&Path>,
+        cwd: Option<&Path>,
+    ) -> Result<DebuggerRun> {
         const IGNORE_FIRST_CHANCE_EXCEPTIONS: bool = true;
+
+        // The crash detector always uses the agent's working directory.
+        let _ = cwd;
+
+        if stdin.is_some() {
+            bail!("delivering inputs on stdin is not supported with check_debugger on Windows");
+        }
+        if self.nice.is_some() {
+            bail!("setting the target's priority is not supported with check_debugger on Windows");
+        }
         let report = input_tester::crash_detector::test_process(
-            self.exe_path,
+            exe_path,
             argv,
             env,
             self.timeout,
             IGNORE_FIRST_CHANCE_EXCEPTIONS,
         )?;
 
+        let exit_code = match report.exit_status {
+            input_tester::test_result::ExitStatus::Code(code) => Some(code),
+            _ => None,
+        };
+
         let crash = if let Some(exception) = report.exceptions.last() {
             let call_stack: Vec<_> = exception
                 .stack_frames
@@ -191,20 +371,34 @@ impl<'a> Tester<'a> {
             None
         };
 
-        Ok(crash)
+        // The crash detector doesn't expose the process to list its modules.
+        Ok((crash, exit_code, None))
     }
 
     #[cfg(target_os = "linux")]
     async fn test_input_debugger(
         &self,
+        exe_path: &Path,
         args: &[impl AsRef<OsStr>],
         env: &HashMap<String, String>,
-    ) -> Result<Option<CrashLog>> {
-        let mut cmd = std::process::Command::new(self.exe_path);
-        cmd.args(args).stdin(Stdio::null());
+        stdin: Option<&Path>,
+        cwd: Option<&Path>,
+    ) -> Result<DebuggerRun> {
+        let stdin = match stdin {
+            Some(path) => Stdio::from(std::fs::File::open(path)?),
+            None => Stdio::null(),
+        };
+
+        let mut cmd = std::process::Command::new(exe_path);
+        cmd.args(args).stdin(stdin);
         cmd.envs(env);
+        if let Some(cwd) = cwd {
+            cmd.current_dir(cwd);
+        }
+        self.confine(&mut cmd);
 
         let (sender, receiver) = tokio::sync::oneshot::channel();
+        let capture_modules = self.capture_module_list;
 
         // Create two async tasks: one off-thread task for the blocking triage run,
         // and one task that will kill the triage target if we time out.
@@ -212,7 +406,7 @@ impl<'a> Tester<'a> {
             // Spawn a triage run, but stop it before execing.
             //
             // This calls a blocking `wait()` internally, on the forked child.
-            let triage = crate::triage::TriageCommand::new(cmd)?;
+            let triage = crate::triage::TriageCommand::new(cmd)?.capture_modules(capture_modules);
 
             // Share the new child ID with main thread.
             let Ok(()) = sender.send(triage.pid()) else {
@@ -244,9 +438,19 @@ impl<'a> Tester<'a> {
         }
 
         let report = timeout???;
+        let modules = report.modules;
+        let exit_code = match report.exit_status {
+            crate::triage::ExitStatus::Exited(code) => Some(code),
+            _ => None,
+        };
 
         let Some(crash) = report.crashes.last() else {
-            return Ok(None);
+            if let crate::triage::ExitStatus::Signaled(signal) = report.exit_status {
+                if self.seccomp_filter.is_some() && seccomp::is_violation(Some(signal as i32)) {
+                    bail!("target killed by its seccomp profile, by signal {signal}");
+                }
+            }
+            return Ok((None, exit_code, modules));
         };
 
         let crash_thread = crash
@@ -275,34 +479,134 @@ impl<'a> Tester<'a> {
         let sanitizer = crash_type.clone();
         let fault_type = crash_type;
 
-        Ok(Some(CrashLog::new(
-            None, None, sanitizer, fault_type, None, None, call_stack,
-        )?))
+        let crash_log = CrashLog::new(None, None, sanitizer, fault_type, None, None, call_stack)?;
+        Ok((Some(crash_log), exit_code, modules))
     }
 
-    pub async fn test_input(&self, input_file: impl AsRef<Path>) -> Result<TestResult> {
-        let asan_dir = if self.check_asan_log {
-            Some(tempdir()?)
+    async fn run_target(
+        &self,
+        exe_path: &Path,
+        argv: Vec<String>,
+        env: &HashMap<String, String>,
+        stdin: Option<&Path>,
+        cwd: Option<&Path>,
+    ) -> Result<(Output, Option<Vec<LoadedModule>>)> {
+        let mut cmd = build_cmd(exe_path, argv, env, stdin)?;
+        if let Some(cwd) = cwd {
+            cmd.current_dir(cwd);
+        }
+        self.confine(&mut cmd);
+
+        let (output, modules) = if self.capture_module_list {
+            let (output, modules) =
+                run_command_sampling_modules(cmd, self.timeout, self.tee_output).await?;
+            (output, Some(modules))
+        } else if self.tee_output {
+            (run_command_tee(cmd, self.timeout).await?, None)
         } else {
-            None
+            (run_command(cmd, self.timeout).await?, None)
         };
 
+        // Distinguish a blocked syscall from a crash, or a clean exit.
+        if self.seccomp_filter.is_some() && seccomp::is_violation(output.exit_status.signal) {
+            bail!(
+                "target killed by its seccomp profile, by {}",
+                output.exit_status
+            );
+        }
+
+        Ok((output, modules))
+    }
+
+    /// Apply the user, resource limits, priority and seccomp filter that the
+    /// target runs with to `cmd`.
+    fn confine(&self, cmd: &mut std::process::Command) {
+        if let Some(user) = &self.target_user {
+            user.apply(cmd);
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(limit_mb) = self.memory_limit_mb {
+            crate::rlimit::limit_address_space(cmd, limit_mb);
+        }
+        if let Some(nice) = self.nice {
+            crate::priority::set_nice(cmd, nice);
+        }
+
+        // Last, since the filter may deny the syscalls of the other hooks.
+        #[cfg(target_os = "linux")]
+        if let Some(filter) = &self.seccomp_filter {
+            filter.apply(cmd);
+        }
+    }
+
+    /// The program, arguments, environment and stdin of a run of the target
+    /// on `input_file`.
+    fn target_run(&self, input_file: &Path, asan_dir: Option<&Path>) -> Result<TargetRun> {
+        // The target may run in a working directory of its own, where relative
+        // paths would no longer resolve.
+        let changes_cwd = self.fresh_state || self.working_dir.is_some();
+        let resolve = |path: &Path| {
+            if changes_cwd {
+                absolute_path(path)
+            } else {
+                Ok(path.to_owned())
+            }
+        };
+        let exe_path = resolve(self.exe_path)?;
+        let setup_dir = resolve(self.setup_dir)?;
+        let extra_setup_dir = self.extra_setup_dir.map(resolve).transpose()?;
+        let input_file = resolve(input_file)?;
+        let input_file = input_file.as_path();
+
+        let stdin = match self.input_delivery {
+            InputDelivery::FileArg => None,
+            InputDelivery::Stdin => Some(input_file),
+        };
+
+        let input_arg = stdin.is_none().then_some(input_file);
         let (argv, env) = {
             let expand = Expand::new(&self.machine_identity)
                 .machine_id()
-                .input_path(input_file)
-                .target_exe(self.exe_path)
+                .set_optional(
+                    input_arg.filter(|_| self.input_placeholder.is_none()),
+                    Expand::input_path,
+                )
+                .set_optional(
+                    self.input_placeholder
+                        .map(|_| PlaceHolder::Input.get_string()),
+                    Expand::input_marker,
+                )
+                .target_exe(&exe_path)
                 .target_options(self.arguments)
-                .setup_dir(self.setup_dir)
-                .set_optional(self.extra_setup_dir, Expand::extra_setup_dir);
-
-            let argv = expand.evaluate(self.arguments)?;
+                .setup_dir(&setup_dir)
+                .set_optional(extra_setup_dir.as_deref(), Expand::extra_setup_dir);
+
+            let placeholder = self
+                .input_placeholder
+                .unwrap_or(PlaceHolder::Input.get_string());
+            let arguments = match self.input_delivery {
+                InputDelivery::FileArg => self.arguments.to_vec(),
+                InputDelivery::Stdin => self
+                    .arguments
+                    .iter()
+                    .filter(|arg| arg.as_str() != placeholder)
+                    .cloned()
+                    .collect(),
+            };
+            let mut argv = expand.evaluate(&arguments)?;
+            if let (Some(placeholder), Some(input)) = (self.input_placeholder, input_arg) {
+                let input = dunce::canonicalize(input).with_context(|| {
+                    format!("unable to canonicalize input: {}", input.display())
+                })?;
+                substitute_placeholder(&mut argv, placeholder, &input.to_string_lossy());
+            }
             let mut env: HashMap<String, String> = HashMap::new();
             for (k, v) in self.environ {
                 env.insert(k.clone(), expand.evaluate_value(v)?);
             }
 
-            let setup_dir = &self.setup_dir.to_path_buf();
+            let setup_dir = &setup_dir;
             if self.add_setup_to_path {
                 let new_path = match env.get(PATH) {
                     Some(v) => update_path(v.clone().into(), setup_dir)?,
@@ -322,33 +626,115 @@ impl<'a> Tester<'a> {
                 );
             }
 
-            if let Some(asan_dir) = &asan_dir {
-                add_asan_log_env(&mut env, asan_dir.path());
+            if let Some(asan_dir) = asan_dir {
+                add_asan_log_env(&mut env, asan_dir);
             }
 
             (argv, env)
         };
 
+        Ok(TargetRun {
+            exe_path,
+            argv,
+            env,
+            stdin: stdin.map(Path::to_owned),
+        })
+    }
+
+    /// A command that runs the target on `input_file` the way `test_input`
+    /// does, with the same input delivery and confinement, for callers that
+    /// run it themselves, such as to record coverage.
+    ///
+    /// The output of the target is discarded, and it runs in the working
+    /// directory, if any, rather than a fresh state directory.
+    pub fn command(&self, input_file: impl AsRef<Path>) -> Result<std::process::Command> {
+        let TargetRun {
+            exe_path,
+            argv,
+            env,
+            stdin,
+        } = self.target_run(input_file.as_ref(), None)?;
+
+        let mut cmd = build_cmd(&exe_path, argv, &env, stdin.as_deref())?;
+        cmd.stdout(Stdio::null()).stderr(Stdio::null());
+        if let Some(cwd) = self.working_dir {
+            cmd.current_dir(cwd);
+        }
+        self.confine(&mut cmd);
+
+        Ok(cmd)
+    }
+
+    pub async fn test_input(&self, input_file: impl AsRef<Path>) -> Result<TestResult> {
+        let asan_dir = if self.check_asan_log {
+            Some(tempdir()?)
+        } else {
+            None
+        };
+        if let (Some(asan_dir), Some(user)) = (&asan_dir, &self.target_user) {
+            user.grant_dir(asan_dir.path())?;
+        }
+
+        let TargetRun {
+            exe_path,
+            argv,
+            env,
+            stdin,
+        } = self.target_run(input_file.as_ref(), asan_dir.as_ref().map(|dir| dir.path()))?;
+        let stdin = stdin.as_deref();
+
         let mut error = None;
         let mut crash_log = None;
+        let mut loaded_modules = None;
 
         let attempts = 1 + self.check_retry_count;
         for _ in 0..attempts {
+            // Removed at the end of the attempt.
+            let state_dir = if self.fresh_state {
+                Some(self.fresh_state_dir().await?)
+            } else {
+                None
+            };
+            let state_path = state_dir.as_ref().map(|dir| dir.path());
+            let env = match state_path {
+                Some(path) => Cow::Owned(with_temp_dir(&env, path)),
+                None => Cow::Borrowed(&env),
+            };
+            let cwd = state_path.or(self.working_dir);
+
             let result = if self.check_debugger {
-                match self.test_input_debugger(&argv, &env).await {
-                    Ok(crash) => (crash, None, None),
-                    Err(error) => (None, Some(error), None),
+                match self
+                    .test_input_debugger(&exe_path, &argv, &env, stdin, cwd)
+                    .await
+                {
+                    Ok((crash, exit_code, modules)) => (crash, None, None, exit_code, modules),
+                    Err(error) => (None, Some(error), None, None, None),
                 }
             } else {
-                match run_cmd(self.exe_path, argv.clone(), &env, self.timeout).await {
-                    Ok(output) => (None, None, Some(output)),
-                    Err(error) => (None, Some(error), None),
+                match self
+                    .run_target(&exe_path, argv.clone(), &env, stdin, cwd)
+                    .await
+                {
+                    Ok((output, modules)) => {
+                        let exit_code = output.exit_status.code;
+                        (None, None, Some(output), exit_code, modules)
+                    }
+                    Err(error) => (None, Some(error), None, None, None),
                 }
             };
 
             crash_log = result.0;
             error = result.1;
             let output = result.2;
+            let exit_code = result.3;
+            loaded_modules = result.4;
+
+            // The target rejected the input, which is not a crash, whatever
+            // it logged.
+            if exit_code.is_some_and(|code| self.expected_exit_codes.contains(&code)) {
+                crash_log = None;
+                break;
+            }
 
             // order of operations for checking for crashes:
             // 1. if we ran under a debugger, and that caught a crash
@@ -377,7 +763,28 @@ impl<'a> Tester<'a> {
             }
         }
 
-        Ok(TestResult { crash_log, error })
+        // Only kept for crashes, as they are all that is reported.
+        if crash_log.is_none() {
+            loaded_modules = None;
+        }
+        if let Some(modules) = &mut loaded_modules {
+            hash_modules(modules).await;
+        }
+
+        Ok(TestResult {
+            crash_log,
+            error,
+            loaded_modules,
+        })
+    }
+
+    async fn fresh_state_dir(&self) -> Result<TempDir> {
+        let dir = tempdir()?;
+        if let Some(user) = &self.target_user {
+            user.grant_dir(dir.path())?;
+        }
+        crate::fs::copy_into_dir(self.auxiliary_files, dir.path()).await?;
+        Ok(dir)
     }
 
     pub async fn is_crash(&self, input_file: impl AsRef<Path>) -> Result<bool> {
@@ -389,3 +796,95 @@ impl<'a> Tester<'a> {
         Ok(test_result.crash_log.is_some())
     }
 }
+
+// A prepared run of the target, before any per-attempt state.
+struct TargetRun {
+    exe_path: PathBuf,
+    argv: Vec<String>,
+    env: HashMap<String, String>,
+    stdin: Option<PathBuf>,
+}
+
+// Crash log, exit code and loaded modules of a run under a debugger.
+type DebuggerRun = (Option<CrashLog>, Option<i32>, Option<Vec<LoadedModule>>);
+
+/// Replace `placeholder` with `input` in `argv`, or append `input` if no
+/// argument contains `placeholder`.
+fn substitute_placeholder(argv: &mut Vec<String>, placeholder: &str, input: &str) {
+    if argv.iter().any(|arg| arg.contains(placeholder)) {
+        for arg in argv.iter_mut() {
+            *arg = arg.replace(placeholder, input);
+        }
+    } else {
+        argv.push(input.to_owned());
+    }
+}
+
+/// `path`, made absolute relative to the agent's working directory.
+fn absolute_path(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_owned())
+    } else {
+        Ok(std::env::current_dir()?.join(path))
+    }
+}
+
+/// `env`, with the temp directory variables pointing at `dir`.
+fn with_temp_dir(env: &HashMap<String, String>, dir: &Path) -> HashMap<String, String> {
+    let mut env = env.clone();
+    let dir = dir.to_string_lossy();
+    for name in ["TMPDIR", "TMP", "TEMP"] {
+        env.insert(name.to_string(), dir.to_string());
+    }
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[cfg(target_family = "unix")]
+    async fn test_fresh_state_relative_input() -> Result<()> {
+        // A relative path to the input, from the agent's working directory.
+        let dir = tempfile::Builder::new().tempdir_in(".")?;
+        let name = dir.path().file_name().unwrap();
+        let input = Path::new(".").join(name).join("input");
+        tokio::fs::write(&input, "input").await?;
+        let copied = dir.path().canonicalize()?.join("copied");
+
+        let script = format!("cp \"$0\" '{}'", copied.display());
+        let arguments = ["-c".to_owned(), script, "{input}".to_owned()];
+        let env = HashMap::new();
+        let machine_identity = MachineIdentity {
+            machine_id: uuid::Uuid::new_v4(),
+            machine_name: "test-input".into(),
+            scaleset_name: None,
+        };
+        let tester = Tester::new(
+            dir.path(),
+            None,
+            Path::new("/bin/sh"),
+            &arguments,
+            &env,
+            machine_identity,
+        )
+        .fresh_state(true);
+
+        let result = tester.test_input(&input).await?;
+        assert!(result.error.is_none(), "{:?}", result.error);
+        assert_eq!(tokio::fs::read_to_string(&copied).await?, "input");
+        Ok(())
+    }
+
+    #[test]
+    fn test_substitute_placeholder() {
+        let mut argv = vec!["--in=@@".to_owned(), "{input}".to_owned()];
+        substitute_placeholder(&mut argv, "@@", "/tmp/crash");
+        assert_eq!(argv, ["--in=/tmp/crash", "{input}"]);
+
+        let mut argv = vec!["-v".to_owned()];
+        substitute_placeholder(&mut argv, "@@", "/tmp/crash");
+        assert_eq!(argv, ["-v", "/tmp/crash"]);
+    }
+}
@@ -1,4 +1,6 @@
 #[derive(Debug, Deserialize, Serialize)]
 pub enum IpcMessageKind {
     Telemetry,
+    Pause,
+    Resume,
 }
@@ -2,11 +2,33 @@
 // Licensed under the MIT License.
 
 use rand::prelude::*;
+use std::sync::Mutex;
 use std::time::Duration;
 use tokio::time::sleep;
 
+lazy_static::lazy_static! {
+    static ref RNG: Mutex<StdRng> = Mutex::new(StdRng::from_entropy());
+}
+
+/// Reseed the process-wide RNG used for jitter and input shuffling, so that
+/// runs can be replayed.
+pub fn set_seed(seed: u64) {
+    *RNG.lock().unwrap() = StdRng::seed_from_u64(seed);
+}
+
+/// Generate a new seed, for use when the user did not specify one.
+pub fn random_seed() -> u64 {
+    thread_rng().gen()
+}
+
+/// Run `f` with the process-wide RNG.
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    let mut rng = RNG.lock().unwrap();
+    f(&mut rng)
+}
+
 pub fn jitter(value: Duration) -> Duration {
-    let random: u64 = thread_rng().gen_range(0..10);
+    let random: u64 = with_rng(|rng| rng.gen_range(0..10));
     Duration::from_secs(random) + value
 }
 
@@ -15,7 +37,7 @@ pub async fn delay_with_jitter(value: Duration) {
 }
 
 pub async fn random_delay(value: Duration) {
-    let random: u64 = thread_rng().gen_range(0..value.as_secs());
+    let random: u64 = with_rng(|rng| rng.gen_range(0..value.as_secs()));
     let delay = Duration::new(random, 0);
     sleep(delay).await
 }
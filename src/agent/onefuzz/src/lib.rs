@@ -14,19 +14,27 @@ pub mod blob;
 pub mod env;
 pub mod expand;
 pub mod fs;
+pub mod handles;
 pub mod heartbeat;
 pub mod http;
 pub mod input_tester;
 pub mod ipc;
 pub mod jitter;
 pub mod libfuzzer;
+pub mod loaded_modules;
 pub mod machine_id;
+pub mod manifest;
 pub mod memory;
 pub mod monitor;
+pub mod priority;
 pub mod process;
+pub mod rlimit;
 pub mod sanitizer;
+pub mod seccomp;
 pub mod sha256;
+pub mod suspend;
 pub mod syncdir;
+pub mod target_user;
 pub mod utils;
 
 #[cfg(target_os = "linux")]
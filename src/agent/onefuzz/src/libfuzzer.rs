@@ -6,11 +6,13 @@ use crate::{
     expand::Expand,
     fs::{list_files, write_file},
     input_tester::{TestResult, Tester},
+    jitter,
     machine_id::MachineIdentity,
+    seccomp::SeccompFilter,
+    target_user::TargetUser,
 };
 use anyhow::{Context, Result};
 use rand::seq::SliceRandom;
-use rand::thread_rng;
 use std::{
     collections::HashMap,
     ffi::{OsStr, OsString},
@@ -44,6 +46,14 @@ pub struct LibFuzzer {
     options: Vec<String>,
     env: HashMap<String, String>,
     machine_identity: MachineIdentity,
+    seccomp_filter: Option<SeccompFilter>,
+    target_user: Option<TargetUser>,
+    fresh_state: bool,
+    capture_module_list: bool,
+    auxiliary_files: Vec<PathBuf>,
+    memory_limit_mb: Option<u64>,
+    nice: Option<i32>,
+    tee_output: bool,
 }
 
 impl LibFuzzer {
@@ -64,9 +74,101 @@ impl LibFuzzer {
             extra_setup_dir,
             extra_output_dir,
             machine_identity,
+            seccomp_filter: None,
+            target_user: None,
+            fresh_state: false,
+            capture_module_list: false,
+            auxiliary_files: Vec::new(),
+            memory_limit_mb: None,
+            nice: None,
+            tee_output: false,
         }
     }
 
+    /// Confine the target with `filter`. Only supported on Linux, and ignored
+    /// elsewhere.
+    pub fn seccomp_filter(self, filter: Option<SeccompFilter>) -> Self {
+        Self {
+            seccomp_filter: filter,
+            ..self
+        }
+    }
+
+    /// Run the target as `user`, if set. Only supported on Linux, and ignored
+    /// elsewhere.
+    pub fn target_user(self, user: Option<TargetUser>) -> Self {
+        Self {
+            target_user: user,
+            ..self
+        }
+    }
+
+    /// Give each reproduction of an input a new, empty working directory and
+    /// temp directory. See [`Tester::fresh_state`].
+    pub fn fresh_state(self, value: bool) -> Self {
+        Self {
+            fresh_state: value,
+            ..self
+        }
+    }
+
+    /// Record the modules loaded by the target when a reproduced input
+    /// crashes it. See [`Tester::capture_module_list`].
+    pub fn capture_module_list(self, value: bool) -> Self {
+        Self {
+            capture_module_list: value,
+            ..self
+        }
+    }
+
+    /// Copy `files` into each fresh working directory when reproducing an
+    /// input. See [`Tester::auxiliary_files`].
+    pub fn auxiliary_files(self, files: Vec<PathBuf>) -> Self {
+        Self {
+            auxiliary_files: files,
+            ..self
+        }
+    }
+
+    /// Limit the target's address space to `limit_mb` megabytes, if set. See
+    /// [`crate::rlimit`]. Only supported on Linux, and ignored elsewhere.
+    pub fn memory_limit_mb(self, limit_mb: Option<u64>) -> Self {
+        Self {
+            memory_limit_mb: limit_mb,
+            ..self
+        }
+    }
+
+    /// Run the target with the scheduling priority `nice`, if set. See
+    /// [`crate::priority`].
+    pub fn nice(self, nice: Option<i32>) -> Self {
+        Self { nice, ..self }
+    }
+
+    /// Log the target's output at debug level as it reproduces an input. See
+    /// [`Tester::tee_output`].
+    pub fn tee_output(self, value: bool) -> Self {
+        Self {
+            tee_output: value,
+            ..self
+        }
+    }
+
+    /// Pass `-seed=<seed>` to libFuzzer, if set and no `-seed` option was
+    /// given explicitly.
+    pub fn seed(mut self, seed: Option<u64>) -> Self {
+        if let Some(seed) = seed {
+            if !self
+                .options
+                .iter()
+                .any(|option| option.starts_with("-seed="))
+            {
+                self.options.push(format!("-seed={seed}"));
+            }
+        }
+        self
+    }
+
     // Build an async `Command`.
     fn build_command(
         &self,
@@ -76,12 +178,33 @@ impl LibFuzzer {
         extra_args: Option<&[&OsStr]>,
         custom_arg_filter: Option<&dyn Fn(String) -> Option<String>>,
     ) -> Result<Command> {
-        let std_cmd = self.build_std_command(
+        self.build_recorded_command(
             fault_dir,
             corpus_dir,
             extra_corpus_dirs,
             extra_args,
             custom_arg_filter,
+            None,
+        )
+    }
+
+    // Build a command, run under `rr record` if `rr_trace_dir` is set.
+    fn build_recorded_command(
+        &self,
+        fault_dir: Option<&Path>,
+        corpus_dir: Option<&Path>,
+        extra_corpus_dirs: Option<&[&Path]>,
+        extra_args: Option<&[&OsStr]>,
+        custom_arg_filter: Option<&dyn Fn(String) -> Option<String>>,
+        rr_trace_dir: Option<&Path>,
+    ) -> Result<Command> {
+        let std_cmd = self.build_std_command_impl(
+            fault_dir,
+            corpus_dir,
+            extra_corpus_dirs,
+            extra_args,
+            custom_arg_filter,
+            rr_trace_dir,
         )?;
 
         // Make async (turn into tokio::process::Command):
@@ -102,7 +225,36 @@ impl LibFuzzer {
         extra_args: Option<&[&OsStr]>,
         custom_arg_filter: Option<&dyn Fn(String) -> Option<String>>,
     ) -> Result<std::process::Command> {
-        let mut cmd = std::process::Command::new(&self.exe);
+        self.build_std_command_impl(
+            fault_dir,
+            corpus_dir,
+            extra_corpus_dirs,
+            extra_args,
+            custom_arg_filter,
+            None,
+        )
+    }
+
+    fn build_std_command_impl(
+        &self,
+        fault_dir: Option<&Path>,
+        corpus_dir: Option<&Path>,
+        extra_corpus_dirs: Option<&[&Path]>,
+        extra_args: Option<&[&OsStr]>,
+        custom_arg_filter: Option<&dyn Fn(String) -> Option<String>>,
+        rr_trace_dir: Option<&Path>,
+    ) -> Result<std::process::Command> {
+        let mut cmd = match rr_trace_dir {
+            Some(trace_dir) => {
+                let mut cmd = std::process::Command::new("rr");
+                cmd.arg("record")
+                    .arg("--output-trace-dir")
+                    .arg(trace_dir)
+                    .arg(&self.exe);
+                cmd
+            }
+            None => std::process::Command::new(&self.exe),
+        };
         cmd.env(PATH, get_path_with_directory(PATH, &self.setup_dir)?)
             .env_remove("RUST_LOG")
             .stdin(Stdio::null())
@@ -117,6 +269,25 @@ impl LibFuzzer {
             );
         }
 
+        if let Some(user) = &self.target_user {
+            user.apply(&mut cmd);
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(limit_mb) = self.memory_limit_mb {
+            crate::rlimit::limit_address_space(&mut cmd, limit_mb);
+        }
+
+        if let Some(nice) = self.nice {
+            crate::priority::set_nice(&mut cmd, nice);
+        }
+
+        // Last, since the filter may deny the syscalls of the other hooks.
+        #[cfg(target_os = "linux")]
+        if let Some(filter) = &self.seccomp_filter {
+            filter.apply(&mut cmd);
+        }
+
         let expand = Expand::new(&self.machine_identity)
             .machine_id()
             .target_exe(&self.exe)
@@ -188,10 +359,7 @@ impl LibFuzzer {
         for input_dir in inputs {
             if tokio::fs::metadata(&input_dir).await.is_ok() {
                 let mut files = list_files(&input_dir).await?;
-                {
-                    let mut rng = thread_rng();
-                    files.shuffle(&mut rng);
-                }
+                jitter::with_rng(|rng| files.shuffle(rng));
 
                 for file in files.iter().take(5) {
                     self.check_input(file)
@@ -206,6 +374,9 @@ impl LibFuzzer {
 
         if !seen_inputs {
             let temp_dir = tempdir()?;
+            if let Some(user) = &self.target_user {
+                user.grant_dir(temp_dir.path())?;
+            }
             let empty = temp_dir.path().join("empty-file.txt");
             write_file(&empty, "").await?;
             self.check_input(&empty)
@@ -325,6 +496,43 @@ impl LibFuzzer {
         Ok(())
     }
 
+    /// The number of coverage counters that libFuzzer finds in the target, as
+    /// reported when running it on an empty input. Zero if the target is not
+    /// instrumented for coverage.
+    pub async fn coverage_counters(&self) -> Result<u64> {
+        let temp_dir = tempdir()?;
+        if let Some(user) = &self.target_user {
+            user.grant_dir(temp_dir.path())?;
+        }
+        let empty = temp_dir.path().join("empty-file.txt");
+        write_file(&empty, "").await?;
+
+        let mut cmd = self.build_command(
+            None,
+            None,
+            None,
+            Some(&[empty.as_ref()]),
+            Some(&|arg: String| {
+                if arg.starts_with("-runs=") {
+                    None
+                } else {
+                    Some(arg)
+                }
+            }),
+        )?;
+
+        let result = cmd
+            .spawn()
+            .with_context(|| format_err!("libfuzzer failed to start: {}", self.exe.display()))?
+            .wait_with_output()
+            .await
+            .with_context(|| format_err!("libfuzzer failed to run: {}", self.exe.display()))?;
+
+        Ok(parse_coverage_counters(&String::from_utf8_lossy(
+            &result.stderr,
+        )))
+    }
+
     async fn find_missing_libraries(&self) -> Result<(Vec<String>, Vec<String>)> {
         let cmd = self.build_std_command(None, None, None, None, None)?;
 
@@ -372,6 +580,35 @@ impl LibFuzzer {
         Ok(child)
     }
 
+    /// Run the target once on `input` under `rr record`, saving the recording
+    /// to `rr_trace_dir`, which must not exist yet. Returns whether the run
+    /// failed, as it does if the input still crashes. The run is killed after
+    /// `timeout`.
+    pub async fn record_input(
+        &self,
+        input: impl AsRef<Path>,
+        rr_trace_dir: impl AsRef<Path>,
+        timeout: Duration,
+    ) -> Result<bool> {
+        let mut cmd = self.build_recorded_command(
+            None,
+            None,
+            None,
+            Some(&[input.as_ref().as_os_str()]),
+            None,
+            Some(rr_trace_dir.as_ref()),
+        )?;
+        cmd.stdout(Stdio::null()).stderr(Stdio::null());
+
+        info!("Running command: {:?}", &cmd);
+
+        let status = tokio::time::timeout(timeout, cmd.status())
+            .await
+            .with_context(|| format_err!("rr record timed out: {}", self.exe.display()))?
+            .context("rr record failed to run")?;
+        Ok(!status.success())
+    }
+
     pub async fn repro(
         &self,
         test_input: impl AsRef<Path>,
@@ -392,7 +629,15 @@ impl LibFuzzer {
         .check_asan_stderr(true)
         .check_retry_count(retry)
         .add_setup_to_path(true)
-        .set_optional(timeout, Tester::timeout);
+        .set_optional(timeout, Tester::timeout)
+        .set_optional(self.seccomp_filter.clone(), Tester::seccomp_filter)
+        .set_optional(self.target_user.clone(), Tester::target_user)
+        .set_optional(self.memory_limit_mb, Tester::memory_limit_mb)
+        .set_optional(self.nice, Tester::nice)
+        .fresh_state(self.fresh_state)
+        .capture_module_list(self.capture_module_list)
+        .auxiliary_files(&self.auxiliary_files)
+        .tee_output(self.tee_output);
 
         if cfg!(target_family = "unix") {
             tester = tester.add_setup_to_ld_library_path(true);
@@ -405,13 +650,33 @@ impl LibFuzzer {
         &self,
         corpus_dir: impl AsRef<Path>,
         extra_corpus_dirs: &[impl AsRef<Path>],
+    ) -> Result<LibFuzzerMergeOutput> {
+        self.merge_with_control_file(corpus_dir, extra_corpus_dirs, None)
+            .await
+    }
+
+    /// Merge as with [`LibFuzzer::merge`], passing `control_file` as
+    /// `-merge_control_file`, if set. libFuzzer records the features of each
+    /// input it runs there, and leaves the file in place once done.
+    pub async fn merge_with_control_file(
+        &self,
+        corpus_dir: impl AsRef<Path>,
+        extra_corpus_dirs: &[impl AsRef<Path>],
+        control_file: Option<&Path>,
     ) -> Result<LibFuzzerMergeOutput> {
         let extra_corpus_dirs: Vec<&Path> = extra_corpus_dirs.iter().map(|x| x.as_ref()).collect();
+        let mut args: Vec<OsString> = vec!["-merge=1".into()];
+        if let Some(control_file) = control_file {
+            let mut arg = OsString::from("-merge_control_file=");
+            arg.push(control_file);
+            args.push(arg);
+        }
+        let args: Vec<&OsStr> = args.iter().map(|x| x.as_os_str()).collect();
         let mut cmd = self.build_command(
             None,
             Some(corpus_dir.as_ref()),
             Some(&extra_corpus_dirs),
-            Some(&["-merge=1".as_ref()]),
+            Some(&args),
             None,
         )?;
 
@@ -443,6 +708,21 @@ impl LibFuzzer {
     }
 }
 
+/// Sum the counters of the modules that libFuzzer reports loading, such as
+/// `INFO: Loaded 1 modules   (1234 inline 8-bit counters): ...`, whether the
+/// target uses inline counters, bool flags, or trace-pc-guard.
+fn parse_coverage_counters(output: &str) -> u64 {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("INFO: Loaded "))
+        .filter(|line| !line.contains("PC tables"))
+        .filter_map(|line| {
+            let counters = line.split_once('(')?.1;
+            counters.split_whitespace().next()?.parse::<u64>().ok()
+        })
+        .sum()
+}
+
 #[cfg(target_os = "windows")]
 fn artifact_prefix(fault_dir: &Path) -> OsString {
     if fault_dir.is_absolute() {
@@ -497,6 +777,46 @@ impl LibFuzzerLine {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_coverage_counters() {
+        let output = "INFO: Running with entropic power schedule (0xFF, 100).
+INFO: Seed: 1234
+INFO: Loaded 1 modules   (1024 inline 8-bit counters): 1024 [0x5d8d80, 0x5d9180),
+INFO: Loaded 1 modules   (16 guards): 16 [0x5d9180, 0x5d91c0),
+INFO: Loaded 1 PC tables (1024 PCs): 1024 [0x5d91c0,0x5dd1c0),
+";
+        assert_eq!(parse_coverage_counters(output), 1040);
+
+        let output = "INFO: Seed: 1234\nRunning: empty-file.txt\n";
+        assert_eq!(parse_coverage_counters(output), 0);
+    }
+
+    #[test]
+    fn test_seed() {
+        let fuzzer = |options: &[&str]| {
+            LibFuzzer::new(
+                PathBuf::from("fuzz.exe"),
+                options.iter().map(|option| option.to_string()).collect(),
+                HashMap::new(),
+                PathBuf::from("setup"),
+                None,
+                None,
+                MachineIdentity {
+                    machine_id: uuid::Uuid::new_v4(),
+                    machine_name: "test-seed".into(),
+                    scaleset_name: None,
+                },
+            )
+        };
+
+        assert_eq!(fuzzer(&["-runs=1"]).seed(None).options, vec!["-runs=1"]);
+        assert_eq!(
+            fuzzer(&["-runs=1"]).seed(Some(7)).options,
+            vec!["-runs=1", "-seed=7"]
+        );
+        assert_eq!(fuzzer(&["-seed=1"]).seed(Some(7)).options, vec!["-seed=1"]);
+    }
+
     #[test]
     fn test_libfuzzer_line_pulse() {
         let line = r"#2097152        pulse  cov: 11 ft: 11 corp: 6/21b lim: 4096 exec/s: 699050 rss: 562Mb";
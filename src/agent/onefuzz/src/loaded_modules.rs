@@ -0,0 +1,229 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Listing the modules loaded by a running process, to pin a crash to the
+//! exact versions of its dependencies, including plugins loaded at runtime.
+
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::sha256;
+
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub struct LoadedModule {
+    pub path: PathBuf,
+    pub base_address: u64,
+    pub size: u64,
+
+    /// SHA-256 of the module file, if it could still be read once the
+    /// process had exited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl LoadedModule {
+    fn new(path: impl Into<PathBuf>, base_address: u64, size: u64) -> Self {
+        Self {
+            path: path.into(),
+            base_address,
+            size,
+            sha256: None,
+        }
+    }
+}
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Lists the modules loaded by a process every few milliseconds while it
+/// runs, for when it can't be stopped to list them when it crashes. Modules
+/// loaded just before the process exits may be missed.
+pub struct ModuleSampler {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Vec<LoadedModule>>,
+}
+
+impl ModuleSampler {
+    pub fn start(pid: u32) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut last = vec![];
+                while !stop.load(Ordering::Acquire) {
+                    // The list is empty once the process has exited, so keep
+                    // the last one seen.
+                    match loaded_modules(pid) {
+                        Ok(modules) if !modules.is_empty() => last = modules,
+                        _ => {}
+                    }
+                    thread::sleep(SAMPLE_INTERVAL);
+                }
+                last
+            })
+        };
+        Self { stop, thread }
+    }
+
+    /// Stop sampling, returning the modules last seen loaded.
+    pub fn finish(self) -> Vec<LoadedModule> {
+        self.stop.store(true, Ordering::Release);
+        self.thread.join().unwrap_or_default()
+    }
+}
+
+/// Set the `sha256` of each of `modules` whose file can be read.
+pub async fn hash_modules(modules: &mut [LoadedModule]) {
+    for module in modules {
+        match sha256::digest_file(&module.path).await {
+            Ok(digest) => module.sha256 = Some(digest),
+            Err(err) => debug!(
+                "unable to hash loaded module {}: {:?}",
+                module.path.display(),
+                err
+            ),
+        }
+    }
+}
+
+/// The modules loaded by the process `pid`, by base address. Empty once the
+/// process has exited, even before it is reaped.
+#[cfg(target_os = "linux")]
+pub fn loaded_modules(pid: u32) -> Result<Vec<LoadedModule>> {
+    let maps = proc_maps::get_process_maps(pid as _)?;
+    let mappings = maps.iter().filter_map(|map| {
+        let start = map.start() as u64;
+        Some((map.filename()?, start, start + map.size() as u64))
+    });
+    Ok(from_mappings(mappings))
+}
+
+/// The modules loaded by the process `pid`, by base address.
+#[cfg(target_family = "windows")]
+pub fn loaded_modules(pid: u32) -> Result<Vec<LoadedModule>> {
+    use anyhow::Context;
+    use windows::Win32::{
+        Foundation::CloseHandle,
+        System::Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot, Module32FirstW, Module32NextW, MODULEENTRY32W,
+            TH32CS_SNAPMODULE, TH32CS_SNAPMODULE32,
+        },
+    };
+
+    let snapshot =
+        unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPMODULE | TH32CS_SNAPMODULE32, pid) }
+            .with_context(|| format!("unable to list modules of process {pid}"))?;
+
+    let mut entry = MODULEENTRY32W {
+        dwSize: std::mem::size_of::<MODULEENTRY32W>() as u32,
+        ..Default::default()
+    };
+
+    let mut modules = vec![];
+    let mut more = unsafe { Module32FirstW(snapshot, &mut entry) }.as_bool();
+    while more {
+        let len = entry
+            .szExePath
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(entry.szExePath.len());
+        let path = String::from_utf16_lossy(&entry.szExePath[..len]);
+        modules.push(LoadedModule::new(
+            path,
+            entry.modBaseAddr as usize as u64,
+            entry.modBaseSize.into(),
+        ));
+        more = unsafe { Module32NextW(snapshot, &mut entry) }.as_bool();
+    }
+
+    unsafe { CloseHandle(snapshot) };
+    modules.sort_by_key(|module| module.base_address);
+    Ok(modules)
+}
+
+#[cfg(not(any(target_os = "linux", target_family = "windows")))]
+pub fn loaded_modules(_pid: u32) -> Result<Vec<LoadedModule>> {
+    bail!("listing loaded modules is not supported on this platform")
+}
+
+// Merge the file-backed `(path, start, end)` memory mappings of a process
+// into one module per file. Anonymous and pseudo mappings, such as
+// `[heap]`, have no absolute path and are skipped.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn from_mappings<'a>(mappings: impl Iterator<Item = (&'a Path, u64, u64)>) -> Vec<LoadedModule> {
+    let mut modules: Vec<LoadedModule> = vec![];
+    for (path, start, end) in mappings {
+        if !path.is_absolute() {
+            continue;
+        }
+        match modules.iter_mut().find(|module| module.path == path) {
+            Some(module) => {
+                let module_end = (module.base_address + module.size).max(end);
+                module.base_address = module.base_address.min(start);
+                module.size = module_end - module.base_address;
+            }
+            None => modules.push(LoadedModule::new(path, start, end - start)),
+        }
+    }
+
+    modules.sort_by_key(|module| module.base_address);
+    modules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_mappings() {
+        let lib = Path::new("/usr/lib/libplugin.so");
+        let exe = Path::new("/setup/fuzz");
+        let mappings = [
+            (exe, 0x1000, 0x2000),
+            (lib, 0x8000, 0x9000),
+            (Path::new("[heap]"), 0x3000, 0x4000),
+            (exe, 0x2000, 0x5000),
+            (lib, 0x7000, 0x8000),
+        ];
+
+        assert_eq!(
+            from_mappings(mappings.into_iter()),
+            vec![
+                LoadedModule::new(exe, 0x1000, 0x4000),
+                LoadedModule::new(lib, 0x7000, 0x2000),
+            ]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_module_sampler() -> Result<()> {
+        let mut child = std::process::Command::new("sleep").arg("1").spawn()?;
+        let sampler = ModuleSampler::start(child.id());
+        child.wait()?;
+
+        let modules = sampler.finish();
+        assert!(modules.iter().any(|module| module.path.ends_with("sleep")));
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_loaded_modules() -> Result<()> {
+        let mut modules = loaded_modules(std::process::id())?;
+        let exe = std::env::current_exe()?;
+        assert!(modules.iter().any(|module| module.path == exe));
+
+        hash_modules(&mut modules).await;
+        let exe_module = modules.iter().find(|module| module.path == exe).unwrap();
+        assert_eq!(exe_module.sha256, Some(sha256::digest_file(&exe).await?));
+        Ok(())
+    }
+}
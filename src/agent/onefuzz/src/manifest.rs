@@ -0,0 +1,192 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Record of the artifacts uploaded by this process, for a task to write out
+//! as a manifest.
+//!
+//! Recording is off until [`enable`] is called, so that files are not hashed
+//! unless a manifest was asked for. Artifacts are keyed by their destination,
+//! so a blob which is uploaded again is recorded once, as last uploaded.
+//! Files synced to a container which already had them, such as corpus inputs
+//! pulled from it, were not produced by the task, and are not recorded.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use reqwest::Url;
+use serde::Serialize;
+use tokio::fs;
+
+use crate::{
+    blob::{BlobContainerUrl, BlobUrl},
+    fs::list_files,
+    sha256,
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ARTIFACTS: Mutex<BTreeMap<String, Entry>> = Mutex::new(BTreeMap::new());
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Artifact {
+    /// Destination of the artifact, without any SAS token.
+    pub url: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+struct Entry {
+    artifact: Artifact,
+
+    // Used to skip rehashing files which are synced repeatedly, unchanged.
+    modified: Option<SystemTime>,
+}
+
+/// Start recording uploaded artifacts.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// The artifacts recorded so far, ordered by URL.
+pub fn artifacts() -> Vec<Artifact> {
+    let artifacts = ARTIFACTS.lock().unwrap();
+    artifacts.values().map(|e| e.artifact.clone()).collect()
+}
+
+/// Record `data`, uploaded to `blob`.
+pub fn record_data(blob: &BlobUrl, data: &[u8]) {
+    if !is_enabled() {
+        return;
+    }
+
+    let artifact = Artifact {
+        url: location(&blob.url()),
+        size: data.len() as u64,
+        sha256: sha256::digest(data),
+    };
+    insert(artifact, None);
+}
+
+/// Record the file at `path`, uploaded to `blob`.
+pub async fn record_file(blob: &BlobUrl, path: &Path) {
+    if !is_enabled() {
+        return;
+    }
+
+    if let Err(err) = try_record_file(location(&blob.url()), path).await {
+        warn!(
+            "unable to record artifact for manifest: {}: {:?}",
+            path.display(),
+            err
+        );
+    }
+}
+
+/// Record each file directly within `dir`, synced to `container`, except
+/// those named in `existing`, which the container had before the sync.
+pub async fn record_dir(dir: &Path, container: &BlobContainerUrl, existing: &HashSet<String>) {
+    if !is_enabled() {
+        return;
+    }
+
+    let files = match list_files(dir).await {
+        Ok(files) => files,
+        Err(err) => {
+            warn!(
+                "unable to record artifacts for manifest: {}: {:?}",
+                dir.display(),
+                err
+            );
+            return;
+        }
+    };
+
+    for file in files {
+        if let Some(name) = file.file_name() {
+            let name = name.to_string_lossy();
+            if !existing.contains(name.as_ref()) {
+                record_file(&container.blob(name), &file).await;
+            }
+        }
+    }
+}
+
+async fn try_record_file(url: String, path: &Path) -> Result<()> {
+    let metadata = fs::metadata(path).await?;
+    let modified = metadata.modified().ok();
+
+    {
+        let artifacts = ARTIFACTS.lock().unwrap();
+        if let Some(entry) = artifacts.get(&url) {
+            if modified.is_some()
+                && entry.modified == modified
+                && entry.artifact.size == metadata.len()
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    let artifact = Artifact {
+        url,
+        size: metadata.len(),
+        sha256: sha256::digest_file(path).await?,
+    };
+    insert(artifact, modified);
+    Ok(())
+}
+
+fn insert(artifact: Artifact, modified: Option<SystemTime>) {
+    let mut artifacts = ARTIFACTS.lock().unwrap();
+    artifacts.insert(artifact.url.clone(), Entry { artifact, modified });
+}
+
+fn location(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_dir() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("pulled"), "seed")?;
+        std::fs::write(dir.path().join("new"), "input")?;
+        let container = tempfile::tempdir()?;
+        let container = BlobContainerUrl::Path(container.path().to_owned());
+
+        enable();
+        let existing = HashSet::from(["pulled".to_owned()]);
+        record_dir(dir.path(), &container, &existing).await;
+
+        let prefix = location(&container.url()?);
+        let recorded: Vec<_> = artifacts()
+            .into_iter()
+            .filter(|artifact| artifact.url.starts_with(&prefix))
+            .collect();
+        assert_eq!(
+            recorded,
+            [Artifact {
+                url: location(&container.blob("new").url()),
+                size: 5,
+                sha256: sha256::digest(b"input"),
+            }]
+        );
+        Ok(())
+    }
+}
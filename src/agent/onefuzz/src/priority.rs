@@ -0,0 +1,143 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Scheduling priority of target processes, so that fuzzing can yield to
+//! interactive work on shared machines.
+//!
+//! Priorities are Unix nice values, from `MIN_NICE` (most favorable) to
+//! `MAX_NICE` (least favorable). On Windows they are mapped to the nearest
+//! priority class. Raising a target's priority above the agent's own, with a
+//! negative value, needs `CAP_SYS_NICE` on Linux, and fails when the target
+//! is started otherwise.
+
+use std::process::Command;
+
+use anyhow::Result;
+
+pub const MIN_NICE: i32 = -20;
+pub const MAX_NICE: i32 = 19;
+
+/// Check that `nice` is within the range accepted on every platform.
+pub fn check_nice(nice: i32) -> Result<()> {
+    if !(MIN_NICE..=MAX_NICE).contains(&nice) {
+        bail!(
+            "nice value {} is outside the allowed range of {} to {}",
+            nice,
+            MIN_NICE,
+            MAX_NICE
+        );
+    }
+    Ok(())
+}
+
+/// Start the child process of `cmd` with the scheduling priority `nice`.
+#[cfg(target_os = "linux")]
+pub fn set_nice(cmd: &mut Command, nice: i32) {
+    use std::os::unix::process::CommandExt;
+
+    // Safety: `setpriority()` is async-signal-safe, and the closure does not allocate.
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Start the child process of `cmd` in the priority class nearest to `nice`.
+///
+/// `Command::creation_flags()` replaces the flags set before it, so commands
+/// with creation flags of their own should set them with
+/// [`with_priority_class`] instead.
+#[cfg(target_os = "windows")]
+pub fn set_nice(cmd: &mut Command, nice: i32) {
+    use std::os::windows::process::CommandExt;
+
+    cmd.creation_flags(with_priority_class(0, nice));
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn set_nice(_cmd: &mut Command, _nice: i32) {}
+
+// https://learn.microsoft.com/en-us/windows/win32/procthread/process-creation-flags
+#[cfg(any(target_os = "windows", test))]
+mod class {
+    pub const IDLE: u32 = 0x0000_0040;
+    pub const BELOW_NORMAL: u32 = 0x0000_4000;
+    pub const NORMAL: u32 = 0x0000_0020;
+    pub const ABOVE_NORMAL: u32 = 0x0000_8000;
+    pub const HIGH: u32 = 0x0000_0080;
+    pub const REALTIME: u32 = 0x0000_0100;
+
+    pub const ALL: u32 = IDLE | BELOW_NORMAL | NORMAL | ABOVE_NORMAL | HIGH | REALTIME;
+}
+
+/// The process creation flags `flags`, with any priority class in them
+/// replaced by the one nearest to `nice`.
+#[cfg(any(target_os = "windows", test))]
+pub fn with_priority_class(flags: u32, nice: i32) -> u32 {
+    (flags & !class::ALL) | priority_class(nice)
+}
+
+#[cfg(any(target_os = "windows", test))]
+fn priority_class(nice: i32) -> u32 {
+    match nice {
+        15.. => class::IDLE,
+        1..=14 => class::BELOW_NORMAL,
+        0 => class::NORMAL,
+        -14..=-1 => class::ABOVE_NORMAL,
+        _ => class::HIGH,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_nice() {
+        assert!(check_nice(MIN_NICE).is_ok());
+        assert!(check_nice(MAX_NICE).is_ok());
+        assert!(check_nice(MIN_NICE - 1).is_err());
+        assert!(check_nice(MAX_NICE + 1).is_err());
+    }
+
+    #[test]
+    fn test_priority_class() {
+        assert_eq!(priority_class(19), 0x40);
+        assert_eq!(priority_class(10), 0x4000);
+        assert_eq!(priority_class(0), 0x20);
+        assert_eq!(priority_class(-5), 0x8000);
+        assert_eq!(priority_class(-20), 0x80);
+    }
+
+    #[test]
+    fn test_with_priority_class() {
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+        assert_eq!(with_priority_class(0, 19), class::IDLE);
+        assert_eq!(
+            with_priority_class(CREATE_NO_WINDOW, 19),
+            CREATE_NO_WINDOW | class::IDLE
+        );
+        assert_eq!(
+            with_priority_class(CREATE_NO_WINDOW | class::HIGH, 10),
+            CREATE_NO_WINDOW | class::BELOW_NORMAL
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_set_nice() -> Result<()> {
+        let mut cmd = Command::new("nice");
+        set_nice(&mut cmd, MAX_NICE);
+
+        let output = cmd.output()?;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "19");
+
+        Ok(())
+    }
+}
@@ -1,12 +1,20 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+use crate::loaded_modules::{LoadedModule, ModuleSampler};
 use anyhow::{Context, Result};
 use process_control::{self, ChildExt, Control};
+use std::io::{BufRead, Read};
 use std::path::Path;
 use std::process::Command;
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
 use std::time::Duration;
-use std::{collections::HashMap, process::Stdio};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    process::Stdio,
+};
 use tokio::{
     io::{AsyncBufReadExt, AsyncRead, BufReader},
     process::Child,
@@ -16,6 +24,10 @@ use tokio::{
 // Chosen to be significantly below the 32k ApplicationInsights message size
 const MAX_LOG_LINE_LENGTH: usize = 8192;
 
+// Bounds on the stderr kept to explain why a monitored process failed
+const STDERR_TAIL_LINES: usize = 20;
+const MAX_STDERR_TAIL_LINE_LENGTH: usize = 512;
+
 /// Serializable representation of a process output.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Output {
@@ -101,27 +113,133 @@ impl From<process_control::ExitStatus> for ExitStatus {
     }
 }
 
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exit code {code}"),
+            (None, Some(signal)) => write!(f, "signal {signal}"),
+            (None, None) => write!(f, "unknown exit status"),
+        }
+    }
+}
+
+/// A monitored process exited unsuccessfully.
+#[derive(Clone, Debug)]
+pub struct ProcessFailure {
+    pub context: String,
+    pub exit_status: ExitStatus,
+
+    /// The last lines written to stderr, bounded in count and length.
+    pub stderr_tail: Vec<String>,
+}
+
+impl fmt::Display for ProcessFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} failed with {}", self.context, self.exit_status)?;
+        if !self.stderr_tail.is_empty() {
+            write!(f, ", stderr tail:\n{}", self.stderr_tail.join("\n"))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ProcessFailure {}
+
+#[derive(Debug, Default)]
+struct StderrTail(Mutex<VecDeque<String>>);
+
+impl StderrTail {
+    fn push(&self, line: &str) {
+        let mut line = line.trim_end().to_owned();
+        if line.len() > MAX_STDERR_TAIL_LINE_LENGTH {
+            let mut end = MAX_STDERR_TAIL_LINE_LENGTH;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            line.truncate(end);
+            line.push_str("...<truncated>");
+        }
+
+        let mut lines = self.0.lock().unwrap();
+        if lines.len() == STDERR_TAIL_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    fn push_all(&self, text: &str) {
+        for line in text.lines() {
+            self.push(line);
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
 pub async fn run_cmd<S: ::std::hash::BuildHasher>(
     program: &Path,
     argv: Vec<String>,
     env: &HashMap<String, String, S>,
     timeout: Duration,
 ) -> Result<Output> {
+    run_cmd_with_stdin(program, argv, env, timeout, None).await
+}
+
+/// Run a command, with its stdin read from the file at `stdin` if given.
+pub async fn run_cmd_with_stdin<S: ::std::hash::BuildHasher>(
+    program: &Path,
+    argv: Vec<String>,
+    env: &HashMap<String, String, S>,
+    timeout: Duration,
+    stdin: Option<&Path>,
+) -> Result<Output> {
+    let cmd = build_cmd(program, argv, env, stdin)?;
+    run_command(cmd, timeout).await
+}
+
+/// Build the command run by `run_cmd_with_stdin`, so that it can be further
+/// configured before being run with `run_command`.
+pub fn build_cmd<S: ::std::hash::BuildHasher>(
+    program: &Path,
+    argv: Vec<String>,
+    env: &HashMap<String, String, S>,
+    stdin: Option<&Path>,
+) -> Result<Command> {
     debug!(
-        "running command with timeout: cmd:{:?} argv:{:?} env:{:?} timeout:{:?}",
-        program, argv, env, timeout
+        "building command: cmd:{:?} argv:{:?} env:{:?} stdin:{:?}",
+        program, argv, env, stdin
     );
 
+    let stdin = match stdin {
+        Some(path) => Stdio::from(
+            std::fs::File::open(path)
+                .with_context(|| format!("unable to open stdin: {}", path.display()))?,
+        ),
+        None => Stdio::null(),
+    };
+
     let mut cmd = Command::new(program);
     cmd.env_remove("RUST_LOG")
-        .stdin(Stdio::null())
+        .stdin(stdin)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .args(argv)
         .envs(env);
 
+    Ok(cmd)
+}
+
+/// Run `cmd` to completion, killing it if it runs for longer than `timeout`.
+pub async fn run_command(mut cmd: Command, timeout: Duration) -> Result<Output> {
+    debug!(
+        "running command with timeout: cmd:{:?} timeout:{:?}",
+        cmd, timeout
+    );
+
     // make a stringified version to save in the context of spawn_blocking
-    let program_name = program.display().to_string();
+    let program_name = Path::new(cmd.get_program()).display().to_string();
 
     let runner = tokio::task::spawn_blocking(move || {
         let child = cmd
@@ -140,7 +258,153 @@ pub async fn run_cmd<S: ::std::hash::BuildHasher>(
     runner.await?.map(|result| result.into())
 }
 
-async fn monitor_stream(name: &str, context: &str, stream: impl AsyncRead + Unpin) -> Result<()> {
+/// Run `cmd` like `run_command`, also logging each line of its stdout and
+/// stderr at debug level as it is written.
+pub async fn run_command_tee(mut cmd: Command, timeout: Duration) -> Result<Output> {
+    debug!(
+        "running command with timeout, logging output: cmd:{:?} timeout:{:?}",
+        cmd, timeout
+    );
+
+    let program_name = Path::new(cmd.get_program()).display().to_string();
+
+    let runner = tokio::task::spawn_blocking(move || {
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("process failed to start: {program_name}"))?;
+
+        // Each stream is drained on its own thread, so the target can't block
+        // writing to a full pipe while we wait for it.
+        let stdout = child.stdout.take().map(|stream| {
+            let context = program_name.clone();
+            thread::spawn(move || tee_stream("stdout", &context, stream))
+        });
+        let stderr = child.stderr.take().map(|stream| {
+            let context = program_name.clone();
+            thread::spawn(move || tee_stream("stderr", &context, stream))
+        });
+
+        let status = child
+            .controlled()
+            .time_limit(timeout)
+            .terminate_for_timeout()
+            .wait()?;
+
+        let stdout = join_tee(stdout)?;
+        let stderr = join_tee(stderr)?;
+        let status = status.ok_or_else(|| format_err!("process timed out"))?;
+
+        Ok(Output {
+            exit_status: status.into(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+        })
+    });
+
+    runner.await?
+}
+
+/// Run `cmd` like `run_command`, or like `run_command_tee` if `tee` is set,
+/// also returning the modules it was last seen to have loaded.
+pub async fn run_command_sampling_modules(
+    mut cmd: Command,
+    timeout: Duration,
+    tee: bool,
+) -> Result<(Output, Vec<LoadedModule>)> {
+    debug!(
+        "running command with timeout, sampling loaded modules: cmd:{:?} timeout:{:?}",
+        cmd, timeout
+    );
+
+    let program_name = Path::new(cmd.get_program()).display().to_string();
+
+    let runner = tokio::task::spawn_blocking(move || {
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("process failed to start: {program_name}"))?;
+        let sampler = ModuleSampler::start(child.id());
+
+        let stdout = child
+            .stdout
+            .take()
+            .map(|stream| drain_stream("stdout", program_name.clone(), stream, tee));
+        let stderr = child
+            .stderr
+            .take()
+            .map(|stream| drain_stream("stderr", program_name.clone(), stream, tee));
+
+        let status = child
+            .controlled()
+            .time_limit(timeout)
+            .terminate_for_timeout()
+            .wait();
+        let modules = sampler.finish();
+        let status = status?;
+
+        let stdout = join_tee(stdout)?;
+        let stderr = join_tee(stderr)?;
+        let status = status.ok_or_else(|| format_err!("process timed out"))?;
+
+        let output = Output {
+            exit_status: status.into(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+        };
+        Ok((output, modules))
+    });
+
+    runner.await?
+}
+
+// Read all of `stream` on a thread of its own, logging each line if `tee` is
+// set.
+fn drain_stream(
+    name: &'static str,
+    context: String,
+    mut stream: impl Read + Send + 'static,
+    tee: bool,
+) -> JoinHandle<Result<Vec<u8>>> {
+    thread::spawn(move || {
+        if tee {
+            tee_stream(name, &context, stream)
+        } else {
+            let mut output = vec![];
+            stream.read_to_end(&mut output)?;
+            Ok(output)
+        }
+    })
+}
+
+fn tee_stream(name: &str, context: &str, stream: impl Read) -> Result<Vec<u8>> {
+    let mut stream = std::io::BufReader::new(stream);
+    let mut output = vec![];
+    loop {
+        let mut buf = vec![];
+        if stream.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        let line = truncate_line(&buf);
+        debug!("process ({}) {}: {}", name, context, line.trim());
+        output.extend(buf);
+    }
+    Ok(output)
+}
+
+fn join_tee(thread: Option<JoinHandle<Result<Vec<u8>>>>) -> Result<Vec<u8>> {
+    match thread {
+        Some(thread) => thread
+            .join()
+            .map_err(|_| format_err!("output logging thread panicked"))?,
+        None => Ok(vec![]),
+    }
+}
+
+async fn monitor_stream(
+    name: &str,
+    context: &str,
+    stream: impl AsyncRead + Unpin,
+    tail: Option<&StderrTail>,
+) -> Result<()> {
     let mut stream = BufReader::new(stream);
     loop {
         let mut buf = vec![];
@@ -150,6 +414,9 @@ async fn monitor_stream(name: &str, context: &str, stream: impl AsyncRead + Unpi
                 if bytes_read == 0 && buf.is_empty() {
                     break;
                 }
+                if let Some(tail) = tail {
+                    tail.push(&String::from_utf8_lossy(&buf));
+                }
                 log_line(name, context, buf);
             }
             Err(e) => {
@@ -167,17 +434,25 @@ fn log_line(name: &str, context: &str, buf: Vec<u8>) {
         return;
     }
 
-    let mut line = String::from_utf8_lossy(&buf).to_string();
+    // Trim whitespace from our line before printing it.
+    let line = truncate_line(&buf);
+    info!("process ({}) {}: {}", name, context, line.trim());
+}
+
+fn truncate_line(buf: &[u8]) -> String {
+    let mut line = String::from_utf8_lossy(buf).to_string();
     if line.len() > MAX_LOG_LINE_LENGTH {
         line.truncate(MAX_LOG_LINE_LENGTH);
         line.push_str("...<truncated>");
     }
-
-    // Trim whitespace from our line before printing it.
-    info!("process ({}) {}: {}", name, context, line.trim());
+    line
 }
 
-async fn wait_process(context: &str, process: Child, stopped: Option<&Notify>) -> Result<()> {
+async fn wait_process(
+    context: &str,
+    process: Child,
+    stopped: Option<&Notify>,
+) -> Result<std::process::Output> {
     debug!("waiting for child: {}", context);
 
     let output = process.wait_with_output().await?;
@@ -187,12 +462,7 @@ async fn wait_process(context: &str, process: Child, stopped: Option<&Notify>) -
         stopped.notify_one();
     }
 
-    if output.status.success() {
-        Ok(())
-    } else {
-        error!("process failed: {:?}", output);
-        bail!("process failed: {:?}", output);
-    }
+    Ok(output)
 }
 
 pub async fn monitor_process(
@@ -201,6 +471,7 @@ pub async fn monitor_process(
     log_output: bool,
     stopped: Option<&Notify>,
 ) -> Result<()> {
+    let stderr_tail = StderrTail::default();
     let tasks = match log_output {
         true => {
             let stderr = process
@@ -213,8 +484,8 @@ pub async fn monitor_process(
                 .take()
                 .ok_or_else(|| format_err!("stdout not captured"))?;
 
-            let stdout_log = monitor_stream("stdout", &context, stdout);
-            let stderr_log = monitor_stream("stderr", &context, stderr);
+            let stdout_log = monitor_stream("stdout", &context, stdout, None);
+            let stderr_log = monitor_stream("stderr", &context, stderr, Some(&stderr_tail));
             Some((stdout_log, stderr_log))
         }
         false => None,
@@ -222,11 +493,32 @@ pub async fn monitor_process(
 
     let child = wait_process(&context, process, stopped);
 
-    if let Some((t1, t2)) = tasks {
-        futures::try_join!(t1, t2, child)?;
+    // Wait for the output streams to be drained before checking the exit
+    // status, so that the stderr tail is complete.
+    let output = if let Some((t1, t2)) = tasks {
+        let (stdout_result, stderr_result, output) = futures::join!(t1, t2, child);
+        let output = output?;
+        if output.status.success() {
+            stdout_result?;
+            stderr_result?;
+        }
+        output
     } else {
-        child.await?;
+        child.await?
+    };
+
+    if output.status.success() {
+        return Ok(());
     }
 
-    Ok(())
+    // If the output wasn't logged as it was written, it was captured instead.
+    stderr_tail.push_all(&String::from_utf8_lossy(&output.stderr));
+
+    let failure = ProcessFailure {
+        context: context.clone(),
+        exit_status: output.status.into(),
+        stderr_tail: stderr_tail.lines(),
+    };
+    error!("process failed: {}", failure);
+    Err(failure.into())
 }
@@ -0,0 +1,63 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Limits on the memory available to target processes.
+//!
+//! Linux ignores `RLIMIT_RSS`, so memory is limited with `RLIMIT_AS`, which
+//! bounds the target's virtual address space. Allocations past the limit fail,
+//! so the target crashes on its own rather than being killed by the OOM
+//! killer, along with anything else running on the node.
+//!
+//! Sanitizers reserve terabytes of address space for their shadow memory, so
+//! targets built with ASan, MSan, or TSan cannot run with a limit. libFuzzer's
+//! `-rss_limit_mb` is the equivalent for such targets.
+
+/// Limit the address space of the child process of `cmd` to `limit_mb`
+/// megabytes, just before it execs.
+#[cfg(target_os = "linux")]
+pub fn limit_address_space(cmd: &mut std::process::Command, limit_mb: u64) {
+    use std::os::unix::process::CommandExt;
+
+    let limit = limit_mb.saturating_mul(1024 * 1024);
+
+    // Safety: `setrlimit()` is async-signal-safe, and the closure does not allocate.
+    unsafe {
+        cmd.pre_exec(move || set_address_space_limit(limit));
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_address_space_limit(bytes: u64) -> std::io::Result<()> {
+    // Also lower the hard limit, so that the target can't raise it again.
+    let limit = libc::rlimit {
+        rlim_cur: bytes as libc::rlim_t,
+        rlim_max: bytes as libc::rlim_t,
+    };
+
+    if unsafe { libc::setrlimit(libc::RLIMIT_AS, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_address_space() -> anyhow::Result<()> {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.arg("-c").arg("ulimit -v");
+        limit_address_space(&mut cmd, 64);
+
+        let output = cmd.output()?;
+        assert!(output.status.success());
+
+        // Reported in KiB.
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "65536");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Confinement of target processes with a seccomp filter.
+//!
+//! Profiles are compiled classic BPF programs, as written by libseccomp's
+//! `seccomp_export_bpf()`. JSON profiles, such as those used by Docker, must be
+//! compiled to BPF first.
+
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::sync::Arc;
+
+use anyhow::Result;
+
+/// Maximum length of a filter program accepted by the kernel.
+#[cfg(target_os = "linux")]
+const BPF_MAXINSNS: usize = 4096;
+
+/// A seccomp filter, installed in a target process just before it `exec`s.
+///
+/// The filter applies from the `execve()` of the target onwards, so it must
+/// allow `execve`, and `ptrace` if the target is run under a debugger.
+#[derive(Clone)]
+pub struct SeccompFilter {
+    #[cfg(target_os = "linux")]
+    program: Arc<[libc::sock_filter]>,
+}
+
+impl std::fmt::Debug for SeccompFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SeccompFilter").finish_non_exhaustive()
+    }
+}
+
+impl SeccompFilter {
+    #[cfg(target_os = "linux")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        use anyhow::Context;
+
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .with_context(|| format!("unable to read seccomp profile: {}", path.display()))?;
+        Self::from_bpf(&data)
+            .with_context(|| format!("invalid seccomp profile: {}", path.display()))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        bail!(
+            "seccomp profiles are only supported on Linux: {}",
+            path.as_ref().display()
+        )
+    }
+
+    /// Parse a filter from a native-endian BPF program.
+    #[cfg(target_os = "linux")]
+    fn from_bpf(data: &[u8]) -> Result<Self> {
+        const INSN_SIZE: usize = std::mem::size_of::<libc::sock_filter>();
+
+        if data.is_empty() || data.len() % INSN_SIZE != 0 {
+            bail!("length {} is not a multiple of {INSN_SIZE}", data.len());
+        }
+
+        let program: Arc<[libc::sock_filter]> = data
+            .chunks_exact(INSN_SIZE)
+            .map(|insn| libc::sock_filter {
+                code: u16::from_ne_bytes([insn[0], insn[1]]),
+                jt: insn[2],
+                jf: insn[3],
+                k: u32::from_ne_bytes([insn[4], insn[5], insn[6], insn[7]]),
+            })
+            .collect();
+
+        if program.len() > BPF_MAXINSNS {
+            bail!(
+                "{} instructions is more than the maximum of {BPF_MAXINSNS}",
+                program.len()
+            );
+        }
+
+        Ok(Self { program })
+    }
+
+    /// Install the filter in the child process of `cmd`, just before it execs.
+    #[cfg(target_os = "linux")]
+    pub fn apply(&self, cmd: &mut std::process::Command) {
+        use std::os::unix::process::CommandExt;
+
+        let program = self.program.clone();
+
+        // Safety: the closure only makes async-signal-safe syscalls, and does not allocate.
+        unsafe {
+            cmd.pre_exec(move || install(&program));
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn install(program: &[libc::sock_filter]) -> std::io::Result<()> {
+    let prog = libc::sock_fprog {
+        len: program.len() as libc::c_ushort,
+        filter: program.as_ptr() as *mut libc::sock_filter,
+    };
+
+    // Required to install a filter without `CAP_SYS_ADMIN`.
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let prog_ptr = &prog as *const libc::sock_fprog;
+    if unsafe { libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER, prog_ptr) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Whether a process killed by `signal` was killed for making a syscall its
+/// seccomp filter does not allow.
+#[cfg(target_os = "linux")]
+pub fn is_violation(signal: Option<i32>) -> bool {
+    signal == Some(libc::SIGSYS)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_violation(_signal: Option<i32>) -> bool {
+    false
+}
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bpf() -> Result<()> {
+        // BPF_RET | BPF_K, SECCOMP_RET_ALLOW
+        let mut allow = vec![0x06, 0x00, 0x00, 0x00];
+        allow.extend(0x7fff_0000u32.to_ne_bytes());
+
+        let filter = SeccompFilter::from_bpf(&allow)?;
+        assert_eq!(filter.program.len(), 1);
+        assert_eq!(filter.program[0].k, 0x7fff_0000);
+
+        assert!(SeccompFilter::from_bpf(&[]).is_err());
+        assert!(SeccompFilter::from_bpf(&allow[..7]).is_err());
+        Ok(())
+    }
+}
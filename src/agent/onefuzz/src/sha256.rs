@@ -4,8 +4,9 @@
 use std::path::Path;
 
 use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
 use sha2::{Digest, Sha256};
-use tokio::fs;
+use tokio::{fs, io::AsyncReadExt};
 
 pub fn digest(data: impl AsRef<[u8]>) -> String {
     hex::encode(Sha256::digest(data.as_ref()))
@@ -21,18 +22,103 @@ pub fn digest_iter(data: impl IntoIterator<Item = impl AsRef<[u8]>>) -> String {
     hex::encode(ctx.finalize())
 }
 
+/// HMAC-SHA256 of `data` with `key`, as hex.
+pub fn hmac(key: impl AsRef<[u8]>, data: impl AsRef<[u8]>) -> String {
+    hex::encode(hmac_bytes(key.as_ref(), data.as_ref()))
+}
+
+/// HMAC-SHA256 of `data` with `key`.
+pub fn hmac_bytes(key: &[u8], data: &[u8]) -> [u8; 32] {
+    // HMAC accepts keys of any length.
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC key of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// Files larger than this are hashed in chunks, rather than read into memory
+/// whole, so that the size of an input doesn't bound the memory used.
+pub const STREAMING_THRESHOLD_BYTES: u64 = 64 * 1024 * 1024;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
 pub async fn digest_file(file: impl AsRef<Path>) -> Result<String> {
-    let file = file.as_ref();
-    let data = fs::read(file)
-        .await
-        .with_context(|| format!("unable to read file to generate digest: {}", file.display()))?;
+    digest_file_with_threshold(file.as_ref(), STREAMING_THRESHOLD_BYTES).await
+}
 
-    Ok(hex::encode(Sha256::digest(data)))
+async fn digest_file_with_threshold(file: &Path, threshold: u64) -> Result<String> {
+    let context = || format!("unable to read file to generate digest: {}", file.display());
+
+    let size = fs::metadata(file).await.with_context(context)?.len();
+    if size <= threshold {
+        let data = fs::read(file).await.with_context(context)?;
+        return Ok(digest(data));
+    }
+
+    let mut reader = fs::File::open(file).await.with_context(context)?;
+    let mut ctx = Sha256::new();
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let len = reader.read(&mut buf).await.with_context(context)?;
+        if len == 0 {
+            break;
+        }
+        ctx.update(&buf[..len]);
+    }
+    Ok(hex::encode(ctx.finalize()))
 }
 
 pub fn digest_file_blocking(file: impl AsRef<Path>) -> Result<String> {
-    let file = file.as_ref();
-    let data = std::fs::read(file)
-        .with_context(|| format!("unable to read file to generate digest: {}", file.display()))?;
-    Ok(hex::encode(Sha256::digest(data)))
+    digest_file_blocking_with_threshold(file.as_ref(), STREAMING_THRESHOLD_BYTES)
+}
+
+fn digest_file_blocking_with_threshold(file: &Path, threshold: u64) -> Result<String> {
+    let context = || format!("unable to read file to generate digest: {}", file.display());
+
+    let size = std::fs::metadata(file).with_context(context)?.len();
+    if size <= threshold {
+        let data = std::fs::read(file).with_context(context)?;
+        return Ok(digest(data));
+    }
+
+    let mut reader = std::fs::File::open(file).with_context(context)?;
+    let mut ctx = Sha256::new();
+    std::io::copy(&mut reader, &mut ctx).with_context(context)?;
+    Ok(hex::encode(ctx.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test cases from RFC 4231.
+    #[test]
+    fn test_hmac() {
+        assert_eq!(
+            hmac("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hmac(
+                [0xaa; 131],
+                "Test Using Larger Than Block-Size Key - Hash Key First"
+            ),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streamed_digest_matches() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("input");
+        let data: Vec<u8> = (0..3 * CHUNK_SIZE + 17).map(|i| i as u8).collect();
+        std::fs::write(&path, &data)?;
+
+        let expected = digest(&data);
+        assert_eq!(digest_file_with_threshold(&path, 0).await?, expected);
+        assert_eq!(digest_file_blocking_with_threshold(&path, 0)?, expected);
+        assert_eq!(digest_file(&path).await?, expected);
+        assert_eq!(digest_file_blocking(&path)?, expected);
+
+        Ok(())
+    }
 }
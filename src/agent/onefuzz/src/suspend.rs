@@ -0,0 +1,144 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Suspend and resume a running process without ending it.
+//!
+//! Only the process itself is suspended, not any children it has started.
+
+use anyhow::Result;
+
+/// Stop the process `pid` from running until it is resumed.
+#[cfg(target_family = "unix")]
+pub fn suspend_process(pid: u32) -> Result<()> {
+    signal(pid, nix::sys::signal::Signal::SIGSTOP)
+}
+
+/// Continue the process `pid` after it was suspended.
+#[cfg(target_family = "unix")]
+pub fn resume_process(pid: u32) -> Result<()> {
+    signal(pid, nix::sys::signal::Signal::SIGCONT)
+}
+
+#[cfg(target_family = "unix")]
+fn signal(pid: u32, signal: nix::sys::signal::Signal) -> Result<()> {
+    use anyhow::Context;
+    use nix::{sys::signal::kill, unistd::Pid};
+
+    kill(Pid::from_raw(pid as _), signal)
+        .with_context(|| format!("unable to send {} to process {}", signal, pid))
+}
+
+/// Stop the process `pid` from running until it is resumed, by suspending
+/// each of its threads.
+#[cfg(target_family = "windows")]
+pub fn suspend_process(pid: u32) -> Result<()> {
+    use windows::Win32::System::Threading::SuspendThread;
+
+    for_each_thread(pid, |thread| unsafe { SuspendThread(thread) })
+}
+
+/// Continue the process `pid` after it was suspended.
+#[cfg(target_family = "windows")]
+pub fn resume_process(pid: u32) -> Result<()> {
+    use windows::Win32::System::Threading::ResumeThread;
+
+    for_each_thread(pid, |thread| unsafe { ResumeThread(thread) })
+}
+
+// Call `f` with a handle to each thread of `pid`, where `f` returns
+// `u32::MAX` on failure, like `SuspendThread()` and `ResumeThread()`.
+#[cfg(target_family = "windows")]
+fn for_each_thread(pid: u32, f: impl Fn(windows::Win32::Foundation::HANDLE) -> u32) -> Result<()> {
+    use anyhow::Context;
+    use windows::Win32::{
+        Foundation::CloseHandle,
+        System::{
+            Diagnostics::ToolHelp::{
+                CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD,
+                THREADENTRY32,
+            },
+            Threading::{OpenThread, THREAD_SUSPEND_RESUME},
+        },
+    };
+
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) }
+        .context("unable to list threads")?;
+
+    let mut entry = THREADENTRY32 {
+        dwSize: std::mem::size_of::<THREADENTRY32>() as u32,
+        ..Default::default()
+    };
+
+    let mut result = Ok(());
+    let mut more = unsafe { Thread32First(snapshot, &mut entry) }.as_bool();
+    while more {
+        if entry.th32OwnerProcessID == pid {
+            match unsafe { OpenThread(THREAD_SUSPEND_RESUME, false, entry.th32ThreadID) } {
+                Ok(thread) => {
+                    if f(thread) == u32::MAX {
+                        result = Err(format_err!(
+                            "unable to suspend or resume thread {} of process {}",
+                            entry.th32ThreadID,
+                            pid
+                        ));
+                    }
+                    unsafe { CloseHandle(thread) };
+                }
+                // The thread may have exited since the snapshot was taken.
+                Err(err) => debug!("unable to open thread {}: {}", entry.th32ThreadID, err),
+            }
+        }
+        more = unsafe { Thread32Next(snapshot, &mut entry) }.as_bool();
+    }
+
+    unsafe { CloseHandle(snapshot) };
+    result
+}
+
+#[cfg(not(any(target_family = "unix", target_family = "windows")))]
+pub fn suspend_process(_pid: u32) -> Result<()> {
+    bail!("suspending processes is not supported on this platform")
+}
+
+#[cfg(not(any(target_family = "unix", target_family = "windows")))]
+pub fn resume_process(_pid: u32) -> Result<()> {
+    bail!("resuming processes is not supported on this platform")
+}
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    fn state(pid: u32) -> Result<char> {
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat"))?;
+        let (_, fields) = stat
+            .rsplit_once(')')
+            .ok_or_else(|| format_err!("invalid stat: {}", stat))?;
+        fields
+            .trim_start()
+            .chars()
+            .next()
+            .ok_or_else(|| format_err!("invalid stat: {}", stat))
+    }
+
+    #[test]
+    fn test_suspend_resume() -> Result<()> {
+        let mut child = Command::new("sleep").arg("60").spawn()?;
+        let pid = child.id();
+
+        suspend_process(pid)?;
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(state(pid)?, 'T');
+
+        resume_process(pid)?;
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_ne!(state(pid)?, 'T');
+
+        child.kill()?;
+        child.wait()?;
+        Ok(())
+    }
+}
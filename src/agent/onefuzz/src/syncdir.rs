@@ -2,22 +2,26 @@
 // Licensed under the MIT License.
 
 use crate::{
-    az_copy,
-    blob::{BlobClient, BlobContainerUrl},
-    fs::{exists, sync, SyncPath},
+    blob::{store, BlobContainerUrl, BlobUrl},
+    fs::exists,
     jitter::delay_with_jitter,
+    manifest,
     monitor::DirectoryMonitor,
-    uploader::BlobUploader,
 };
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use dunce::canonicalize;
 use onefuzz_result::job_result::{JobResultData, JobResultSender, TaskJobResultClient};
 use onefuzz_telemetry::{Event, EventData};
-use reqwest::{StatusCode, Url};
-use reqwest_retry::{RetryCheck, SendRetry, DEFAULT_RETRY_PERIOD, MAX_RETRY_ATTEMPTS};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::{env::current_dir, path::PathBuf, str, time::Duration};
+use std::collections::{HashMap, HashSet};
+use std::{
+    env::current_dir,
+    path::{Path, PathBuf},
+    str,
+    time::Duration,
+};
 use tokio::{fs, select};
 use tokio_util::sync::CancellationToken;
 
@@ -28,7 +32,7 @@ pub enum SyncOperation {
 }
 
 const DELAY: Duration = Duration::from_secs(10);
-const DEFAULT_CONTINUOUS_SYNC_DELAY_SECONDS: u64 = 60;
+pub const DEFAULT_CONTINUOUS_SYNC_DELAY_SECONDS: u64 = 60;
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
 pub struct SyncedDir {
@@ -69,56 +73,105 @@ impl SyncedDir {
         Ok(url)
     }
 
-    pub async fn sync(&self, operation: SyncOperation, delete_dst: bool) -> Result<()> {
-        let dir = &self.local_path.join("");
+    /// Sync the dir with its container. Once `cancellation_token` is cancelled,
+    /// the sync stops between files and returns `Ok`.
+    pub async fn sync(
+        &self,
+        operation: SyncOperation,
+        delete_dst: bool,
+        cancellation_token: &CancellationToken,
+    ) -> Result<()> {
+        self.sync_with_metadata(operation, delete_dst, &HashMap::new(), cancellation_token)
+            .await
+    }
 
-        if let Some(dest) = self.remote_path.clone().and_then(|u| u.as_file_path()) {
-            debug!("syncing {:?} {}", operation, dest.display());
-            match operation {
-                SyncOperation::Push => {
-                    sync(
-                        SyncPath::dir(dir),
-                        SyncPath::dir(dest.as_path()),
-                        delete_dst,
-                    )
-                    .await
-                }
-                SyncOperation::Pull => {
-                    sync(
-                        SyncPath::dir(dest.as_path()),
-                        SyncPath::dir(dir),
-                        delete_dst,
-                    )
-                    .await
-                }
+    /// Sync as [`Self::sync`] does, setting `metadata` on each blob pushed.
+    /// With metadata, blobs are pushed one by one, since azcopy can't set it.
+    async fn sync_with_metadata(
+        &self,
+        operation: SyncOperation,
+        delete_dst: bool,
+        metadata: &HashMap<String, String>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<()> {
+        let existing = match (operation, &self.remote_path) {
+            (SyncOperation::Push, Some(remote_path)) if manifest::is_enabled() => {
+                Self::existing_blobs(remote_path).await
             }
-        } else if let Some(url) = self.remote_path.clone().and_then(|u| u.url().ok()) {
-            let url = url.as_ref();
-            debug!("syncing {:?} {}", operation, dir.display());
-            match operation {
-                SyncOperation::Push => az_copy::sync(dir, url, delete_dst).await.context(format!(
-                    "Failed sync push from {} to {}",
-                    dir.display(),
-                    url
-                )),
-                SyncOperation::Pull => az_copy::sync(url, dir, delete_dst).await.context(format!(
-                    "Failed sync pull from {} to {}",
-                    url,
-                    dir.display()
-                )),
+            _ => None,
+        };
+
+        self.sync_dir(operation, delete_dst, metadata, cancellation_token)
+            .await?;
+
+        if let (Some(existing), Some(remote_path)) = (existing, &self.remote_path) {
+            manifest::record_dir(&self.local_path, remote_path, &existing).await;
+        }
+
+        Ok(())
+    }
+
+    /// The blobs in `container` before a push, which the manifest leaves out.
+    async fn existing_blobs(container: &BlobContainerUrl) -> Option<HashSet<String>> {
+        let names = match store::open(container) {
+            Ok(store) => store.list().await,
+            Err(err) => Err(err),
+        };
+        match names {
+            Ok(names) => Some(names.into_iter().collect()),
+            Err(err) => {
+                warn!(
+                    "unable to list {} for manifest, not recording its artifacts: {:?}",
+                    container, err
+                );
+                None
             }
-        } else {
-            Ok(())
         }
     }
 
+    async fn sync_dir(
+        &self,
+        operation: SyncOperation,
+        delete_dst: bool,
+        metadata: &HashMap<String, String>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<()> {
+        let Some(container) = &self.remote_path else {
+            return Ok(());
+        };
+
+        let dir = &self.local_path.join("");
+        debug!("syncing {:?} {}", operation, dir.display());
+        let store = store::open_with_metadata(container, metadata)?;
+        let synced = if metadata.is_empty() {
+            store
+                .sync(dir, operation, delete_dst, cancellation_token)
+                .await
+        } else {
+            store::sync(
+                store.as_ref(),
+                dir,
+                operation,
+                delete_dst,
+                cancellation_token,
+            )
+            .await
+        };
+        synced.with_context(|| {
+            format!(
+                "Failed sync {operation:?} of {} with {container}",
+                dir.display()
+            )
+        })
+    }
+
     pub fn try_url(&self) -> Option<BlobContainerUrl> {
         self.remote_path.clone()
     }
 
-    pub async fn init_pull(&self) -> Result<()> {
+    pub async fn init_pull(&self, cancellation_token: &CancellationToken) -> Result<()> {
         self.init().await.context("init failed")?;
-        self.sync(SyncOperation::Pull, false)
+        self.sync(SyncOperation::Pull, false, cancellation_token)
             .await
             .context("pull failed")
     }
@@ -150,14 +203,48 @@ impl SyncedDir {
         }
     }
 
-    pub async fn sync_pull(&self) -> Result<()> {
-        self.sync(SyncOperation::Pull, false)
+    /// The names of all blobs in the container, or of all files within the
+    /// local dir if there is no remote.
+    pub async fn list(&self) -> Result<Vec<String>> {
+        store::open(&self.remote_url()?)?
+            .list()
+            .await
+            .context("SyncedDir.list")
+    }
+
+    /// Pull only the blobs last modified after `since`, returning their local
+    /// paths. Other blobs are left undownloaded.
+    pub async fn pull_modified_since(
+        &self,
+        since: DateTime<Utc>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Vec<PathBuf>> {
+        self.init().await.context("init failed")?;
+        let store = store::open(&self.remote_url()?)?;
+        store::pull_modified_since(store.as_ref(), &self.local_path, since, cancellation_token)
+            .await
+            .with_context(|| format!("pull of inputs modified since {since} failed"))
+    }
+
+    pub async fn sync_pull(&self, cancellation_token: &CancellationToken) -> Result<()> {
+        self.sync(SyncOperation::Pull, false, cancellation_token)
             .await
             .context("sync pull failed")
     }
 
-    pub async fn sync_push(&self) -> Result<()> {
-        self.sync(SyncOperation::Push, false)
+    /// Push the dir, setting `metadata` on each blob uploaded.
+    pub async fn sync_push_with_metadata(
+        &self,
+        metadata: &HashMap<String, String>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<()> {
+        self.sync_with_metadata(SyncOperation::Push, false, metadata, cancellation_token)
+            .await
+            .context("sync push failed")
+    }
+
+    pub async fn sync_push(&self, cancellation_token: &CancellationToken) -> Result<()> {
+        self.sync(SyncOperation::Push, false, cancellation_token)
             .await
             .context("sync push failed")
     }
@@ -175,7 +262,7 @@ impl SyncedDir {
         let delay = Duration::from_secs(delay_seconds);
 
         loop {
-            self.sync(operation, false).await?;
+            self.sync(operation, false, cancellation_token).await?;
             select! {
                 _ = cancellation_token.cancelled() => {
                     break;
@@ -191,46 +278,104 @@ impl SyncedDir {
 
     // Conditionally upload a report, if it would not be a duplicate.
     pub async fn upload<T: Serialize>(&self, name: &str, data: &T) -> Result<bool> {
+        self.upload_with_metadata(name, data, &HashMap::new()).await
+    }
+
+    /// Upload `data` as `name`, setting `metadata` on the blob. Metadata is
+    /// ignored for local containers.
+    pub async fn upload_with_metadata<T: Serialize>(
+        &self,
+        name: &str,
+        data: &T,
+        metadata: &HashMap<String, String>,
+    ) -> Result<bool> {
+        let data = serde_json::to_vec(data)?;
+        self.put_if_absent(name, data, "application/json", metadata)
+            .await
+    }
+
+    /// Conditionally upload `data` as `name`, if there is no such blob yet.
+    pub async fn upload_bytes(
+        &self,
+        name: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<bool> {
+        self.put_if_absent(name, data, content_type, &HashMap::new())
+            .await
+    }
+
+    /// Conditionally upload the file `src` as `name`, if there is no such blob
+    /// yet. The file is streamed, rather than read into memory.
+    pub async fn upload_file(&self, name: &str, src: &Path) -> Result<bool> {
+        let (blob, created) = match &self.remote_path {
+            Some(url) => match url.as_file_path() {
+                Some(path) => {
+                    let path = path.join(name);
+                    (
+                        BlobUrl::LocalFile(path.clone()),
+                        copy_if_absent(src, &path).await?,
+                    )
+                }
+                None => {
+                    let created = store::open(url)?
+                        .upload(src, name)
+                        .await
+                        .context("SyncedDir.upload_file")?;
+                    (url.blob(name), created)
+                }
+            },
+            None => {
+                let path = self.local_path.join(name);
+                (
+                    BlobUrl::LocalFile(path.clone()),
+                    copy_if_absent(src, &path).await?,
+                )
+            }
+        };
+        if created {
+            manifest::record_file(&blob, src).await;
+        }
+        Ok(created)
+    }
+
+    async fn put_if_absent(
+        &self,
+        name: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        metadata: &HashMap<String, String>,
+    ) -> Result<bool> {
         if let Some(url) = self.remote_path.clone() {
             match url.as_file_path() {
                 Some(path) => {
                     let path = path.join(name);
                     if !exists(&path).await? {
-                        let data = serde_json::to_vec(&data)?;
-                        fs::write(path, data).await?;
+                        create_parent_dir(&path).await?;
+                        fs::write(&path, &data).await?;
+                        manifest::record_data(&BlobUrl::LocalFile(path), &data);
                         Ok(true)
                     } else {
                         Ok(false)
                     }
                 }
                 None => {
-                    let url = url.blob(name).url();
-                    let blob = BlobClient::new();
-                    let result = blob
-                        .put(url.clone())
-                        .json(data)
-                        // Conditional PUT, only if-not-exists.
-                        // https://docs.microsoft.com/en-us/rest/api/storageservices/specifying-conditional-headers-for-blob-service-operations
-                        .header("If-None-Match", "*")
-                        .send_retry(
-                            |code| match code {
-                                StatusCode::CONFLICT => RetryCheck::Succeed,
-                                _ => RetryCheck::Retry,
-                            },
-                            DEFAULT_RETRY_PERIOD,
-                            MAX_RETRY_ATTEMPTS,
-                        )
+                    let created = store::open_with_metadata(&url, metadata)?
+                        .upload_data(name, data.clone(), content_type)
                         .await
                         .context("SyncedDir.upload")?;
-
-                    Ok(result.status() == StatusCode::CREATED)
+                    if created && manifest::is_enabled() {
+                        manifest::record_data(&url.blob(name), &data);
+                    }
+                    Ok(created)
                 }
             }
         } else {
             let path = self.local_path.join(name);
             if !exists(&path).await? {
-                let data = serde_json::to_vec(&data)?;
-                fs::write(path, data).await?;
+                create_parent_dir(&path).await?;
+                fs::write(&path, &data).await?;
+                manifest::record_data(&BlobUrl::LocalFile(path), &data);
                 Ok(true)
             } else {
                 Ok(false)
@@ -244,6 +389,8 @@ impl SyncedDir {
         event: Event,
         ignore_dotfiles: bool,
         jr_client: &Option<TaskJobResultClient>,
+        cancellation_token: &CancellationToken,
+        metadata: &HashMap<String, String>,
     ) -> Result<()> {
         debug!("monitoring {}", path.display());
 
@@ -252,12 +399,11 @@ impl SyncedDir {
         if let Some(path) = url.as_file_path() {
             fs::create_dir_all(&path).await?;
 
-            while let Some(item) = monitor.next_file().await? {
+            while let Some(item) = Self::next_file(&mut monitor, cancellation_token).await? {
                 let file_name = item
                     .file_name()
                     .ok_or_else(|| anyhow!("invalid file path"))?;
                 let file_name_event_str = file_name.to_string_lossy();
-                let file_name_str_metric_str = file_name.to_string_lossy();
 
                 // explicitly ignore azcopy temporary files
                 // https://github.com/Azure/azure-storage-azcopy/blob/main/ste/xfer-remoteToLocal-file.go#L35
@@ -268,41 +414,9 @@ impl SyncedDir {
                 if ignore_dotfiles && file_name_event_str.starts_with('.') {
                     continue;
                 }
-                event!(event.clone(); EventData::Path = file_name_event_str);
-                metric!(event.clone(); 1.0; EventData::Path = file_name_str_metric_str);
-                if let Some(jr_client) = jr_client {
-                    match event {
-                        Event::new_result => {
-                            jr_client
-                                .send_direct(
-                                    JobResultData::NewCrashingInput,
-                                    HashMap::from([("count".to_string(), 1.0)]),
-                                )
-                                .await;
-                        }
-                        Event::new_coverage => {
-                            jr_client
-                                .send_direct(
-                                    JobResultData::NewCoverage,
-                                    HashMap::from([("count".to_string(), 1.0)]),
-                                )
-                                .await;
-                        }
-                        Event::new_crashdump => {
-                            jr_client
-                                .send_direct(
-                                    JobResultData::NewCrashDump,
-                                    HashMap::from([("count".to_string(), 1.0)]),
-                                )
-                                .await;
-                        }
-                        _ => {
-                            warn!("Unhandled job result!");
-                        }
-                    }
-                }
+                Self::report_new_file(&event, &file_name_event_str, jr_client).await;
                 let destination = path.join(file_name);
-                if let Err(err) = fs::copy(&item, &destination).await {
+                if let Err(err) = copy_file(&item, &destination).await {
                     let error_message = format!(
                         "Couldn't upload file.  path:{item:?} dir:{destination:?} err:{err:?}"
                     );
@@ -315,16 +429,17 @@ impl SyncedDir {
                     }
                     bail!("{}", error_message);
                 }
+
+                manifest::record_file(&BlobUrl::LocalFile(destination), &item).await;
             }
         } else {
-            let mut uploader = BlobUploader::new(url.url()?);
+            let store = store::open_with_metadata(&url, metadata)?;
 
-            while let Some(item) = monitor.next_file().await? {
+            while let Some(item) = Self::next_file(&mut monitor, cancellation_token).await? {
                 let file_name = item
                     .file_name()
                     .ok_or_else(|| anyhow!("invalid file path"))?;
                 let file_name_event_str = file_name.to_string_lossy();
-                let file_name_str_metric_str = file_name.to_string_lossy();
 
                 // explicitly ignore azcopy temporary files
                 // https://github.com/Azure/azure-storage-azcopy/blob/main/ste/xfer-remoteToLocal-file.go#L35
@@ -336,32 +451,8 @@ impl SyncedDir {
                     continue;
                 }
 
-                event!(event.clone(); EventData::Path = file_name_event_str);
-                metric!(event.clone(); 1.0; EventData::Path = file_name_str_metric_str);
-                if let Some(jr_client) = jr_client {
-                    match event {
-                        Event::new_result => {
-                            jr_client
-                                .send_direct(
-                                    JobResultData::NewCrashingInput,
-                                    HashMap::from([("count".to_string(), 1.0)]),
-                                )
-                                .await;
-                        }
-                        Event::new_coverage => {
-                            jr_client
-                                .send_direct(
-                                    JobResultData::NewCoverage,
-                                    HashMap::from([("count".to_string(), 1.0)]),
-                                )
-                                .await;
-                        }
-                        _ => {
-                            warn!("Unhandled job result!");
-                        }
-                    }
-                }
-                if let Err(err) = uploader.upload(item.clone()).await {
+                Self::report_new_file(&event, &file_name_event_str, jr_client).await;
+                if let Err(err) = store.upload(&item, &file_name.to_string_lossy()).await {
                     let error_message = format!(
                         "Couldn't upload file.  path:{} dir:{} err:{:?}",
                         item.display(),
@@ -377,11 +468,55 @@ impl SyncedDir {
                     }
                     bail!("{}", error_message);
                 }
+
+                manifest::record_file(&url.blob(file_name.to_string_lossy()), &item).await;
             }
         }
         Ok(())
     }
 
+    /// Send `event` for a new file named `file_name`, and its job result.
+    async fn report_new_file(
+        event: &Event,
+        file_name: &str,
+        jr_client: &Option<TaskJobResultClient>,
+    ) {
+        event!(event.clone(); EventData::Path = file_name);
+        metric!(event.clone(); 1.0; EventData::Path = file_name);
+        if let Some(jr_client) = jr_client {
+            let data = match event {
+                Event::new_result => JobResultData::NewCrashingInput,
+                Event::new_coverage => JobResultData::NewCoverage,
+                Event::new_crashdump => JobResultData::NewCrashDump,
+                _ => {
+                    warn!("Unhandled job result!");
+                    return;
+                }
+            };
+            jr_client
+                .send_direct(data, HashMap::from([("count".to_string(), 1.0)]))
+                .await;
+        }
+    }
+
+    /// Wait for the next file from `monitor`, or `None` if cancelled.
+    ///
+    /// Cancellation is only observed between files, so that a file is never
+    /// abandoned part-way through being synced.
+    async fn next_file(
+        monitor: &mut DirectoryMonitor,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Option<PathBuf>> {
+        if cancellation_token.is_cancelled() {
+            return Ok(None);
+        }
+
+        select! {
+            _ = cancellation_token.cancelled() => Ok(None),
+            item = monitor.next_file() => item,
+        }
+    }
+
     /// Monitor a directory for results.
     ///
     /// This function does not require the directory to exist before it is called.
@@ -392,14 +527,39 @@ impl SyncedDir {
     /// The intent of this is to support use cases where we usually want a directory
     /// to be initialized, but a user-supplied binary, (such as AFL) logically owns
     /// a directory, and may reset it.
+    ///
+    /// Returns once `cancellation_token` is cancelled, after finishing the sync
+    /// of any file already in progress.
     pub async fn monitor_results(
         &self,
         event: Event,
         ignore_dotfiles: bool,
         job_result_client: &Option<TaskJobResultClient>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<()> {
+        self.monitor_results_with_metadata(
+            event,
+            ignore_dotfiles,
+            job_result_client,
+            cancellation_token,
+            &HashMap::new(),
+        )
+        .await
+    }
+
+    /// Monitor a directory for results, as with [`Self::monitor_results`],
+    /// setting `metadata` on each uploaded blob. Metadata is ignored for local
+    /// containers.
+    pub async fn monitor_results_with_metadata(
+        &self,
+        event: Event,
+        ignore_dotfiles: bool,
+        job_result_client: &Option<TaskJobResultClient>,
+        cancellation_token: &CancellationToken,
+        metadata: &HashMap<String, String>,
     ) -> Result<()> {
         if let Some(url) = self.remote_path.clone() {
-            loop {
+            while !cancellation_token.is_cancelled() {
                 debug!("waiting to monitor {}", self.local_path.display());
 
                 while fs::metadata(&self.local_path).await.is_err() {
@@ -407,7 +567,10 @@ impl SyncedDir {
                         "dir {} not ready to monitor, delaying",
                         self.local_path.display()
                     );
-                    delay_with_jitter(DELAY).await;
+                    select! {
+                        _ = cancellation_token.cancelled() => return Ok(()),
+                        _ = delay_with_jitter(DELAY) => {}
+                    }
                 }
 
                 debug!("starting monitor for {}", self.local_path.display());
@@ -417,19 +580,98 @@ impl SyncedDir {
                     event.clone(),
                     ignore_dotfiles,
                     job_result_client,
+                    cancellation_token,
+                    metadata,
                 )
                 .await?;
             }
-        } else {
-            Ok(())
         }
+        Ok(())
     }
+
+    /// Send `event`, and its job result, for each new file in the directory,
+    /// as [`Self::monitor_results`] does, but without uploading the files.
+    ///
+    /// Returns once `cancellation_token` is cancelled.
+    pub async fn monitor_events(
+        &self,
+        event: Event,
+        ignore_dotfiles: bool,
+        job_result_client: &Option<TaskJobResultClient>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<()> {
+        while !cancellation_token.is_cancelled() {
+            while fs::metadata(&self.local_path).await.is_err() {
+                select! {
+                    _ = cancellation_token.cancelled() => return Ok(()),
+                    _ = delay_with_jitter(DELAY) => {}
+                }
+            }
+
+            let mut monitor = DirectoryMonitor::new(&self.local_path).await?;
+            while let Some(item) = Self::next_file(&mut monitor, cancellation_token).await? {
+                let Some(file_name) = item.file_name() else {
+                    continue;
+                };
+                let file_name = file_name.to_string_lossy();
+                if file_name.starts_with(".azDownload-")
+                    || (ignore_dotfiles && file_name.starts_with('.'))
+                {
+                    continue;
+                }
+                Self::report_new_file(&event, &file_name, job_result_client).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Copy `src` to `dst` via a temporary file in the destination directory, so that
+/// an interrupted copy never leaves a truncated file under the final name.
+async fn copy_file(src: &Path, dst: &Path) -> Result<()> {
+    let file_name = dst
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid file path"))?;
+    let temp = dst.with_file_name(format!(".{}.partial", file_name.to_string_lossy()));
+
+    let result = async {
+        fs::copy(src, &temp).await?;
+        fs::rename(&temp, dst).await?;
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp).await;
+    }
+
+    result
+}
+
+/// Copy `src` to `dst`, as [`copy_file`] does, if there is no `dst` yet.
+/// Returns whether it was copied.
+async fn copy_if_absent(src: &Path, dst: &Path) -> Result<bool> {
+    if exists(dst).await? {
+        return Ok(false);
+    }
+    create_parent_dir(dst).await?;
+    copy_file(src, dst).await?;
+    Ok(true)
+}
+
+/// Create the parent directory of `path`, for blob names with a `/`.
+async fn create_parent_dir(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    Ok(())
 }
 
 pub async fn continuous_sync(
     dirs: &[SyncedDir],
     operation: SyncOperation,
     delay_seconds: Option<u64>,
+    cancellation_token: &CancellationToken,
 ) -> Result<()> {
     let delay_seconds = delay_seconds.unwrap_or(DEFAULT_CONTINUOUS_SYNC_DELAY_SECONDS);
     if delay_seconds == 0 {
@@ -440,9 +682,15 @@ pub async fn continuous_sync(
 
     loop {
         for dir in dirs {
-            dir.sync(operation, false).await?;
+            if cancellation_token.is_cancelled() {
+                return Ok(());
+            }
+            dir.sync(operation, false, cancellation_token).await?;
+        }
+        select! {
+            _ = cancellation_token.cancelled() => return Ok(()),
+            _ = delay_with_jitter(delay) => {}
         }
-        delay_with_jitter(delay).await;
     }
 }
 
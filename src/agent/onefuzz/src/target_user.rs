@@ -0,0 +1,112 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Running targets as an unprivileged user, while the agent keeps its own
+//! privileges.
+//!
+//! The target is started with the user's UID and primary GID, and no
+//! supplementary groups. Directories the target writes to must be owned by the
+//! user, via [`TargetUser::grant_dir`]. Inputs must be readable by it, which
+//! they usually are, or can be made with `normalize_input_permissions`.
+
+use std::path::Path;
+
+use anyhow::Result;
+
+/// A user to run targets as. Only supported on Linux.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetUser {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl TargetUser {
+    #[cfg(target_os = "linux")]
+    pub fn lookup(name: &str) -> Result<Self> {
+        use anyhow::Context;
+
+        let user = nix::unistd::User::from_name(name)
+            .with_context(|| format!("unable to look up user: {name}"))?
+            .ok_or_else(|| format_err!("no such user: {name}"))?;
+
+        Ok(Self {
+            name: name.to_owned(),
+            uid: user.uid.as_raw(),
+            gid: user.gid.as_raw(),
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn lookup(name: &str) -> Result<Self> {
+        bail!("running targets as another user is only supported on Linux: {name}")
+    }
+
+    /// Start the child process of `cmd` as this user.
+    pub fn apply(&self, cmd: &mut std::process::Command) {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::process::CommandExt;
+
+            cmd.uid(self.uid).gid(self.gid);
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = cmd;
+    }
+
+    /// Start the child process of the async `cmd` as this user.
+    pub fn apply_async(&self, cmd: &mut tokio::process::Command) {
+        #[cfg(target_os = "linux")]
+        cmd.uid(self.uid).gid(self.gid);
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = cmd;
+    }
+
+    /// Give this user ownership of `dir`, so that the target can create and
+    /// remove files in it. Files already in the directory are unchanged.
+    pub fn grant_dir(&self, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+
+        #[cfg(target_os = "linux")]
+        {
+            use anyhow::Context;
+            use nix::unistd::{chown, Gid, Uid};
+
+            chown(
+                dir,
+                Some(Uid::from_raw(self.uid)),
+                Some(Gid::from_raw(self.gid)),
+            )
+            .with_context(|| {
+                format!(
+                    "unable to give {} ownership of {}",
+                    self.name,
+                    dir.display()
+                )
+            })?;
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = dir;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() -> Result<()> {
+        let root = TargetUser::lookup("root")?;
+        assert_eq!(root.uid, 0);
+        assert_eq!(root.gid, 0);
+
+        assert!(TargetUser::lookup("onefuzz-no-such-user").is_err());
+        Ok(())
+    }
+}
@@ -11,6 +11,8 @@ use pete::{
 use proc_maps::MapRange;
 use serde::Serialize;
 
+use crate::loaded_modules::{loaded_modules, LoadedModule};
+
 use std::collections::BTreeMap;
 use std::fmt;
 use std::process::Command;
@@ -19,6 +21,7 @@ pub struct TriageCommand {
     tracer: Ptracer,
     tracee: Tracee,
     pid: Pid,
+    capture_modules: bool,
     _kill_on_drop: KillOnDrop,
 }
 impl TriageCommand {
@@ -39,6 +42,7 @@ impl TriageCommand {
             tracer,
             tracee,
             pid,
+            capture_modules: false,
             _kill_on_drop,
         })
     }
@@ -47,11 +51,21 @@ impl TriageCommand {
         self.pid
     }
 
+    /// Record the modules loaded by the target when it first crashes, or
+    /// else as it exits, while it is stopped and its mappings can be read.
+    pub fn capture_modules(self, value: bool) -> Self {
+        Self {
+            capture_modules: value,
+            ..self
+        }
+    }
+
     pub fn run(mut self) -> Result<TriageReport> {
         self.tracer.restart(self.tracee, Restart::Continue)?;
 
         let mut crashes = vec![];
         let mut exit_status = None;
+        let mut modules = None;
 
         while let Some(tracee) = self.tracer.wait()? {
             match tracee.stop {
@@ -60,10 +74,16 @@ impl TriageCommand {
                         // Can unwrap due to signal-delivery-stop.
                         let siginfo = tracee.siginfo()?.unwrap();
                         crashes.push(Crash::new(self.pid, signal, siginfo)?);
+                        if self.capture_modules && modules.is_none() {
+                            modules = capture_modules(self.pid);
+                        }
                     }
                 }
                 Stop::Exiting { exit_code } => {
                     exit_status = Some(ExitStatus::Exited(exit_code));
+                    if self.capture_modules && modules.is_none() && tracee.pid == self.pid {
+                        modules = capture_modules(self.pid);
+                    }
                 }
                 Stop::Signaling { signal, .. } => {
                     exit_status = Some(ExitStatus::Signaled(signal));
@@ -80,10 +100,21 @@ impl TriageCommand {
         Ok(TriageReport {
             exit_status,
             crashes,
+            modules,
         })
     }
 }
 
+fn capture_modules(pid: Pid) -> Option<Vec<LoadedModule>> {
+    match loaded_modules(pid.as_raw() as u32) {
+        Ok(modules) => Some(modules),
+        Err(err) => {
+            warn!("unable to list modules loaded by {}: {:?}", pid, err);
+            None
+        }
+    }
+}
+
 // Wrapper for a PID that signals it with SIGKILL when dropped.
 //
 // Lets us avoid an impl of `Drop` for `TriageCommand`, which constraints how
@@ -102,6 +133,10 @@ impl Drop for KillOnDrop {
 pub struct TriageReport {
     pub exit_status: ExitStatus,
     pub crashes: Vec<Crash>,
+
+    /// Modules loaded by the target, if captured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modules: Option<Vec<LoadedModule>>,
 }
 
 impl TriageReport {
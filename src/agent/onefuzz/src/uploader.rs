@@ -1,11 +1,11 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 
 use anyhow::{Context, Result};
 use futures::stream::TryStreamExt;
-use reqwest::{Body, Client, Response, StatusCode, Url};
+use reqwest::{Body, Client, RequestBuilder, Response, StatusCode, Url};
 use reqwest_retry::{
     send_retry_reqwest, RetryCheck, SendRetry, DEFAULT_RETRY_PERIOD, MAX_RETRY_ATTEMPTS,
 };
@@ -17,20 +17,44 @@ use tokio_util::codec;
 pub struct BlobUploader {
     client: Client,
     url: Url,
+    metadata: HashMap<String, String>,
+    overwrite: bool,
 }
 
 impl BlobUploader {
     pub fn new(url: Url) -> Self {
         let client = Client::new();
 
-        Self { client, url }
+        Self {
+            client,
+            url,
+            metadata: HashMap::new(),
+            overwrite: false,
+        }
+    }
+
+    /// Set `metadata` on each uploaded blob.
+    pub fn metadata(self, metadata: HashMap<String, String>) -> Self {
+        Self { metadata, ..self }
+    }
+
+    /// Replace existing blobs, rather than leaving them as they are.
+    pub fn overwrite(self) -> Self {
+        Self {
+            overwrite: true,
+            ..self
+        }
     }
 
     pub async fn upload(&mut self, file_path: impl AsRef<Path>) -> Result<Response> {
         let file_path = file_path.as_ref();
-
         let file_name = file_path.file_name().unwrap().to_str().unwrap();
+        self.upload_as(file_path, file_name).await
+    }
 
+    /// Upload `file_path` as the blob `file_name`, which may contain `/` to
+    /// place it in a virtual directory.
+    pub async fn upload_as(&mut self, file_path: &Path, file_name: &str) -> Result<Response> {
         let metadata = fs::metadata(file_path).await?;
         let file_len = metadata.len();
 
@@ -53,15 +77,16 @@ impl BlobUploader {
                     .map_ok(bytes::BytesMut::freeze)
                     .into_stream();
 
-                let request_builder = self
-                    .client
-                    .put(url.clone())
-                    // https://learn.microsoft.com/en-us/rest/api/storageservices/put-blob-from-url#request-headers
-                    .header("Content-Length", &content_length)
-                    .header("x-ms-blob-type", "BlockBlob")
+                let mut request_builder =
+                    with_metadata(self.client.put(url.clone()), &self.metadata)
+                        // https://learn.microsoft.com/en-us/rest/api/storageservices/put-blob-from-url#request-headers
+                        .header("Content-Length", &content_length)
+                        .header("x-ms-blob-type", "BlockBlob")
+                        .body(Body::wrap_stream(file_stream));
+                if !self.overwrite {
                     // upload only if the the destination blob does not exist
-                    .header("If-None-Match", "*")
-                    .body(Body::wrap_stream(file_stream));
+                    request_builder = request_builder.header("If-None-Match", "*");
+                }
 
                 Ok(request_builder)
             },
@@ -94,9 +119,7 @@ impl BlobUploader {
             url
         };
 
-        let resp = self
-            .client
-            .put(url)
+        let resp = with_metadata(self.client.put(url), &self.metadata)
             .header("x-ms-blob-type", "BlockBlob")
             .json(&data)
             .send_retry_default()
@@ -106,3 +129,14 @@ impl BlobUploader {
         Ok(resp)
     }
 }
+
+/// Add a blob metadata header for each entry in `metadata`.
+pub fn with_metadata(
+    mut request: RequestBuilder,
+    metadata: &HashMap<String, String>,
+) -> RequestBuilder {
+    for (name, value) in metadata {
+        request = request.header(format!("x-ms-meta-{name}"), value);
+    }
+    request
+}
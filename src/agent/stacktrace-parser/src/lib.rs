@@ -67,6 +67,26 @@ impl StackEntry {
             Some(parts.join(" "))
         }
     }
+
+    /// The frame without anything that varies between builds of the same
+    /// code: the function name without its arguments, or, for a frame with no
+    /// symbols, the module file name and the offset into that module. Absolute
+    /// addresses, offsets into functions, and source lines are left out.
+    pub fn normalized(&self) -> Option<String> {
+        if let Some(function_name) = &self.function_name {
+            return Some(function_without_args(function_name));
+        }
+
+        let module_path = self.module_path.as_ref()?;
+        let module_name = module_path
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(module_path);
+        match self.module_offset {
+            Some(offset) => Some(format!("{module_name}+{offset:#x}")),
+            None => Some(module_name.to_owned()),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -153,6 +173,16 @@ fn filter_funcs(entry: &StackEntry, stack_filter: &RegexSet) -> Option<StackEntr
     Some(entry)
 }
 
+/// The frames of `stack` which ClusterFuzz keeps, as for the minimized stack,
+/// but without falling back to any frames if none are kept.
+pub fn clusterfuzz_frames(stack: &[StackEntry]) -> Vec<StackEntry> {
+    let stack_filter = get_stack_filter();
+    stack
+        .iter()
+        .filter_map(|x| filter_funcs(x, stack_filter))
+        .collect()
+}
+
 impl CrashLog {
     pub fn new(
         text: Option<String>,
@@ -163,11 +193,7 @@ impl CrashLog {
         scariness_description: Option<String>,
         stack: Vec<StackEntry>,
     ) -> Result<Self> {
-        let stack_filter = get_stack_filter();
-        let mut minimized_stack_details: Vec<StackEntry> = stack
-            .iter()
-            .filter_map(|x| filter_funcs(x, stack_filter))
-            .collect();
+        let mut minimized_stack_details = clusterfuzz_frames(&stack);
         // if we don't have a minimized stack, if one of these functions is on
         // the stack, use it
         for entry in [
@@ -254,6 +280,24 @@ impl CrashLog {
     pub fn minimized_stack_function_lines_sha256(&self, depth: Option<usize>) -> String {
         digest_iter(&self.minimized_stack_function_lines, depth)
     }
+
+    /// Hash of the top `frame_count` normalized frames of the minimized stack,
+    /// or of the full stack if nothing is left after minimizing it. See
+    /// [`StackEntry::normalized`].
+    pub fn normalized_stack_sha256(&self, frame_count: usize) -> String {
+        let stack = if self.minimized_stack_details.is_empty() {
+            &self.full_stack_details
+        } else {
+            &self.minimized_stack_details
+        };
+
+        // Each frame is terminated, so that frames can't run together.
+        let frames = stack
+            .iter()
+            .filter_map(StackEntry::normalized)
+            .map(|frame| frame + "\n");
+        digest_iter(frames, Some(frame_count))
+    }
 }
 
 fn stack_lines(stack: &[StackEntry]) -> Vec<String> {
@@ -328,7 +372,7 @@ pub fn digest_iter(
 
 #[cfg(test)]
 mod tests {
-    use super::CrashLog;
+    use super::{CrashLog, StackEntry};
     use anyhow::Context;
     use std::ffi::OsStr;
     use std::fs;
@@ -515,4 +559,43 @@ mod tests {
 
         check_dir(src_dir, expected_dir, &skip_files, &skip_minimized_check);
     }
+
+    #[test]
+    fn test_normalized_stack_sha256() {
+        let frame = |address, function_name: Option<&str>, line| StackEntry {
+            line: format!("#0 {address:#x} in frame"),
+            address: Some(address),
+            function_name: function_name.map(ToOwned::to_owned),
+            source_file_line: Some(line),
+            module_path: Some("/out/build-1/libtarget.so".to_owned()),
+            module_offset: Some(0x10),
+            ..Default::default()
+        };
+
+        let entry = frame(0x4f8a21, None, 1);
+        assert_eq!(entry.normalized().as_deref(), Some("libtarget.so+0x10"));
+        let entry = frame(0x4f8a21, Some("parse(char const*)"), 1);
+        assert_eq!(entry.normalized().as_deref(), Some("parse"));
+
+        let crash_log = |stack| {
+            CrashLog::new(None, None, "asan".into(), "crash".into(), None, None, stack).unwrap()
+        };
+        let build_1 = crash_log(vec![
+            frame(0x4f8a21, Some("parse(char const*)"), 10),
+            frame(0x4f8b00, Some("read_header"), 20),
+        ]);
+        let build_2 = crash_log(vec![
+            frame(0x5a0000, Some("parse(char const*)"), 12),
+            frame(0x5a0100, Some("read_body"), 30),
+        ]);
+
+        assert_eq!(
+            build_1.normalized_stack_sha256(1),
+            build_2.normalized_stack_sha256(1)
+        );
+        assert_ne!(
+            build_1.normalized_stack_sha256(2),
+            build_2.normalized_stack_sha256(2)
+        );
+    }
 }
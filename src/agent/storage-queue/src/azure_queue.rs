@@ -3,7 +3,7 @@
 
 use anyhow::{Context, Result};
 use base64::Engine;
-use bytes::Buf;
+use bytes::{Buf, Bytes};
 use reqwest::{Client, Url};
 use reqwest_retry::SendRetry;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -36,7 +36,8 @@ pub struct AzureQueueMessage {
     // ExpirationTime
     pub pop_receipt: String,
     // TimeNextVisible
-    // DequeueCount
+    #[serde(default)]
+    pub dequeue_count: u64,
     pub message_text: String,
 
     #[serde(skip)]
@@ -151,24 +152,34 @@ impl AzureQueueClient {
     }
 
     pub async fn pop(&self) -> Result<Option<AzureQueueMessage>> {
+        self.receive(self.messages_url.clone()).await
+    }
+
+    /// Pop a message that is hidden from other clients for `visibility_timeout`,
+    /// after which it is visible again unless it has been deleted.
+    pub async fn pop_with_visibility_timeout(
+        &self,
+        visibility_timeout: Duration,
+    ) -> Result<Option<AzureQueueMessage>> {
+        let mut url = self.messages_url.clone();
+        url.query_pairs_mut().append_pair(
+            "visibilitytimeout",
+            &visibility_timeout.as_secs().max(1).to_string(),
+        );
+        self.receive(url).await
+    }
+
+    async fn receive(&self, url: Url) -> Result<Option<AzureQueueMessage>> {
         let response = self
             .http
-            .get(self.messages_url.clone())
+            .get(url)
             .send_retry_default()
             .await
             .context("storage queue pop failed")?
             .error_for_status()
             .context("storage queue pop failed with error")?;
 
-        let buf = {
-            let buf = response.bytes().await?;
-            //remove the byte order mark if present
-            if buf.slice(0..3).to_vec() == [0xef, 0xbb, 0xbf] {
-                buf.slice(3..)
-            } else {
-                buf
-            }
-        };
+        let buf = strip_bom(response.bytes().await?);
 
         let msg: AzureQueueMessageList =
             quick_xml::de::from_reader(buf.reader()).context("deserializing queue message")?;
@@ -180,3 +191,12 @@ impl AzureQueueClient {
         Ok(m)
     }
 }
+
+// Remove the byte order mark, if present.
+fn strip_bom(buf: Bytes) -> Bytes {
+    if buf.starts_with(&[0xef, 0xbb, 0xbf]) {
+        buf.slice(3..)
+    } else {
+        buf
+    }
+}
@@ -81,6 +81,27 @@ impl QueueClient {
             }
         }
     }
+
+    /// Pop a message that becomes visible to other clients again after
+    /// `visibility_timeout`, unless it is deleted first.
+    ///
+    /// Local queues have a single consumer, so their messages are removed
+    /// when popped, as with [`QueueClient::pop`].
+    pub async fn pop_with_visibility_timeout(
+        &self,
+        visibility_timeout: Duration,
+    ) -> Result<Option<Message>> {
+        match self {
+            QueueClient::AzureQueue(queue_client) => {
+                let message = queue_client
+                    .pop_with_visibility_timeout(visibility_timeout)
+                    .await
+                    .context("QueueClient.pop_with_visibility_timeout")?;
+                Ok(message.map(Message::QueueMessage))
+            }
+            _ => self.pop().await,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -131,6 +152,15 @@ impl Message {
         }
     }
 
+    /// How many times the message has been popped, including this time.
+    /// Local queue messages are only ever popped once.
+    pub fn dequeue_count(&self) -> u64 {
+        match self {
+            Message::QueueMessage(message) => message.dequeue_count,
+            Message::LocalQueueMessage(_) => 1,
+        }
+    }
+
     pub fn id(&self) -> Uuid {
         match self {
             Message::QueueMessage(message) => message.message_id,
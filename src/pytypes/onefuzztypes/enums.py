@@ -315,6 +315,9 @@ class ErrorCode(Enum):
 class HeartbeatType(Enum):
     MachineAlive = "MachineAlive"
     TaskAlive = "TaskAlive"
+    TaskStarted = "TaskStarted"
+    TaskPaused = "TaskPaused"
+    TaskFailed = "TaskFailed"
 
 
 class PoolType(Enum):